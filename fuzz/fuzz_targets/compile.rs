@@ -0,0 +1,33 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate wasmer_runtime;
+extern crate wasmer_runtime_core;
+
+use wasmer_runtime::{
+    compile_with_config_with,
+    compiler_for_backend,
+    Backend,
+};
+use wasmer_runtime_core::backend::{CompilerConfig, Features};
+
+fuzz_target!(|data: &[u8]| {
+    // Compiling must report an error rather than panic, whatever the input
+    // and whichever proposals are enabled.
+    for backend in &[Backend::Cranelift, Backend::Singlepass, Backend::LLVM] {
+        if let Some(compiler) = compiler_for_backend(*backend) {
+            let config = CompilerConfig {
+                features: Features {
+                    simd: true,
+                    multi_value: true,
+                    bulk_memory: true,
+                    threads: true,
+                    reference_types: true,
+                    sign_extension: true,
+                    sat_float_to_int: true,
+                },
+                ..Default::default()
+            };
+            let _ = compile_with_config_with(data, config, &*compiler);
+        }
+    }
+});
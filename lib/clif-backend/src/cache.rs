@@ -15,19 +15,28 @@ use serde_bench::{deserialize, serialize};
 pub struct CacheGenerator {
     backend_cache: BackendCache,
     memory: Arc<Memory>,
+    /// Breakpoint handlers are compiled in as pointers to closures of this
+    /// process, so code referring to them can't be cached.
+    has_breakpoints: bool,
 }
 
 impl CacheGenerator {
-    pub fn new(backend_cache: BackendCache, memory: Arc<Memory>) -> Self {
+    pub fn new(backend_cache: BackendCache, memory: Arc<Memory>, has_breakpoints: bool) -> Self {
         Self {
             backend_cache,
             memory,
+            has_breakpoints,
         }
     }
 }
 
 impl CacheGen for CacheGenerator {
     fn generate_cache(&self) -> Result<(Box<[u8]>, Memory), Error> {
+        if self.has_breakpoints {
+            return Err(Error::SerializeError(
+                "modules with breakpoints cannot be cached".to_string(),
+            ));
+        }
         // Clone the memory to a new location. This could take a long time,
         // depending on the throughput of your memcpy implementation.
         let compiled_code = (*self.memory).clone();
@@ -23,14 +23,15 @@ use wasmer_runtime_core::{
     codegen::*,
     memory::MemoryType,
     module::{ModuleInfo, ModuleInner},
+    parse::type_to_wp_type,
     structures::{Map, TypedIndex},
     types::{
         FuncIndex, FuncSig, GlobalIndex, LocalFuncIndex, LocalOrImport, MemoryIndex, SigIndex,
-        TableIndex,
+        TableIndex, Type,
     },
     vm,
 };
-use wasmparser::Type as WpType;
+use wasmparser::{Operator, Type as WpType, TypeOrFuncType};
 
 pub struct CraneliftModuleCodeGenerator {
     isa: Box<dyn isa::TargetIsa>,
@@ -58,7 +59,23 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
         Backend::Cranelift
     }
 
-    fn check_precondition(&mut self, _module_info: &ModuleInfo) -> Result<(), CodegenError> {
+    fn check_precondition(&mut self, module_info: &ModuleInfo) -> Result<(), CodegenError> {
+        let sig_types = module_info
+            .signatures
+            .iter()
+            .flat_map(|(_, sig)| sig.params().iter().chain(sig.returns()));
+        let local_global_types = module_info.globals.iter().map(|(_, init)| &init.desc.ty);
+        let imported_global_types = module_info
+            .imported_globals
+            .iter()
+            .map(|(_, (_, desc))| &desc.ty);
+        if sig_types
+            .chain(local_global_types)
+            .chain(imported_global_types)
+            .any(|&ty| ty == Type::V128)
+        {
+            return Err(simd_unsupported());
+        }
         Ok(())
     }
 
@@ -73,13 +90,14 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
         let func_index = LocalFuncIndex::new(self.functions.len());
         let name = ir::ExternalName::user(0, func_index.index() as u32);
 
-        let sig = generate_signature(
-            self,
-            self.get_func_type(
-                &module_info.read().unwrap(),
-                Converter(func_index.convert_up(&module_info.read().unwrap())).into(),
-            ),
+        let clif_sig_index = self.get_func_type(
+            &module_info.read().unwrap(),
+            Converter(func_index.convert_up(&module_info.read().unwrap())).into(),
         );
+        let sig = generate_signature(self, clif_sig_index);
+        let returns = module_info.read().unwrap().signatures[Converter(clif_sig_index).into()]
+            .returns()
+            .to_vec();
 
         let func = ir::Function::with_name_signature(name, sig);
 
@@ -90,6 +108,9 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
             func_translator,
             next_local: 0,
             position: Position::default(),
+            has_breakpoints: false,
+            returns,
+            multi_value_frames: vec![],
             func_env: FunctionEnvironment {
                 module_info: Arc::clone(&module_info),
                 target_config: self.isa.frontend_config().clone(),
@@ -121,12 +142,21 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
 
         // Set up the translation state with a single pushed control block representing the whole
         // function and its return values.
+        //
+        // A function with more than one result returns a pointer to the return buffer instead,
+        // so the results are passed to the exit block in variables, like those of a block with a
+        // multi-value type.
         let exit_block = builder.create_ebb();
-        builder.append_ebb_params_for_function_returns(exit_block);
+        let mut exit_signature = builder.func.signature.clone();
+        if func_env.returns.len() > 1 {
+            exit_signature.returns.clear();
+        } else {
+            builder.append_ebb_params_for_function_returns(exit_block);
+        }
         func_env
             .func_translator
             .state
-            .initialize(&builder.func.signature, exit_block);
+            .initialize(&exit_signature, exit_block);
 
         #[cfg(feature = "debug")]
         {
@@ -296,6 +326,7 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
         self,
         module_info: &ModuleInfo,
     ) -> Result<(Caller, Box<dyn CacheGen>), CodegenError> {
+        let has_breakpoints = self.functions.iter().any(|f| f.has_breakpoints);
         let mut func_bodies: Map<LocalFuncIndex, ir::Function> = Map::new();
         for f in self.functions.into_iter() {
             func_bodies.push(f.func);
@@ -322,6 +353,7 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
         let cache_gen = Box::new(CacheGenerator::new(
             backend_cache,
             Arc::clone(&func_resolver.memory),
+            has_breakpoints,
         ));
 
         Ok((
@@ -358,17 +390,23 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
 }
 
 fn convert_func_sig(sig: &FuncSig, call_conv: CallConv) -> ir::Signature {
+    // Functions with more than one result write them to the return buffer of the
+    // `Ctx` and return a pointer to it.
+    let returns = if sig.returns().len() > 1 {
+        vec![ir::AbiParam::new(ir::types::I64)]
+    } else {
+        sig.returns()
+            .iter()
+            .map(|returns| Converter(*returns).into())
+            .collect::<Vec<_>>()
+    };
     ir::Signature {
         params: sig
             .params()
             .iter()
             .map(|params| Converter(*params).into())
             .collect::<Vec<_>>(),
-        returns: sig
-            .returns()
-            .iter()
-            .map(|returns| Converter(*returns).into())
-            .collect::<Vec<_>>(),
+        returns,
         call_conv,
     }
 }
@@ -394,9 +432,37 @@ pub struct CraneliftFunctionCodeGenerator {
     func_translator: FuncTranslator,
     next_local: usize,
     position: Position,
+    /// Whether a breakpoint handler was compiled into the function.
+    has_breakpoints: bool,
+    /// The result types of the function.
+    returns: Vec<Type>,
+    /// The frames of the control stack of the translation state, with the variables
+    /// that pass the values of the frames that the translator can't handle.
+    multi_value_frames: Vec<Option<MultiValueFrame>>,
     func_env: FunctionEnvironment,
 }
 
+/// A control frame with params or more than one result, or the frame of a function
+/// with more than one result.
+///
+/// The translator only supports frames without params and with at most one result,
+/// so it is given a frame without a type instead: the params stay on the value
+/// stack, and the values passed to a branch target are defined as variables that are
+/// read back at the target.
+struct MultiValueFrame {
+    /// The params of an `if`, which the `else` arm takes as well.
+    params: Vec<ir::Value>,
+    /// The variables carrying the values passed by a branch to this frame, the params
+    /// for a loop and the results otherwise.
+    branch_vars: Vec<Variable>,
+    /// The variables carrying the results to the end of the frame.
+    result_vars: Vec<Variable>,
+    /// The height of the value stack below the params of the frame.
+    base: usize,
+    /// Whether this is an `if` that hasn't seen its `else` yet.
+    pending_else: bool,
+}
+
 pub struct FunctionEnvironment {
     module_info: Arc<RwLock<ModuleInfo>>,
     target_config: isa::TargetFrontendConfig,
@@ -643,7 +709,7 @@ impl FuncEnvironment for FunctionEnvironment {
                 });
 
                 let table_struct_ptr_offset =
-                    local_table_index.index() * vm::LocalTable::size() as usize;
+                    local_table_index.index() * mem::size_of::<*mut vm::LocalTable>();
 
                 let table_struct_ptr_ptr = func.create_global_value(ir::GlobalValueData::IAddImm {
                     base: tables_base,
@@ -665,7 +731,7 @@ impl FuncEnvironment for FunctionEnvironment {
                 });
 
                 let table_struct_ptr_offset =
-                    import_table_index.index() * vm::LocalTable::size() as usize;
+                    import_table_index.index() * mem::size_of::<*mut vm::LocalTable>();
 
                 let table_struct_ptr_ptr = func.create_global_value(ir::GlobalValueData::IAddImm {
                     base: tables_base,
@@ -1116,6 +1182,9 @@ impl FunctionCodeGenerator<CodegenError> for CraneliftFunctionCodeGenerator {
     }
 
     fn feed_local(&mut self, ty: WpType, n: usize) -> Result<(), CodegenError> {
+        if ty == WpType::V128 {
+            return Err(simd_unsupported());
+        }
         let mut next_local = self.next_local;
         cranelift_wasm::declare_locals(&mut self.builder(), n as u32, ty, &mut next_local)?;
         self.next_local = next_local;
@@ -1123,18 +1192,50 @@ impl FunctionCodeGenerator<CodegenError> for CraneliftFunctionCodeGenerator {
     }
 
     fn begin_body(&mut self, _module_info: &ModuleInfo) -> Result<(), CodegenError> {
+        let frame = if self.returns.len() > 1 {
+            let mut builder = FunctionBuilder::new(
+                &mut self.func,
+                &mut self.func_translator.func_ctx,
+                &mut self.position,
+            );
+            let result_vars = declare_vars(&mut builder, &mut self.next_local, &self.returns);
+            Some(MultiValueFrame {
+                params: vec![],
+                branch_vars: result_vars.clone(),
+                result_vars,
+                base: 0,
+                pending_else: false,
+            })
+        } else {
+            None
+        };
+        self.multi_value_frames.push(frame);
         Ok(())
     }
 
-    fn feed_event(&mut self, event: Event, _module_info: &ModuleInfo) -> Result<(), CodegenError> {
+    fn feed_event(&mut self, event: Event, module_info: &ModuleInfo) -> Result<(), CodegenError> {
         let op = match event {
             Event::Wasm(x) => x,
             Event::WasmOwned(ref x) => x,
-            Event::Internal(_x) => {
-                return Ok(());
+            Event::Internal(x) => {
+                return self.translate_internal_event(x);
             }
         };
 
+        if produces_v128(op) {
+            return Err(simd_unsupported());
+        }
+
+        // An `if` without an `else` passes its params on as its results, which is what an
+        // empty `else` arm does.
+        let pending_else = match (op, self.multi_value_frames.last()) {
+            (Operator::End, Some(Some(frame))) => frame.pending_else,
+            _ => false,
+        };
+        if pending_else {
+            self.feed_event(Event::Wasm(&Operator::Else), module_info)?;
+        }
+
         //let builder = self.builder.as_mut().unwrap();
         //let func_environment = FuncEnv::new();
         //let state = TranslationState::new();
@@ -1149,6 +1250,230 @@ impl FunctionCodeGenerator<CodegenError> for CraneliftFunctionCodeGenerator {
             &mut self.position,
         );
         let state = &mut self.func_translator.state;
+        if let Some((intrinsic_offset, immediates, num_operands)) = bulk_memory_intrinsic(op) {
+            if state.reachable {
+                let mut args: Vec<ir::Value> = immediates
+                    .iter()
+                    .map(|&imm| builder.ins().iconst(ir::types::I32, imm as i64))
+                    .collect();
+                args.extend_from_slice(state.peekn(num_operands));
+                state.popn(num_operands);
+                translate_intrinsic_call(&mut builder, &self.func_env, intrinsic_offset, &args);
+            }
+            return Ok(());
+        }
+
+        let frames = &mut self.multi_value_frames;
+        debug_assert_eq!(frames.len(), state.control_stack.len());
+        match *op {
+            Operator::Block { ty } | Operator::Loop { ty } | Operator::If { ty } => {
+                let sig = match ty {
+                    TypeOrFuncType::FuncType(index) => {
+                        &module_info.signatures[SigIndex::new(index as usize)]
+                    }
+                    TypeOrFuncType::Type(_) => {
+                        translate_operator(op, &mut builder, state, &mut self.func_env)?;
+                        frames.push(None);
+                        return Ok(());
+                    }
+                };
+                let ty = match (sig.params(), sig.returns()) {
+                    (&[], &[]) => Some(WpType::EmptyBlockType),
+                    (&[], &[ty]) => Some(type_to_wp_type(ty)),
+                    _ => None,
+                };
+                let translated_ty = TypeOrFuncType::Type(ty.unwrap_or(WpType::EmptyBlockType));
+                let translated_op = match *op {
+                    Operator::Block { .. } => Operator::Block { ty: translated_ty },
+                    Operator::Loop { .. } => Operator::Loop { ty: translated_ty },
+                    _ => Operator::If { ty: translated_ty },
+                };
+                // Frames that the translator supports need no variables, nor do frames in
+                // unreachable code, which pass no values.
+                if ty.is_some() || !state.reachable {
+                    translate_operator(&translated_op, &mut builder, state, &mut self.func_env)?;
+                    frames.push(None);
+                    return Ok(());
+                }
+
+                let is_loop = match *op {
+                    Operator::Loop { .. } => true,
+                    _ => false,
+                };
+                let condition = match *op {
+                    Operator::If { .. } => Some(state.pop1()),
+                    _ => None,
+                };
+                let params = state.peekn(sig.params().len()).to_vec();
+                state.popn(params.len());
+                let result_vars = declare_vars(&mut builder, &mut self.next_local, sig.returns());
+                let branch_vars = if is_loop {
+                    let param_vars = declare_vars(&mut builder, &mut self.next_local, sig.params());
+                    define_vars(&mut builder, &param_vars, &params);
+                    param_vars
+                } else {
+                    result_vars.clone()
+                };
+                if let Some(condition) = condition {
+                    state.push1(condition);
+                }
+
+                translate_operator(&translated_op, &mut builder, state, &mut self.func_env)?;
+
+                let base = state.stack.len();
+                if is_loop {
+                    // The params of a loop are read in its header, where the branches back
+                    // to the loop join.
+                    for &var in &branch_vars {
+                        state.push1(builder.use_var(var));
+                    }
+                } else {
+                    state.stack.extend_from_slice(&params);
+                }
+                frames.push(Some(MultiValueFrame {
+                    params,
+                    branch_vars,
+                    result_vars,
+                    base,
+                    pending_else: match *op {
+                        Operator::If { .. } => true,
+                        _ => false,
+                    },
+                }));
+                return Ok(());
+            }
+            Operator::Else => {
+                if let Some(Some(frame)) = frames.last_mut() {
+                    if state.reachable {
+                        let results = state.peekn(frame.result_vars.len()).to_vec();
+                        define_vars(&mut builder, &frame.result_vars, &results);
+                    }
+                    translate_operator(op, &mut builder, state, &mut self.func_env)?;
+                    frame.pending_else = false;
+                    if state.reachable {
+                        state.stack.truncate(frame.base);
+                        state.stack.extend_from_slice(&frame.params);
+                    }
+                    return Ok(());
+                }
+            }
+            Operator::End => {
+                if let Some(Some(frame)) = frames.pop() {
+                    if state.reachable {
+                        let results = state.peekn(frame.result_vars.len()).to_vec();
+                        define_vars(&mut builder, &frame.result_vars, &results);
+                    }
+                    translate_operator(op, &mut builder, state, &mut self.func_env)?;
+                    if state.reachable {
+                        for &var in &frame.result_vars {
+                            state.push1(builder.use_var(var));
+                        }
+                    }
+                    return Ok(());
+                }
+            }
+            Operator::Br { relative_depth } | Operator::BrIf { relative_depth } => {
+                let frame = &frames[frames.len() - 1 - relative_depth as usize];
+                if let (Some(frame), true) = (frame, state.reachable) {
+                    // The condition of a `br_if` is above the values passed.
+                    let end = match *op {
+                        Operator::BrIf { .. } => state.stack.len() - 1,
+                        _ => state.stack.len(),
+                    };
+                    let values = state.stack[end - frame.branch_vars.len()..end].to_vec();
+                    define_vars(&mut builder, &frame.branch_vars, &values);
+                }
+            }
+            Operator::BrTable { ref table } => {
+                let (targets, default) = table.read_table().map_err(|e| CodegenError {
+                    message: format!("{:?}", e),
+                })?;
+                let has_multi_value_target = targets
+                    .iter()
+                    .chain(Some(&default))
+                    .any(|&depth| frames[frames.len() - 1 - depth as usize].is_some());
+                if has_multi_value_target && state.reachable {
+                    // The translator passes the same number of values to all targets, which
+                    // doesn't hold for frames passing their values in variables, so branch
+                    // to each target from its own block instead.
+                    let index = state.pop1();
+                    let mut depths = targets.clone();
+                    depths.push(default);
+                    depths.sort();
+                    depths.dedup();
+                    let ebbs: Vec<Ebb> = depths.iter().map(|_| builder.create_ebb()).collect();
+                    let ebb_for = |depth: u32| ebbs[depths.binary_search(&depth).unwrap()];
+                    for (i, &depth) in targets.iter().enumerate() {
+                        let is_target =
+                            builder
+                                .ins()
+                                .icmp_imm(ir::condcodes::IntCC::Equal, index, i as i64);
+                        builder.ins().brnz(is_target, ebb_for(depth), &[]);
+                    }
+                    builder.ins().jump(ebb_for(default), &[]);
+
+                    let stack = state.stack.clone();
+                    for (&depth, &ebb) in depths.iter().zip(ebbs.iter()) {
+                        builder.switch_to_block(ebb);
+                        builder.seal_block(ebb);
+                        state.stack = stack.clone();
+                        state.reachable = true;
+                        if let Some(frame) = &frames[frames.len() - 1 - depth as usize] {
+                            let values = state.peekn(frame.branch_vars.len()).to_vec();
+                            define_vars(&mut builder, &frame.branch_vars, &values);
+                        }
+                        translate_operator(
+                            &Operator::Br {
+                                relative_depth: depth,
+                            },
+                            &mut builder,
+                            state,
+                            &mut self.func_env,
+                        )?;
+                    }
+                    return Ok(());
+                }
+            }
+            Operator::Return => {
+                if let (Some(frame), true) = (&frames[0], state.reachable) {
+                    // Branch to the end of the function, which returns the results.
+                    let results = state.peekn(frame.branch_vars.len()).to_vec();
+                    define_vars(&mut builder, &frame.branch_vars, &results);
+                    translate_operator(
+                        &Operator::Br {
+                            relative_depth: frames.len() as u32 - 1,
+                        },
+                        &mut builder,
+                        state,
+                        &mut self.func_env,
+                    )?;
+                    return Ok(());
+                }
+            }
+            Operator::Call { function_index } => {
+                let sig_index = module_info.func_assoc[FuncIndex::new(function_index as usize)];
+                let returns = module_info.signatures[sig_index].returns();
+                if returns.len() > 1 && state.reachable {
+                    translate_operator(op, &mut builder, state, &mut self.func_env)?;
+                    let results = state.pop1();
+                    let values = load_return_values(&mut builder, results, returns);
+                    state.stack.extend_from_slice(&values);
+                    return Ok(());
+                }
+            }
+            Operator::CallIndirect { index, .. } => {
+                let returns = module_info.signatures[SigIndex::new(index as usize)].returns();
+                if returns.len() > 1 && state.reachable {
+                    translate_operator(op, &mut builder, state, &mut self.func_env)?;
+                    let results = state.pop1();
+                    let values = load_return_values(&mut builder, results, returns);
+                    state.stack.extend_from_slice(&values);
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+
         translate_operator(op, &mut builder, state, &mut self.func_env)?;
         Ok(())
     }
@@ -1170,7 +1495,26 @@ impl FunctionCodeGenerator<CodegenError> for CraneliftFunctionCodeGenerator {
         // generate a return instruction that doesn't match the signature.
         if state.reachable {
             debug_assert!(builder.is_pristine());
-            if !builder.is_unreachable() {
+            if !builder.is_unreachable() && self.returns.len() > 1 {
+                let vmctx = builder
+                    .func
+                    .special_param(ir::ArgumentPurpose::VMContext)
+                    .expect("missing vmctx parameter");
+                let return_values = builder.ins().load(
+                    self.func_env.pointer_type(),
+                    ir::MemFlags::trusted(),
+                    vmctx,
+                    vm::Ctx::offset_return_values() as i32,
+                );
+                let mut offset = 0;
+                for (&value, &ty) in state.stack.iter().zip(self.returns.iter()) {
+                    builder
+                        .ins()
+                        .store(ir::MemFlags::trusted(), value, return_values, offset);
+                    offset += return_value_size(ty);
+                }
+                builder.ins().return_(&[return_values]);
+            } else if !builder.is_unreachable() {
                 match return_mode {
                     ReturnMode::NormalReturns => builder.ins().return_(&state.stack),
                     ReturnMode::FallthroughReturn => builder.ins().fallthrough_return(&state.stack),
@@ -1204,6 +1548,170 @@ impl CraneliftModuleCodeGenerator {
     }
 }
 
+/// Returns the offset of the runtime intrinsic implementing a bulk memory operator,
+/// the immediates passed to it and the number of stack operands it consumes.
+fn bulk_memory_intrinsic(op: &Operator) -> Option<(u8, Vec<u32>, usize)> {
+    Some(match *op {
+        Operator::MemoryCopy => (vm::Intrinsics::offset_memory_copy(), vec![0], 3),
+        Operator::MemoryFill => (vm::Intrinsics::offset_memory_fill(), vec![0], 3),
+        Operator::MemoryInit { segment } => {
+            (vm::Intrinsics::offset_memory_init(), vec![0, segment], 3)
+        }
+        Operator::DataDrop { segment } => (vm::Intrinsics::offset_data_drop(), vec![segment], 0),
+        Operator::TableCopy => (vm::Intrinsics::offset_table_copy(), vec![0], 3),
+        Operator::TableInit { segment } => {
+            (vm::Intrinsics::offset_table_init(), vec![0, segment], 3)
+        }
+        Operator::ElemDrop { segment } => (vm::Intrinsics::offset_elem_drop(), vec![segment], 0),
+        _ => return None,
+    })
+}
+
+/// The number of bytes a value of type `ty` takes in the return buffer.
+fn return_value_size(ty: Type) -> i32 {
+    match ty {
+        Type::V128 => 16,
+        _ => 8,
+    }
+}
+
+/// Loads the results of a call to a function with more than one result from the
+/// return buffer that `results` points to.
+fn load_return_values(
+    builder: &mut FunctionBuilder,
+    results: ir::Value,
+    returns: &[Type],
+) -> Vec<ir::Value> {
+    let mut offset = 0;
+    returns
+        .iter()
+        .map(|&ty| {
+            let value = builder.ins().load(
+                Converter(ty).into(),
+                ir::MemFlags::trusted(),
+                results,
+                offset,
+            );
+            offset += return_value_size(ty);
+            value
+        })
+        .collect()
+}
+
+/// Declares a variable of each of `types`, numbered after the locals of the function.
+fn declare_vars(
+    builder: &mut FunctionBuilder,
+    next_local: &mut usize,
+    types: &[Type],
+) -> Vec<Variable> {
+    types
+        .iter()
+        .map(|&ty| {
+            let var = Variable::new(*next_local);
+            *next_local += 1;
+            builder.declare_var(var, Converter(ty).into());
+            var
+        })
+        .collect()
+}
+
+/// Assigns `values` to `vars` at the current position.
+fn define_vars(builder: &mut FunctionBuilder, vars: &[Variable], values: &[ir::Value]) {
+    for (&var, &value) in vars.iter().zip(values.iter()) {
+        builder.def_var(var, value);
+    }
+}
+
+/// Loads the pointer to the `Ctx` internal fields.
+fn load_internals(builder: &mut FunctionBuilder, func_env: &FunctionEnvironment) -> ir::Value {
+    let vmctx = builder
+        .func
+        .special_param(ir::ArgumentPurpose::VMContext)
+        .expect("missing vmctx parameter");
+    builder.ins().load(
+        func_env.pointer_type(),
+        ir::MemFlags::trusted(),
+        vmctx,
+        vm::Ctx::offset_internals() as i32,
+    )
+}
+
+/// The error for modules that use the SIMD proposal. Cranelift can't lower vector types
+/// yet, so SIMD is left to the singlepass and LLVM backends.
+fn simd_unsupported() -> CodegenError {
+    CodegenError {
+        message: "the cranelift backend does not support SIMD, use the singlepass or llvm backend"
+            .to_string(),
+    }
+}
+
+/// Whether `op` makes a `v128` out of values of other types. Every other SIMD operator
+/// takes a `v128` operand, so a module that has neither these nor `v128` locals, globals
+/// or signatures doesn't use SIMD.
+fn produces_v128(op: &Operator) -> bool {
+    match *op {
+        Operator::V128Const { .. }
+        | Operator::V128Load { .. }
+        | Operator::I8x16Splat
+        | Operator::I16x8Splat
+        | Operator::I32x4Splat
+        | Operator::I64x2Splat
+        | Operator::F32x4Splat
+        | Operator::F64x2Splat
+        | Operator::I8x16LoadSplat { .. }
+        | Operator::I16x8LoadSplat { .. }
+        | Operator::I32x4LoadSplat { .. }
+        | Operator::I64x2LoadSplat { .. } => true,
+        _ => false,
+    }
+}
+
+/// Emits an indirect call through the `Ctx` intrinsics table to a runtime intrinsic
+/// that takes `vmctx` followed by `args`, and returns nothing.
+fn translate_intrinsic_call(
+    builder: &mut FunctionBuilder,
+    func_env: &FunctionEnvironment,
+    intrinsic_offset: u8,
+    args: &[ir::Value],
+) {
+    let pointer_type = func_env.pointer_type();
+
+    let mut params = vec![ir::AbiParam::special(
+        pointer_type,
+        ir::ArgumentPurpose::VMContext,
+    )];
+    params.extend(
+        args.iter()
+            .map(|&arg| ir::AbiParam::new(builder.func.dfg.value_type(arg))),
+    );
+    let signature = builder.func.import_signature(ir::Signature {
+        call_conv: func_env.target_config().default_call_conv,
+        params,
+        returns: vec![],
+    });
+
+    let vmctx = builder
+        .func
+        .special_param(ir::ArgumentPurpose::VMContext)
+        .expect("missing vmctx parameter");
+    let intrinsics = builder.ins().load(
+        pointer_type,
+        ir::MemFlags::trusted(),
+        vmctx,
+        vm::Ctx::offset_intrinsics() as i32,
+    );
+    let func_ptr = builder.ins().load(
+        pointer_type,
+        ir::MemFlags::trusted(),
+        intrinsics,
+        intrinsic_offset as i32,
+    );
+
+    let mut call_args = vec![vmctx];
+    call_args.extend_from_slice(args);
+    builder.ins().call_indirect(signature, func_ptr, &call_args);
+}
+
 impl CraneliftFunctionCodeGenerator {
     pub fn builder(&mut self) -> FunctionBuilder {
         FunctionBuilder::new(
@@ -1216,6 +1724,73 @@ impl CraneliftFunctionCodeGenerator {
     pub fn return_mode(&self) -> ReturnMode {
         ReturnMode::NormalReturns
     }
+
+    /// Lowers the events that middlewares emit in addition to wasm operators.
+    fn translate_internal_event(&mut self, event: InternalEvent) -> Result<(), CodegenError> {
+        if self.func_translator.state.control_stack.is_empty() {
+            return Ok(());
+        }
+
+        let mut builder = FunctionBuilder::new(
+            &mut self.func,
+            &mut self.func_translator.func_ctx,
+            &mut self.position,
+        );
+        let state = &mut self.func_translator.state;
+        if !state.reachable {
+            return Ok(());
+        }
+
+        match event {
+            InternalEvent::FunctionBegin(_) | InternalEvent::FunctionEnd => {}
+            InternalEvent::Breakpoint(callback) => {
+                // Like the LLVM backend, the handler is leaked so that it lives as long
+                // as the code referring to it.
+                self.has_breakpoints = true;
+                let raw = Box::into_raw(Box::new(callback)) as i64;
+                let handler = builder.ins().iconst(self.func_env.pointer_type(), raw);
+                translate_intrinsic_call(
+                    &mut builder,
+                    &self.func_env,
+                    vm::Intrinsics::offset_breakpoint(),
+                    &[handler],
+                );
+            }
+            InternalEvent::Trap(trap) => {
+                let code = builder.ins().iconst(ir::types::I32, trap as i64);
+                translate_intrinsic_call(
+                    &mut builder,
+                    &self.func_env,
+                    vm::Intrinsics::offset_internal_trap(),
+                    &[code],
+                );
+            }
+            InternalEvent::GetInternal(idx) => {
+                let idx = idx as usize;
+                assert!(idx < vm::INTERNALS_SIZE);
+
+                let internals = load_internals(&mut builder, &self.func_env);
+                let value = builder.ins().load(
+                    ir::types::I64,
+                    ir::MemFlags::trusted(),
+                    internals,
+                    (idx * 8) as i32,
+                );
+                state.push1(value);
+            }
+            InternalEvent::SetInternal(idx) => {
+                let idx = idx as usize;
+                assert!(idx < vm::INTERNALS_SIZE);
+
+                let value = state.pop1();
+                let internals = load_internals(&mut builder, &self.func_env);
+                builder
+                    .ins()
+                    .store(ir::MemFlags::trusted(), value, internals, (idx * 8) as i32);
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Creates a signature with VMContext as the last param
@@ -33,6 +33,9 @@ fn get_isa() -> Box<dyn isa::TargetIsa> {
         let mut builder = settings::builder();
         builder.set("opt_level", "best").unwrap();
         builder.set("jump_tables_enabled", "false").unwrap();
+        // Check division operands explicitly so that dividing by zero and
+        // overflowing traps can be told apart.
+        builder.set("avoid_div_traps", "true").unwrap();
 
         if cfg!(not(test)) {
             builder.set("enable_verifier", "false").unwrap();
@@ -48,6 +51,9 @@ fn get_isa() -> Box<dyn isa::TargetIsa> {
 /// The current version of this crate
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+pub use code::CraneliftFunctionCodeGenerator as FunctionCodeGenerator;
+pub use code::CraneliftModuleCodeGenerator as ModuleCodeGenerator;
+
 use wasmer_runtime_core::codegen::SimpleStreamingCompilerGen;
 
 pub type CraneliftCompiler = SimpleStreamingCompilerGen<
@@ -37,6 +37,7 @@ impl Module {
         let cache_gen = Box::new(CacheGenerator::new(
             backend_cache,
             Arc::clone(&func_resolver.memory),
+            false,
         ));
 
         let runnable_module = Caller::new(handler_data, trampolines, func_resolver);
@@ -121,6 +122,8 @@ impl From<Converter<Type>> for ir::Type {
             Type::F32 => ir::types::F32,
             Type::F64 => ir::types::F64,
             Type::V128 => ir::types::I32X4,
+            // References are passed as pointer-sized values.
+            Type::AnyRef | Type::FuncRef => ir::types::I64,
         }
     }
 }
@@ -133,6 +136,7 @@ impl From<Converter<Type>> for ir::AbiParam {
             Type::F32 => ir::AbiParam::new(ir::types::F32),
             Type::F64 => ir::AbiParam::new(ir::types::F64),
             Type::V128 => ir::AbiParam::new(ir::types::I32X4),
+            Type::AnyRef | Type::FuncRef => ir::AbiParam::new(ir::types::I64),
         }
     }
 }
@@ -95,6 +95,10 @@ pub struct RelocSink {
     /// Relocations recorded for the function.
     pub external_relocs: Vec<ExternalRelocation>,
     pub local_relocs: Vec<LocalRelocation>,
+    /// The first relocation that couldn't be recorded, if any. The
+    /// `binemit::RelocSink` methods can't fail, so this is checked once the
+    /// function has been emitted.
+    pub error: Option<String>,
 }
 
 impl binemit::RelocSink for RelocSink {
@@ -105,7 +109,7 @@ impl binemit::RelocSink for RelocSink {
         _ebb_offset: binemit::CodeOffset,
     ) {
         // This should use the `offsets` field of `ir::Function`.
-        unimplemented!();
+        self.unsupported("ebb relocations are not supported".to_string());
     }
     fn reloc_external(
         &mut self,
@@ -118,7 +122,9 @@ impl binemit::RelocSink for RelocSink {
             binemit::Reloc::Abs8 => Reloc::Abs8,
             binemit::Reloc::X86PCRel4 => Reloc::X86PCRel4,
             binemit::Reloc::X86CallPCRel4 => Reloc::X86CallPCRel4,
-            _ => unimplemented!("unimplented reloc type: {}", reloc),
+            _ => {
+                return self.unsupported(format!("unsupported relocation type: {}", reloc));
+            }
         };
 
         match *name {
@@ -126,7 +132,12 @@ impl binemit::RelocSink for RelocSink {
                 namespace: 0,
                 index,
             } => {
-                assert_eq!(reloc, Reloc::X86CallPCRel4);
+                if reloc != Reloc::X86CallPCRel4 {
+                    return self.unsupported(format!(
+                        "unsupported relocation type for a local call: {:?}",
+                        reloc
+                    ));
+                }
                 self.local_relocs.push(LocalRelocation {
                     offset,
                     addend,
@@ -146,7 +157,7 @@ impl binemit::RelocSink for RelocSink {
 
                         DYNAMIC_MEM_GROW => VmCallKind::DynamicMemoryGrow,
                         DYNAMIC_MEM_SIZE => VmCallKind::DynamicMemorySize,
-                        _ => unimplemented!(),
+                        _ => return self.unsupported(format!("unknown vm call: {}", index)),
                     })),
                     IMPORT_NAMESPACE => RelocationType::VmCall(VmCall::Import(match index {
                         STATIC_MEM_GROW => VmCallKind::StaticMemoryGrow,
@@ -157,10 +168,13 @@ impl binemit::RelocSink for RelocSink {
 
                         DYNAMIC_MEM_GROW => VmCallKind::DynamicMemoryGrow,
                         DYNAMIC_MEM_SIZE => VmCallKind::DynamicMemorySize,
-                        _ => unimplemented!(),
+                        _ => return self.unsupported(format!("unknown vm call: {}", index)),
                     })),
                     SIG_NAMESPACE => RelocationType::Signature(SigIndex::new(index as usize)),
-                    _ => unimplemented!(),
+                    _ => {
+                        return self
+                            .unsupported(format!("unknown relocation namespace: {}", namespace));
+                    }
                 };
                 self.external_relocs.push(ExternalRelocation {
                     reloc,
@@ -190,7 +204,7 @@ impl binemit::RelocSink for RelocSink {
                     ir::LibCall::TruncF64 => LibCall::TruncF64,
                     ir::LibCall::NearestF64 => LibCall::NearestF64,
                     ir::LibCall::Probestack => LibCall::Probestack,
-                    _ => unimplemented!("unimplemented libcall: {}", libcall),
+                    _ => return self.unsupported(format!("unsupported libcall: {}", libcall)),
                 };
                 let relocation_type = RelocationType::LibCall(libcall);
                 self.external_relocs.push(ExternalRelocation {
@@ -208,7 +222,7 @@ impl binemit::RelocSink for RelocSink {
         _reloc: binemit::Reloc,
         _jt: ir::JumpTable,
     ) {
-        unimplemented!();
+        self.unsupported("jump table relocations are not supported".to_string());
     }
 }
 
@@ -234,6 +248,13 @@ impl RelocSink {
         Self {
             external_relocs: Vec::new(),
             local_relocs: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn unsupported(&mut self, msg: String) {
+        if self.error.is_none() {
+            self.error = Some(msg);
         }
     }
 }
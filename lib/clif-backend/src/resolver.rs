@@ -117,6 +117,9 @@ impl FuncResolverBuilder {
                             &mut local_trap_sink,
                         )
                         .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+                        if let Some(msg) = reloc_sink.error.take() {
+                            return Err(CompileError::InternalError { msg });
+                        }
                         ctx.clear();
                         Ok((code_buf, (reloc_sink, local_trap_sink)))
                     },
@@ -257,8 +260,12 @@ impl FuncResolverBuilder {
                             VmCallKind::StaticMemoryGrow => vmcalls::local_static_memory_grow as _,
                             VmCallKind::StaticMemorySize => vmcalls::local_static_memory_size as _,
 
-                            VmCallKind::SharedStaticMemoryGrow => unimplemented!(),
-                            VmCallKind::SharedStaticMemorySize => unimplemented!(),
+                            VmCallKind::SharedStaticMemoryGrow => {
+                                vmcalls::local_shared_static_memory_grow as _
+                            }
+                            VmCallKind::SharedStaticMemorySize => {
+                                vmcalls::local_shared_static_memory_size as _
+                            }
 
                             VmCallKind::DynamicMemoryGrow => {
                                 vmcalls::local_dynamic_memory_grow as _
@@ -275,8 +282,12 @@ impl FuncResolverBuilder {
                                 vmcalls::imported_static_memory_size as _
                             }
 
-                            VmCallKind::SharedStaticMemoryGrow => unimplemented!(),
-                            VmCallKind::SharedStaticMemorySize => unimplemented!(),
+                            VmCallKind::SharedStaticMemoryGrow => {
+                                vmcalls::imported_shared_static_memory_grow as _
+                            }
+                            VmCallKind::SharedStaticMemorySize => {
+                                vmcalls::imported_shared_static_memory_size as _
+                            }
 
                             VmCallKind::DynamicMemoryGrow => {
                                 vmcalls::imported_dynamic_memory_grow as _
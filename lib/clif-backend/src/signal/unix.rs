@@ -94,6 +94,11 @@ pub fn call_protected<T>(
                             TrapCode::IndirectCallToNull => WasmTrapInfo::CallIndirectOOB,
                             TrapCode::HeapOutOfBounds => WasmTrapInfo::MemoryOutOfBounds,
                             TrapCode::TableOutOfBounds => WasmTrapInfo::CallIndirectOOB,
+                            TrapCode::IntegerDivisionByZero => WasmTrapInfo::DivideByZero,
+                            TrapCode::IntegerOverflow => WasmTrapInfo::IntegerOverflow,
+                            TrapCode::BadConversionToInteger => WasmTrapInfo::IllegalArithmetic,
+                            TrapCode::StackOverflow => WasmTrapInfo::StackOverflow,
+                            TrapCode::UnreachableCodeReached => WasmTrapInfo::Unreachable,
                             _ => WasmTrapInfo::Unknown,
                         },
                         Ok(SIGSEGV) | Ok(SIGBUS) => WasmTrapInfo::MemoryOutOfBounds,
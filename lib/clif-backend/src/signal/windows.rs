@@ -64,12 +64,15 @@ pub fn call_protected(
                 TrapCode::HeapOutOfBounds => WasmTrapInfo::MemoryOutOfBounds,
                 TrapCode::TableOutOfBounds => WasmTrapInfo::CallIndirectOOB,
                 TrapCode::UnreachableCodeReached => WasmTrapInfo::Unreachable,
+                TrapCode::IntegerDivisionByZero => WasmTrapInfo::DivideByZero,
+                TrapCode::IntegerOverflow => WasmTrapInfo::IntegerOverflow,
+                TrapCode::BadConversionToInteger => WasmTrapInfo::IllegalArithmetic,
+                TrapCode::StackOverflow => WasmTrapInfo::StackOverflow,
                 _ => WasmTrapInfo::Unknown,
             },
-            EXCEPTION_STACK_OVERFLOW => WasmTrapInfo::Unknown,
-            EXCEPTION_INT_DIVIDE_BY_ZERO | EXCEPTION_INT_OVERFLOW => {
-                WasmTrapInfo::IllegalArithmetic
-            }
+            EXCEPTION_STACK_OVERFLOW => WasmTrapInfo::StackOverflow,
+            EXCEPTION_INT_DIVIDE_BY_ZERO => WasmTrapInfo::DivideByZero,
+            EXCEPTION_INT_OVERFLOW => WasmTrapInfo::IntegerOverflow,
             _ => WasmTrapInfo::Unknown,
         }))
     } else {
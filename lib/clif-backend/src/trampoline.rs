@@ -182,15 +182,27 @@ fn generate_func(func_sig: &FuncSig) -> ir::Function {
 
     let return_values = pos.func.dfg.inst_results(call_inst).to_vec();
 
-    for (index, return_val) in return_values.iter().enumerate() {
+    if func_sig.returns().len() > 1 {
+        // The callee returns a pointer to its return buffer, copy the results from there.
         let mem_flags = ir::MemFlags::trusted();
-
-        pos.ins().store(
-            mem_flags,
-            *return_val,
-            returns_ptr,
-            (index * mem::size_of::<u64>()) as i32,
-        );
+        for index in 0..func_sig.return_slots() {
+            let offset = (index * mem::size_of::<u64>()) as i32;
+            let val = pos
+                .ins()
+                .load(ir::types::I64, mem_flags, return_values[0], offset);
+            pos.ins().store(mem_flags, val, returns_ptr, offset);
+        }
+    } else {
+        for (index, return_val) in return_values.iter().enumerate() {
+            let mem_flags = ir::MemFlags::trusted();
+
+            pos.ins().store(
+                mem_flags,
+                *return_val,
+                returns_ptr,
+                (index * mem::size_of::<u64>()) as i32,
+            );
+        }
     }
 
     pos.ins().return_(&[]);
@@ -205,6 +217,7 @@ fn wasm_ty_to_clif(ty: Type) -> ir::types::Type {
         Type::F32 => ir::types::F32,
         Type::F64 => ir::types::F64,
         Type::V128 => ir::types::I32X4,
+        Type::AnyRef | Type::FuncRef => ir::types::I64,
     }
 }
 
@@ -246,8 +259,13 @@ fn generate_export_signature(func_sig: &FuncSig) -> ir::Signature {
     .chain(func_sig_iter)
     .collect();
 
-    export_clif_sig.returns = func_sig
-        .returns()
+    // Functions with more than one result return a pointer to the return buffer.
+    let returns = if func_sig.returns().len() > 1 {
+        &[Type::I64][..]
+    } else {
+        func_sig.returns()
+    };
+    export_clif_sig.returns = returns
         .iter()
         .map(|wasm_ty| ir::AbiParam {
             value_type: wasm_ty_to_clif(*wasm_ty),
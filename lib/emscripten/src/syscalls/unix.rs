@@ -866,6 +866,7 @@ pub fn ___syscall180(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
     let offset: i64 = varargs.get(ctx);
 
     let buf_ptr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
+    ctx.memory(0).prepare_os_write(buf as usize, count as usize);
 
     unsafe { pread(fd, buf_ptr, count as _, offset) as _ }
 }
@@ -91,6 +91,9 @@ public:
     MemoryOutOfBounds = 2,
     CallIndirectOOB = 3,
     IllegalArithmetic = 4,
+    DivideByZero = 5,
+    IntegerOverflow = 6,
+    StackOverflow = 7,
     Unknown,
   };
 
@@ -123,6 +126,15 @@ private:
     case Type::IllegalArithmetic:
       out << "illegal arithmetic operation";
       break;
+    case Type::DivideByZero:
+      out << "integer divide by zero";
+      break;
+    case Type::IntegerOverflow:
+      out << "integer overflow";
+      break;
+    case Type::StackOverflow:
+      out << "call stack exhausted";
+      break;
     case Type::Unknown:
     default:
       out << "unknown";
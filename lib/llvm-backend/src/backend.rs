@@ -104,14 +104,45 @@ fn get_callbacks() -> Callbacks {
             fn_name!("vm.memory.size.dynamic.local") => vmcalls::local_dynamic_memory_size as _,
             fn_name!("vm.memory.grow.static.local") => vmcalls::local_static_memory_grow as _,
             fn_name!("vm.memory.size.static.local") => vmcalls::local_static_memory_size as _,
+            fn_name!("vm.memory.grow.shared.local") => {
+                vmcalls::local_shared_static_memory_grow as _
+            }
+            fn_name!("vm.memory.size.shared.local") => {
+                vmcalls::local_shared_static_memory_size as _
+            }
 
             fn_name!("vm.memory.grow.dynamic.import") => vmcalls::imported_dynamic_memory_grow as _,
             fn_name!("vm.memory.size.dynamic.import") => vmcalls::imported_dynamic_memory_size as _,
             fn_name!("vm.memory.grow.static.import") => vmcalls::imported_static_memory_grow as _,
             fn_name!("vm.memory.size.static.import") => vmcalls::imported_static_memory_size as _,
+            fn_name!("vm.memory.grow.shared.import") => {
+                vmcalls::imported_shared_static_memory_grow as _
+            }
+            fn_name!("vm.memory.size.shared.import") => {
+                vmcalls::imported_shared_static_memory_size as _
+            }
+
+            fn_name!("vm.memory.copy") => vmcalls::memory_copy as _,
+            fn_name!("vm.memory.fill") => vmcalls::memory_fill as _,
+            fn_name!("vm.memory.init") => vmcalls::memory_init as _,
+            fn_name!("vm.data.drop") => vmcalls::data_drop as _,
+            fn_name!("vm.table.copy") => vmcalls::table_copy as _,
+            fn_name!("vm.table.init") => vmcalls::table_init as _,
+            fn_name!("vm.elem.drop") => vmcalls::elem_drop as _,
+
+            fn_name!("vm.memory.atomic.wait32") => vmcalls::memory_atomic_wait32 as _,
+            fn_name!("vm.memory.atomic.wait64") => vmcalls::memory_atomic_wait64 as _,
+            fn_name!("vm.memory.atomic.notify") => vmcalls::memory_atomic_notify as _,
+
+            fn_name!("vm.table.get") => vmcalls::table_get as _,
+            fn_name!("vm.table.set") => vmcalls::table_set as _,
+            fn_name!("vm.table.size") => vmcalls::table_size as _,
+            fn_name!("vm.table.grow") => vmcalls::table_grow as _,
+            fn_name!("vm.table.fill") => vmcalls::table_fill as _,
 
             fn_name!("vm.exception.trap") => throw_trap as _,
             fn_name!("vm.breakpoint") => throw_breakpoint as _,
+            fn_name!("vm.internal.trap") => vmcalls::internal_trap as _,
 
             _ => ptr::null(),
         }
@@ -157,7 +188,7 @@ pub struct LLVMBackend {
 }
 
 impl LLVMBackend {
-    pub fn new(module: Module, _intrinsics: Intrinsics) -> (Self, LLVMCache) {
+    pub fn new(module: Module, _intrinsics: Intrinsics) -> Result<(Self, LLVMCache), String> {
         Target::initialize_x86(&InitializationConfig {
             asm_parser: true,
             asm_printer: true,
@@ -167,7 +198,7 @@ impl LLVMBackend {
             machine_code: true,
         });
         let triple = TargetMachine::get_default_triple().to_string();
-        let target = Target::from_triple(&triple).unwrap();
+        let target = Target::from_triple(&triple).map_err(|e| e.to_string())?;
         let target_machine = target
             .create_target_machine(
                 &triple,
@@ -177,11 +208,11 @@ impl LLVMBackend {
                 RelocMode::PIC,
                 CodeModel::Default,
             )
-            .unwrap();
+            .ok_or_else(|| "cannot create target machine".to_string())?;
 
         let memory_buffer = target_machine
             .write_to_memory_buffer(&module, FileType::Object)
-            .unwrap();
+            .map_err(|e| e.to_string())?;
         let mem_buf_slice = memory_buffer.as_slice();
 
         if let Some(path) = unsafe { &crate::GLOBAL_OPTIONS.obj_file } {
@@ -211,18 +242,18 @@ impl LLVMBackend {
         });
 
         if res != LLVMResult::OK {
-            panic!("failed to load object")
+            return Err("failed to load object".to_string());
         }
 
         let buffer = Arc::new(Buffer::LlvmMemory(memory_buffer));
 
-        (
+        Ok((
             Self {
                 module,
                 buffer: Arc::clone(&buffer),
             },
             LLVMCache { buffer },
-        )
+        ))
     }
 
     pub unsafe fn from_buffer(memory: Memory) -> Result<(Self, LLVMCache), String> {
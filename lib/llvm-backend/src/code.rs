@@ -3,12 +3,12 @@ use inkwell::{
     context::Context,
     module::{Linkage, Module},
     passes::PassManager,
-    types::{BasicType, BasicTypeEnum, FunctionType, PointerType, VectorType},
+    types::{BasicType, BasicTypeEnum, FunctionType, IntType, PointerType, VectorType},
     values::{
         BasicValue, BasicValueEnum, FloatValue, FunctionValue, IntValue, PhiValue, PointerValue,
         VectorValue,
     },
-    AddressSpace, FloatPredicate, IntPredicate,
+    AddressSpace, AtomicOrdering, AtomicRMWBinOp, FloatPredicate, IntPredicate,
 };
 use smallvec::SmallVec;
 use std::sync::{Arc, RwLock};
@@ -26,12 +26,12 @@ use wasmer_runtime_core::{
 use wasmparser::{BinaryReaderError, MemoryImmediate, Operator, Type as WpType};
 
 use crate::backend::LLVMBackend;
-use crate::intrinsics::{CtxType, GlobalCache, Intrinsics, MemoryCache};
-use crate::read_info::{blocktype_to_type, type_to_type};
+use crate::intrinsics::{type_to_llvm_ptr, CtxType, GlobalCache, Intrinsics, MemoryCache};
+use crate::read_info::{blocktype_to_types, type_to_type};
 use crate::state::{ControlFrame, IfElseState, State};
 use crate::trampolines::generate_trampolines;
 
-fn func_sig_to_llvm(context: &Context, intrinsics: &Intrinsics, sig: &FuncSig) -> FunctionType {
+fn func_sig_to_llvm(_context: &Context, intrinsics: &Intrinsics, sig: &FuncSig) -> FunctionType {
     let user_param_types = sig.params().iter().map(|&ty| type_to_llvm(intrinsics, ty));
 
     let param_types: Vec<_> = std::iter::once(intrinsics.ctx_ptr_ty.as_basic_type_enum())
@@ -41,16 +41,38 @@ fn func_sig_to_llvm(context: &Context, intrinsics: &Intrinsics, sig: &FuncSig) -
     match sig.returns() {
         &[] => intrinsics.void_ty.fn_type(&param_types, false),
         &[single_value] => type_to_llvm(intrinsics, single_value).fn_type(&param_types, false),
-        returns @ _ => {
-            let basic_types: Vec<_> = returns
-                .iter()
-                .map(|&ty| type_to_llvm(intrinsics, ty))
-                .collect();
-
-            context
-                .struct_type(&basic_types, false)
-                .fn_type(&param_types, false)
-        }
+        // Functions with multiple results store them in the return buffer of their
+        // `Ctx` and return a pointer to it.
+        _ => intrinsics.i64_ptr_ty.fn_type(&param_types, false),
+    }
+}
+
+/// Loads the results of a function with multiple results from the return buffer
+/// `ptr` points to, and pushes them to the value stack.
+fn push_multi_value_results(
+    builder: &Builder,
+    intrinsics: &Intrinsics,
+    state: &mut State,
+    ptr: PointerValue,
+    returns: &[Type],
+) {
+    let mut slot = 0;
+    for &ty in returns {
+        let value_ptr = unsafe {
+            builder.build_in_bounds_gep(
+                ptr,
+                &[intrinsics.i32_ty.const_int(slot, false)],
+                "return_value_ptr",
+            )
+        };
+        let value_ptr = builder.build_pointer_cast(
+            value_ptr,
+            type_to_llvm_ptr(intrinsics, ty),
+            "typed_return_value_ptr",
+        );
+        let value = builder.build_load(value_ptr, &state.var_name());
+        state.push1(value);
+        slot += if ty == Type::V128 { 2 } else { 1 };
     }
 }
 
@@ -61,6 +83,7 @@ fn type_to_llvm(intrinsics: &Intrinsics, ty: Type) -> BasicTypeEnum {
         Type::F32 => intrinsics.f32_ty.as_basic_type_enum(),
         Type::F64 => intrinsics.f64_ty.as_basic_type_enum(),
         Type::V128 => intrinsics.i128_ty.as_basic_type_enum(),
+        Type::AnyRef | Type::FuncRef => intrinsics.i64_ty.as_basic_type_enum(),
     }
 }
 
@@ -276,13 +299,14 @@ fn trap_if_zero_or_overflow(
         unreachable!()
     };
 
+    let divisor_is_zero = builder.build_int_compare(
+        IntPredicate::EQ,
+        right,
+        int_type.const_int(0, false),
+        "divisor_is_zero",
+    );
     let should_trap = builder.build_or(
-        builder.build_int_compare(
-            IntPredicate::EQ,
-            right,
-            int_type.const_int(0, false),
-            "divisor_is_zero",
-        ),
+        divisor_is_zero,
         builder.build_and(
             builder.build_int_compare(IntPredicate::EQ, left, min_value, "left_is_min"),
             builder.build_int_compare(IntPredicate::EQ, right, neg_one_value, "right_is_neg_one"),
@@ -309,11 +333,13 @@ fn trap_if_zero_or_overflow(
     let should_trap_block = context.append_basic_block(function, "should_trap_block");
     builder.build_conditional_branch(should_trap, &should_trap_block, &shouldnt_trap_block);
     builder.position_at_end(&should_trap_block);
-    builder.build_call(
-        intrinsics.throw_trap,
-        &[intrinsics.trap_illegal_arithmetic],
-        "throw",
+    let trap_code = builder.build_select(
+        divisor_is_zero,
+        intrinsics.trap_divide_by_zero,
+        intrinsics.trap_integer_overflow,
+        "trap_code",
     );
+    builder.build_call(intrinsics.throw_trap, &[trap_code], "throw");
     builder.build_unreachable();
     builder.position_at_end(&shouldnt_trap_block);
 }
@@ -353,7 +379,7 @@ fn trap_if_zero(
     builder.position_at_end(&should_trap_block);
     builder.build_call(
         intrinsics.throw_trap,
-        &[intrinsics.trap_illegal_arithmetic],
+        &[intrinsics.trap_divide_by_zero],
         "throw",
     );
     builder.build_unreachable();
@@ -491,6 +517,160 @@ fn resolve_memory_ptr(
     Ok(builder.build_int_to_ptr(effective_address_int, ptr_ty, &state.var_name()))
 }
 
+/// Traps unless `ptr` is aligned to `size` bytes, as atomic accesses must be.
+fn trap_if_misaligned(
+    builder: &Builder,
+    intrinsics: &Intrinsics,
+    context: &Context,
+    function: &FunctionValue,
+    size: u32,
+    ptr: PointerValue,
+) {
+    if size == 1 {
+        return;
+    }
+    let value = builder.build_ptr_to_int(ptr, intrinsics.i64_ty, "ptr_as_int");
+    let misalignment = builder.build_and(
+        value,
+        intrinsics.i64_ty.const_int(size as u64 - 1, false),
+        "misalignment",
+    );
+    let aligned = builder.build_int_compare(
+        IntPredicate::EQ,
+        misalignment,
+        intrinsics.i64_zero,
+        "aligned",
+    );
+    let aligned = builder
+        .build_call(
+            intrinsics.expect_i1,
+            &[
+                aligned.as_basic_value_enum(),
+                intrinsics.i1_ty.const_int(1, false).as_basic_value_enum(),
+            ],
+            "aligned_expect",
+        )
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value();
+
+    let aligned_continue_block = context.append_basic_block(function, "aligned_continue_block");
+    let misaligned_block = context.append_basic_block(function, "misaligned_block");
+    builder.build_conditional_branch(aligned, &aligned_continue_block, &misaligned_block);
+    builder.position_at_end(&misaligned_block);
+    builder.build_call(
+        intrinsics.throw_trap,
+        &[intrinsics.trap_misaligned_atomic],
+        "throw",
+    );
+    builder.build_unreachable();
+    builder.position_at_end(&aligned_continue_block);
+}
+
+/// The memory accessed by an atomic operator, which can be narrower than
+/// the value it operates on.
+struct AtomicAccess {
+    size: u32,
+    value_size: u32,
+    memory_ty: IntType,
+    memory_ptr_ty: PointerType,
+    value_ty: IntType,
+}
+
+impl AtomicAccess {
+    fn new(intrinsics: &Intrinsics, op: &Operator) -> Self {
+        let (size, value_size) = match *op {
+            Operator::I32AtomicLoad8U { .. }
+            | Operator::I32AtomicStore8 { .. }
+            | Operator::I32AtomicRmw8UAdd { .. }
+            | Operator::I32AtomicRmw8USub { .. }
+            | Operator::I32AtomicRmw8UAnd { .. }
+            | Operator::I32AtomicRmw8UOr { .. }
+            | Operator::I32AtomicRmw8UXor { .. }
+            | Operator::I32AtomicRmw8UXchg { .. }
+            | Operator::I32AtomicRmw8UCmpxchg { .. } => (1, 4),
+            Operator::I32AtomicLoad16U { .. }
+            | Operator::I32AtomicStore16 { .. }
+            | Operator::I32AtomicRmw16UAdd { .. }
+            | Operator::I32AtomicRmw16USub { .. }
+            | Operator::I32AtomicRmw16UAnd { .. }
+            | Operator::I32AtomicRmw16UOr { .. }
+            | Operator::I32AtomicRmw16UXor { .. }
+            | Operator::I32AtomicRmw16UXchg { .. }
+            | Operator::I32AtomicRmw16UCmpxchg { .. } => (2, 4),
+            Operator::I32AtomicLoad { .. }
+            | Operator::I32AtomicStore { .. }
+            | Operator::I32AtomicRmwAdd { .. }
+            | Operator::I32AtomicRmwSub { .. }
+            | Operator::I32AtomicRmwAnd { .. }
+            | Operator::I32AtomicRmwOr { .. }
+            | Operator::I32AtomicRmwXor { .. }
+            | Operator::I32AtomicRmwXchg { .. }
+            | Operator::I32AtomicRmwCmpxchg { .. } => (4, 4),
+            Operator::I64AtomicLoad8U { .. }
+            | Operator::I64AtomicStore8 { .. }
+            | Operator::I64AtomicRmw8UAdd { .. }
+            | Operator::I64AtomicRmw8USub { .. }
+            | Operator::I64AtomicRmw8UAnd { .. }
+            | Operator::I64AtomicRmw8UOr { .. }
+            | Operator::I64AtomicRmw8UXor { .. }
+            | Operator::I64AtomicRmw8UXchg { .. }
+            | Operator::I64AtomicRmw8UCmpxchg { .. } => (1, 8),
+            Operator::I64AtomicLoad16U { .. }
+            | Operator::I64AtomicStore16 { .. }
+            | Operator::I64AtomicRmw16UAdd { .. }
+            | Operator::I64AtomicRmw16USub { .. }
+            | Operator::I64AtomicRmw16UAnd { .. }
+            | Operator::I64AtomicRmw16UOr { .. }
+            | Operator::I64AtomicRmw16UXor { .. }
+            | Operator::I64AtomicRmw16UXchg { .. }
+            | Operator::I64AtomicRmw16UCmpxchg { .. } => (2, 8),
+            Operator::I64AtomicLoad32U { .. }
+            | Operator::I64AtomicStore32 { .. }
+            | Operator::I64AtomicRmw32UAdd { .. }
+            | Operator::I64AtomicRmw32USub { .. }
+            | Operator::I64AtomicRmw32UAnd { .. }
+            | Operator::I64AtomicRmw32UOr { .. }
+            | Operator::I64AtomicRmw32UXor { .. }
+            | Operator::I64AtomicRmw32UXchg { .. }
+            | Operator::I64AtomicRmw32UCmpxchg { .. } => (4, 8),
+            _ => (8, 8),
+        };
+        let int_ty = |size| match size {
+            1 => (intrinsics.i8_ty, intrinsics.i8_ptr_ty),
+            2 => (intrinsics.i16_ty, intrinsics.i16_ptr_ty),
+            4 => (intrinsics.i32_ty, intrinsics.i32_ptr_ty),
+            _ => (intrinsics.i64_ty, intrinsics.i64_ptr_ty),
+        };
+        let (memory_ty, memory_ptr_ty) = int_ty(size);
+        let (value_ty, _) = int_ty(value_size);
+        AtomicAccess {
+            size,
+            value_size,
+            memory_ty,
+            memory_ptr_ty,
+            value_ty,
+        }
+    }
+
+    fn truncate(&self, builder: &Builder, value: IntValue, name: &str) -> IntValue {
+        if self.size == self.value_size {
+            value
+        } else {
+            builder.build_int_truncate(value, self.memory_ty, name)
+        }
+    }
+
+    fn zero_extend(&self, builder: &Builder, value: IntValue, name: &str) -> IntValue {
+        if self.size == self.value_size {
+            value
+        } else {
+            builder.build_int_z_extend(value, self.value_ty, name)
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CodegenError {
     pub message: String,
@@ -564,6 +744,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
             Type::F32 => intrinsics.f32_zero.as_basic_value_enum(),
             Type::F64 => intrinsics.f64_zero.as_basic_value_enum(),
             Type::V128 => intrinsics.i128_zero.as_basic_value_enum(),
+            Type::AnyRef | Type::FuncRef => intrinsics.i64_zero.as_basic_value_enum(),
         };
 
         let builder = self.builder.as_ref().unwrap();
@@ -637,6 +818,16 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                         );
                         return Ok(());
                     }
+                    InternalEvent::Trap(trap) => {
+                        if state.reachable {
+                            let code = intrinsics.i32_ty.const_int(trap as u64, false);
+                            builder.build_call(
+                                intrinsics.internal_trap,
+                                &[ctx.basic(), code.as_basic_value_enum()],
+                                "",
+                            );
+                        }
+                    }
                     InternalEvent::GetInternal(idx) => {
                         if state.reachable {
                             let idx = idx as usize;
@@ -695,38 +886,49 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                 let end_block = context.append_basic_block(&function, "end");
                 builder.position_at_end(&end_block);
 
-                let phis = if let Ok(wasmer_ty) = blocktype_to_type(ty) {
-                    let llvm_ty = type_to_llvm(intrinsics, wasmer_ty);
-                    [llvm_ty]
-                        .iter()
-                        .map(|&ty| builder.build_phi(ty, &state.var_name()))
-                        .collect()
-                } else {
-                    SmallVec::new()
-                };
+                let (params, returns) = blocktype_to_types(info, ty)?;
+                let phis = returns
+                    .iter()
+                    .map(|&ty| builder.build_phi(type_to_llvm(intrinsics, ty), &state.var_name()))
+                    .collect();
 
-                state.push_block(end_block, phis);
+                state.push_block(end_block, phis, params.len());
                 builder.position_at_end(&current_block);
             }
             Operator::Loop { ty } => {
+                let current_block = builder.get_insert_block().ok_or(BinaryReaderError {
+                    message: "not currently in a block",
+                    offset: -1isize as usize,
+                })?;
                 let loop_body = context.append_basic_block(&function, "loop_body");
                 let loop_next = context.append_basic_block(&function, "loop_outer");
 
                 builder.build_unconditional_branch(&loop_body);
 
+                let (params, returns) = blocktype_to_types(info, ty)?;
+
                 builder.position_at_end(&loop_next);
-                let phis = if let Ok(wasmer_ty) = blocktype_to_type(ty) {
-                    let llvm_ty = type_to_llvm(intrinsics, wasmer_ty);
-                    [llvm_ty]
-                        .iter()
-                        .map(|&ty| builder.build_phi(ty, &state.var_name()))
-                        .collect()
-                } else {
-                    SmallVec::new()
-                };
+                let phis = returns
+                    .iter()
+                    .map(|&ty| builder.build_phi(type_to_llvm(intrinsics, ty), &state.var_name()))
+                    .collect();
 
+                // The loop parameters enter the loop body through phis, so that
+                // branches back to the loop header can supply new values.
                 builder.position_at_end(&loop_body);
-                state.push_loop(loop_body, loop_next, phis);
+                let loop_body_phis: SmallVec<[PhiValue; 1]> = params
+                    .iter()
+                    .map(|&ty| builder.build_phi(type_to_llvm(intrinsics, ty), &state.var_name()))
+                    .collect();
+                let param_values = state.popn_save(params.len())?;
+                for (phi, value) in loop_body_phis.iter().zip(param_values.iter()) {
+                    phi.add_incoming(&[(value, &current_block)]);
+                }
+
+                state.push_loop(loop_body, loop_next, phis, loop_body_phis.clone());
+                for phi in loop_body_phis.iter() {
+                    state.push1(phi.as_basic_value());
+                }
             }
             Operator::Br { relative_depth } => {
                 let frame = state.frame_at_depth(relative_depth)?;
@@ -736,18 +938,14 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     offset: -1isize as usize,
                 })?;
 
-                let value_len = if frame.is_loop() {
-                    0
-                } else {
-                    frame.phis().len()
-                };
+                let value_len = frame.br_phis().len();
 
                 let values = state.peekn(value_len)?;
 
-                // For each result of the block we're branching to,
+                // For each value carried to the block we're branching to,
                 // pop a value off the value stack and load it into
                 // the corresponding phi.
-                for (phi, value) in frame.phis().iter().zip(values.iter()) {
+                for (phi, value) in frame.br_phis().iter().zip(values.iter()) {
                     phi.add_incoming(&[(value, &current_block)]);
                 }
 
@@ -765,15 +963,11 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     offset: -1isize as usize,
                 })?;
 
-                let value_len = if frame.is_loop() {
-                    0
-                } else {
-                    frame.phis().len()
-                };
+                let value_len = frame.br_phis().len();
 
                 let param_stack = state.peekn(value_len)?;
 
-                for (phi, value) in frame.phis().iter().zip(param_stack.iter()) {
+                for (phi, value) in frame.br_phis().iter().zip(param_stack.iter()) {
                     phi.add_incoming(&[(value, &current_block)]);
                 }
 
@@ -800,14 +994,9 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
 
                 let default_frame = state.frame_at_depth(default_depth)?;
 
-                let args = if default_frame.is_loop() {
-                    &[]
-                } else {
-                    let res_len = default_frame.phis().len();
-                    state.peekn(res_len)?
-                };
+                let args = state.peekn(default_frame.br_phis().len())?;
 
-                for (phi, value) in default_frame.phis().iter().zip(args.iter()) {
+                for (phi, value) in default_frame.br_phis().iter().zip(args.iter()) {
                     phi.add_incoming(&[(value, &current_block)]);
                 }
 
@@ -824,7 +1013,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                         let case_index_literal =
                             context.i32_type().const_int(case_index as u64, false);
 
-                        for (phi, value) in frame.phis().iter().zip(args.iter()) {
+                        for (phi, value) in frame.br_phis().iter().zip(args.iter()) {
                             phi.add_incoming(&[(value, &current_block)]);
                         }
 
@@ -847,24 +1036,25 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                 let if_else_block = context.append_basic_block(&function, "if_else");
                 let end_block = context.append_basic_block(&function, "if_end");
 
+                let (params, returns) = blocktype_to_types(info, ty)?;
+
                 let end_phis = {
                     builder.position_at_end(&end_block);
 
-                    let phis = if let Ok(wasmer_ty) = blocktype_to_type(ty) {
-                        let llvm_ty = type_to_llvm(intrinsics, wasmer_ty);
-                        [llvm_ty]
-                            .iter()
-                            .map(|&ty| builder.build_phi(ty, &state.var_name()))
-                            .collect()
-                    } else {
-                        SmallVec::new()
-                    };
+                    let phis = returns
+                        .iter()
+                        .map(|&ty| {
+                            builder.build_phi(type_to_llvm(intrinsics, ty), &state.var_name())
+                        })
+                        .collect();
 
                     builder.position_at_end(&current_block);
                     phis
                 };
 
                 let cond = state.pop1()?;
+                let else_params: SmallVec<[BasicValueEnum; 1]> =
+                    state.popn_save(params.len())?.into_iter().collect();
 
                 let cond_value = builder.build_int_compare(
                     IntPredicate::NE,
@@ -875,7 +1065,16 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
 
                 builder.build_conditional_branch(cond_value, &if_then_block, &if_else_block);
                 builder.position_at_end(&if_then_block);
-                state.push_if(if_then_block, if_else_block, end_block, end_phis);
+                state.push_if(
+                    if_then_block,
+                    if_else_block,
+                    end_block,
+                    end_phis,
+                    else_params.clone(),
+                );
+                for param in else_params.iter() {
+                    state.push1(*param);
+                }
             }
             Operator::Else => {
                 if state.reachable {
@@ -892,6 +1091,13 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     }
                 }
 
+                let else_params =
+                    if let ControlFrame::IfElse { else_params, .. } = state.frame_at_depth(0)? {
+                        else_params.clone()
+                    } else {
+                        unreachable!()
+                    };
+
                 let (if_else_block, if_else_state) = if let ControlFrame::IfElse {
                     if_else,
                     if_else_state,
@@ -906,6 +1112,9 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                 *if_else_state = IfElseState::Else;
 
                 builder.position_at_end(if_else_block);
+                for param in else_params.iter() {
+                    state.push1(*param);
+                }
                 state.reachable = true;
             }
 
@@ -928,13 +1137,19 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                 if let ControlFrame::IfElse {
                     if_else,
                     next,
+                    phis,
+                    else_params,
                     if_else_state,
                     ..
                 } = &frame
                 {
                     if let IfElseState::If = if_else_state {
+                        // Without an else arm, the parameters are passed through as results.
                         builder.position_at_end(if_else);
                         builder.build_unconditional_branch(next);
+                        for (phi, value) in phis.iter().zip(else_params.iter()) {
+                            phi.add_incoming(&[(value, if_else)]);
+                        }
                     }
                 }
 
@@ -956,7 +1171,14 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                             BasicTypeEnum::FloatType(float_ty) => {
                                 float_ty.const_float(0.0).as_basic_value_enum()
                             }
-                            _ => unimplemented!(),
+                            _ => {
+                                return Err(CodegenError {
+                                    message: format!(
+                                        "unsupported block result type: {:?}",
+                                        basic_ty
+                                    ),
+                                });
+                            }
                         };
                         state.push1(placeholder_value);
                         phi.as_instruction().erase_from_basic_block();
@@ -1214,16 +1436,13 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                 if let Some(basic_value) = call_site.try_as_basic_value().left() {
                     match func_sig.returns().len() {
                         1 => state.push1(basic_value),
-                        count @ _ => {
-                            // This is a multi-value return.
-                            let struct_value = basic_value.into_struct_value();
-                            for i in 0..(count as u32) {
-                                let value = builder
-                                    .build_extract_value(struct_value, i, &state.var_name())
-                                    .unwrap();
-                                state.push1(value);
-                            }
-                        }
+                        _ => push_multi_value_results(
+                            builder,
+                            intrinsics,
+                            state,
+                            basic_value.into_pointer_value(),
+                            func_sig.returns(),
+                        ),
                     }
                 }
             }
@@ -1359,6 +1578,21 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                 builder.build_unreachable();
                 builder.position_at_end(&continue_block);
 
+                // Host functions stored in a table have no context of their own
+                // and are called with the caller's.
+                let ctx_ptr_is_null = builder.build_int_compare(
+                    IntPredicate::EQ,
+                    builder.build_ptr_to_int(
+                        ctx_ptr.into_pointer_value(),
+                        intrinsics.i64_ty,
+                        "ctx_ptr_as_int",
+                    ),
+                    intrinsics.i64_zero,
+                    "ctx_ptr_is_null",
+                );
+                let ctx_ptr =
+                    builder.build_select(ctx_ptr_is_null, ctx.basic(), ctx_ptr, "ctx_ptr");
+
                 let wasmer_fn_sig = &info.signatures[sig_index];
                 let fn_ty = signatures[sig_index];
 
@@ -1382,7 +1616,17 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                         let value = call_site.try_as_basic_value().left().unwrap();
                         state.push1(value);
                     }
-                    _ => unimplemented!("multi-value returns"),
+                    returns => push_multi_value_results(
+                        builder,
+                        intrinsics,
+                        state,
+                        call_site
+                            .try_as_basic_value()
+                            .left()
+                            .unwrap()
+                            .into_pointer_value(),
+                        returns,
+                    ),
                 }
             }
 
@@ -4462,8 +4706,411 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                 );
                 state.push1(result.try_as_basic_value().left().unwrap());
             }
+            Operator::MemoryCopy | Operator::MemoryFill | Operator::TableCopy => {
+                let func_value = match *op {
+                    Operator::MemoryCopy => intrinsics.memory_copy,
+                    Operator::MemoryFill => intrinsics.memory_fill,
+                    _ => intrinsics.table_copy,
+                };
+                let index_const = intrinsics.i32_zero.as_basic_value_enum();
+                let (v1, v2, v3) = state.pop3()?;
+                builder.build_call(
+                    func_value,
+                    &[ctx.basic(), index_const, v1, v2, v3],
+                    &state.var_name(),
+                );
+            }
+            Operator::MemoryInit { segment } | Operator::TableInit { segment } => {
+                let func_value = match *op {
+                    Operator::MemoryInit { .. } => intrinsics.memory_init,
+                    _ => intrinsics.table_init,
+                };
+                let index_const = intrinsics.i32_zero.as_basic_value_enum();
+                let segment_const = intrinsics
+                    .i32_ty
+                    .const_int(segment as u64, false)
+                    .as_basic_value_enum();
+                let (v1, v2, v3) = state.pop3()?;
+                builder.build_call(
+                    func_value,
+                    &[ctx.basic(), index_const, segment_const, v1, v2, v3],
+                    &state.var_name(),
+                );
+            }
+            Operator::DataDrop { segment } | Operator::ElemDrop { segment } => {
+                let func_value = match *op {
+                    Operator::DataDrop { .. } => intrinsics.data_drop,
+                    _ => intrinsics.elem_drop,
+                };
+                let segment_const = intrinsics
+                    .i32_ty
+                    .const_int(segment as u64, false)
+                    .as_basic_value_enum();
+                builder.build_call(func_value, &[ctx.basic(), segment_const], &state.var_name());
+            }
+            Operator::RefNull => {
+                state.push1(intrinsics.i64_zero);
+            }
+            Operator::RefIsNull => {
+                let value = state.pop1()?.into_int_value();
+                let is_null = builder.build_int_compare(
+                    IntPredicate::EQ,
+                    value,
+                    intrinsics.i64_zero,
+                    &state.var_name(),
+                );
+                let res = builder.build_int_z_extend(is_null, intrinsics.i32_ty, &state.var_name());
+                state.push1(res);
+            }
+            Operator::TableGet { table } => {
+                let table_const = intrinsics
+                    .i32_ty
+                    .const_int(table as u64, false)
+                    .as_basic_value_enum();
+                let index = state.pop1()?;
+                let res = builder
+                    .build_call(
+                        intrinsics.table_get,
+                        &[ctx.basic(), table_const, index],
+                        &state.var_name(),
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap();
+                state.push1(res);
+            }
+            Operator::TableSet { table } => {
+                let table_const = intrinsics
+                    .i32_ty
+                    .const_int(table as u64, false)
+                    .as_basic_value_enum();
+                let (index, value) = state.pop2()?;
+                builder.build_call(
+                    intrinsics.table_set,
+                    &[ctx.basic(), table_const, index, value],
+                    &state.var_name(),
+                );
+            }
+            Operator::TableSize { table } => {
+                let table_const = intrinsics
+                    .i32_ty
+                    .const_int(table as u64, false)
+                    .as_basic_value_enum();
+                let res = builder
+                    .build_call(
+                        intrinsics.table_size,
+                        &[ctx.basic(), table_const],
+                        &state.var_name(),
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap();
+                state.push1(res);
+            }
+            Operator::TableGrow { table } => {
+                let table_const = intrinsics
+                    .i32_ty
+                    .const_int(table as u64, false)
+                    .as_basic_value_enum();
+                let (value, delta) = state.pop2()?;
+                let res = builder
+                    .build_call(
+                        intrinsics.table_grow,
+                        &[ctx.basic(), table_const, value, delta],
+                        &state.var_name(),
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap();
+                state.push1(res);
+            }
+            Operator::TableFill { table } => {
+                let table_const = intrinsics
+                    .i32_ty
+                    .const_int(table as u64, false)
+                    .as_basic_value_enum();
+                let (dst, value, len) = state.pop3()?;
+                builder.build_call(
+                    intrinsics.table_fill,
+                    &[ctx.basic(), table_const, dst, value, len],
+                    &state.var_name(),
+                );
+            }
+            Operator::I32AtomicLoad { ref memarg }
+            | Operator::I64AtomicLoad { ref memarg }
+            | Operator::I32AtomicLoad8U { ref memarg }
+            | Operator::I32AtomicLoad16U { ref memarg }
+            | Operator::I64AtomicLoad8U { ref memarg }
+            | Operator::I64AtomicLoad16U { ref memarg }
+            | Operator::I64AtomicLoad32U { ref memarg } => {
+                let access = AtomicAccess::new(intrinsics, op);
+                let effective_address = resolve_memory_ptr(
+                    builder,
+                    intrinsics,
+                    context,
+                    &function,
+                    &mut state,
+                    &mut ctx,
+                    memarg,
+                    access.memory_ptr_ty,
+                    access.size as usize,
+                )?;
+                trap_if_misaligned(
+                    builder,
+                    intrinsics,
+                    context,
+                    &function,
+                    access.size,
+                    effective_address,
+                );
+                let narrow_result = builder.build_load(effective_address, &state.var_name());
+                let load = narrow_result.as_instruction_value().unwrap();
+                load.set_alignment(access.size).unwrap();
+                load.set_atomic_ordering(AtomicOrdering::SequentiallyConsistent)
+                    .unwrap();
+                let result =
+                    access.zero_extend(builder, narrow_result.into_int_value(), &state.var_name());
+                state.push1(result);
+            }
+            Operator::I32AtomicStore { ref memarg }
+            | Operator::I64AtomicStore { ref memarg }
+            | Operator::I32AtomicStore8 { ref memarg }
+            | Operator::I32AtomicStore16 { ref memarg }
+            | Operator::I64AtomicStore8 { ref memarg }
+            | Operator::I64AtomicStore16 { ref memarg }
+            | Operator::I64AtomicStore32 { ref memarg } => {
+                let value = state.pop1()?.into_int_value();
+                let access = AtomicAccess::new(intrinsics, op);
+                let effective_address = resolve_memory_ptr(
+                    builder,
+                    intrinsics,
+                    context,
+                    &function,
+                    &mut state,
+                    &mut ctx,
+                    memarg,
+                    access.memory_ptr_ty,
+                    access.size as usize,
+                )?;
+                trap_if_misaligned(
+                    builder,
+                    intrinsics,
+                    context,
+                    &function,
+                    access.size,
+                    effective_address,
+                );
+                let narrow_value = access.truncate(builder, value, &state.var_name());
+                let store = builder.build_store(effective_address, narrow_value);
+                store.set_alignment(access.size).unwrap();
+                store
+                    .set_atomic_ordering(AtomicOrdering::SequentiallyConsistent)
+                    .unwrap();
+            }
+            Operator::I32AtomicRmwAdd { ref memarg }
+            | Operator::I64AtomicRmwAdd { ref memarg }
+            | Operator::I32AtomicRmw8UAdd { ref memarg }
+            | Operator::I32AtomicRmw16UAdd { ref memarg }
+            | Operator::I64AtomicRmw8UAdd { ref memarg }
+            | Operator::I64AtomicRmw16UAdd { ref memarg }
+            | Operator::I64AtomicRmw32UAdd { ref memarg }
+            | Operator::I32AtomicRmwSub { ref memarg }
+            | Operator::I64AtomicRmwSub { ref memarg }
+            | Operator::I32AtomicRmw8USub { ref memarg }
+            | Operator::I32AtomicRmw16USub { ref memarg }
+            | Operator::I64AtomicRmw8USub { ref memarg }
+            | Operator::I64AtomicRmw16USub { ref memarg }
+            | Operator::I64AtomicRmw32USub { ref memarg }
+            | Operator::I32AtomicRmwAnd { ref memarg }
+            | Operator::I64AtomicRmwAnd { ref memarg }
+            | Operator::I32AtomicRmw8UAnd { ref memarg }
+            | Operator::I32AtomicRmw16UAnd { ref memarg }
+            | Operator::I64AtomicRmw8UAnd { ref memarg }
+            | Operator::I64AtomicRmw16UAnd { ref memarg }
+            | Operator::I64AtomicRmw32UAnd { ref memarg }
+            | Operator::I32AtomicRmwOr { ref memarg }
+            | Operator::I64AtomicRmwOr { ref memarg }
+            | Operator::I32AtomicRmw8UOr { ref memarg }
+            | Operator::I32AtomicRmw16UOr { ref memarg }
+            | Operator::I64AtomicRmw8UOr { ref memarg }
+            | Operator::I64AtomicRmw16UOr { ref memarg }
+            | Operator::I64AtomicRmw32UOr { ref memarg }
+            | Operator::I32AtomicRmwXor { ref memarg }
+            | Operator::I64AtomicRmwXor { ref memarg }
+            | Operator::I32AtomicRmw8UXor { ref memarg }
+            | Operator::I32AtomicRmw16UXor { ref memarg }
+            | Operator::I64AtomicRmw8UXor { ref memarg }
+            | Operator::I64AtomicRmw16UXor { ref memarg }
+            | Operator::I64AtomicRmw32UXor { ref memarg }
+            | Operator::I32AtomicRmwXchg { ref memarg }
+            | Operator::I64AtomicRmwXchg { ref memarg }
+            | Operator::I32AtomicRmw8UXchg { ref memarg }
+            | Operator::I32AtomicRmw16UXchg { ref memarg }
+            | Operator::I64AtomicRmw8UXchg { ref memarg }
+            | Operator::I64AtomicRmw16UXchg { ref memarg }
+            | Operator::I64AtomicRmw32UXchg { ref memarg } => {
+                let rmw_op = match *op {
+                    Operator::I32AtomicRmwAdd { .. }
+                    | Operator::I64AtomicRmwAdd { .. }
+                    | Operator::I32AtomicRmw8UAdd { .. }
+                    | Operator::I32AtomicRmw16UAdd { .. }
+                    | Operator::I64AtomicRmw8UAdd { .. }
+                    | Operator::I64AtomicRmw16UAdd { .. }
+                    | Operator::I64AtomicRmw32UAdd { .. } => AtomicRMWBinOp::Add,
+                    Operator::I32AtomicRmwSub { .. }
+                    | Operator::I64AtomicRmwSub { .. }
+                    | Operator::I32AtomicRmw8USub { .. }
+                    | Operator::I32AtomicRmw16USub { .. }
+                    | Operator::I64AtomicRmw8USub { .. }
+                    | Operator::I64AtomicRmw16USub { .. }
+                    | Operator::I64AtomicRmw32USub { .. } => AtomicRMWBinOp::Sub,
+                    Operator::I32AtomicRmwAnd { .. }
+                    | Operator::I64AtomicRmwAnd { .. }
+                    | Operator::I32AtomicRmw8UAnd { .. }
+                    | Operator::I32AtomicRmw16UAnd { .. }
+                    | Operator::I64AtomicRmw8UAnd { .. }
+                    | Operator::I64AtomicRmw16UAnd { .. }
+                    | Operator::I64AtomicRmw32UAnd { .. } => AtomicRMWBinOp::And,
+                    Operator::I32AtomicRmwOr { .. }
+                    | Operator::I64AtomicRmwOr { .. }
+                    | Operator::I32AtomicRmw8UOr { .. }
+                    | Operator::I32AtomicRmw16UOr { .. }
+                    | Operator::I64AtomicRmw8UOr { .. }
+                    | Operator::I64AtomicRmw16UOr { .. }
+                    | Operator::I64AtomicRmw32UOr { .. } => AtomicRMWBinOp::Or,
+                    Operator::I32AtomicRmwXor { .. }
+                    | Operator::I64AtomicRmwXor { .. }
+                    | Operator::I32AtomicRmw8UXor { .. }
+                    | Operator::I32AtomicRmw16UXor { .. }
+                    | Operator::I64AtomicRmw8UXor { .. }
+                    | Operator::I64AtomicRmw16UXor { .. }
+                    | Operator::I64AtomicRmw32UXor { .. } => AtomicRMWBinOp::Xor,
+                    _ => AtomicRMWBinOp::Xchg,
+                };
+                let value = state.pop1()?.into_int_value();
+                let access = AtomicAccess::new(intrinsics, op);
+                let effective_address = resolve_memory_ptr(
+                    builder,
+                    intrinsics,
+                    context,
+                    &function,
+                    &mut state,
+                    &mut ctx,
+                    memarg,
+                    access.memory_ptr_ty,
+                    access.size as usize,
+                )?;
+                trap_if_misaligned(
+                    builder,
+                    intrinsics,
+                    context,
+                    &function,
+                    access.size,
+                    effective_address,
+                );
+                let narrow_value = access.truncate(builder, value, &state.var_name());
+                let old = builder
+                    .build_atomicrmw(
+                        rmw_op,
+                        effective_address,
+                        narrow_value,
+                        AtomicOrdering::SequentiallyConsistent,
+                    )
+                    .unwrap();
+                let result = access.zero_extend(builder, old, &state.var_name());
+                state.push1(result);
+            }
+            Operator::I32AtomicRmwCmpxchg { ref memarg }
+            | Operator::I64AtomicRmwCmpxchg { ref memarg }
+            | Operator::I32AtomicRmw8UCmpxchg { ref memarg }
+            | Operator::I32AtomicRmw16UCmpxchg { ref memarg }
+            | Operator::I64AtomicRmw8UCmpxchg { ref memarg }
+            | Operator::I64AtomicRmw16UCmpxchg { ref memarg }
+            | Operator::I64AtomicRmw32UCmpxchg { ref memarg } => {
+                let (expected, replacement) = state.pop2()?;
+                let access = AtomicAccess::new(intrinsics, op);
+                let effective_address = resolve_memory_ptr(
+                    builder,
+                    intrinsics,
+                    context,
+                    &function,
+                    &mut state,
+                    &mut ctx,
+                    memarg,
+                    access.memory_ptr_ty,
+                    access.size as usize,
+                )?;
+                trap_if_misaligned(
+                    builder,
+                    intrinsics,
+                    context,
+                    &function,
+                    access.size,
+                    effective_address,
+                );
+                let narrow_expected =
+                    access.truncate(builder, expected.into_int_value(), &state.var_name());
+                let narrow_replacement =
+                    access.truncate(builder, replacement.into_int_value(), &state.var_name());
+                let old = builder
+                    .build_cmpxchg(
+                        effective_address,
+                        narrow_expected,
+                        narrow_replacement,
+                        AtomicOrdering::SequentiallyConsistent,
+                        AtomicOrdering::SequentiallyConsistent,
+                    )
+                    .unwrap();
+                let old = builder
+                    .build_extract_value(old, 0, &state.var_name())
+                    .unwrap()
+                    .into_int_value();
+                let result = access.zero_extend(builder, old, &state.var_name());
+                state.push1(result);
+            }
+            Operator::I32Wait { ref memarg } | Operator::I64Wait { ref memarg } => {
+                let func_value = match *op {
+                    Operator::I32Wait { .. } => intrinsics.memory_atomic_wait32,
+                    _ => intrinsics.memory_atomic_wait64,
+                };
+                let index_const = intrinsics.i32_zero.as_basic_value_enum();
+                let offset_const = intrinsics
+                    .i32_ty
+                    .const_int(memarg.offset as u64, false)
+                    .as_basic_value_enum();
+                let (addr, expected, timeout) = state.pop3()?;
+                let result = builder.build_call(
+                    func_value,
+                    &[
+                        ctx.basic(),
+                        index_const,
+                        offset_const,
+                        addr,
+                        expected,
+                        timeout,
+                    ],
+                    &state.var_name(),
+                );
+                state.push1(result.try_as_basic_value().left().unwrap());
+            }
+            Operator::Wake { ref memarg } => {
+                let index_const = intrinsics.i32_zero.as_basic_value_enum();
+                let offset_const = intrinsics
+                    .i32_ty
+                    .const_int(memarg.offset as u64, false)
+                    .as_basic_value_enum();
+                let (addr, count) = state.pop2()?;
+                let result = builder.build_call(
+                    intrinsics.memory_atomic_notify,
+                    &[ctx.basic(), index_const, offset_const, addr, count],
+                    &state.var_name(),
+                );
+                state.push1(result.try_as_basic_value().left().unwrap());
+            }
             _ => {
-                unimplemented!("{:?}", op);
+                return Err(CodegenError {
+                    message: format!("unsupported operator: {:?}", op),
+                });
             }
         }
 
@@ -4480,7 +5127,31 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
             [one_value] => {
                 self.builder.as_ref().unwrap().build_return(Some(one_value));
             }
-            _ => unimplemented!("multi-value returns not yet implemented"),
+            _ => {
+                let builder = self.builder.as_ref().unwrap();
+                let intrinsics = self.intrinsics.as_ref().unwrap();
+                let buffer = self.ctx.as_mut().unwrap().return_values(builder);
+
+                let mut slot = 0;
+                for (value, &ty) in results.iter().zip(self.func_sig.returns().iter()) {
+                    let value_ptr = unsafe {
+                        builder.build_in_bounds_gep(
+                            buffer,
+                            &[intrinsics.i32_ty.const_int(slot, false)],
+                            "return_value_ptr",
+                        )
+                    };
+                    let value_ptr = builder.build_pointer_cast(
+                        value_ptr,
+                        type_to_llvm_ptr(intrinsics, ty),
+                        "typed_return_value_ptr",
+                    );
+                    builder.build_store(value_ptr, *value);
+                    slot += if ty == Type::V128 { 2 } else { 1 };
+                }
+
+                builder.build_return(Some(&buffer));
+            }
         }
         Ok(())
     }
@@ -4576,7 +5247,7 @@ impl ModuleCodeGenerator<LLVMFunctionCodeGenerator, LLVMBackend, CodegenError>
             .map(|ty| builder.build_phi(ty, &state.var_name()))
             .collect();
 
-        state.push_block(return_block, phis);
+        state.push_block(return_block, phis, 0);
         builder.position_at_end(&entry_block);
 
         let mut locals = Vec::new();
@@ -4668,7 +5339,8 @@ impl ModuleCodeGenerator<LLVMFunctionCodeGenerator, LLVMBackend, CodegenError>
             self.module.print_to_file(path).unwrap();
         }
 
-        let (backend, cache_gen) = LLVMBackend::new(self.module, self.intrinsics.take().unwrap());
+        let (backend, cache_gen) = LLVMBackend::new(self.module, self.intrinsics.take().unwrap())
+            .map_err(|message| CodegenError { message })?;
         Ok((backend, Box::new(cache_gen)))
     }
 
@@ -22,13 +22,14 @@ use wasmer_runtime_core::{
     vm::{Ctx, INTERNALS_SIZE},
 };
 
-fn type_to_llvm_ptr(intrinsics: &Intrinsics, ty: Type) -> PointerType {
+pub fn type_to_llvm_ptr(intrinsics: &Intrinsics, ty: Type) -> PointerType {
     match ty {
         Type::I32 => intrinsics.i32_ptr_ty,
         Type::I64 => intrinsics.i64_ptr_ty,
         Type::F32 => intrinsics.f32_ptr_ty,
         Type::F64 => intrinsics.f64_ptr_ty,
         Type::V128 => intrinsics.i128_ptr_ty,
+        Type::AnyRef | Type::FuncRef => intrinsics.i64_ptr_ty,
     }
 }
 
@@ -131,6 +132,9 @@ pub struct Intrinsics {
     pub trap_call_indirect_oob: BasicValueEnum,
     pub trap_memory_oob: BasicValueEnum,
     pub trap_illegal_arithmetic: BasicValueEnum,
+    pub trap_divide_by_zero: BasicValueEnum,
+    pub trap_integer_overflow: BasicValueEnum,
+    pub trap_misaligned_atomic: BasicValueEnum,
 
     // VM intrinsics.
     pub memory_grow_dynamic_local: FunctionValue,
@@ -147,8 +151,27 @@ pub struct Intrinsics {
     pub memory_size_static_import: FunctionValue,
     pub memory_size_shared_import: FunctionValue,
 
+    pub memory_copy: FunctionValue,
+    pub memory_fill: FunctionValue,
+    pub memory_init: FunctionValue,
+    pub data_drop: FunctionValue,
+    pub table_copy: FunctionValue,
+    pub table_init: FunctionValue,
+    pub elem_drop: FunctionValue,
+
+    pub memory_atomic_wait32: FunctionValue,
+    pub memory_atomic_wait64: FunctionValue,
+    pub memory_atomic_notify: FunctionValue,
+
+    pub table_get: FunctionValue,
+    pub table_set: FunctionValue,
+    pub table_size: FunctionValue,
+    pub table_grow: FunctionValue,
+    pub table_fill: FunctionValue,
+
     pub throw_trap: FunctionValue,
     pub throw_breakpoint: FunctionValue,
+    pub internal_trap: FunctionValue,
 
     pub ctx_ptr_ty: PointerType,
 }
@@ -216,6 +239,7 @@ impl Intrinsics {
         let memory_bound_ty = i8_ty;
         let internals_ty = i64_ty;
         let interrupt_signal_mem_ty = i8_ty;
+        let return_values_ty = i64_ty;
         let local_function_ty = i8_ptr_ty;
 
         let anyfunc_ty = context.struct_type(
@@ -277,6 +301,9 @@ impl Intrinsics {
                 interrupt_signal_mem_ty
                     .ptr_type(AddressSpace::Generic)
                     .as_basic_type_enum(),
+                return_values_ty
+                    .ptr_type(AddressSpace::Generic)
+                    .as_basic_type_enum(),
                 local_function_ty
                     .ptr_type(AddressSpace::Generic)
                     .as_basic_type_enum(),
@@ -309,6 +336,95 @@ impl Intrinsics {
         );
         let ret_i32_take_ctx_i32 =
             i32_ty.fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false);
+        let ret_void_take_ctx_i32 =
+            void_ty.fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false);
+        let ret_void_take_ctx_i32_i32_i32_i32 = void_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+            ],
+            false,
+        );
+        let ret_void_take_ctx_i32_i32_i32_i32_i32 = void_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+            ],
+            false,
+        );
+
+        let ret_i32_take_ctx_i32_i32_i32_i32 = i32_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+            ],
+            false,
+        );
+        let ret_i32_take_ctx_i32_i32_i32_i32_i64 = i32_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+                i64_ty_basic,
+            ],
+            false,
+        );
+        let ret_i32_take_ctx_i32_i32_i32_i64_i64 = i32_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+                i64_ty_basic,
+                i64_ty_basic,
+            ],
+            false,
+        );
+
+        let ret_i64_take_ctx_i32_i32 = i64_ty.fn_type(
+            &[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic, i32_ty_basic],
+            false,
+        );
+        let ret_void_take_ctx_i32_i32_i64 = void_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i64_ty_basic,
+            ],
+            false,
+        );
+        let ret_i32_take_ctx_i32_i64_i32 = i32_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i64_ty_basic,
+                i32_ty_basic,
+            ],
+            false,
+        );
+        let ret_void_take_ctx_i32_i32_i64_i32 = void_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i64_ty_basic,
+                i32_ty_basic,
+            ],
+            false,
+        );
 
         let ret_i1_take_i1_i1 = i1_ty.fn_type(&[i1_ty_basic, i1_ty_basic], false);
         let intrinsics = Self {
@@ -458,6 +574,9 @@ impl Intrinsics {
             trap_call_indirect_oob: i32_ty.const_int(3, false).as_basic_value_enum(),
             trap_memory_oob: i32_ty.const_int(2, false).as_basic_value_enum(),
             trap_illegal_arithmetic: i32_ty.const_int(4, false).as_basic_value_enum(),
+            trap_divide_by_zero: i32_ty.const_int(5, false).as_basic_value_enum(),
+            trap_integer_overflow: i32_ty.const_int(6, false).as_basic_value_enum(),
+            trap_misaligned_atomic: i32_ty.const_int(9, false).as_basic_value_enum(),
 
             // VM intrinsics.
             memory_grow_dynamic_local: module.add_function(
@@ -521,6 +640,61 @@ impl Intrinsics {
                 ret_i32_take_ctx_i32,
                 None,
             ),
+
+            memory_copy: module.add_function(
+                "vm.memory.copy",
+                ret_void_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+            memory_fill: module.add_function(
+                "vm.memory.fill",
+                ret_void_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+            memory_init: module.add_function(
+                "vm.memory.init",
+                ret_void_take_ctx_i32_i32_i32_i32_i32,
+                None,
+            ),
+            data_drop: module.add_function("vm.data.drop", ret_void_take_ctx_i32, None),
+            table_copy: module.add_function(
+                "vm.table.copy",
+                ret_void_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+            table_init: module.add_function(
+                "vm.table.init",
+                ret_void_take_ctx_i32_i32_i32_i32_i32,
+                None,
+            ),
+            elem_drop: module.add_function("vm.elem.drop", ret_void_take_ctx_i32, None),
+
+            memory_atomic_wait32: module.add_function(
+                "vm.memory.atomic.wait32",
+                ret_i32_take_ctx_i32_i32_i32_i32_i64,
+                None,
+            ),
+            memory_atomic_wait64: module.add_function(
+                "vm.memory.atomic.wait64",
+                ret_i32_take_ctx_i32_i32_i32_i64_i64,
+                None,
+            ),
+            memory_atomic_notify: module.add_function(
+                "vm.memory.atomic.notify",
+                ret_i32_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+
+            table_get: module.add_function("vm.table.get", ret_i64_take_ctx_i32_i32, None),
+            table_set: module.add_function("vm.table.set", ret_void_take_ctx_i32_i32_i64, None),
+            table_size: module.add_function("vm.table.size", ret_i32_take_ctx_i32, None),
+            table_grow: module.add_function("vm.table.grow", ret_i32_take_ctx_i32_i64_i32, None),
+            table_fill: module.add_function(
+                "vm.table.fill",
+                ret_void_take_ctx_i32_i32_i64_i32,
+                None,
+            ),
+
             throw_trap: module.add_function(
                 "vm.exception.trap",
                 void_ty.fn_type(&[i32_ty_basic], false),
@@ -531,6 +705,7 @@ impl Intrinsics {
                 void_ty.fn_type(&[i64_ty_basic], false),
                 None,
             ),
+            internal_trap: module.add_function("vm.internal.trap", ret_void_take_ctx_i32, None),
             ctx_ptr_ty,
         };
 
@@ -563,6 +738,9 @@ impl Intrinsics {
         intrinsics
             .throw_breakpoint
             .add_attribute(AttributeLoc::Function, noreturn);
+        intrinsics
+            .internal_trap
+            .add_attribute(AttributeLoc::Function, noreturn);
 
         intrinsics
     }
@@ -788,6 +966,21 @@ impl<'a> CtxType<'a> {
         )
     }
 
+    /// Loads the pointer to the buffer that functions with multiple results
+    /// write their results to.
+    pub fn return_values(&mut self, builder: &Builder) -> PointerValue {
+        let return_values_ptr_ptr = unsafe {
+            builder.build_struct_gep(
+                self.ctx_ptr_value,
+                offset_to_index(Ctx::offset_return_values()),
+                "return_values_ptr_ptr",
+            )
+        };
+        builder
+            .build_load(return_values_ptr_ptr, "return_values_ptr")
+            .into_pointer_value()
+    }
+
     pub fn local_func(
         &mut self,
         index: LocalFuncIndex,
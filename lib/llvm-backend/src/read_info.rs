@@ -1,4 +1,8 @@
-use wasmer_runtime_core::types::Type;
+use wasmer_runtime_core::{
+    module::ModuleInfo,
+    structures::TypedIndex,
+    types::{SigIndex, Type},
+};
 use wasmparser::{BinaryReaderError, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
 
 pub fn type_to_type(ty: WpType) -> Result<Type, BinaryReaderError> {
@@ -8,6 +12,8 @@ pub fn type_to_type(ty: WpType) -> Result<Type, BinaryReaderError> {
         WpType::F32 => Type::F32,
         WpType::F64 => Type::F64,
         WpType::V128 => Type::V128,
+        WpType::AnyRef => Type::AnyRef,
+        WpType::AnyFunc => Type::FuncRef,
         _ => {
             return Err(BinaryReaderError {
                 message: "that type is not supported as a wasmer type",
@@ -17,15 +23,23 @@ pub fn type_to_type(ty: WpType) -> Result<Type, BinaryReaderError> {
     })
 }
 
-pub fn blocktype_to_type(ty: WpTypeOrFuncType) -> Result<Type, BinaryReaderError> {
+/// Returns the parameter and result types of a block.
+pub fn blocktype_to_types(
+    info: &ModuleInfo,
+    ty: WpTypeOrFuncType,
+) -> Result<(Vec<Type>, Vec<Type>), BinaryReaderError> {
     match ty {
-        WpTypeOrFuncType::Type(inner_ty) => type_to_type(inner_ty),
-        _ => {
-            return Err(BinaryReaderError {
-                message:
-                    "the wasmer llvm backend does not yet support the multi-value return extension",
-                offset: -1isize as usize,
-            });
+        WpTypeOrFuncType::Type(WpType::EmptyBlockType) => Ok((vec![], vec![])),
+        WpTypeOrFuncType::Type(inner_ty) => Ok((vec![], vec![type_to_type(inner_ty)?])),
+        WpTypeOrFuncType::FuncType(index) => {
+            let sig =
+                info.signatures
+                    .get(SigIndex::new(index as usize))
+                    .ok_or(BinaryReaderError {
+                        message: "block type refers to an unknown signature",
+                        offset: -1isize as usize,
+                    })?;
+            Ok((sig.params().to_vec(), sig.returns().to_vec()))
         }
     }
 }
@@ -17,6 +17,7 @@ pub enum ControlFrame {
         body: BasicBlock,
         next: BasicBlock,
         phis: SmallVec<[PhiValue; 1]>,
+        loop_body_phis: SmallVec<[PhiValue; 1]>,
         stack_size_snapshot: usize,
     },
     IfElse {
@@ -24,6 +25,7 @@ pub enum ControlFrame {
         if_else: BasicBlock,
         next: BasicBlock,
         phis: SmallVec<[PhiValue; 1]>,
+        else_params: SmallVec<[BasicValueEnum; 1]>,
         stack_size_snapshot: usize,
         if_else_state: IfElseState,
     },
@@ -59,6 +61,17 @@ impl ControlFrame {
         }
     }
 
+    /// The phis receiving the values carried by a branch to this frame: the loop
+    /// parameters for a loop, the results otherwise.
+    pub fn br_phis(&self) -> &[PhiValue] {
+        match self {
+            ControlFrame::Loop {
+                ref loop_body_phis, ..
+            } => loop_body_phis.as_slice(),
+            _ => self.phis(),
+        }
+    }
+
     pub fn is_loop(&self) -> bool {
         match self {
             ControlFrame::Loop { .. } => true,
@@ -208,35 +221,55 @@ impl State {
         Ok(())
     }
 
-    pub fn push_block(&mut self, next: BasicBlock, phis: SmallVec<[PhiValue; 1]>) {
+    /// Pushes a block frame. The `num_params` topmost values of the stack are the
+    /// block parameters and belong to the block.
+    pub fn push_block(
+        &mut self,
+        next: BasicBlock,
+        phis: SmallVec<[PhiValue; 1]>,
+        num_params: usize,
+    ) {
         self.control_stack.push(ControlFrame::Block {
             next,
             phis,
-            stack_size_snapshot: self.stack.len(),
+            stack_size_snapshot: self.stack.len() - num_params,
         });
     }
 
-    pub fn push_loop(&mut self, body: BasicBlock, next: BasicBlock, phis: SmallVec<[PhiValue; 1]>) {
+    /// Pushes a loop frame. The loop parameters must already be popped off the
+    /// stack, they flow in through `loop_body_phis`.
+    pub fn push_loop(
+        &mut self,
+        body: BasicBlock,
+        next: BasicBlock,
+        phis: SmallVec<[PhiValue; 1]>,
+        loop_body_phis: SmallVec<[PhiValue; 1]>,
+    ) {
         self.control_stack.push(ControlFrame::Loop {
             body,
             next,
             phis,
+            loop_body_phis,
             stack_size_snapshot: self.stack.len(),
         });
     }
 
+    /// Pushes an if frame. The parameters must already be popped off the stack,
+    /// they are pushed again at the start of each arm.
     pub fn push_if(
         &mut self,
         if_then: BasicBlock,
         if_else: BasicBlock,
         next: BasicBlock,
         phis: SmallVec<[PhiValue; 1]>,
+        else_params: SmallVec<[BasicValueEnum; 1]>,
     ) {
         self.control_stack.push(ControlFrame::IfElse {
             if_then,
             if_else,
             next,
             phis,
+            else_params,
             stack_size_snapshot: self.stack.len(),
             if_else_state: IfElseState::If,
         });
@@ -73,6 +73,7 @@ fn generate_trampoline(
         Type::F32 => intrinsics.f32_ptr_ty,
         Type::F64 => intrinsics.f64_ptr_ty,
         Type::V128 => intrinsics.i128_ptr_ty,
+        Type::AnyRef | Type::FuncRef => intrinsics.i64_ptr_ty,
     };
 
     let mut args_vec = Vec::with_capacity(func_sig.params().len() + 1);
@@ -110,7 +111,27 @@ fn generate_trampoline(
                 call_site.try_as_basic_value().left().unwrap(),
             );
         }
-        _ => unimplemented!("multi-value returns"),
+        returns => {
+            // The callee returns a pointer to its return buffer, copy the results from there.
+            let slots = returns
+                .iter()
+                .map(|&ty| if ty == Type::V128 { 2 } else { 1 })
+                .sum::<u64>();
+            let src_ptr = call_site
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_pointer_value();
+            for i in 0..slots {
+                let index = intrinsics.i32_ty.const_int(i, false);
+                let src_item_ptr =
+                    unsafe { builder.build_in_bounds_gep(src_ptr, &[index], "src_ret_ptr") };
+                let dst_item_ptr =
+                    unsafe { builder.build_in_bounds_gep(returns_ptr, &[index], "dst_ret_ptr") };
+                let value = builder.build_load(src_item_ptr, "ret");
+                builder.build_store(dst_item_ptr, value);
+            }
+        }
     }
 
     builder.build_return(None);
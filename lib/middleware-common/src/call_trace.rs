@@ -1,6 +1,8 @@
 use wasmer_runtime_core::{
     codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
     module::ModuleInfo,
+    structures::TypedIndex,
+    types::FuncIndex,
 };
 
 pub struct CallTrace;
@@ -10,19 +12,31 @@ impl FunctionMiddleware for CallTrace {
     fn feed_event<'a, 'b: 'a>(
         &mut self,
         op: Event<'a, 'b>,
-        _module_info: &ModuleInfo,
+        module_info: &ModuleInfo,
         sink: &mut EventSink<'a, 'b>,
     ) -> Result<(), Self::Error> {
         match op {
-            Event::Internal(InternalEvent::FunctionBegin(id)) => sink.push(Event::Internal(
-                InternalEvent::Breakpoint(Box::new(move |_| {
-                    eprintln!("func ({})", id);
-                    Ok(())
-                })),
-            )),
+            Event::Internal(InternalEvent::FunctionBegin(id)) => {
+                let func_index =
+                    FuncIndex::new(id as usize + module_info.imported_functions.len());
+                let label = match module_info.function_name(func_index) {
+                    Some(name) => name.to_string(),
+                    None => format!("func ({})", id),
+                };
+                sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
+                    move |_| {
+                        eprintln!("{}", label);
+                        Ok(())
+                    },
+                ))))
+            }
             _ => {}
         }
         sink.push(op);
         Ok(())
     }
+
+    fn fingerprint(&self) -> String {
+        "call_trace".to_string()
+    }
 }
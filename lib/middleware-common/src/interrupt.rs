@@ -0,0 +1,128 @@
+/// InterruptCheck makes instances stop when a call made with a timeout (e.g.
+/// `Instance::call_with_timeout`) runs past its deadline.
+///
+/// Streaming compilers already add it for the Cranelift and LLVM backends, and the
+/// singlepass backend polls for interrupts on its own, so it only needs to be pushed
+/// explicitly to run it before other middlewares of a chain.
+pub use wasmer_runtime_core::fault::InterruptCheck;
+
+#[cfg(all(test, any(feature = "singlepass", feature = "llvm", feature = "clif")))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use wabt::wat2wasm;
+
+    use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
+    use wasmer_runtime_core::{
+        backend::Compiler, compile_with, error::RuntimeError, imports, Func,
+    };
+
+    #[cfg(feature = "llvm")]
+    fn get_compiler(check_interrupts: bool) -> impl Compiler {
+        use wasmer_llvm_backend::ModuleCodeGenerator as LLVMMCG;
+        let c: StreamingCompiler<LLVMMCG, _, _, _, _> = StreamingCompiler::new(move || {
+            let mut chain = MiddlewareChain::new();
+            if check_interrupts {
+                chain.push(InterruptCheck);
+            }
+            chain
+        });
+        c
+    }
+
+    #[cfg(feature = "clif")]
+    fn get_compiler(check_interrupts: bool) -> impl Compiler {
+        use wasmer_clif_backend::ModuleCodeGenerator as CraneliftMCG;
+        let c: StreamingCompiler<CraneliftMCG, _, _, _, _> = StreamingCompiler::new(move || {
+            let mut chain = MiddlewareChain::new();
+            if check_interrupts {
+                chain.push(InterruptCheck);
+            }
+            chain
+        });
+        c
+    }
+
+    #[cfg(feature = "singlepass")]
+    fn get_compiler(check_interrupts: bool) -> impl Compiler {
+        use wasmer_singlepass_backend::ModuleCodeGenerator as SinglePassMCG;
+        let c: StreamingCompiler<SinglePassMCG, _, _, _, _> = StreamingCompiler::new(move || {
+            let mut chain = MiddlewareChain::new();
+            if check_interrupts {
+                chain.push(InterruptCheck);
+            }
+            chain
+        });
+        c
+    }
+
+    static WAT: &'static str = r#"
+        (module
+          (type $t0 (func (param i32) (result i32)))
+          (func $spin (export "spin") (type $t0) (param $p0 i32) (result i32)
+            loop $L0
+              br $L0
+            end
+            get_local $p0
+          )
+          (func $id (export "id") (type $t0) (param $p0 i32) (result i32)
+            get_local $p0
+          )
+        )
+        "#;
+
+    #[test]
+    fn test_call_with_timeout() {
+        let wasm_binary = wat2wasm(WAT).unwrap();
+        let module = compile_with(&wasm_binary, &get_compiler(true)).unwrap();
+        let instance = module.instantiate(&imports! {}).unwrap();
+
+        let spin: Func<i32, i32> = instance.func("spin").unwrap();
+        match spin.call_with_timeout(1, Duration::from_millis(100)) {
+            Err(RuntimeError::Timeout) => {}
+            Err(err) => panic!("unexpected error: {:?}", err),
+            Ok(_) => panic!("infinite loop returned"),
+        }
+
+        // Calls that finish before the deadline are unaffected, and the
+        // instance can be called again after timing out.
+        let id: Func<i32, i32> = instance.func("id").unwrap();
+        let value = id.call_with_timeout(7, Duration::from_secs(10)).unwrap();
+        assert_eq!(value, 7);
+        assert_eq!(id.call(8).unwrap(), 8);
+    }
+
+    #[test]
+    fn test_call_with_timeout_without_interrupt_check() {
+        let wasm_binary = wat2wasm(WAT).unwrap();
+        let module = compile_with(&wasm_binary, &get_compiler(false)).unwrap();
+        let instance = module.instantiate(&imports! {}).unwrap();
+
+        let spin: Func<i32, i32> = instance.func("spin").unwrap();
+        match spin.call_with_timeout(1, Duration::from_millis(100)) {
+            Err(RuntimeError::Timeout) => {}
+            Err(err) => panic!("unexpected error: {:?}", err),
+            Ok(_) => panic!("infinite loop returned"),
+        }
+    }
+
+    #[test]
+    fn test_cached_module_call_with_timeout() {
+        use wasmer_runtime_core::{cache::Artifact, load_cache_with};
+
+        let wasm_binary = wat2wasm(WAT).unwrap();
+        let compiler = get_compiler(true);
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        let bytes = module.cache().unwrap().serialize().unwrap();
+        let artifact = Artifact::deserialize(&bytes).unwrap();
+        let module = unsafe { load_cache_with(artifact, &compiler) }.unwrap();
+        let instance = module.instantiate(&imports! {}).unwrap();
+
+        let spin: Func<i32, i32> = instance.func("spin").unwrap();
+        match spin.call_with_timeout(1, Duration::from_millis(100)) {
+            Err(RuntimeError::Timeout) => {}
+            Err(err) => panic!("unexpected error: {:?}", err),
+            Ok(_) => panic!("infinite loop returned"),
+        }
+    }
+}
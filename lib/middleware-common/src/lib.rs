@@ -8,4 +8,6 @@
     unreachable_patterns
 )]
 pub mod call_trace;
+#[cfg(all(unix, target_arch = "x86_64"))]
+pub mod interrupt;
 pub mod metering;
@@ -1,12 +1,22 @@
+use std::collections::HashMap;
 use wasmer_runtime_core::{
-    codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
+    codegen::{Event, EventSink, FunctionMiddleware, InternalEvent, InternalTrap},
     module::ModuleInfo,
+    structures::TypedIndex,
+    types::ImportedFuncIndex,
     vm::{Ctx, InternalField},
     wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType},
     Instance,
 };
 
+pub use wasmer_runtime_core::error::ExecutionLimitExceededError;
+
 static INTERNAL_FIELD: InternalField = InternalField::allocate();
+static GROW_DELTA_FIELD: InternalField = InternalField::allocate();
+static LIMIT_FIELD: InternalField = InternalField::allocate();
+/// Non-zero when `LIMIT_FIELD` holds a limit set for the instance. Kept apart from the limit
+/// itself so that every `u64`, zero included, can be set as a limit.
+static LIMIT_SET_FIELD: InternalField = InternalField::allocate();
 
 /// Metering is a compiler middleware that calculates the cost of WebAssembly instructions at compile
 /// time and will count the cost of executed instructions at runtime. Within the Metering functionality,
@@ -17,41 +27,456 @@ static INTERNAL_FIELD: InternalField = InternalField::allocate();
 /// trap. Each instance has a `points_used` field which can be used to track points used during
 /// a function call and should be set back to zero after a function call.
 ///
+/// The limit is only a default: `set_execution_limit` replaces it for a single instance, so that
+/// the same compiled module can run with a different limit on every call. For that reason the
+/// limit isn't part of the fingerprint of the middleware, and a module loaded from a cache keeps
+/// the default it was compiled with; set the limit of its instances with `set_execution_limit`.
+///
 /// Each compiler backend with Metering enabled should produce the same cost used at runtime for
 /// the same function calls so we can say that the metering is deterministic.
 ///
+/// By default every operator costs one point. A custom `CostFunction` can be supplied with
+/// `Metering::with_cost_function` to price operators differently. Cost functions can't be
+/// compared, so each one is given an id for modules compiled with different ones to be told
+/// apart, e.g. in a cache.
+///
+/// Time spent in imported functions is not counted automatically. A host function can charge
+/// points itself with `add_points_used_ctx`, and a fixed cost per call can be attached to an
+/// import with `Metering::with_import_cost`. The limit is checked again as soon as an imported
+/// function returns, so a host call that exceeds the limit traps before any more guest code runs.
+///
+/// Growing linear memory can be charged per requested page with `Metering::with_memory_grow_cost`,
+/// and growing a table per requested element with `Metering::with_table_grow_cost`. To put a hard
+/// cap on the number of pages an instance's memories can grow to, see `InstanceConfig::memory_page_limit`.
+///
+/// By default, operators are paid for in batches once control reaches a block boundary, a branch
+/// or a call, so a trap in between isn't charged for and the limit can be overshot. Use
+/// `Metering::with_exact_accounting` when every backend must agree on the points used.
+///
 pub struct Metering {
     limit: u64,
     current_block: u64,
+    cost_function: CostFunction,
+    cost_function_id: String,
+    import_costs: HashMap<(String, String), u64>,
+    memory_grow_cost: u64,
+    table_grow_cost: u64,
+    exact: bool,
+}
+
+/// Returns the number of points charged for executing a single WebAssembly operator.
+///
+/// The cost of a basic block is the sum of the costs of its operators. The function must be
+/// deterministic, otherwise the points used will differ between compilations and backends.
+pub type CostFunction = Box<dyn Fn(&Operator) -> u64>;
+
+/// The cost function used by `Metering::new`: every operator costs one point.
+pub fn default_cost(_op: &Operator) -> u64 {
+    1
 }
 
 impl Metering {
     pub fn new(limit: u64) -> Metering {
+        Metering::with_cost_function(limit, "default", default_cost)
+    }
+
+    /// Creates a `Metering` middleware that charges `cost_function(op)` points for each
+    /// operator `op` instead of one point per operator.
+    ///
+    /// `id` names the cost function in the fingerprint of the middleware. Different cost
+    /// functions must have different ids, otherwise modules compiled with them can be mistaken
+    /// for one another. The id `"default"` is used by `Metering::new`.
+    pub fn with_cost_function<F: Fn(&Operator) -> u64 + 'static>(
+        limit: u64,
+        id: &str,
+        cost_function: F,
+    ) -> Metering {
         Metering {
             limit,
             current_block: 0,
+            cost_function: Box::new(cost_function),
+            cost_function_id: id.to_string(),
+            import_costs: HashMap::new(),
+            memory_grow_cost: 0,
+            table_grow_cost: 0,
+            exact: false,
+        }
+    }
+
+    /// Pays for operators before they run and never lets the points used go past the limit.
+    ///
+    /// An operator that can trap, or that has effects outliving the call, only runs once it has
+    /// been paid for along with the operators before it. The points used after a trap then count
+    /// exactly the operators that ran, including the one that trapped. If paying would take the
+    /// points used past the limit, the call traps with `ExecutionLimitExceededError` and the
+    /// points aren't charged, so using exactly `limit` points is allowed.
+    ///
+    /// Points charged by host functions are checked once they return, but can't be refused.
+    pub fn with_exact_accounting(mut self) -> Metering {
+        self.exact = true;
+        self
+    }
+
+    /// Charges `cost` points for every page requested by a `memory.grow` instruction, on top
+    /// of the cost of the instruction itself. The points are charged before the memory grows,
+    /// whether or not the growth succeeds.
+    pub fn with_memory_grow_cost(mut self, cost: u64) -> Metering {
+        self.memory_grow_cost = cost;
+        self
+    }
+
+    /// Charges `cost` points for every element requested by a `table.grow` instruction, on top
+    /// of the cost of the instruction itself. Like `with_memory_grow_cost`, the points are
+    /// charged before the table grows, whether or not the growth succeeds.
+    pub fn with_table_grow_cost(mut self, cost: u64) -> Metering {
+        self.table_grow_cost = cost;
+        self
+    }
+
+    /// Charges `cost` extra points every time the guest calls the imported function
+    /// `namespace`.`name` directly.
+    pub fn with_import_cost(mut self, namespace: &str, name: &str, cost: u64) -> Metering {
+        self.import_costs
+            .insert((namespace.to_string(), name.to_string()), cost);
+        self
+    }
+
+    fn import_cost(&self, module_info: &ModuleInfo, function_index: u32) -> Option<u64> {
+        let import_name = module_info
+            .imported_functions
+            .get(ImportedFuncIndex::new(function_index as usize))?;
+        let namespace = module_info.namespace_table.get(import_name.namespace_index);
+        let name = module_info.name_table.get(import_name.name_index);
+        Some(
+            self.import_costs
+                .get(&(namespace.to_string(), name.to_string()))
+                .cloned()
+                .unwrap_or(0),
+        )
+    }
+
+    /// Charges `cost` points per unit of the `memory.grow` or `table.grow` delta on top of the
+    /// stack, leaving the stack unchanged.
+    ///
+    /// The delta is chosen by the guest, so the points saturate at `u64::max_value()` instead of
+    /// wrapping around to a small number.
+    fn emit_grow_charge<'a, 'b: 'a>(&self, sink: &mut EventSink<'a, 'b>, cost: u64) {
+        // Stash the delta in an internal field since it is needed more than once.
+        sink.push(Event::WasmOwned(Operator::I64ExtendUI32));
+        sink.push(Event::Internal(InternalEvent::SetInternal(
+            GROW_DELTA_FIELD.index() as _,
+        )));
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            GROW_DELTA_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I32WrapI64));
+
+        if self.exact {
+            emit_saturating_grow_total(sink, cost);
+            self.emit_limit(sink);
+            sink.push(Event::WasmOwned(Operator::I64GtU));
+            emit_trap_if(sink);
+            emit_saturating_grow_total(sink, cost);
+            sink.push(Event::Internal(InternalEvent::SetInternal(
+                INTERNAL_FIELD.index() as _,
+            )));
+        } else {
+            emit_saturating_grow_total(sink, cost);
+            sink.push(Event::Internal(InternalEvent::SetInternal(
+                INTERNAL_FIELD.index() as _,
+            )));
+            self.emit_limit_check(sink);
         }
     }
+
+    /// Charges the points pushed by `push_points`, unless that would take the points used past
+    /// the limit, in which case it traps with `ExecutionLimitExceededError` instead.
+    fn emit_exact_charge<'a, 'b: 'a, F: Fn(&mut EventSink<'a, 'b>)>(
+        &self,
+        sink: &mut EventSink<'a, 'b>,
+        push_points: F,
+    ) {
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            INTERNAL_FIELD.index() as _,
+        )));
+        push_points(sink);
+        sink.push(Event::WasmOwned(Operator::I64Add));
+        self.emit_limit(sink);
+        sink.push(Event::WasmOwned(Operator::I64GtU));
+        emit_trap_if(sink);
+
+        emit_charge(sink, push_points);
+    }
+
+    /// Traps with `ExecutionLimitExceededError` if the points used reach the limit, or exceed it
+    /// when accounting exactly.
+    fn emit_limit_check<'a, 'b: 'a>(&self, sink: &mut EventSink<'a, 'b>) {
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            INTERNAL_FIELD.index() as _,
+        )));
+        self.emit_limit(sink);
+        sink.push(Event::WasmOwned(if self.exact {
+            Operator::I64GtU
+        } else {
+            Operator::I64GeU
+        }));
+        emit_trap_if(sink);
+    }
+
+    /// Pushes the limit set for the instance, or `self.limit` if there is none.
+    fn emit_limit<'a, 'b: 'a>(&self, sink: &mut EventSink<'a, 'b>) {
+        sink.push(Event::WasmOwned(Operator::I64Const {
+            value: self.limit as i64,
+        }));
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            LIMIT_FIELD.index() as _,
+        )));
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            LIMIT_SET_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64Eqz));
+        sink.push(Event::WasmOwned(Operator::Select));
+    }
+}
+
+/// Adds the points pushed by `push_points` to the points used.
+fn emit_charge<'a, 'b: 'a, F: Fn(&mut EventSink<'a, 'b>)>(
+    sink: &mut EventSink<'a, 'b>,
+    push_points: F,
+) {
+    sink.push(Event::Internal(InternalEvent::GetInternal(
+        INTERNAL_FIELD.index() as _,
+    )));
+    push_points(sink);
+    sink.push(Event::WasmOwned(Operator::I64Add));
+    sink.push(Event::Internal(InternalEvent::SetInternal(
+        INTERNAL_FIELD.index() as _,
+    )));
+}
+
+/// Pushes `cost` times the delta stashed by `emit_grow_charge`, or `u64::max_value()` if the
+/// product doesn't fit in 64 bits.
+fn emit_saturating_grow_points<'a, 'b: 'a>(sink: &mut EventSink<'a, 'b>, cost: u64) {
+    let push_product = |sink: &mut EventSink<'a, 'b>| {
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            GROW_DELTA_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64Const { value: cost as i64 }));
+        sink.push(Event::WasmOwned(Operator::I64Mul));
+    };
+    // The delta is at most `u32::max_value()`, so small costs can't overflow.
+    if (u64::from(u32::max_value())).checked_mul(cost).is_some() {
+        push_product(sink);
+        return;
+    }
+    sink.push(Event::WasmOwned(Operator::I64Const { value: -1 }));
+    push_product(sink);
+    sink.push(Event::Internal(InternalEvent::GetInternal(
+        GROW_DELTA_FIELD.index() as _,
+    )));
+    sink.push(Event::WasmOwned(Operator::I64Const {
+        value: (u64::max_value() / cost) as i64,
+    }));
+    sink.push(Event::WasmOwned(Operator::I64GtU));
+    sink.push(Event::WasmOwned(Operator::Select));
+}
+
+/// Pushes the points used plus the points for the stashed grow delta, or `u64::max_value()`
+/// if the sum doesn't fit in 64 bits.
+fn emit_saturating_grow_total<'a, 'b: 'a>(sink: &mut EventSink<'a, 'b>, cost: u64) {
+    let push_sum = |sink: &mut EventSink<'a, 'b>| {
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            INTERNAL_FIELD.index() as _,
+        )));
+        emit_saturating_grow_points(sink, cost);
+        sink.push(Event::WasmOwned(Operator::I64Add));
+    };
+    sink.push(Event::WasmOwned(Operator::I64Const { value: -1 }));
+    push_sum(sink);
+    // The sum wrapped around if it is smaller than the points used.
+    push_sum(sink);
+    sink.push(Event::Internal(InternalEvent::GetInternal(
+        INTERNAL_FIELD.index() as _,
+    )));
+    sink.push(Event::WasmOwned(Operator::I64LtU));
+    sink.push(Event::WasmOwned(Operator::Select));
 }
 
-#[derive(Copy, Clone, Debug)]
-pub struct ExecutionLimitExceededError;
+/// Traps with `ExecutionLimitExceededError` if the `i32` on top of the stack isn't zero.
+fn emit_trap_if<'a, 'b: 'a>(sink: &mut EventSink<'a, 'b>) {
+    sink.push(Event::WasmOwned(Operator::If {
+        ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+    }));
+    sink.push(Event::Internal(InternalEvent::Trap(
+        InternalTrap::ExecutionLimitExceeded,
+    )));
+    sink.push(Event::WasmOwned(Operator::End));
+}
+
+/// Whether `op` can trap, or has effects that outlive the call it runs in. When accounting
+/// exactly, such an operator only runs once it has been paid for.
+fn has_effects(op: &Operator) -> bool {
+    match *op {
+        Operator::Unreachable
+        | Operator::Call { .. }
+        | Operator::CallIndirect { .. }
+        | Operator::SetGlobal { .. }
+        | Operator::I32Load { .. }
+        | Operator::I64Load { .. }
+        | Operator::F32Load { .. }
+        | Operator::F64Load { .. }
+        | Operator::I32Load8S { .. }
+        | Operator::I32Load8U { .. }
+        | Operator::I32Load16S { .. }
+        | Operator::I32Load16U { .. }
+        | Operator::I64Load8S { .. }
+        | Operator::I64Load8U { .. }
+        | Operator::I64Load16S { .. }
+        | Operator::I64Load16U { .. }
+        | Operator::I64Load32S { .. }
+        | Operator::I64Load32U { .. }
+        | Operator::V128Load { .. }
+        | Operator::I8x16LoadSplat { .. }
+        | Operator::I16x8LoadSplat { .. }
+        | Operator::I32x4LoadSplat { .. }
+        | Operator::I64x2LoadSplat { .. }
+        | Operator::I32Store { .. }
+        | Operator::I64Store { .. }
+        | Operator::F32Store { .. }
+        | Operator::F64Store { .. }
+        | Operator::I32Store8 { .. }
+        | Operator::I32Store16 { .. }
+        | Operator::I64Store8 { .. }
+        | Operator::I64Store16 { .. }
+        | Operator::I64Store32 { .. }
+        | Operator::V128Store { .. }
+        | Operator::MemoryGrow { .. }
+        | Operator::MemoryInit { .. }
+        | Operator::MemoryCopy
+        | Operator::MemoryFill
+        | Operator::DataDrop { .. }
+        | Operator::TableGet { .. }
+        | Operator::TableSet { .. }
+        | Operator::TableGrow { .. }
+        | Operator::TableFill { .. }
+        | Operator::TableCopy { .. }
+        | Operator::TableInit { .. }
+        | Operator::ElemDrop { .. }
+        | Operator::I32DivS
+        | Operator::I32DivU
+        | Operator::I32RemS
+        | Operator::I32RemU
+        | Operator::I64DivS
+        | Operator::I64DivU
+        | Operator::I64RemS
+        | Operator::I64RemU
+        | Operator::I32TruncSF32
+        | Operator::I32TruncUF32
+        | Operator::I32TruncSF64
+        | Operator::I32TruncUF64
+        | Operator::I64TruncSF32
+        | Operator::I64TruncUF32
+        | Operator::I64TruncSF64
+        | Operator::I64TruncUF64
+        | Operator::Wake { .. }
+        | Operator::I32Wait { .. }
+        | Operator::I64Wait { .. }
+        | Operator::I32AtomicLoad { .. }
+        | Operator::I64AtomicLoad { .. }
+        | Operator::I32AtomicLoad8U { .. }
+        | Operator::I32AtomicLoad16U { .. }
+        | Operator::I64AtomicLoad8U { .. }
+        | Operator::I64AtomicLoad16U { .. }
+        | Operator::I64AtomicLoad32U { .. }
+        | Operator::I32AtomicStore { .. }
+        | Operator::I64AtomicStore { .. }
+        | Operator::I32AtomicStore8 { .. }
+        | Operator::I32AtomicStore16 { .. }
+        | Operator::I64AtomicStore8 { .. }
+        | Operator::I64AtomicStore16 { .. }
+        | Operator::I64AtomicStore32 { .. }
+        | Operator::I32AtomicRmwAdd { .. }
+        | Operator::I64AtomicRmwAdd { .. }
+        | Operator::I32AtomicRmw8UAdd { .. }
+        | Operator::I32AtomicRmw16UAdd { .. }
+        | Operator::I64AtomicRmw8UAdd { .. }
+        | Operator::I64AtomicRmw16UAdd { .. }
+        | Operator::I64AtomicRmw32UAdd { .. }
+        | Operator::I32AtomicRmwSub { .. }
+        | Operator::I64AtomicRmwSub { .. }
+        | Operator::I32AtomicRmw8USub { .. }
+        | Operator::I32AtomicRmw16USub { .. }
+        | Operator::I64AtomicRmw8USub { .. }
+        | Operator::I64AtomicRmw16USub { .. }
+        | Operator::I64AtomicRmw32USub { .. }
+        | Operator::I32AtomicRmwAnd { .. }
+        | Operator::I64AtomicRmwAnd { .. }
+        | Operator::I32AtomicRmw8UAnd { .. }
+        | Operator::I32AtomicRmw16UAnd { .. }
+        | Operator::I64AtomicRmw8UAnd { .. }
+        | Operator::I64AtomicRmw16UAnd { .. }
+        | Operator::I64AtomicRmw32UAnd { .. }
+        | Operator::I32AtomicRmwOr { .. }
+        | Operator::I64AtomicRmwOr { .. }
+        | Operator::I32AtomicRmw8UOr { .. }
+        | Operator::I32AtomicRmw16UOr { .. }
+        | Operator::I64AtomicRmw8UOr { .. }
+        | Operator::I64AtomicRmw16UOr { .. }
+        | Operator::I64AtomicRmw32UOr { .. }
+        | Operator::I32AtomicRmwXor { .. }
+        | Operator::I64AtomicRmwXor { .. }
+        | Operator::I32AtomicRmw8UXor { .. }
+        | Operator::I32AtomicRmw16UXor { .. }
+        | Operator::I64AtomicRmw8UXor { .. }
+        | Operator::I64AtomicRmw16UXor { .. }
+        | Operator::I64AtomicRmw32UXor { .. }
+        | Operator::I32AtomicRmwXchg { .. }
+        | Operator::I64AtomicRmwXchg { .. }
+        | Operator::I32AtomicRmw8UXchg { .. }
+        | Operator::I32AtomicRmw16UXchg { .. }
+        | Operator::I64AtomicRmw8UXchg { .. }
+        | Operator::I64AtomicRmw16UXchg { .. }
+        | Operator::I64AtomicRmw32UXchg { .. }
+        | Operator::I32AtomicRmwCmpxchg { .. }
+        | Operator::I64AtomicRmwCmpxchg { .. }
+        | Operator::I32AtomicRmw8UCmpxchg { .. }
+        | Operator::I32AtomicRmw16UCmpxchg { .. }
+        | Operator::I64AtomicRmw8UCmpxchg { .. }
+        | Operator::I64AtomicRmw16UCmpxchg { .. }
+        | Operator::I64AtomicRmw32UCmpxchg { .. } => true,
+        _ => false,
+    }
+}
 
 impl FunctionMiddleware for Metering {
     type Error = String;
     fn feed_event<'a, 'b: 'a>(
         &mut self,
         op: Event<'a, 'b>,
-        _module_info: &ModuleInfo,
+        module_info: &ModuleInfo,
         sink: &mut EventSink<'a, 'b>,
     ) -> Result<(), Self::Error> {
+        // Calls that may end up in host code need another check once they return.
+        let mut check_after = false;
         match op {
             Event::Internal(InternalEvent::FunctionBegin(_)) => {
                 self.current_block = 0;
             }
             Event::Wasm(&ref op) | Event::WasmOwned(ref op) => {
-                self.current_block += 1;
+                self.current_block += (self.cost_function)(op);
                 match *op {
+                    Operator::Call { function_index } => {
+                        if let Some(cost) = self.import_cost(module_info, function_index) {
+                            self.current_block += cost;
+                            check_after = true;
+                        }
+                    }
+                    Operator::CallIndirect { .. } => {
+                        check_after = true;
+                    }
+                    _ => {}
+                }
+                let ends_block = match *op {
                     Operator::Loop { .. }
                     | Operator::Block { .. }
                     | Operator::End
@@ -63,41 +488,46 @@ impl FunctionMiddleware for Metering {
                     | Operator::BrIf { .. }
                     | Operator::Call { .. }
                     | Operator::CallIndirect { .. }
-                    | Operator::Return => {
-                        sink.push(Event::Internal(InternalEvent::GetInternal(
-                            INTERNAL_FIELD.index() as _,
-                        )));
-                        sink.push(Event::WasmOwned(Operator::I64Const {
-                            value: self.current_block as i64,
-                        }));
-                        sink.push(Event::WasmOwned(Operator::I64Add));
-                        sink.push(Event::Internal(InternalEvent::SetInternal(
-                            INTERNAL_FIELD.index() as _,
-                        )));
+                    | Operator::Return => true,
+                    _ => false,
+                };
+                if self.exact {
+                    if (ends_block || has_effects(op)) && self.current_block != 0 {
+                        let points = self.current_block;
+                        self.emit_exact_charge(sink, |sink| {
+                            sink.push(Event::WasmOwned(Operator::I64Const {
+                                value: points as i64,
+                            }))
+                        });
                         self.current_block = 0;
                     }
-                    _ => {}
+                } else {
+                    if ends_block {
+                        let points = self.current_block;
+                        emit_charge(sink, |sink| {
+                            sink.push(Event::WasmOwned(Operator::I64Const {
+                                value: points as i64,
+                            }))
+                        });
+                        self.current_block = 0;
+                    }
+                    match *op {
+                        Operator::Br { .. }
+                        | Operator::BrTable { .. }
+                        | Operator::BrIf { .. }
+                        | Operator::Call { .. }
+                        | Operator::CallIndirect { .. } => {
+                            self.emit_limit_check(sink);
+                        }
+                        _ => {}
+                    }
                 }
                 match *op {
-                    Operator::Br { .. }
-                    | Operator::BrTable { .. }
-                    | Operator::BrIf { .. }
-                    | Operator::Call { .. }
-                    | Operator::CallIndirect { .. } => {
-                        sink.push(Event::Internal(InternalEvent::GetInternal(
-                            INTERNAL_FIELD.index() as _,
-                        )));
-                        sink.push(Event::WasmOwned(Operator::I64Const {
-                            value: self.limit as i64,
-                        }));
-                        sink.push(Event::WasmOwned(Operator::I64GeU));
-                        sink.push(Event::WasmOwned(Operator::If {
-                            ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
-                        }));
-                        sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(|_| {
-                            Err(Box::new(ExecutionLimitExceededError))
-                        }))));
-                        sink.push(Event::WasmOwned(Operator::End));
+                    Operator::MemoryGrow { .. } if self.memory_grow_cost != 0 => {
+                        self.emit_grow_charge(sink, self.memory_grow_cost);
+                    }
+                    Operator::TableGrow { .. } if self.table_grow_cost != 0 => {
+                        self.emit_grow_charge(sink, self.table_grow_cost);
                     }
                     _ => {}
                 }
@@ -105,8 +535,24 @@ impl FunctionMiddleware for Metering {
             _ => {}
         }
         sink.push(op);
+        if check_after {
+            self.emit_limit_check(sink);
+        }
         Ok(())
     }
+
+    fn fingerprint(&self) -> String {
+        let mut import_costs: Vec<_> = self.import_costs.iter().collect();
+        import_costs.sort();
+        format!(
+            "metering(cost: {:?}, imports: {:?}, grow: {}, table_grow: {}, exact: {})",
+            self.cost_function_id,
+            import_costs,
+            self.memory_grow_cost,
+            self.table_grow_cost,
+            self.exact
+        )
+    }
 }
 
 /// Returns the number of points used by an Instance.
@@ -129,31 +575,81 @@ pub fn set_points_used_ctx(ctx: &mut Ctx, value: u64) {
     ctx.set_internal(&INTERNAL_FIELD, value);
 }
 
-#[cfg(all(test, any(feature = "singlepass", feature = "llvm")))]
+/// Returns the limit set for an Instance with `set_execution_limit`, if any.
+pub fn get_execution_limit(instance: &Instance) -> Option<u64> {
+    match instance.get_internal(&LIMIT_SET_FIELD) {
+        0 => None,
+        _ => Some(instance.get_internal(&LIMIT_FIELD)),
+    }
+}
+
+/// Sets the maximum number of points an Instance can use, in place of the limit the
+/// `Metering` middleware was created with.
+///
+/// A limit of zero makes any metered code trap.
+pub fn set_execution_limit(instance: &mut Instance, limit: u64) {
+    instance.set_internal(&LIMIT_FIELD, limit);
+    instance.set_internal(&LIMIT_SET_FIELD, 1);
+}
+
+/// Makes an Instance use the limit the `Metering` middleware was created with again.
+pub fn clear_execution_limit(instance: &mut Instance) {
+    instance.set_internal(&LIMIT_SET_FIELD, 0);
+}
+
+/// Returns the limit set in a Ctx with `set_execution_limit_ctx`, if any.
+pub fn get_execution_limit_ctx(ctx: &Ctx) -> Option<u64> {
+    match ctx.get_internal(&LIMIT_SET_FIELD) {
+        0 => None,
+        _ => Some(ctx.get_internal(&LIMIT_FIELD)),
+    }
+}
+
+/// Sets the maximum number of points that can be used in a Ctx, in place of the limit the
+/// `Metering` middleware was created with.
+pub fn set_execution_limit_ctx(ctx: &mut Ctx, limit: u64) {
+    ctx.set_internal(&LIMIT_FIELD, limit);
+    ctx.set_internal(&LIMIT_SET_FIELD, 1);
+}
+
+/// Adds `points` to the number of points used in a Ctx.
+///
+/// This is meant to be called from imported functions to charge the calling instance for
+/// the work done on the host side.
+pub fn add_points_used_ctx(ctx: &mut Ctx, points: u64) {
+    let used = get_points_used_ctx(ctx);
+    set_points_used_ctx(ctx, used.saturating_add(points));
+}
+
+#[cfg(all(test, any(feature = "singlepass", feature = "llvm", feature = "clif")))]
 mod tests {
     use super::*;
     use wabt::wat2wasm;
 
     use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
-    use wasmer_runtime_core::{backend::Compiler, compile_with, imports, Func};
+    use wasmer_runtime_core::{backend::Compiler, compile_with, func, imports, Func};
 
-    #[cfg(feature = "llvm")]
     fn get_compiler(limit: u64) -> impl Compiler {
+        get_compiler_with(move || Metering::new(limit))
+    }
+
+    #[cfg(feature = "llvm")]
+    fn get_compiler_with<F: Fn() -> Metering + 'static>(metering: F) -> impl Compiler {
         use wasmer_llvm_backend::ModuleCodeGenerator as LLVMMCG;
         let c: StreamingCompiler<LLVMMCG, _, _, _, _> = StreamingCompiler::new(move || {
             let mut chain = MiddlewareChain::new();
-            chain.push(Metering::new(limit));
+            chain.push(metering());
             chain
         });
         c
     }
 
     #[cfg(feature = "singlepass")]
-    fn get_compiler(limit: u64) -> impl Compiler {
+    fn get_compiler_with<F: Fn() -> Metering + 'static>(metering: F) -> impl Compiler {
         use wasmer_singlepass_backend::ModuleCodeGenerator as SinglePassMCG;
         let c: StreamingCompiler<SinglePassMCG, _, _, _, _> = StreamingCompiler::new(move || {
             let mut chain = MiddlewareChain::new();
-            chain.push(Metering::new(limit));
+            chain.push(metering());
             chain
         });
         c
@@ -163,10 +659,14 @@ mod tests {
     compile_error!("compiler not specified, activate a compiler via features");
 
     #[cfg(feature = "clif")]
-    fn get_compiler(_limit: u64) -> impl Compiler {
-        compile_error!("cranelift does not implement metering");
-        use wasmer_clif_backend::CraneliftCompiler;
-        CraneliftCompiler::new()
+    fn get_compiler_with<F: Fn() -> Metering + 'static>(metering: F) -> impl Compiler {
+        use wasmer_clif_backend::ModuleCodeGenerator as CraneliftMCG;
+        let c: StreamingCompiler<CraneliftMCG, _, _, _, _> = StreamingCompiler::new(move || {
+            let mut chain = MiddlewareChain::new();
+            chain.push(metering());
+            chain
+        });
+        c
     }
 
     // Assemblyscript
@@ -251,6 +751,268 @@ mod tests {
         assert_eq!(get_points_used(&instance), 74);
     }
 
+    #[test]
+    fn test_points_weighted_by_cost_function() {
+        let wasm_binary = wat2wasm(WAT).unwrap();
+
+        let limit = 1000u64;
+
+        let compiler = get_compiler_with(move || Metering::with_cost_function(limit, "two", |_| 2));
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+
+        let import_object = imports! {};
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        set_points_used(&mut instance, 0u64);
+
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        let value = add_to.call(3, 4).unwrap();
+
+        // verify it returns the correct value
+        assert_eq!(value, 7);
+
+        // verify every operator was charged twice the default cost
+        assert_eq!(get_points_used(&instance), 148);
+    }
+
+    static WAT_HOST: &'static str = r#"
+        (module
+          (type $t0 (func (param i32)))
+          (type $t1 (func (param i32) (result i32)))
+          (import "env" "gas" (func $env.gas (type $t0)))
+          (func $use_gas (export "use_gas") (type $t1) (param $p0 i32) (result i32)
+            get_local $p0
+            call $env.gas
+            i32.const 1))
+        "#;
+
+    fn gas(ctx: &mut Ctx, points: i32) {
+        add_points_used_ctx(ctx, points as u64);
+    }
+
+    #[test]
+    fn test_points_charged_by_host_function() {
+        let wasm_binary = wat2wasm(WAT_HOST).unwrap();
+
+        let limit = 100u64;
+
+        let module = compile_with(&wasm_binary, &get_compiler(limit)).unwrap();
+
+        let import_object = imports! {
+            "env" => {
+                "gas" => func!(gas),
+            },
+        };
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        set_points_used(&mut instance, 0u64);
+
+        let use_gas: Func<i32, i32> = instance.func("use_gas").unwrap();
+        assert_eq!(use_gas.call(10).unwrap(), 1);
+
+        // 4 operators plus the 10 points charged by the host function
+        assert_eq!(get_points_used(&instance), 14);
+    }
+
+    #[test]
+    fn test_import_cost() {
+        let wasm_binary = wat2wasm(WAT_HOST).unwrap();
+
+        let limit = 1000u64;
+
+        let compiler =
+            get_compiler_with(move || Metering::new(limit).with_import_cost("env", "gas", 100));
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+
+        let import_object = imports! {
+            "env" => {
+                "gas" => func!(gas),
+            },
+        };
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        set_points_used(&mut instance, 0u64);
+
+        let use_gas: Func<i32, i32> = instance.func("use_gas").unwrap();
+        assert_eq!(use_gas.call(10).unwrap(), 1);
+
+        assert_eq!(get_points_used(&instance), 114);
+    }
+
+    #[test]
+    fn test_traps_after_costly_host_call() {
+        use wasmer_runtime_core::error::RuntimeError;
+        let wasm_binary = wat2wasm(WAT_HOST).unwrap();
+
+        let limit = 100u64;
+
+        let module = compile_with(&wasm_binary, &get_compiler(limit)).unwrap();
+
+        let import_object = imports! {
+            "env" => {
+                "gas" => func!(gas),
+            },
+        };
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        set_points_used(&mut instance, 0u64);
+
+        let use_gas: Func<i32, i32> = instance.func("use_gas").unwrap();
+        let err = use_gas.call(200).unwrap_err();
+        match err {
+            RuntimeError::Error { data } => {
+                assert!(data.downcast_ref::<ExecutionLimitExceededError>().is_some());
+            }
+            _ => unreachable!(),
+        }
+
+        // the trap fires as soon as the host function returns
+        assert_eq!(get_points_used(&instance), 202);
+    }
+
+    static WAT_GROW: &'static str = r#"
+        (module
+          (type $t0 (func (param i32) (result i32)))
+          (func $grow (export "grow") (type $t0) (param $p0 i32) (result i32)
+            get_local $p0
+            memory.grow)
+          (memory $memory (export "memory") 1 100))
+        "#;
+
+    #[test]
+    fn test_memory_grow_cost() {
+        let wasm_binary = wat2wasm(WAT_GROW).unwrap();
+
+        let limit = 1000u64;
+
+        let compiler = get_compiler_with(move || Metering::new(limit).with_memory_grow_cost(10));
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+
+        let import_object = imports! {};
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        set_points_used(&mut instance, 0u64);
+
+        let grow: Func<i32, i32> = instance.func("grow").unwrap();
+        assert_eq!(grow.call(2).unwrap(), 1);
+
+        // 3 operators plus 10 points for each of the 2 pages
+        assert_eq!(get_points_used(&instance), 23);
+    }
+
+    #[test]
+    fn test_traps_after_costly_memory_grow() {
+        use wasmer_runtime_core::error::RuntimeError;
+        let wasm_binary = wat2wasm(WAT_GROW).unwrap();
+
+        let limit = 100u64;
+
+        let compiler = get_compiler_with(move || Metering::new(limit).with_memory_grow_cost(10));
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+
+        let import_object = imports! {};
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        set_points_used(&mut instance, 0u64);
+
+        let grow: Func<i32, i32> = instance.func("grow").unwrap();
+        let err = grow.call(50).unwrap_err();
+        match err {
+            RuntimeError::Error { data } => {
+                assert!(data.downcast_ref::<ExecutionLimitExceededError>().is_some());
+            }
+            _ => unreachable!(),
+        }
+
+        // the memory was not grown
+        assert_eq!(get_points_used(&instance), 500);
+        assert_eq!(instance.context().memory(0).size().0, 1);
+    }
+
+    #[test]
+    fn test_memory_grow_cost_saturates() {
+        use wasmer_runtime_core::error::RuntimeError;
+        let wasm_binary = wat2wasm(WAT_GROW).unwrap();
+
+        // 2 pages at 2^63 points each would wrap around to 0 points.
+        let compiler =
+            get_compiler_with(move || Metering::new(1000).with_memory_grow_cost(1 << 63));
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+        set_points_used(&mut instance, 0u64);
+
+        let grow: Func<i32, i32> = instance.func("grow").unwrap();
+        match grow.call(2).unwrap_err() {
+            RuntimeError::Error { data } => {
+                assert!(data.downcast_ref::<ExecutionLimitExceededError>().is_some());
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(get_points_used(&instance), u64::max_value());
+        assert_eq!(instance.context().memory(0).size().0, 1);
+
+        // The points used can't wrap around either.
+        let compiler = get_compiler_with(move || {
+            Metering::new(1000)
+                .with_memory_grow_cost(10)
+                .with_exact_accounting()
+        });
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+        set_execution_limit(&mut instance, u64::max_value());
+        set_points_used(&mut instance, u64::max_value() - 5);
+
+        let grow: Func<i32, i32> = instance.func("grow").unwrap();
+        assert!(grow.call(1).is_err());
+        assert_eq!(instance.context().memory(0).size().0, 1);
+    }
+
+    #[cfg(not(feature = "clif"))]
+    #[test]
+    fn test_table_grow_cost() {
+        use wasmer_runtime_core::backend::{CompilerConfig, Features};
+        use wasmer_runtime_core::compile_with_config;
+
+        static WAT_TABLE_GROW: &'static str = r#"
+            (module
+              (table $refs 1 anyref)
+              (func (export "grow") (param i32) (result i32)
+                ref.null
+                get_local 0
+                table.grow $refs))
+            "#;
+        let mut features = wabt::Features::new();
+        features.enable_reference_types();
+        let wasm_binary = wabt::wat2wasm_with_features(WAT_TABLE_GROW, features).unwrap();
+
+        let compiler = get_compiler_with(move || Metering::new(1000).with_table_grow_cost(7));
+        let module = compile_with_config(
+            &wasm_binary,
+            &compiler,
+            CompilerConfig {
+                features: Features {
+                    reference_types: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+        set_points_used(&mut instance, 0u64);
+
+        let grow: Func<i32, i32> = instance.func("grow").unwrap();
+        assert_eq!(grow.call(3), Ok(1));
+
+        // 4 operators plus 7 points for each of the 3 elements
+        assert_eq!(get_points_used(&instance), 25);
+
+        // Growing by more elements than the limit pays for traps.
+        set_points_used(&mut instance, 0u64);
+        assert!(grow.call(200).is_err());
+        assert_eq!(get_points_used(&instance), 1404);
+    }
+
     #[test]
     fn test_traps_after_costly_call() {
         use wasmer_runtime_core::error::RuntimeError;
@@ -280,4 +1042,199 @@ mod tests {
         assert_eq!(get_points_used(&instance), 109); // Used points will be slightly more than `limit` because of the way we do gas checking.
     }
 
+    #[test]
+    fn test_execution_limit_per_instance() {
+        use wasmer_runtime_core::error::RuntimeError;
+        let wasm_binary = wat2wasm(WAT).unwrap();
+
+        let module = compile_with(&wasm_binary, &get_compiler(100)).unwrap();
+
+        let import_object = imports! {};
+        let mut instance = module.instantiate(&import_object).unwrap();
+        assert_eq!(get_execution_limit(&instance), None);
+
+        // The same module runs with a higher limit.
+        set_execution_limit(&mut instance, 10_000);
+        assert_eq!(get_execution_limit(&instance), Some(10_000));
+        set_points_used(&mut instance, 0u64);
+        {
+            let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+            assert_eq!(add_to.call(10, 4), Ok(49));
+        }
+        assert!(get_points_used(&instance) > 100);
+
+        // And with a lower one.
+        set_execution_limit(&mut instance, 50);
+        set_points_used(&mut instance, 0u64);
+        {
+            let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+            match add_to.call(3, 4).unwrap_err() {
+                RuntimeError::Error { data } => {
+                    assert!(data.downcast_ref::<ExecutionLimitExceededError>().is_some());
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        // A limit of zero doesn't let any metered code run.
+        set_execution_limit(&mut instance, 0);
+        assert_eq!(get_execution_limit(&instance), Some(0));
+        set_points_used(&mut instance, 0u64);
+        {
+            let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+            match add_to.call(3, 4).unwrap_err() {
+                RuntimeError::Error { data } => {
+                    assert!(data.downcast_ref::<ExecutionLimitExceededError>().is_some());
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        clear_execution_limit(&mut instance);
+        assert_eq!(get_execution_limit(&instance), None);
+        set_points_used(&mut instance, 0u64);
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        assert_eq!(add_to.call(3, 4), Ok(7));
+    }
+
+    static WAT_TRAPS: &'static str = r#"
+        (module
+          (type $t0 (func (result i32)))
+          (func $three (export "three") (type $t0) (result i32)
+            i32.const 1
+            i32.const 2
+            i32.add)
+          (func $out_of_bounds (export "out_of_bounds") (type $t0) (result i32)
+            i32.const 1
+            i32.const 2
+            i32.add
+            drop
+            i32.const 100000
+            i32.load)
+          (func $unreachable (export "unreachable") (type $t0) (result i32)
+            i32.const 1
+            drop
+            unreachable)
+          (memory $memory (export "memory") 1))
+        "#;
+
+    fn exact_instance(wat: &str, limit: u64) -> Instance {
+        let wasm_binary = wat2wasm(wat).unwrap();
+        let compiler = get_compiler_with(move || Metering::new(limit).with_exact_accounting());
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+        set_points_used(&mut instance, 0u64);
+        instance
+    }
+
+    #[test]
+    fn test_exact_points_after_trap() {
+        let instance = exact_instance(WAT_TRAPS, 1000);
+        let out_of_bounds: Func<(), i32> = instance.func("out_of_bounds").unwrap();
+        assert!(out_of_bounds.call().is_err());
+        // Every operator up to and including the load is charged.
+        assert_eq!(get_points_used(&instance), 6);
+
+        let instance = exact_instance(WAT_TRAPS, 1000);
+        let unreachable: Func<(), i32> = instance.func("unreachable").unwrap();
+        assert!(unreachable.call().is_err());
+        assert_eq!(get_points_used(&instance), 3);
+
+        // Operators are charged the same as without exact accounting.
+        let instance = exact_instance(WAT, 1000);
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        assert_eq!(add_to.call(3, 4), Ok(7));
+        assert_eq!(get_points_used(&instance), 74);
+    }
+
+    #[test]
+    fn test_exact_limit_is_never_exceeded() {
+        use wasmer_runtime_core::error::RuntimeError;
+
+        // Three operators and the end of the function.
+        let instance = exact_instance(WAT_TRAPS, 4);
+        let three: Func<(), i32> = instance.func("three").unwrap();
+        assert_eq!(three.call(), Ok(3));
+        assert_eq!(get_points_used(&instance), 4);
+
+        let instance = exact_instance(WAT_TRAPS, 3);
+        let three: Func<(), i32> = instance.func("three").unwrap();
+        assert!(three.call().is_err());
+        assert_eq!(get_points_used(&instance), 0);
+
+        let instance = exact_instance(WAT, 100);
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        match add_to.call(10_000_000, 4).unwrap_err() {
+            RuntimeError::Error { data } => {
+                assert!(data.downcast_ref::<ExecutionLimitExceededError>().is_some());
+            }
+            _ => unreachable!(),
+        }
+        assert!(get_points_used(&instance) <= 100);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let wasm_binary = wat2wasm(WAT).unwrap();
+
+        let compiler = get_compiler(100);
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        let fingerprint = compiler.middleware_fingerprint();
+        assert!(!fingerprint.is_empty());
+        assert_eq!(module.info().middleware_fingerprint, fingerprint);
+
+        // Only the configuration of the middleware matters, and the limit is set at runtime.
+        assert_eq!(get_compiler(100).middleware_fingerprint(), fingerprint);
+        assert_eq!(get_compiler(101).middleware_fingerprint(), fingerprint);
+        let others = vec![
+            get_compiler_with(|| Metering::with_cost_function(100, "two", |_| 2))
+                .middleware_fingerprint(),
+            get_compiler_with(|| Metering::with_cost_function(100, "three", |_| 3))
+                .middleware_fingerprint(),
+            get_compiler_with(|| Metering::new(100).with_import_cost("env", "gas", 1))
+                .middleware_fingerprint(),
+            get_compiler_with(|| Metering::new(100).with_memory_grow_cost(1))
+                .middleware_fingerprint(),
+            get_compiler_with(|| Metering::new(100).with_table_grow_cost(1))
+                .middleware_fingerprint(),
+            get_compiler_with(|| Metering::new(100).with_exact_accounting())
+                .middleware_fingerprint(),
+        ];
+        for (i, other) in others.iter().enumerate() {
+            assert_ne!(*other, fingerprint);
+            assert!(others[..i].iter().all(|previous| previous != other));
+        }
+    }
+
+    #[test]
+    fn test_cached_module_runs_with_execution_limit() {
+        use wasmer_runtime_core::{cache::Artifact, error::RuntimeError, load_cache_with};
+        let wasm_binary = wat2wasm(WAT).unwrap();
+
+        let compiler = get_compiler(100);
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        let bytes = module.cache().unwrap().serialize().unwrap();
+        let artifact = Artifact::deserialize(&bytes).unwrap();
+        let module = unsafe { load_cache_with(artifact, &compiler) }.unwrap();
+
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+        set_execution_limit(&mut instance, 10_000);
+        set_points_used(&mut instance, 0u64);
+        {
+            let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+            assert_eq!(add_to.call(10, 4), Ok(49));
+        }
+        assert!(get_points_used(&instance) > 100);
+
+        set_execution_limit(&mut instance, 50);
+        set_points_used(&mut instance, 0u64);
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        match add_to.call(3, 4).unwrap_err() {
+            RuntimeError::Error { data } => {
+                assert!(data.downcast_ref::<ExecutionLimitExceededError>().is_some());
+            }
+            _ => unreachable!(),
+        }
+    }
+
 }
@@ -8,11 +8,11 @@ use crate::{
     memory::wasmer_memory_t,
     module::wasmer_module_t,
     table::wasmer_table_t,
-    value::{wasmer_value, wasmer_value_t, wasmer_value_tag},
+    value::{wasmer_value_t, wasmer_value_tag, write_value_tags},
     wasmer_byte_array, wasmer_result_t,
 };
 use libc::c_int;
-use std::{ptr, slice};
+use std::{convert::TryFrom, ptr, slice};
 use wasmer_runtime::{Instance, Memory, Module, Value};
 use wasmer_runtime_core::{export::Export, module::ExportIndex};
 
@@ -229,10 +229,7 @@ pub unsafe extern "C" fn wasmer_export_func_params(
     if let Export::Function { ref signature, .. } = *export {
         let params: &mut [wasmer_value_tag] =
             slice::from_raw_parts_mut(params, params_len as usize);
-        for (i, item) in signature.params().iter().enumerate() {
-            params[i] = item.into();
-        }
-        wasmer_result_t::WASMER_OK
+        write_value_tags(signature.params(), params)
     } else {
         update_last_error(CApiError {
             msg: "func ptr error in wasmer_export_func_params".to_string(),
@@ -259,10 +256,7 @@ pub unsafe extern "C" fn wasmer_export_func_returns(
     if let Export::Function { ref signature, .. } = *export {
         let returns: &mut [wasmer_value_tag] =
             slice::from_raw_parts_mut(returns, returns_len as usize);
-        for (i, item) in signature.returns().iter().enumerate() {
-            returns[i] = item.into();
-        }
-        wasmer_result_t::WASMER_OK
+        write_value_tags(signature.returns(), returns)
     } else {
         update_last_error(CApiError {
             msg: "func ptr error in wasmer_export_func_returns".to_string(),
@@ -384,27 +378,14 @@ pub unsafe extern "C" fn wasmer_export_func_call(
     let result = instance.call(&named_export.name, &params[..]);
     match result {
         Ok(results_vec) => {
-            if !results_vec.is_empty() {
-                let ret = match results_vec[0] {
-                    Value::I32(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_I32,
-                        value: wasmer_value { I32: x },
-                    },
-                    Value::I64(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_I64,
-                        value: wasmer_value { I64: x },
-                    },
-                    Value::F32(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_F32,
-                        value: wasmer_value { F32: x },
-                    },
-                    Value::F64(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_F64,
-                        value: wasmer_value { F64: x },
-                    },
-                    Value::V128(_) => unimplemented!("returning V128 type"),
+            if let Some(value) = results_vec.into_iter().next() {
+                results[0] = match wasmer_value_t::try_from(value) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        update_last_error(err);
+                        return wasmer_result_t::WASMER_ERROR;
+                    }
                 };
-                results[0] = ret;
             }
             wasmer_result_t::WASMER_OK
         }
@@ -1,6 +1,11 @@
 //! Create, set, get and destroy global variables of an instance.
 
-use crate::value::{wasmer_value_t, wasmer_value_tag};
+use crate::{
+    error::update_last_error,
+    value::{wasmer_value_t, wasmer_value_tag},
+    wasmer_result_t,
+};
+use std::convert::TryFrom;
 use wasmer_runtime::Global;
 
 #[repr(C)]
@@ -29,13 +34,29 @@ pub unsafe extern "C" fn wasmer_global_new(
     Box::into_raw(Box::new(global)) as *mut wasmer_global_t
 }
 
-/// Gets the value stored by the given Global
+/// Stores the value of the given Global in `value`.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` if the value has no `wasmer_value_tag`. Use
+/// `wasmer_last_error_length` and `wasmer_last_error_message` to get an error message.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
-pub extern "C" fn wasmer_global_get(global: *mut wasmer_global_t) -> wasmer_value_t {
-    let global = unsafe { &*(global as *mut Global) };
-    let value: wasmer_value_t = global.get().into();
-    value
+pub unsafe extern "C" fn wasmer_global_get(
+    global: *mut wasmer_global_t,
+    value: *mut wasmer_value_t,
+) -> wasmer_result_t {
+    let global = &*(global as *mut Global);
+    match wasmer_value_t::try_from(global.get()) {
+        Ok(global_value) => {
+            *value = global_value;
+            wasmer_result_t::WASMER_OK
+        }
+        Err(err) => {
+            update_last_error(err);
+            wasmer_result_t::WASMER_ERROR
+        }
+    }
 }
 
 /// Sets the value stored by the given Global
@@ -46,17 +67,32 @@ pub extern "C" fn wasmer_global_set(global: *mut wasmer_global_t, value: wasmer_
     global.set(value.into());
 }
 
-/// Returns a descriptor (type, mutability) of the given Global
+/// Stores a descriptor (type, mutability) of the given Global in `descriptor`.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` if the type has no `wasmer_value_tag`. Use
+/// `wasmer_last_error_length` and `wasmer_last_error_message` to get an error message.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
-pub extern "C" fn wasmer_global_get_descriptor(
+pub unsafe extern "C" fn wasmer_global_get_descriptor(
     global: *mut wasmer_global_t,
-) -> wasmer_global_descriptor_t {
-    let global = unsafe { &*(global as *mut Global) };
-    let descriptor = global.descriptor();
-    wasmer_global_descriptor_t {
-        mutable: descriptor.mutable,
-        kind: descriptor.ty.into(),
+    descriptor: *mut wasmer_global_descriptor_t,
+) -> wasmer_result_t {
+    let global = &*(global as *mut Global);
+    let global_descriptor = global.descriptor();
+    match wasmer_value_tag::try_from(global_descriptor.ty) {
+        Ok(kind) => {
+            *descriptor = wasmer_global_descriptor_t {
+                mutable: global_descriptor.mutable,
+                kind,
+            };
+            wasmer_result_t::WASMER_OK
+        }
+        Err(err) => {
+            update_last_error(err);
+            wasmer_result_t::WASMER_ERROR
+        }
     }
 }
 
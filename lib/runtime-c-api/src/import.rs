@@ -5,12 +5,23 @@ use crate::{
     error::{update_last_error, CApiError},
     export::{wasmer_import_export_kind, wasmer_import_export_value},
     module::wasmer_module_t,
-    value::wasmer_value_tag,
+    value::{wasmer_value_tag, write_value_tags},
     wasmer_byte_array, wasmer_result_t,
 };
+#[cfg(all(unix, target_arch = "x86_64"))]
+use crate::{
+    instance::wasmer_instance_context_t,
+    value::{wasmer_value, wasmer_value_t},
+};
 use libc::c_uint;
+#[cfg(all(unix, target_arch = "x86_64"))]
+use std::convert::TryFrom;
 use std::{ffi::c_void, ptr, slice, sync::Arc};
+#[cfg(all(unix, target_arch = "x86_64"))]
+use wasmer_runtime::{Ctx, DynamicFunc};
 use wasmer_runtime::{Global, Memory, Module, Table};
+#[cfg(all(unix, target_arch = "x86_64"))]
+use wasmer_runtime_core::IsExport;
 use wasmer_runtime_core::{
     export::{Context, Export, FuncPointer},
     import::ImportObject,
@@ -342,6 +353,78 @@ pub unsafe extern "C" fn wasmer_import_func_new(
     Box::into_raw(export) as *mut wasmer_import_func_t
 }
 
+/// Creates new func whose signature is given at runtime.
+///
+/// Calls to the func are forwarded to `func` with the arguments as `wasmer_value_t`s.
+/// `func` must write the results into the `results` buffer, whose tags are already set,
+/// and return `wasmer_result_t::WASMER_OK`. Returning `wasmer_result_t::WASMER_ERROR`
+/// traps the calling instance. `data` is passed to every call of `func`.
+///
+/// Returns `NULL` if the signature isn't supported. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+///
+/// The caller owns the object and should call `wasmer_import_func_destroy` to free it.
+#[cfg(all(unix, target_arch = "x86_64"))]
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_import_func_new_dynamic(
+    func: extern "C" fn(
+        ctx: *const wasmer_instance_context_t,
+        params: *const wasmer_value_t,
+        params_len: c_uint,
+        results: *mut wasmer_value_t,
+        results_len: c_uint,
+        data: *mut c_void,
+    ) -> wasmer_result_t,
+    data: *mut c_void,
+    params: *const wasmer_value_tag,
+    params_len: c_uint,
+    returns: *const wasmer_value_tag,
+    returns_len: c_uint,
+) -> *mut wasmer_import_func_t {
+    let params: &[wasmer_value_tag] = slice::from_raw_parts(params, params_len as usize);
+    let params: Vec<Type> = params.iter().cloned().map(|x| x.into()).collect();
+    let return_tags: Vec<wasmer_value_tag> =
+        slice::from_raw_parts(returns, returns_len as usize).to_vec();
+    let returns: Vec<Type> = return_tags.iter().cloned().map(|x| x.into()).collect();
+
+    let signature = FuncSig::new(params, returns);
+    let dynamic_func = match DynamicFunc::new(signature, move |ctx, args| {
+        let params = args
+            .iter()
+            .cloned()
+            .map(wasmer_value_t::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| err.to_string())?;
+        let mut results: Vec<wasmer_value_t> = return_tags
+            .iter()
+            .map(|tag| wasmer_value_t {
+                tag: tag.clone(),
+                value: wasmer_value { I64: 0 },
+            })
+            .collect();
+        let result = func(
+            ctx as *const Ctx as *const wasmer_instance_context_t,
+            params.as_ptr(),
+            params.len() as c_uint,
+            results.as_mut_ptr(),
+            results.len() as c_uint,
+            data,
+        );
+        match result {
+            wasmer_result_t::WASMER_OK => Ok(results.into_iter().map(|x| x.into()).collect()),
+            wasmer_result_t::WASMER_ERROR => Err("host function returned an error".to_string()),
+        }
+    }) {
+        Ok(dynamic_func) => dynamic_func,
+        Err(err) => {
+            update_last_error(err);
+            return ptr::null_mut();
+        }
+    };
+    Box::into_raw(Box::new(dynamic_func.to_export())) as *mut wasmer_import_func_t
+}
+
 /// Sets the params buffer to the parameter types of the given wasmer_import_func_t
 ///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
@@ -359,10 +442,7 @@ pub unsafe extern "C" fn wasmer_import_func_params(
     if let Export::Function { ref signature, .. } = *export {
         let params: &mut [wasmer_value_tag] =
             slice::from_raw_parts_mut(params, params_len as usize);
-        for (i, item) in signature.params().iter().enumerate() {
-            params[i] = item.into();
-        }
-        wasmer_result_t::WASMER_OK
+        write_value_tags(signature.params(), params)
     } else {
         update_last_error(CApiError {
             msg: "func ptr error in wasmer_import_func_params".to_string(),
@@ -388,10 +468,7 @@ pub unsafe extern "C" fn wasmer_import_func_returns(
     if let Export::Function { ref signature, .. } = *export {
         let returns: &mut [wasmer_value_tag] =
             slice::from_raw_parts_mut(returns, returns_len as usize);
-        for (i, item) in signature.returns().iter().enumerate() {
-            returns[i] = item.into();
-        }
-        wasmer_result_t::WASMER_OK
+        write_value_tags(signature.returns(), returns)
     } else {
         update_last_error(CApiError {
             msg: "func ptr error in wasmer_import_func_returns".to_string(),
@@ -6,11 +6,11 @@ use crate::{
     import::{wasmer_import_object_t, wasmer_import_t},
     memory::wasmer_memory_t,
     module::wasmer_module_t,
-    value::{wasmer_value, wasmer_value_t, wasmer_value_tag},
+    value::wasmer_value_t,
     wasmer_result_t,
 };
 use libc::{c_char, c_int, c_void};
-use std::{collections::HashMap, ffi::CStr, slice};
+use std::{collections::HashMap, convert::TryFrom, ffi::CStr, slice};
 use wasmer_runtime::{Ctx, Global, Instance, Memory, Module, Table, Value};
 use wasmer_runtime_core::{
     export::Export,
@@ -198,27 +198,14 @@ pub unsafe extern "C" fn wasmer_instance_call(
 
     match result {
         Ok(results_vec) => {
-            if !results_vec.is_empty() {
-                let ret = match results_vec[0] {
-                    Value::I32(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_I32,
-                        value: wasmer_value { I32: x },
-                    },
-                    Value::I64(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_I64,
-                        value: wasmer_value { I64: x },
-                    },
-                    Value::F32(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_F32,
-                        value: wasmer_value { F32: x },
-                    },
-                    Value::F64(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_F64,
-                        value: wasmer_value { F64: x },
-                    },
-                    Value::V128(_) => unimplemented!("calling function with V128 parameter"),
+            if let Some(value) = results_vec.into_iter().next() {
+                results[0] = match wasmer_value_t::try_from(value) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        update_last_error(err);
+                        return wasmer_result_t::WASMER_ERROR;
+                    }
                 };
-                results[0] = ret;
             }
             wasmer_result_t::WASMER_OK
         }
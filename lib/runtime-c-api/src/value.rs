@@ -1,5 +1,10 @@
 //! Create and map Rust to WebAssembly values.
 
+use crate::{
+    error::{update_last_error, CApiError},
+    wasmer_result_t,
+};
+use std::convert::TryFrom;
 use wasmer_runtime::Value;
 use wasmer_runtime_core::types::Type;
 
@@ -57,9 +62,18 @@ impl From<wasmer_value_t> for Value {
     }
 }
 
-impl From<Value> for wasmer_value_t {
-    fn from(val: Value) -> Self {
-        match val {
+/// The error for values of types that have no `wasmer_value_tag`.
+fn unsupported_type(ty: Type) -> CApiError {
+    CApiError {
+        msg: format!("values of type {} are not supported by the C API", ty),
+    }
+}
+
+impl TryFrom<Value> for wasmer_value_t {
+    type Error = CApiError;
+
+    fn try_from(val: Value) -> Result<Self, CApiError> {
+        Ok(match val {
             Value::I32(x) => wasmer_value_t {
                 tag: wasmer_value_tag::WASM_I32,
                 value: wasmer_value { I32: x },
@@ -76,20 +90,23 @@ impl From<Value> for wasmer_value_t {
                 tag: wasmer_value_tag::WASM_F64,
                 value: wasmer_value { F64: x },
             },
-            Value::V128(_) => unimplemented!("V128 not supported in C API"),
-        }
+            Value::V128(_) | Value::AnyRef(_) | Value::FuncRef(_) => {
+                return Err(unsupported_type(val.ty()))
+            }
+        })
     }
 }
 
-impl From<Type> for wasmer_value_tag {
-    fn from(ty: Type) -> Self {
-        #[allow(unreachable_patterns)]
+impl TryFrom<Type> for wasmer_value_tag {
+    type Error = CApiError;
+
+    fn try_from(ty: Type) -> Result<Self, CApiError> {
         match ty {
-            Type::I32 => wasmer_value_tag::WASM_I32,
-            Type::I64 => wasmer_value_tag::WASM_I64,
-            Type::F32 => wasmer_value_tag::WASM_F32,
-            Type::F64 => wasmer_value_tag::WASM_F64,
-            Type::V128 => unreachable!("V128 not supported in C API"),
+            Type::I32 => Ok(wasmer_value_tag::WASM_I32),
+            Type::I64 => Ok(wasmer_value_tag::WASM_I64),
+            Type::F32 => Ok(wasmer_value_tag::WASM_F32),
+            Type::F64 => Ok(wasmer_value_tag::WASM_F64),
+            Type::V128 | Type::AnyRef | Type::FuncRef => Err(unsupported_type(ty)),
         }
     }
 }
@@ -107,14 +124,24 @@ impl From<wasmer_value_tag> for Type {
     }
 }
 
-impl From<&wasmer_runtime::wasm::Type> for wasmer_value_tag {
-    fn from(ty: &Type) -> Self {
-        match *ty {
-            Type::I32 => wasmer_value_tag::WASM_I32,
-            Type::I64 => wasmer_value_tag::WASM_I64,
-            Type::F32 => wasmer_value_tag::WASM_F32,
-            Type::F64 => wasmer_value_tag::WASM_F64,
-            Type::V128 => unimplemented!("V128 not supported in C API"),
+impl TryFrom<&wasmer_runtime::wasm::Type> for wasmer_value_tag {
+    type Error = CApiError;
+
+    fn try_from(ty: &Type) -> Result<Self, CApiError> {
+        wasmer_value_tag::try_from(*ty)
+    }
+}
+
+/// Writes the tags of `types` to `tags`, failing if one of the types has no tag.
+pub(crate) fn write_value_tags(types: &[Type], tags: &mut [wasmer_value_tag]) -> wasmer_result_t {
+    for (tag, ty) in tags.iter_mut().zip(types) {
+        match wasmer_value_tag::try_from(ty) {
+            Ok(ty_tag) => *tag = ty_tag,
+            Err(err) => {
+                update_last_error(err);
+                return wasmer_result_t::WASMER_ERROR;
+            }
         }
     }
+    wasmer_result_t::WASMER_OK
 }
@@ -10,7 +10,9 @@ int main()
     val.value.I32 = 7;
     wasmer_global_t *global = wasmer_global_new(val, true);
 
-    wasmer_value_t get_val = wasmer_global_get(global);
+    wasmer_value_t get_val;
+    wasmer_result_t get_result = wasmer_global_get(global, &get_val);
+    assert(get_result == WASMER_OK);
     assert( get_val.value.I32 == 7);
 
     wasmer_value_t val2;
@@ -18,10 +20,14 @@ int main()
     val2.value.I32 = 14;
     wasmer_global_set(global, val2);
 
-    wasmer_value_t new_get_val = wasmer_global_get(global);
+    wasmer_value_t new_get_val;
+    wasmer_result_t new_get_result = wasmer_global_get(global, &new_get_val);
+    assert(new_get_result == WASMER_OK);
     assert( new_get_val.value.I32 == 14);
 
-    wasmer_global_descriptor_t desc = wasmer_global_get_descriptor(global);
+    wasmer_global_descriptor_t desc;
+    wasmer_result_t desc_result = wasmer_global_get_descriptor(global, &desc);
+    assert(desc_result == WASMER_OK);
     assert(desc.mutable_);
     assert(desc.kind == WASM_I32);
 
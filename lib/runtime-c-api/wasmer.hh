@@ -245,11 +245,18 @@ int wasmer_exports_len(wasmer_exports_t *exports);
 /// Frees memory for the given Global
 void wasmer_global_destroy(wasmer_global_t *global);
 
-/// Gets the value stored by the given Global
-wasmer_value_t wasmer_global_get(wasmer_global_t *global);
+/// Stores the value of the given Global in `value`.
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` if the value has no `wasmer_value_tag`. Use
+/// `wasmer_last_error_length` and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_global_get(wasmer_global_t *global, wasmer_value_t *value);
 
-/// Returns a descriptor (type, mutability) of the given Global
-wasmer_global_descriptor_t wasmer_global_get_descriptor(wasmer_global_t *global);
+/// Stores a descriptor (type, mutability) of the given Global in `descriptor`.
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` if the type has no `wasmer_value_tag`. Use
+/// `wasmer_last_error_length` and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_global_get_descriptor(wasmer_global_t *global,
+                                             wasmer_global_descriptor_t *descriptor);
 
 /// Creates a new Global and returns a pointer to it.
 /// The caller owns the object and should call `wasmer_global_destroy` to free it.
@@ -293,6 +300,21 @@ wasmer_import_func_t *wasmer_import_func_new(void (*func)(void *data),
                                              const wasmer_value_tag *returns,
                                              unsigned int returns_len);
 
+/// Creates new func whose signature is given at runtime.
+/// Calls to the func are forwarded to `func` with the arguments as `wasmer_value_t`s.
+/// `func` must write the results into the `results` buffer, whose tags are already set,
+/// and return `wasmer_result_t::WASMER_OK`. Returning `wasmer_result_t::WASMER_ERROR`
+/// traps the calling instance. `data` is passed to every call of `func`.
+/// Returns `NULL` if the signature isn't supported. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+/// The caller owns the object and should call `wasmer_import_func_destroy` to free it.
+wasmer_import_func_t *wasmer_import_func_new_dynamic(wasmer_result_t (*func)(const wasmer_instance_context_t *ctx, const wasmer_value_t *params, unsigned int params_len, wasmer_value_t *results, unsigned int results_len, void *data),
+                                                     void *data,
+                                                     const wasmer_value_tag *params,
+                                                     unsigned int params_len,
+                                                     const wasmer_value_tag *returns,
+                                                     unsigned int returns_len);
+
 /// Sets the params buffer to the parameter types of the given wasmer_import_func_t
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
 /// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
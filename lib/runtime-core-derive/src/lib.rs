@@ -0,0 +1,87 @@
+//! Derive macros for `wasmer-runtime-core`.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Ident, Meta, NestedMeta};
+
+/// Implements `ValueType` for a struct, so that it can be read from and written
+/// to memory through a `WasmPtr`.
+///
+/// The struct must be `Copy` and `#[repr(C)]` or `#[repr(transparent)]`, every
+/// field must be a `ValueType`, and its layout must have no padding, since
+/// padding bytes would be read uninitialized. These are checked at compile time.
+///
+/// The generated code refers to `ValueType` as it is named where the derive is
+/// used; importing the derive, which shares its name, brings the trait into
+/// scope as well.
+#[proc_macro_derive(ValueType)]
+pub fn derive_value_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match value_type_impl(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn value_type_impl(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let name = &input.ident;
+    let fields = match input.data {
+        Data::Struct(ref data) => &data.fields,
+        _ => {
+            return Err(Error::new_spanned(
+                input,
+                "ValueType can only be derived for structs",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "ValueType can't be derived for generic structs",
+        ));
+    }
+    if !has_stable_layout(input) {
+        return Err(Error::new_spanned(
+            input,
+            "ValueType can only be derived for #[repr(C)] or #[repr(transparent)] structs",
+        ));
+    }
+
+    let field_types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let checked_types = field_types.iter();
+    let sized_types = field_types.iter();
+    let assertions = Ident::new(
+        &format!("__assert_value_type_layout_{}", name),
+        Span::call_site(),
+    );
+    Ok(quote! {
+        unsafe impl ValueType for #name {}
+
+        #[allow(dead_code, non_snake_case)]
+        fn #assertions() {
+            fn assert_value_type<T: ValueType>() {}
+            #(assert_value_type::<#checked_types>();)*
+
+            // Fails to compile if the fields don't add up to the size of the
+            // struct, i.e. if it has padding.
+            let _: [(); 0] = [(); (::std::mem::size_of::<#name>()
+                != 0 #(+ ::std::mem::size_of::<#sized_types>())*) as usize];
+        }
+    })
+}
+
+/// Whether the struct has `#[repr(C)]` or `#[repr(transparent)]`.
+fn has_stable_layout(input: &DeriveInput) -> bool {
+    input.attrs.iter().any(|attr| match attr.parse_meta() {
+        Ok(Meta::List(ref list)) if list.ident == "repr" => {
+            list.nested.iter().any(|nested| match nested {
+                NestedMeta::Meta(Meta::Word(ident)) => ident == "C" || ident == "transparent",
+                _ => false,
+            })
+        }
+        _ => false,
+    })
+}
@@ -111,9 +111,35 @@ impl Default for MemoryBoundCheckMode {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Features {
+    /// The SIMD proposal. The Cranelift backend doesn't support it and fails to
+    /// compile modules that use `v128` values.
     pub simd: bool,
+    pub multi_value: bool,
+    pub bulk_memory: bool,
+    pub threads: bool,
+    pub reference_types: bool,
+    /// The `i32.extend8_s` family of operators.
+    pub sign_extension: bool,
+    /// The `i32.trunc_sat_f32_s` family of operators.
+    pub sat_float_to_int: bool,
+}
+
+impl Default for Features {
+    /// Sign-extension and non-trapping float-to-int conversions are enabled
+    /// by default, since toolchains emit them by default.
+    fn default() -> Features {
+        Features {
+            simd: false,
+            multi_value: false,
+            bulk_memory: false,
+            threads: false,
+            reference_types: false,
+            sign_extension: true,
+            sat_float_to_int: true,
+        }
+    }
 }
 
 /// Configuration data for the compiler
@@ -139,6 +165,15 @@ pub trait Compiler {
     ) -> CompileResult<ModuleInner>;
 
     unsafe fn from_cache(&self, cache: Artifact, _: Token) -> Result<ModuleInner, CacheError>;
+
+    /// Returns a fingerprint of the middlewares the compiler runs on the code, or an
+    /// empty string if it runs none.
+    ///
+    /// It matches the `middleware_fingerprint` of the modules it compiles, and is meant
+    /// to be passed to `Cache::load_with_fingerprint`.
+    fn middleware_fingerprint(&self) -> String {
+        String::new()
+    }
 }
 
 pub trait RunnableModule: Send + Sync {
@@ -3,19 +3,22 @@ use crate::{
     export::{Context, Export},
     global::Global,
     import::ImportObject,
-    memory::Memory,
+    memory::{Memory, PageBudget},
     module::{ImportName, ModuleInfo, ModuleInner},
     sig_registry::SigRegistry,
     structures::{BoxedMap, Map, SliceMap, TypedIndex},
     table::Table,
     types::{
-        ImportedFuncIndex, ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex,
-        Initializer, LocalFuncIndex, LocalGlobalIndex, LocalMemoryIndex, LocalOrImport,
-        LocalTableIndex, SigIndex, Value,
+        DataIndex, ElemIndex, FuncIndex, ImportedFuncIndex, ImportedGlobalIndex,
+        ImportedMemoryIndex, ImportedTableIndex, Initializer, LocalFuncIndex, LocalGlobalIndex,
+        LocalMemoryIndex, LocalOrImport, LocalTableIndex, SigIndex, Value,
     },
+    units::Pages,
     vm,
 };
-use std::{fmt::Debug, slice};
+#[cfg(all(unix, target_arch = "x86_64"))]
+use crate::{fault::DirtyPageTracker, state::ExecutionStateImage};
+use std::{collections::HashSet, fmt::Debug, slice, sync::Arc};
 
 pub const INTERNALS_SIZE: usize = 256;
 
@@ -52,12 +55,37 @@ pub struct LocalBacking {
     pub(crate) local_functions: BoxedMap<LocalFuncIndex, *const vm::Func>,
 
     pub(crate) internals: Internals,
+
+    /// The budget the memories of the instance are charged to when they grow,
+    /// from `InstanceConfig::memory_page_limit`. The memories only hold weak
+    /// references to it, so it is kept alive here.
+    #[allow(dead_code)]
+    pub(crate) page_budget: Option<Arc<PageBudget>>,
+
+    /// The buffer that functions with more than one result write their
+    /// results to, sized for the largest result count in the module.
+    pub(crate) return_values: Box<[u64]>,
+
+    /// The passive data segments that have not been dropped by `data.drop`.
+    pub(crate) passive_data: HashSet<DataIndex>,
+    /// The passive element segments that have not been dropped by `elem.drop`.
+    pub(crate) passive_elements: HashSet<ElemIndex>,
+
+    /// The execution state of the call that was interrupted last, if the
+    /// instance hasn't been called since.
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub(crate) interrupted_state: Option<ExecutionStateImage>,
+    /// Records the pages of the first memory written to since the last
+    /// snapshot, once `Instance::track_dirty_pages` was called.
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub(crate) dirty_pages: Option<DirtyPageTracker>,
 }
 
 impl LocalBacking {
     pub(crate) fn new(
         module: &ModuleInner,
         imports: &ImportBacking,
+        page_limit: Option<Pages>,
         vmctx: *mut vm::Ctx,
     ) -> LinkResult<Self> {
         let mut memories = Self::generate_memories(module);
@@ -67,6 +95,10 @@ impl LocalBacking {
         // Ensure all initializers are valid before running finalizers
         Self::validate_memories(module, imports)?;
         Self::validate_tables(module, imports, &mut tables)?;
+        let page_budget = match page_limit {
+            Some(limit) => Some(Self::limit_memories(&memories, imports, limit)?),
+            None => None,
+        };
 
         let vm_memories = Self::finalize_memories(module, imports, &mut memories);
         let vm_tables = Self::finalize_tables(module, imports, &mut tables, vmctx);
@@ -88,9 +120,32 @@ impl LocalBacking {
             local_functions,
 
             internals: Internals([0; INTERNALS_SIZE]),
+
+            page_budget,
+
+            return_values: Self::generate_return_values(&module.info),
+
+            passive_data: module.info.passive_data.keys().cloned().collect(),
+            passive_elements: module.info.passive_elements.keys().cloned().collect(),
+
+            #[cfg(all(unix, target_arch = "x86_64"))]
+            interrupted_state: None,
+            #[cfg(all(unix, target_arch = "x86_64"))]
+            dirty_pages: None,
         })
     }
 
+    fn generate_return_values(info: &ModuleInfo) -> Box<[u64]> {
+        let max_slots = info
+            .signatures
+            .iter()
+            .filter(|(_, sig)| sig.return_slots() > 1)
+            .map(|(_, sig)| sig.return_slots())
+            .max()
+            .unwrap_or(0);
+        vec![0; max_slots].into_boxed_slice()
+    }
+
     fn generate_local_functions(module: &ModuleInner) -> BoxedMap<LocalFuncIndex, *const vm::Func> {
         (0..module.info.func_assoc.len() - module.info.imported_functions.len())
             .map(|index| {
@@ -125,6 +180,36 @@ impl LocalBacking {
         memories.into_boxed_map()
     }
 
+    /// Charges the growth of the memories of the instance to a budget of
+    /// `limit` pages, failing if they already add up to more than that.
+    fn limit_memories(
+        memories: &SliceMap<LocalMemoryIndex, Memory>,
+        imports: &ImportBacking,
+        limit: Pages,
+    ) -> LinkResult<Arc<PageBudget>> {
+        let all_memories = || {
+            memories
+                .iter()
+                .map(|(_, memory)| memory)
+                .chain(imports.memories.iter().map(|(_, memory)| memory))
+        };
+
+        let total = all_memories().map(|memory| memory.size().0 as u64).sum();
+        let budget = PageBudget::new(limit, total).map_err(|_| {
+            vec![LinkError::Generic {
+                message: format!(
+                    "memories of {} pages exceed the page limit of {}",
+                    total, limit.0
+                ),
+            }]
+        })?;
+
+        for memory in all_memories() {
+            memory.add_page_budget(&budget);
+        }
+        Ok(budget)
+    }
+
     /// Validate each locally-defined memory in the Module.
     ///
     /// This involves copying in the data initializers.
@@ -307,31 +392,8 @@ impl LocalBacking {
                     let table = &tables[local_table_index];
                     table.anyfunc_direct_access_mut(|elements| {
                         for (i, &func_index) in init.elements.iter().enumerate() {
-                            let sig_index = module.info.func_assoc[func_index];
-                            // let signature = &module.info.signatures[sig_index];
-                            let signature = SigRegistry
-                                .lookup_signature_ref(&module.info.signatures[sig_index]);
-                            let sig_id =
-                                vm::SigId(SigRegistry.lookup_sig_index(signature).index() as u32);
-
-                            let (func, ctx) = match func_index.local_or_import(&module.info) {
-                                LocalOrImport::Local(local_func_index) => (
-                                    module
-                                        .runnable_module
-                                        .get_func(&module.info, local_func_index)
-                                        .unwrap()
-                                        .as_ptr()
-                                        as *const vm::Func,
-                                    vmctx,
-                                ),
-                                LocalOrImport::Import(imported_func_index) => {
-                                    let vm::ImportedFunc { func, vmctx } =
-                                        imports.vm_functions[imported_func_index];
-                                    (func, vmctx)
-                                }
-                            };
-
-                            elements[init_base + i] = vm::Anyfunc { func, ctx, sig_id };
+                            elements[init_base + i] =
+                                func_index_to_anyfunc(module, imports, vmctx, func_index);
                         }
                     });
                 }
@@ -340,31 +402,8 @@ impl LocalBacking {
 
                     table.anyfunc_direct_access_mut(|elements| {
                         for (i, &func_index) in init.elements.iter().enumerate() {
-                            let sig_index = module.info.func_assoc[func_index];
-                            let signature = SigRegistry
-                                .lookup_signature_ref(&module.info.signatures[sig_index]);
-                            // let signature = &module.info.signatures[sig_index];
-                            let sig_id =
-                                vm::SigId(SigRegistry.lookup_sig_index(signature).index() as u32);
-
-                            let (func, ctx) = match func_index.local_or_import(&module.info) {
-                                LocalOrImport::Local(local_func_index) => (
-                                    module
-                                        .runnable_module
-                                        .get_func(&module.info, local_func_index)
-                                        .unwrap()
-                                        .as_ptr()
-                                        as *const vm::Func,
-                                    vmctx,
-                                ),
-                                LocalOrImport::Import(imported_func_index) => {
-                                    let vm::ImportedFunc { func, vmctx } =
-                                        imports.vm_functions[imported_func_index];
-                                    (func, vmctx)
-                                }
-                            };
-
-                            elements[init_base + i] = vm::Anyfunc { func, ctx, sig_id };
+                            elements[init_base + i] =
+                                func_index_to_anyfunc(module, imports, vmctx, func_index);
                         }
                     });
                 }
@@ -415,6 +454,36 @@ impl LocalBacking {
     }
 }
 
+/// Creates the table element referring to function `func_index` of the instance
+/// owning `vmctx`.
+pub(crate) fn func_index_to_anyfunc(
+    module: &ModuleInner,
+    imports: &ImportBacking,
+    vmctx: *mut vm::Ctx,
+    func_index: FuncIndex,
+) -> vm::Anyfunc {
+    let sig_index = module.info.func_assoc[func_index];
+    let signature = SigRegistry.lookup_signature_ref(&module.info.signatures[sig_index]);
+    let sig_id = vm::SigId(SigRegistry.lookup_sig_index(signature).index() as u32);
+
+    let (func, ctx) = match func_index.local_or_import(&module.info) {
+        LocalOrImport::Local(local_func_index) => (
+            module
+                .runnable_module
+                .get_func(&module.info, local_func_index)
+                .unwrap()
+                .as_ptr() as *const vm::Func,
+            vmctx,
+        ),
+        LocalOrImport::Import(imported_func_index) => {
+            let vm::ImportedFunc { func, vmctx } = imports.vm_functions[imported_func_index];
+            (func, vmctx)
+        }
+    };
+
+    vm::Anyfunc { func, ctx, sig_id }
+}
+
 #[derive(Debug)]
 pub struct ImportBacking {
     pub(crate) memories: BoxedMap<ImportedMemoryIndex, Memory>,
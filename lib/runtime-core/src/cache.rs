@@ -21,6 +21,12 @@ pub enum Error {
     InvalidFile(InvalidFileType),
     InvalidatedCache,
     UnsupportedBackend(Backend),
+    /// The module was compiled with other middlewares, or with other
+    /// configurations of them, than the ones asked for.
+    MiddlewareMismatch {
+        expected: String,
+        found: String,
+    },
 }
 
 impl From<io::Error> for Error {
@@ -58,6 +64,30 @@ impl WasmHash {
         WasmHash(first_part, second_part)
     }
 
+    /// Folds a middleware fingerprint, as returned by `MiddlewareChain::fingerprint`,
+    /// into the hash, so that modules compiled from the same wasm with different
+    /// middlewares get different keys.
+    ///
+    /// Folding in an empty fingerprint returns the hash unchanged.
+    pub fn with_middleware_fingerprint(self, fingerprint: &str) -> Self {
+        if fingerprint.is_empty() {
+            return self;
+        }
+        let mut first_part = [0u8; 32];
+        let mut second_part = [0u8; 32];
+
+        let mut state = blake2bp::State::new();
+        state.update(&self.into_array());
+        state.update(fingerprint.as_bytes());
+
+        let hasher = state.finalize();
+        let generic_array = hasher.as_bytes();
+
+        first_part.copy_from_slice(&generic_array[0..32]);
+        second_part.copy_from_slice(&generic_array[32..64]);
+        WasmHash(first_part, second_part)
+    }
+
     /// Create the hexadecimal representation of the
     /// stored hash.
     pub fn encode(self) -> String {
@@ -96,7 +126,7 @@ impl WasmHash {
     }
 }
 
-const CURRENT_CACHE_VERSION: u64 = 0;
+const CURRENT_CACHE_VERSION: u64 = 1;
 static WASMER_CACHE_MAGIC: [u8; 8] = *b"WASMER\0\0";
 
 /// The header of a cache file.
@@ -235,6 +265,33 @@ pub trait Cache {
     /// loads a cached module using a specific `Backend`
     fn load_with_backend(&self, key: WasmHash, backend: Backend)
         -> Result<Module, Self::LoadError>;
+    /// loads a cached module using a specific `Backend`, that was compiled with the
+    /// middlewares described by `middleware_fingerprint` (see `Compiler::middleware_fingerprint`)
+    ///
+    /// `load` and `load_with_backend` only load modules compiled without middlewares.
+    ///
+    /// By default, this calls `load_with_backend` if `middleware_fingerprint` is empty, and
+    /// fails with `Error::MiddlewareMismatch` otherwise, since a cache that doesn't know about
+    /// fingerprints can't tell how the modules it holds were compiled.
+    fn load_with_fingerprint(
+        &self,
+        key: WasmHash,
+        backend: Backend,
+        middleware_fingerprint: &str,
+    ) -> Result<Module, Self::LoadError>
+    where
+        Self::LoadError: From<Error>,
+    {
+        if middleware_fingerprint.is_empty() {
+            self.load_with_backend(key, backend)
+        } else {
+            Err(Error::MiddlewareMismatch {
+                expected: middleware_fingerprint.to_string(),
+                found: String::new(),
+            }
+            .into())
+        }
+    }
     fn store(&mut self, key: WasmHash, module: Module) -> Result<(), Self::StoreError>;
 }
 
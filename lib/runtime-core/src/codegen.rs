@@ -7,6 +7,7 @@ use crate::{
     structures::Map,
     types::{FuncIndex, FuncSig, SigIndex},
 };
+use blake2b_simd::blake2bp;
 use smallvec::SmallVec;
 use std::any::Any;
 use std::collections::HashMap;
@@ -34,6 +35,7 @@ pub enum InternalEvent {
     Breakpoint(BreakpointHandler),
     SetInternal(u32),
     GetInternal(u32),
+    Trap(InternalTrap),
 }
 
 impl fmt::Debug for InternalEvent {
@@ -44,6 +46,31 @@ impl fmt::Debug for InternalEvent {
             InternalEvent::Breakpoint(_) => write!(f, "Breakpoint"),
             InternalEvent::SetInternal(_) => write!(f, "SetInternal"),
             InternalEvent::GetInternal(_) => write!(f, "GetInternal"),
+            InternalEvent::Trap(trap) => write!(f, "Trap({:?})", trap),
+        }
+    }
+}
+
+/// An error that middleware can make compiled code unwind with.
+///
+/// Unlike a `Breakpoint`, whose handler is an arbitrary closure, it is compiled to a
+/// call into the runtime with a fixed code, so modules that raise it can be cached.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub enum InternalTrap {
+    /// Unwinds with `fault::ExecutionInterrupted`.
+    Interrupted = 0,
+    /// Unwinds with `error::ExecutionLimitExceededError`.
+    ExecutionLimitExceeded = 1,
+}
+
+impl InternalTrap {
+    /// Returns the trap a backend passed to the runtime as `code`.
+    pub fn from_code(code: u32) -> Option<InternalTrap> {
+        match code {
+            0 => Some(InternalTrap::Interrupted),
+            1 => Some(InternalTrap::ExecutionLimitExceeded),
+            _ => None,
         }
     }
 }
@@ -140,16 +167,52 @@ impl<
 pub fn validating_parser_config(features: &Features) -> wasmparser::ValidatingParserConfig {
     wasmparser::ValidatingParserConfig {
         operator_config: wasmparser::OperatorValidatorConfig {
-            enable_threads: false,
-            enable_reference_types: false,
+            enable_threads: features.threads,
+            enable_reference_types: features.reference_types,
             enable_simd: features.simd,
-            enable_bulk_memory: false,
-            enable_multi_value: false,
+            enable_bulk_memory: features.bulk_memory,
+            enable_multi_value: features.multi_value,
         },
         mutable_global_imports: true,
     }
 }
 
+/// Checks the operators that wasmparser accepts regardless of its config
+/// against `features`.
+pub fn check_operator_features(
+    op: &Operator,
+    features: &Features,
+) -> Result<(), wasmparser::BinaryReaderError> {
+    let message = match *op {
+        Operator::I32Extend8S
+        | Operator::I32Extend16S
+        | Operator::I64Extend8S
+        | Operator::I64Extend16S
+        | Operator::I64Extend32S
+            if !features.sign_extension =>
+        {
+            "sign extension support is not enabled"
+        }
+        Operator::I32TruncSSatF32
+        | Operator::I32TruncUSatF32
+        | Operator::I32TruncSSatF64
+        | Operator::I32TruncUSatF64
+        | Operator::I64TruncSSatF32
+        | Operator::I64TruncUSatF32
+        | Operator::I64TruncSSatF64
+        | Operator::I64TruncUSatF64
+            if !features.sat_float_to_int =>
+        {
+            "non-trapping float-to-int conversions support is not enabled"
+        }
+        _ => return Ok(()),
+    };
+    Err(wasmparser::BinaryReaderError {
+        message,
+        offset: -1isize as usize,
+    })
+}
+
 fn validate_with_features(bytes: &[u8], features: &Features) -> CompileResult<()> {
     let mut parser =
         wasmparser::ValidatingParser::new(bytes, Some(validating_parser_config(features)));
@@ -160,6 +223,10 @@ fn validate_with_features(bytes: &[u8], features: &Features) -> CompileResult<()
             wasmparser::ParserState::Error(err) => Err(CompileError::ValidationError {
                 msg: err.message.to_string(),
             })?,
+            wasmparser::ParserState::CodeOperator(ref op) => check_operator_features(op, features)
+                .map_err(|err| CompileError::ValidationError {
+                    msg: err.message.to_string(),
+                })?,
             _ => {}
         }
     }
@@ -185,6 +252,14 @@ impl<
 
         let mut mcg = MCG::new();
         let mut chain = (self.middleware_chain_generator)();
+        // Backends other than singlepass don't poll the interrupt signal page, so
+        // calls with a timeout rely on `INTERRUPT_FIELD` being checked.
+        #[cfg(all(unix, target_arch = "x86_64"))]
+        {
+            if MCG::backend_id() != Backend::Singlepass && !chain.checks_interrupts() {
+                chain.push_builtin(crate::fault::InterruptCheck);
+            }
+        }
         let info = crate::parse::read_module(
             wasm,
             MCG::backend_id(),
@@ -211,6 +286,10 @@ impl<
     ) -> Result<ModuleInner, CacheError> {
         MCG::from_cache(artifact, token)
     }
+
+    fn middleware_fingerprint(&self) -> String {
+        (self.middleware_chain_generator)().fingerprint()
+    }
 }
 
 fn requires_pre_validation(backend: Backend) -> bool {
@@ -233,17 +312,54 @@ impl<'a, 'b> EventSink<'a, 'b> {
 
 pub struct MiddlewareChain {
     chain: Vec<Box<dyn GenericFunctionMiddleware>>,
+    /// Middlewares added by the compiler itself, run after `chain`. They don't change
+    /// what the compiled module computes, so they are left out of the fingerprint.
+    builtins: Vec<Box<dyn GenericFunctionMiddleware>>,
 }
 
 impl MiddlewareChain {
     pub fn new() -> MiddlewareChain {
-        MiddlewareChain { chain: vec![] }
+        MiddlewareChain {
+            chain: vec![],
+            builtins: vec![],
+        }
     }
 
     pub fn push<M: FunctionMiddleware + 'static>(&mut self, m: M) {
         self.chain.push(Box::new(m));
     }
 
+    /// Returns a fingerprint of the middlewares in the chain, in order, and of their
+    /// configuration, or an empty string if the chain is empty.
+    ///
+    /// Modules compiled with chains that have different fingerprints must not be
+    /// used in place of one another.
+    pub fn fingerprint(&self) -> String {
+        if self.chain.is_empty() {
+            return String::new();
+        }
+        let mut state = blake2bp::State::new();
+        for m in &self.chain {
+            let fingerprint = m.fingerprint();
+            state.update(&(fingerprint.len() as u64).to_le_bytes());
+            state.update(fingerprint.as_bytes());
+        }
+        hex::encode(state.finalize().as_bytes())
+    }
+
+    /// Whether a middleware in the chain checks `fault::INTERRUPT_FIELD`.
+    pub fn checks_interrupts(&self) -> bool {
+        self.chain
+            .iter()
+            .chain(self.builtins.iter())
+            .any(|m| m.checks_interrupts())
+    }
+
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    fn push_builtin<M: FunctionMiddleware + 'static>(&mut self, m: M) {
+        self.builtins.push(Box::new(m));
+    }
+
     pub(crate) fn run<E: Debug, FCG: FunctionCodeGenerator<E>>(
         &mut self,
         fcg: Option<&mut FCG>,
@@ -254,7 +370,7 @@ impl MiddlewareChain {
             buffer: SmallVec::new(),
         };
         sink.push(ev);
-        for m in &mut self.chain {
+        for m in self.chain.iter_mut().chain(self.builtins.iter_mut()) {
             let prev: SmallVec<[Event; 2]> = sink.buffer.drain().collect();
             for ev in prev {
                 m.feed_event(ev, module_info, &mut sink)?;
@@ -279,6 +395,26 @@ pub trait FunctionMiddleware {
         module_info: &ModuleInfo,
         sink: &mut EventSink<'a, 'b>,
     ) -> Result<(), Self::Error>;
+
+    /// Describes the middleware and its configuration, so that modules compiled with
+    /// differently configured middlewares can be told apart, e.g. in a cache.
+    ///
+    /// Middlewares that transform code differently must have different fingerprints.
+    /// The default, an empty string, is only suitable for a middleware that can't be
+    /// configured and is never used alongside other such middlewares.
+    fn fingerprint(&self) -> String {
+        String::new()
+    }
+
+    /// Whether the middleware makes functions stop, by raising `InternalTrap::Interrupted`,
+    /// once `fault::INTERRUPT_FIELD` is set, at least before every call and at every loop
+    /// header.
+    ///
+    /// Streaming compilers add `fault::InterruptCheck` to chains without such a middleware
+    /// when the backend doesn't poll the interrupt signal page.
+    fn checks_interrupts(&self) -> bool {
+        false
+    }
 }
 
 pub(crate) trait GenericFunctionMiddleware {
@@ -288,6 +424,8 @@ pub(crate) trait GenericFunctionMiddleware {
         module_info: &ModuleInfo,
         sink: &mut EventSink<'a, 'b>,
     ) -> Result<(), String>;
+    fn fingerprint(&self) -> String;
+    fn checks_interrupts(&self) -> bool;
 }
 
 impl<E: Debug, T: FunctionMiddleware<Error = E>> GenericFunctionMiddleware for T {
@@ -300,6 +438,14 @@ impl<E: Debug, T: FunctionMiddleware<Error = E>> GenericFunctionMiddleware for T
         <Self as FunctionMiddleware>::feed_event(self, op, module_info, sink)
             .map_err(|x| format!("{:?}", x))
     }
+
+    fn fingerprint(&self) -> String {
+        <Self as FunctionMiddleware>::fingerprint(self)
+    }
+
+    fn checks_interrupts(&self) -> bool {
+        <Self as FunctionMiddleware>::checks_interrupts(self)
+    }
 }
 
 /// The function-scope code generator trait.
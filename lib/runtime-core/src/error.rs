@@ -1,4 +1,11 @@
-use crate::types::{FuncSig, GlobalDescriptor, MemoryDescriptor, TableDescriptor, Type};
+use crate::backend::Backend;
+use crate::cache::WasmHash;
+use crate::structures::TypedIndex;
+use crate::typed_func::WasmTrapInfo;
+use crate::types::{
+    FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex, MemoryDescriptor, TableDescriptor,
+    TableIndex, Type, Value,
+};
 use core::borrow::Borrow;
 use std::any::Any;
 
@@ -126,8 +133,109 @@ impl std::error::Error for LinkError {}
 ///
 /// Comparing two `RuntimeError`s always evaluates to false.
 pub enum RuntimeError {
-    Trap { msg: Box<str> },
-    Error { data: Box<dyn Any> },
+    Trap {
+        msg: Box<str>,
+        details: Box<TrapDetails>,
+    },
+    Error {
+        data: Box<dyn Any>,
+    },
+    /// Execution was stopped through the instance's `InterruptHandle`.
+    Interrupted,
+    /// A call made with a timeout ran past its deadline.
+    Timeout,
+}
+
+impl RuntimeError {
+    pub(crate) fn from_trap(details: TrapDetails) -> RuntimeError {
+        RuntimeError::Trap {
+            msg: details.to_string().into(),
+            details: Box::new(details),
+        }
+    }
+
+    /// Converts the payload a call into WebAssembly unwound with into
+    /// a `RuntimeError`.
+    ///
+    /// Runtime errors returned by imported functions, e.g. from calls they
+    /// made back into the instance, are passed on unchanged.
+    pub(crate) fn from_unwind_payload(data: Box<dyn Any>) -> RuntimeError {
+        #[cfg(all(unix, target_arch = "x86_64"))]
+        {
+            if data.is::<crate::fault::ExecutionInterrupted>() {
+                return RuntimeError::Interrupted;
+            }
+        }
+        let data = match data.downcast::<RuntimeError>() {
+            Ok(error) => return *error,
+            Err(data) => data,
+        };
+        let data = match data.downcast::<CallError>() {
+            Ok(error) => match *error {
+                CallError::Runtime(error) => return error,
+                error => Box::new(error) as Box<dyn Any>,
+            },
+            Err(data) => data,
+        };
+        match data.downcast::<TrapDetails>() {
+            Ok(details) => RuntimeError::from_trap(*details),
+            Err(data) => RuntimeError::Error { data },
+        }
+    }
+}
+
+/// Structured information about a WebAssembly trap.
+///
+/// Backends fill in as much as they know: the kind of trap is always
+/// present, while the location and the frames require the backend to
+/// track execution state (currently only singlepass does).
+#[derive(Debug, Clone)]
+pub struct TrapDetails {
+    pub kind: WasmTrapInfo,
+    /// The function that was executing when the trap occurred.
+    pub func_index: Option<FuncIndex>,
+    /// The name of `func_index` from the module's "name" section, if any.
+    pub func_name: Option<String>,
+    /// Index of the trapping operator within the body of `func_index`.
+    pub wasm_inst_offset: Option<usize>,
+    /// The WebAssembly call stack at the time of the trap, innermost frame first.
+    pub frames: Vec<TrapFrame>,
+}
+
+/// A single frame of the call stack captured with a trap.
+#[derive(Debug, Clone)]
+pub struct TrapFrame {
+    pub func_index: FuncIndex,
+    /// Index of the operator being executed within the body of `func_index`.
+    pub wasm_inst_offset: usize,
+}
+
+impl TrapDetails {
+    pub fn new(kind: WasmTrapInfo) -> TrapDetails {
+        TrapDetails {
+            kind,
+            func_index: None,
+            func_name: None,
+            wasm_inst_offset: None,
+            frames: vec![],
+        }
+    }
+}
+
+impl std::fmt::Display for TrapDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(func_index) = self.func_index {
+            match self.func_name {
+                Some(ref name) => write!(f, " in function {} ({})", name, func_index.index())?,
+                None => write!(f, " in function {}", func_index.index())?,
+            }
+            if let Some(offset) = self.wasm_inst_offset {
+                write!(f, " at instruction {}", offset)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl PartialEq for RuntimeError {
@@ -139,7 +247,7 @@ impl PartialEq for RuntimeError {
 impl std::fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            RuntimeError::Trap { ref msg } => {
+            RuntimeError::Trap { ref msg, .. } => {
                 write!(f, "WebAssembly trap occurred during runtime: {}", msg)
             }
             RuntimeError::Error { data } => {
@@ -151,6 +259,8 @@ impl std::fmt::Display for RuntimeError {
                     write!(f, "unknown error")
                 }
             }
+            RuntimeError::Interrupted => write!(f, "WebAssembly execution was interrupted"),
+            RuntimeError::Timeout => write!(f, "WebAssembly execution timed out"),
         }
     }
 }
@@ -243,6 +353,217 @@ impl std::fmt::Debug for CallError {
 
 impl std::error::Error for CallError {}
 
+/// This error type is produced when taking a snapshot of an instance, or
+/// when loading an `InstanceImage` into one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageError {
+    /// The instance was interrupted, but the backend didn't track its execution
+    /// state. Singlepass only does so when compiling with `track_state` set.
+    NoExecutionState,
+    /// A table element refers to a function of another instance, which an
+    /// image can't describe.
+    ForeignTableElement { table: TableIndex, index: u32 },
+    /// The instance has a global holding a reference, which an image can't
+    /// describe.
+    ReferenceGlobal(GlobalIndex),
+    /// A delta was requested, but no snapshot was taken since dirty page
+    /// tracking started.
+    NoBaseImage,
+    /// Dirty page tracking relies on the signal handlers of singlepass, and
+    /// the module was compiled by another backend.
+    DirtyPagesUnsupported(Backend),
+    /// Too many memories already have their dirty pages tracked.
+    TooManyDirtyPageTrackers,
+    /// The image doesn't fit the instance it is loaded into.
+    Mismatch(String),
+    /// The image was taken from an instance of another module.
+    WrongModule { expected: WasmHash, found: WasmHash },
+    /// The image was taken from a module compiled by another backend.
+    WrongBackend { expected: Backend, found: Backend },
+    /// The image was taken from a module compiled with other middlewares, or
+    /// with other configurations of them.
+    WrongMiddlewares { expected: String, found: String },
+    /// The input doesn't start like an image file.
+    InvalidMagic,
+    /// The image file was written in a format version that can't be read.
+    UnsupportedVersion(u32),
+    /// The contents of the image file don't match its checksum.
+    ChecksumMismatch,
+    /// The image file couldn't be decoded.
+    Malformed(String),
+    /// The image couldn't be encoded as an image file.
+    Encoding(String),
+}
+
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImageError::NoExecutionState => write!(
+                f,
+                "the execution state of the instance is unknown, compile the module with state tracking enabled"
+            ),
+            ImageError::ForeignTableElement { table, index } => write!(
+                f,
+                "element {} of table {} refers to a function of another instance",
+                index,
+                table.index()
+            ),
+            ImageError::ReferenceGlobal(global) => write!(
+                f,
+                "global {} holds a reference, which an image can't describe",
+                global.index()
+            ),
+            ImageError::NoBaseImage => write!(
+                f,
+                "no snapshot was taken since dirty page tracking started, there is no image to take a delta from"
+            ),
+            ImageError::DirtyPagesUnsupported(backend) => write!(
+                f,
+                "dirty page tracking needs the singlepass backend, but the module was compiled by the {} backend",
+                backend.to_string()
+            ),
+            ImageError::TooManyDirtyPageTrackers => write!(
+                f,
+                "too many memories have their dirty pages tracked already"
+            ),
+            ImageError::Mismatch(msg) => write!(f, "the image doesn't match the instance: {}", msg),
+            ImageError::WrongModule { expected, found } => write!(
+                f,
+                "the image belongs to module {}, not to module {}",
+                found.encode(),
+                expected.encode()
+            ),
+            ImageError::WrongBackend { expected, found } => write!(
+                f,
+                "the image was taken with the {} backend, not the {} backend",
+                found.to_string(),
+                expected.to_string()
+            ),
+            ImageError::WrongMiddlewares { expected, found } => write!(
+                f,
+                "the image was taken with the middlewares {:?}, not {:?}",
+                found, expected
+            ),
+            ImageError::InvalidMagic => write!(f, "the input is not an instance image"),
+            ImageError::UnsupportedVersion(version) => write!(
+                f,
+                "the image format version {} is not supported, expected version {}",
+                version,
+                crate::state::IMAGE_FORMAT_VERSION
+            ),
+            ImageError::ChecksumMismatch => {
+                write!(f, "the image is corrupted, its checksum doesn't match")
+            }
+            ImageError::Malformed(msg) => write!(f, "the image is malformed: {}", msg),
+            ImageError::Encoding(msg) => write!(f, "the image couldn't be encoded: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// This error type is produced by `Instance::resume`.
+///
+/// If the image can't be loaded, this will be the `ResumeError::Image(ImageError)`
+/// variant. If the resumed call traps, it will be `ResumeError::Runtime(RuntimeError)`.
+///
+/// Comparing two `ResumeError`s always evaluates to false.
+pub enum ResumeError {
+    Image(ImageError),
+    Runtime(RuntimeError),
+}
+
+impl PartialEq for ResumeError {
+    fn eq(&self, _other: &ResumeError) -> bool {
+        false
+    }
+}
+
+impl std::fmt::Display for ResumeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ResumeError::Image(image_error) => write!(f, "Resume error: {}", image_error),
+            ResumeError::Runtime(runtime_error) => write!(f, "Resume error: {}", runtime_error),
+        }
+    }
+}
+
+impl std::fmt::Debug for ResumeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ResumeError::Image(image_err) => write!(f, "ImageError: {:?}", image_err),
+            ResumeError::Runtime(runtime_err) => write!(f, "RuntimeError: {:?}", runtime_err),
+        }
+    }
+}
+
+impl std::error::Error for ResumeError {}
+
+impl From<ImageError> for ResumeError {
+    fn from(image_err: ImageError) -> Self {
+        ResumeError::Image(image_err)
+    }
+}
+
+impl From<RuntimeError> for ResumeError {
+    fn from(runtime_err: RuntimeError) -> Self {
+        ResumeError::Runtime(runtime_err)
+    }
+}
+
+/// This error type is produced when passing data to an instance through
+/// a `GuestAllocator`.
+///
+/// Comparing two `GuestMemoryError`s always evaluates to false.
+#[derive(Debug)]
+pub enum GuestMemoryError {
+    /// Calling the allocator failed.
+    Call(CallError),
+    /// The allocator returned a null pointer.
+    AllocationFailed { size: u32 },
+    /// A function of the allocator didn't return what the protocol expects.
+    UnexpectedResults {
+        function: String,
+        results: Vec<Value>,
+    },
+    /// The data doesn't fit in the memory where the allocator put it.
+    OutOfBounds,
+    /// The data is too large for its length to fit in 32 bits.
+    TooLarge,
+}
+
+impl PartialEq for GuestMemoryError {
+    fn eq(&self, _other: &GuestMemoryError) -> bool {
+        false
+    }
+}
+
+impl std::fmt::Display for GuestMemoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GuestMemoryError::Call(call_error) => write!(f, "the allocator failed: {}", call_error),
+            GuestMemoryError::AllocationFailed { size } => {
+                write!(f, "the allocator couldn't allocate {} bytes", size)
+            }
+            GuestMemoryError::UnexpectedResults { function, results } => write!(
+                f,
+                "the allocator function {} returned unexpected results: {:?}",
+                function, results
+            ),
+            GuestMemoryError::OutOfBounds => write!(f, "the allocation is out of bounds"),
+            GuestMemoryError::TooLarge => write!(f, "the data is larger than 4 GiB"),
+        }
+    }
+}
+
+impl std::error::Error for GuestMemoryError {}
+
+impl From<CallError> for GuestMemoryError {
+    fn from(call_err: CallError) -> Self {
+        GuestMemoryError::Call(call_err)
+    }
+}
+
 /// This error type is produced when creating something,
 /// like a `Memory` or a `Table`.
 #[derive(Debug, Clone)]
@@ -374,6 +695,7 @@ pub enum GrowError {
     TableGrowError,
     ExceededMaxPages(PageError),
     ExceededMaxPagesForMemory(usize, usize),
+    ExceededPageLimit(usize, usize),
     CouldNotProtectMemory(MemoryProtectionError),
     CouldNotCreateMemory(MemoryCreationError),
 }
@@ -385,6 +707,7 @@ impl std::fmt::Display for GrowError {
             GrowError::TableGrowError => write!(f, "Unable to grow table"),
             GrowError::ExceededMaxPages(e) => write!(f, "Grow Error: {}", e),
             GrowError::ExceededMaxPagesForMemory(left, added) => write!(f, "Failed to add pages because would exceed maximum number of pages for the memory. Left: {}, Added: {}", left, added),
+            GrowError::ExceededPageLimit(requested, limit) => write!(f, "Failed to add pages because would exceed the page limit. Requested: {}, Limit: {}", requested, limit),
             GrowError::CouldNotCreateMemory(e) => write!(f, "Grow Error: {}", e),
             GrowError::CouldNotProtectMemory(e) => write!(f, "Grow Error: {}", e),
         }
@@ -480,3 +803,30 @@ impl From<wasmparser::BinaryReaderError> for ParseError {
         ParseError::BinaryReadError
     }
 }
+
+/// An error returned when a `DynamicFunc` can't be created for a signature.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicFuncError {
+    /// The signature has a parameter or result of a type that host functions
+    /// can't receive or return.
+    UnsupportedType(Type),
+}
+
+impl std::fmt::Display for DynamicFuncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DynamicFuncError::UnsupportedType(ty) => write!(
+                f,
+                "{:?} values are not supported by dynamic host functions",
+                ty
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DynamicFuncError {}
+
+/// The unwind payload used when a metered call uses more points than its
+/// execution limit allows.
+#[derive(Copy, Clone, Debug)]
+pub struct ExecutionLimitExceededError;
@@ -3,7 +3,7 @@ use crate::{
     module::ModuleInner, table::Table, types::FuncSig, vm,
 };
 use indexmap::map::Iter as IndexMapIter;
-use std::sync::Arc;
+use std::{any::Any, rc::Rc, sync::Arc};
 
 #[derive(Debug, Copy, Clone)]
 pub enum Context {
@@ -24,14 +24,20 @@ pub enum Export {
 }
 
 #[derive(Debug, Clone)]
-pub struct FuncPointer(*const vm::Func);
+pub struct FuncPointer(*const vm::Func, Option<Rc<dyn Any>>);
 
 impl FuncPointer {
     /// This needs to be unsafe because there is
     /// no way to check whether the passed function
     /// is valid and has the right signature.
     pub unsafe fn new(f: *const vm::Func) -> Self {
-        FuncPointer(f)
+        FuncPointer(f, None)
+    }
+
+    /// Like `new`, but keeps `owner` alive as long as the pointer,
+    /// for functions that live in memory owned by `owner`.
+    pub(crate) unsafe fn with_owner(f: *const vm::Func, owner: Rc<dyn Any>) -> Self {
+        FuncPointer(f, Some(owner))
     }
 
     pub(crate) fn inner(&self) -> *const vm::Func {
@@ -8,20 +8,40 @@ mod raw {
     }
 }
 
-use crate::codegen::{BreakpointInfo, BreakpointMap};
+use crate::backend::Backend;
+use crate::codegen::{
+    BreakpointInfo, BreakpointMap, Event, EventSink, FunctionMiddleware, InternalEvent,
+    InternalTrap,
+};
+use crate::error::{RuntimeError, TrapDetails, TrapFrame};
+use crate::module::ModuleInfo;
 use crate::state::x64::{build_instance_image, read_stack, X64Register, GPR, XMM};
-use crate::vm;
-use libc::{mmap, mprotect, siginfo_t, MAP_ANON, MAP_PRIVATE, PROT_NONE, PROT_READ, PROT_WRITE};
+use crate::state::ExecutionStateImage;
+use crate::structures::TypedIndex;
+use crate::typed_func::WasmTrapInfo;
+use crate::types::FuncIndex;
+use crate::vm::{self, InternalField};
+use libc::{
+    mmap, mprotect, munmap, siginfo_t, MAP_ANON, MAP_PRIVATE, PROT_NONE, PROT_READ, PROT_WRITE,
+};
 use nix::sys::signal::{
     sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal, SIGBUS, SIGFPE, SIGILL, SIGINT,
     SIGSEGV, SIGTRAP,
 };
 use std::any::Any;
 use std::cell::UnsafeCell;
+use std::cmp;
 use std::ffi::c_void;
+use std::mem;
+use std::ops::Range;
 use std::process;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Once;
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
 
 pub(crate) unsafe fn run_on_alternative_stack(stack_end: *mut u64, stack_begin: *mut u64) -> u64 {
     raw::run_on_alternative_stack(stack_end, stack_begin)
@@ -86,6 +106,655 @@ pub unsafe fn clear_wasm_interrupt() {
     }
 }
 
+/// A page owned by a single instance that its code polls at function entries and
+/// loop headers, in place of the process-wide interrupt signal memory.
+pub(crate) struct InterruptSignalPage(*mut u8);
+unsafe impl Send for InterruptSignalPage {}
+unsafe impl Sync for InterruptSignalPage {}
+
+impl InterruptSignalPage {
+    pub(crate) fn new() -> InterruptSignalPage {
+        let ptr = unsafe {
+            mmap(
+                ::std::ptr::null_mut(),
+                INTERRUPT_SIGNAL_MEM_SIZE,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANON,
+                -1,
+                0,
+            )
+        };
+        if ptr as isize == -1 {
+            panic!("cannot allocate interrupt signal page");
+        }
+        InterruptSignalPage(ptr as _)
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut u8 {
+        self.0
+    }
+}
+
+impl Drop for InterruptSignalPage {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.0 as _, INTERRUPT_SIGNAL_MEM_SIZE);
+        }
+    }
+}
+
+/// What an `InterruptHandle` stops: the signal page the instance polls, and its
+/// `INTERRUPT_FIELD`, which is detached once the instance is dropped.
+pub(crate) struct InterruptTarget {
+    page: InterruptSignalPage,
+    field: Mutex<*const AtomicU64>,
+}
+unsafe impl Send for InterruptTarget {}
+unsafe impl Sync for InterruptTarget {}
+
+impl InterruptTarget {
+    pub(crate) unsafe fn new(ctx: *mut vm::Ctx) -> InterruptTarget {
+        InterruptTarget {
+            page: InterruptSignalPage::new(),
+            field: Mutex::new(interrupt_field(ctx)),
+        }
+    }
+
+    pub(crate) fn page_ptr(&self) -> *mut u8 {
+        self.page.as_ptr()
+    }
+
+    /// Stops using the `INTERRUPT_FIELD` of the instance, which is being dropped.
+    pub(crate) fn detach(&self) {
+        *self.field.lock().unwrap() = ptr::null();
+    }
+}
+
+/// A handle that can be used, from any thread, to stop the execution of
+/// the instance it was obtained from.
+///
+/// The instance stops at the next function entry or loop header (before
+/// the next call, with backends other than singlepass) and the call that
+/// was running returns [`RuntimeError::Interrupted`]. If the instance is
+/// not running when `interrupt` is called, the next call into it is
+/// interrupted instead.
+///
+/// [`RuntimeError::Interrupted`]: ../error/enum.RuntimeError.html#variant.Interrupted
+#[derive(Clone)]
+pub struct InterruptHandle {
+    target: Arc<InterruptTarget>,
+}
+
+impl InterruptHandle {
+    pub(crate) fn new(target: Arc<InterruptTarget>) -> InterruptHandle {
+        InterruptHandle { target }
+    }
+
+    /// Requests the instance to stop as soon as possible.
+    pub fn interrupt(&self) {
+        let field = self.target.field.lock().unwrap();
+        unsafe {
+            if !field.is_null() {
+                (**field).store(1, Ordering::SeqCst);
+            }
+            set_interrupt_page(self.target.page_ptr());
+        }
+    }
+}
+
+/// Returns the `INTERRUPT_FIELD` of the instance owning `ctx`.
+unsafe fn interrupt_field(ctx: *mut vm::Ctx) -> *const AtomicU64 {
+    let field_index = INTERRUPT_FIELD.index();
+    (*(*ctx).internal.internals).as_mut_ptr().add(field_index) as *const AtomicU64
+}
+
+/// Re-arms the interrupts of the instance owning `ctx` after it was stopped, so that
+/// it can be called again.
+pub(crate) unsafe fn clear_interrupt(ctx: *mut vm::Ctx) {
+    (*interrupt_field(ctx)).store(0, Ordering::SeqCst);
+    let signal_mem = (*ctx).internal.interrupt_signal_mem;
+    if signal_mem != get_wasm_interrupt_signal_mem() {
+        clear_interrupt_page(signal_mem);
+    }
+}
+
+unsafe fn set_interrupt_page(mem: *mut u8) {
+    if mprotect(mem as _, INTERRUPT_SIGNAL_MEM_SIZE, PROT_NONE) < 0 {
+        panic!("cannot set PROT_NONE on interrupt signal page");
+    }
+}
+
+unsafe fn clear_interrupt_page(mem: *mut u8) {
+    if mprotect(mem as _, INTERRUPT_SIGNAL_MEM_SIZE, PROT_READ | PROT_WRITE) < 0 {
+        panic!("cannot set PROT_READ | PROT_WRITE on interrupt signal page");
+    }
+}
+
+/// The unwind payload used when an instance is stopped through its `InterruptHandle`.
+pub struct ExecutionInterrupted;
+
+/// Forgets the execution state captured when the instance owning `ctx` was last
+/// interrupted, which no longer describes the instance once it is called again.
+pub(crate) unsafe fn discard_interrupted_state(ctx: *mut vm::Ctx) {
+    (*(*ctx).local_backing).interrupted_state = None;
+}
+
+/// The most memories whose pages can be tracked at the same time.
+const MAX_DIRTY_REGIONS: usize = 1024;
+
+/// The region isn't owned by any `DirtyPageTracker`.
+const REGION_FREE: usize = 0;
+/// The region is owned by a `DirtyPageTracker`, but doesn't describe any memory.
+const REGION_IDLE: usize = 1;
+/// The region describes tracked memory, and is read by the signal handler.
+const REGION_ACTIVE: usize = 2;
+
+/// A region of memory whose pages are kept read-only until they're first written
+/// to, so that its `DirtyPageTracker` can tell which of them changed.
+///
+/// Regions are read from the signal handler, which can't take locks or allocate,
+/// so they live in a preallocated table and are made of atomics. Only the tracker
+/// owning a region changes it, after making it idle and waiting for the signal
+/// handlers still reading it to return.
+struct DirtyRegion {
+    state: AtomicUsize,
+    /// The number of signal handlers reading the region.
+    readers: AtomicUsize,
+    start: AtomicUsize,
+    len: AtomicUsize,
+    /// A bit per page, set once the page is written to. Allocated by the tracker.
+    dirty: AtomicPtr<AtomicU64>,
+    /// Set once the memory moved elsewhere, leaving the region unmapped.
+    moved: AtomicBool,
+}
+
+impl DirtyRegion {
+    fn new() -> DirtyRegion {
+        DirtyRegion {
+            state: AtomicUsize::new(REGION_FREE),
+            readers: AtomicUsize::new(0),
+            start: AtomicUsize::new(0),
+            len: AtomicUsize::new(0),
+            dirty: AtomicPtr::new(ptr::null_mut()),
+            moved: AtomicBool::new(false),
+        }
+    }
+
+    /// Marks the pages of the region overlapping the `len` bytes at `addr` as
+    /// written to, and makes them writable. Returns whether there were any.
+    ///
+    /// Safe to call from the signal handler.
+    unsafe fn record_write(&self, addr: usize, len: usize, page_size: usize) -> bool {
+        self.readers.fetch_add(1, Ordering::SeqCst);
+        // Checked after announcing the read, so that the tracker either waits for
+        // it or has already made the region idle.
+        let recorded = self.state.load(Ordering::SeqCst) == REGION_ACTIVE
+            && !self.moved.load(Ordering::SeqCst)
+            && {
+                let start = self.start.load(Ordering::SeqCst);
+                let end = start + self.len.load(Ordering::SeqCst);
+                let (from, to) = (cmp::max(addr, start), cmp::min(addr + len, end));
+                from < to && {
+                    let dirty = self.dirty.load(Ordering::SeqCst);
+                    let pages = (from - start) / page_size..(to - start - 1) / page_size + 1;
+                    for page in pages.clone() {
+                        (*dirty.add(page / 64)).fetch_or(1 << (page % 64), Ordering::SeqCst);
+                    }
+                    let first = start + pages.start * page_size;
+                    let last = cmp::min(start + pages.end * page_size, end);
+                    mprotect(first as _, last - first, PROT_READ | PROT_WRITE) == 0
+                }
+            };
+        self.readers.fetch_sub(1, Ordering::SeqCst);
+        recorded
+    }
+}
+
+lazy_static! {
+    static ref DIRTY_REGIONS: Vec<DirtyRegion> =
+        (0..MAX_DIRTY_REGIONS).map(|_| DirtyRegion::new()).collect();
+}
+/// Set once `DIRTY_REGIONS` is initialized, which the signal handler can't do.
+static DIRTY_REGIONS_READY: AtomicBool = AtomicBool::new(false);
+
+/// The memory a region described when its tracker stopped tracking it.
+struct TrackedMemory {
+    start: usize,
+    len: usize,
+    dirty: Box<[AtomicU64]>,
+    moved: bool,
+}
+
+impl TrackedMemory {
+    /// The ranges of the memory, now `len` bytes at `start`, that may have been
+    /// written to while it was tracked.
+    fn dirty_ranges(&self, start: usize, len: usize, page_size: usize) -> Vec<Range<usize>> {
+        if len == 0 {
+            return vec![];
+        }
+        if self.moved || start != self.start {
+            return vec![0..len];
+        }
+
+        let pages = (0..(self.len + page_size - 1) / page_size)
+            .filter(|&i| self.dirty[i / 64].load(Ordering::SeqCst) & (1 << (i % 64)) != 0)
+            .map(|i| i * page_size..cmp::min((i + 1) * page_size, self.len));
+        // Memory that was added since isn't tracked.
+        let grown = if len > self.len {
+            Some(self.len..len)
+        } else {
+            None
+        };
+
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for range in pages.chain(grown) {
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        }
+        ranges
+    }
+}
+
+/// Records which pages of the memory of an instance are written to between
+/// snapshots of it.
+///
+/// Tracked pages are made read-only, and the first write to each of them is
+/// caught by the signal handler, which records the page and makes it writable
+/// again.
+#[derive(Debug)]
+pub(crate) struct DirtyPageTracker(usize);
+
+impl DirtyPageTracker {
+    /// Returns `None` if `MAX_DIRTY_REGIONS` memories are already tracked.
+    pub(crate) fn new() -> Option<DirtyPageTracker> {
+        ensure_sighandler();
+        lazy_static::initialize(&DIRTY_REGIONS);
+        DIRTY_REGIONS_READY.store(true, Ordering::SeqCst);
+        DIRTY_REGIONS
+            .iter()
+            .position(|region| {
+                region
+                    .state
+                    .compare_exchange(REGION_FREE, REGION_IDLE, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            })
+            .map(DirtyPageTracker)
+    }
+
+    /// Starts tracking writes to the `len` bytes of memory at `base` anew.
+    pub(crate) unsafe fn reset(&self, base: *mut u8, len: usize) {
+        self.stop();
+        self.start(base, len);
+    }
+
+    /// Returns the size the memory had when tracking last started, along with
+    /// the ranges of it that may have been written to since, then starts
+    /// tracking writes to the `len` bytes of memory at `base` anew.
+    ///
+    /// Returns `None`, without tracking anything, if tracking never started.
+    pub(crate) unsafe fn take(
+        &self,
+        base: *mut u8,
+        len: usize,
+    ) -> Option<(usize, Vec<Range<usize>>)> {
+        let memory = self.stop()?;
+        let ranges = memory.dirty_ranges(base as usize, len, page_size::get());
+        self.start(base, len);
+        Some((memory.len, ranges))
+    }
+
+    unsafe fn start(&self, base: *mut u8, len: usize) {
+        let page_size = page_size::get();
+        let region = &DIRTY_REGIONS[self.0];
+        let pages = (len + page_size - 1) / page_size;
+        let dirty: Box<[AtomicU64]> = (0..(pages + 63) / 64).map(|_| AtomicU64::new(0)).collect();
+        region.start.store(base as usize, Ordering::SeqCst);
+        region.len.store(len, Ordering::SeqCst);
+        region
+            .dirty
+            .store(Box::into_raw(dirty) as *mut AtomicU64, Ordering::SeqCst);
+        region.moved.store(false, Ordering::SeqCst);
+        // Made active before the pages are protected, so that no write to them
+        // can fault unnoticed.
+        region.state.store(REGION_ACTIVE, Ordering::SeqCst);
+        if len > 0 && mprotect(base as _, len, PROT_READ) < 0 {
+            panic!("cannot set PROT_READ on tracked memory");
+        }
+    }
+
+    /// Makes the tracked memory writable again and stops tracking it.
+    ///
+    /// Returns `None` if tracking never started.
+    unsafe fn stop(&self) -> Option<TrackedMemory> {
+        let region = &DIRTY_REGIONS[self.0];
+        if region.state.load(Ordering::SeqCst) != REGION_ACTIVE {
+            return None;
+        }
+        let start = region.start.load(Ordering::SeqCst);
+        let len = region.len.load(Ordering::SeqCst);
+        let moved = region.moved.load(Ordering::SeqCst);
+        // Unprotected while the region is still active, so that no write to it
+        // can fault unnoticed. The memory can outlive the instance tracking it.
+        if !moved && len > 0 {
+            mprotect(start as _, len, PROT_READ | PROT_WRITE);
+        }
+        region.state.store(REGION_IDLE, Ordering::SeqCst);
+        while region.readers.load(Ordering::SeqCst) != 0 {
+            thread::yield_now();
+        }
+
+        let pages = (len + page_size::get() - 1) / page_size::get();
+        let dirty = region.dirty.swap(ptr::null_mut(), Ordering::SeqCst);
+        Some(TrackedMemory {
+            start,
+            len,
+            dirty: Box::from_raw(slice::from_raw_parts_mut(dirty, (pages + 63) / 64)),
+            moved,
+        })
+    }
+}
+
+impl Drop for DirtyPageTracker {
+    fn drop(&mut self) {
+        unsafe { self.stop() };
+        DIRTY_REGIONS[self.0]
+            .state
+            .store(REGION_FREE, Ordering::SeqCst);
+    }
+}
+
+/// Stops tracking writes to the memory at `base`, which is about to be unmapped
+/// because the memory moved elsewhere. All of the memory then counts as written to.
+pub(crate) fn dirty_region_moved(base: *mut u8) {
+    if !DIRTY_REGIONS_READY.load(Ordering::SeqCst) {
+        return;
+    }
+    for region in DIRTY_REGIONS.iter() {
+        if region.state.load(Ordering::SeqCst) == REGION_ACTIVE
+            && region.start.load(Ordering::SeqCst) == base as usize
+        {
+            region.moved.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Records the tracked pages overlapping the `len` bytes at `addr` as written to,
+/// and makes them writable.
+///
+/// Writes from the operating system, such as a `read` into the memory, don't fault
+/// on read-only pages but fail with `EFAULT`, so they have to be announced first.
+pub(crate) unsafe fn prepare_tracked_write(addr: usize, len: usize) {
+    if len == 0 || !DIRTY_REGIONS_READY.load(Ordering::SeqCst) {
+        return;
+    }
+    let page_size = page_size::get();
+    for region in DIRTY_REGIONS.iter() {
+        if region.state.load(Ordering::SeqCst) == REGION_ACTIVE {
+            region.record_write(addr, len, page_size);
+        }
+    }
+}
+
+/// Records a write to `addr` if it lies in a tracked page, making the page
+/// writable again. Returns whether it did.
+///
+/// Called from the signal handler, so it only touches atomics and `mprotect`.
+unsafe fn handle_dirty_page_write(addr: usize) -> bool {
+    if !DIRTY_REGIONS_READY.load(Ordering::SeqCst) {
+        return false;
+    }
+    let page_size = page_size::get();
+    DIRTY_REGIONS.iter().any(|region| {
+        region.state.load(Ordering::SeqCst) == REGION_ACTIVE
+            && region.record_write(addr, 1, page_size)
+    })
+}
+
+/// Set to a non-zero value when a call made with a timeout runs past its deadline, or
+/// when the instance is stopped through an `InterruptHandle`.
+///
+/// Backends that don't poll the interrupt signal page observe the deadline through
+/// this field instead, which `InterruptCheck` checks before calls and at loop headers
+/// before raising `InternalTrap::Interrupted`. Other middleware doing the same must
+/// return `true` from `FunctionMiddleware::checks_interrupts`.
+pub static INTERRUPT_FIELD: InternalField = InternalField::allocate();
+
+/// The error returned by a call made with a timeout when the module can't be stopped
+/// at its deadline, e.g. when it was loaded from a cache entry written by a version
+/// that didn't check `INTERRUPT_FIELD`.
+#[derive(Copy, Clone, Debug)]
+pub struct TimeoutUnsupportedError;
+
+/// InterruptCheck is a compiler middleware that makes functions stop with
+/// `ExecutionInterrupted` once `INTERRUPT_FIELD` is set.
+///
+/// It checks the field at every loop header and before every call. Streaming compilers
+/// add it on their own for backends that don't poll the interrupt signal page, unless
+/// a middleware of the chain already checks interrupts.
+pub struct InterruptCheck;
+
+impl InterruptCheck {
+    fn emit_check<'a, 'b: 'a>(sink: &mut EventSink<'a, 'b>) {
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            INTERRUPT_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64Const { value: 0 }));
+        sink.push(Event::WasmOwned(Operator::I64Ne));
+        sink.push(Event::WasmOwned(Operator::If {
+            ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+        }));
+        sink.push(Event::Internal(InternalEvent::Trap(
+            InternalTrap::Interrupted,
+        )));
+        sink.push(Event::WasmOwned(Operator::End));
+    }
+}
+
+impl FunctionMiddleware for InterruptCheck {
+    type Error = String;
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        _module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) -> Result<(), Self::Error> {
+        let is_loop = match op {
+            Event::Wasm(&ref op) | Event::WasmOwned(ref op) => match *op {
+                Operator::Call { .. } | Operator::CallIndirect { .. } => {
+                    Self::emit_check(sink);
+                    false
+                }
+                Operator::Loop { .. } => true,
+                _ => false,
+            },
+            _ => false,
+        };
+        sink.push(op);
+        if is_loop {
+            Self::emit_check(sink);
+        }
+        Ok(())
+    }
+
+    fn fingerprint(&self) -> String {
+        "interrupt_check".to_string()
+    }
+
+    fn checks_interrupts(&self) -> bool {
+        true
+    }
+}
+
+/// A deadline of a call made with a timeout.
+struct Deadline {
+    id: u64,
+    at: Instant,
+    /// The `INTERRUPT_FIELD` of the instance making the call.
+    field: *const AtomicU64,
+    /// The interrupt signal page the instance polls.
+    signal_mem: *mut u8,
+}
+
+unsafe impl Send for Deadline {}
+
+/// The thread, shared by all calls made with a timeout, that stops them once their
+/// deadline passes.
+struct Watchdog {
+    deadlines: Mutex<Vec<Deadline>>,
+    changed: Condvar,
+}
+
+lazy_static! {
+    static ref WATCHDOG: Arc<Watchdog> = Watchdog::spawn();
+}
+static NEXT_DEADLINE: AtomicU64 = AtomicU64::new(0);
+
+impl Watchdog {
+    fn spawn() -> Arc<Watchdog> {
+        let watchdog = Arc::new(Watchdog {
+            deadlines: Mutex::new(vec![]),
+            changed: Condvar::new(),
+        });
+        let shared = watchdog.clone();
+        thread::Builder::new()
+            .name("wasmer-timeout-watchdog".to_string())
+            .spawn(move || shared.run())
+            .expect("cannot spawn timeout watchdog");
+        watchdog
+    }
+
+    fn run(&self) {
+        let mut deadlines = self.deadlines.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            deadlines.retain(|deadline| {
+                if deadline.at > now {
+                    return true;
+                }
+                unsafe {
+                    (*deadline.field).store(1, Ordering::SeqCst);
+                    set_interrupt_page(deadline.signal_mem);
+                }
+                false
+            });
+            deadlines = match deadlines.iter().map(|deadline| deadline.at).min() {
+                Some(next) => self.changed.wait_timeout(deadlines, next - now).unwrap().0,
+                None => self.changed.wait(deadlines).unwrap(),
+            };
+        }
+    }
+
+    fn watch(&self, deadline: Deadline) {
+        self.deadlines.lock().unwrap().push(deadline);
+        self.changed.notify_one();
+    }
+
+    /// Stops watching the deadline `id`. Returns `false` if it has already passed.
+    fn unwatch(&self, id: u64) -> bool {
+        let mut deadlines = self.deadlines.lock().unwrap();
+        match deadlines.iter().position(|deadline| deadline.id == id) {
+            Some(index) => {
+                deadlines.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Keeps the watchdog watching a deadline until it is dropped, so that it never
+/// touches an instance after the call returned, or panicked.
+struct WatchedDeadline(u64);
+
+impl WatchedDeadline {
+    fn new(at: Instant, field: *const AtomicU64, signal_mem: *mut u8) -> WatchedDeadline {
+        let id = NEXT_DEADLINE.fetch_add(1, Ordering::SeqCst);
+        WATCHDOG.watch(Deadline {
+            id,
+            at,
+            field,
+            signal_mem,
+        });
+        WatchedDeadline(id)
+    }
+
+    /// Stops watching the deadline, returning whether it passed.
+    fn passed(self) -> bool {
+        let passed = !WATCHDOG.unwatch(self.0);
+        mem::forget(self);
+        passed
+    }
+}
+
+impl Drop for WatchedDeadline {
+    fn drop(&mut self) {
+        WATCHDOG.unwatch(self.0);
+    }
+}
+
+/// Runs `f`, a call into the instance owning `ctx`, and stops it once `timeout` has
+/// elapsed, in which case `RuntimeError::Timeout` is returned.
+///
+/// Fails with `TimeoutUnsupportedError`, without running `f`, if the module of the
+/// instance has no way of observing the deadline, which only happens to modules
+/// loaded from old cache entries.
+pub(crate) unsafe fn run_with_timeout<R, F: FnOnce() -> Result<R, RuntimeError>>(
+    ctx: *mut vm::Ctx,
+    timeout: Duration,
+    f: F,
+) -> Result<R, RuntimeError> {
+    let info = &(*(*ctx).module).info;
+    if info.backend != Backend::Singlepass && !info.checks_interrupts {
+        return Err(RuntimeError::Error {
+            data: Box::new(TimeoutUnsupportedError),
+        });
+    }
+    let at = match Instant::now().checked_add(timeout) {
+        Some(at) => at,
+        // The deadline is too far away to ever pass.
+        None => return f(),
+    };
+
+    // Instances without an `InterruptHandle` poll the process-wide signal memory,
+    // so give them a page of their own for the duration of the call.
+    let old_signal_mem = (*ctx).internal.interrupt_signal_mem;
+    let call_page = if old_signal_mem == get_wasm_interrupt_signal_mem() {
+        let page = InterruptSignalPage::new();
+        (*ctx).internal.interrupt_signal_mem = page.as_ptr();
+        Some(page)
+    } else {
+        None
+    };
+    let signal_mem = (*ctx).internal.interrupt_signal_mem as usize;
+
+    // The field isn't reset before the call, which would lose an interrupt requested
+    // through an `InterruptHandle` in the meantime; stopping resets it.
+    let field = interrupt_field(ctx);
+    let watched = WatchedDeadline::new(at, field, signal_mem as *mut u8);
+    let result = f();
+    let timed_out = watched.passed();
+
+    if timed_out {
+        // The guest may have returned, or stopped through `INTERRUPT_FIELD`, without
+        // touching the page; don't leave it, nor the field, armed for the next call.
+        clear_interrupt(ctx);
+    }
+    if call_page.is_some() {
+        (*ctx).internal.interrupt_signal_mem = old_signal_mem;
+    }
+
+    if timed_out {
+        match result {
+            Err(RuntimeError::Interrupted) => Err(RuntimeError::Timeout),
+            result => result,
+        }
+    } else {
+        result
+    }
+}
+
 pub unsafe fn catch_unsafe_unwind<R, F: FnOnce() -> R>(
     f: F,
     breakpoints: Option<BreakpointMap>,
@@ -173,10 +842,23 @@ extern "C" fn signal_trap_handler(
     unsafe {
         let fault = get_fault_info(siginfo as _, ucontext);
 
+        // Writes to tracked memory can come from anywhere, not only from wasm code.
+        match Signal::from_c_int(signum) {
+            Ok(SIGSEGV) | Ok(SIGBUS) if handle_dirty_page_write(fault.faulting_addr as usize) => {
+                return;
+            }
+            _ => {}
+        }
+
         let mut unwind_result: Box<dyn Any> = Box::new(());
 
         let should_unwind = allocate_and_run(TRAP_STACK_SIZE, || {
             let mut is_suspend_signal = false;
+            let mut is_interrupt = false;
+
+            // TODO: make this safer
+            let ctx = &mut *(fault.known_registers[X64Register::GPR(GPR::R15).to_index().0].unwrap()
+                as *mut vm::Ctx);
 
             match Signal::from_c_int(signum) {
                 Ok(SIGTRAP) => {
@@ -204,14 +886,18 @@ extern "C" fn signal_trap_handler(
                         is_suspend_signal = true;
                         clear_wasm_interrupt();
                         INTERRUPT_SIGNAL_DELIVERED.store(false, Ordering::SeqCst);
+                    } else if fault.faulting_addr as usize
+                        == ctx.internal.interrupt_signal_mem as usize
+                    {
+                        // Interrupted through an `InterruptHandle`, or a timeout. Re-arm the
+                        // page so that the instance can be called again.
+                        clear_interrupt(ctx);
+                        is_interrupt = true;
                     }
                 }
                 _ => {}
             }
 
-            // TODO: make this safer
-            let ctx = &mut *(fault.known_registers[X64Register::GPR(GPR::R15).to_index().0].unwrap()
-                as *mut vm::Ctx);
             let rsp = fault.known_registers[X64Register::GPR(GPR::RSP).to_index().0].unwrap();
 
             let msm = (*ctx.module)
@@ -227,9 +913,15 @@ extern "C" fn signal_trap_handler(
                 Some(fault.ip as usize as u64),
             );
 
-            if is_suspend_signal {
-                let image = build_instance_image(ctx, es_image);
-                unwind_result = Box::new(image);
+            if is_interrupt {
+                // Kept for `Instance::snapshot`.
+                (*ctx.local_backing).interrupted_state = Some(es_image);
+                unwind_result = Box::new(ExecutionInterrupted);
+            } else if is_suspend_signal {
+                unwind_result = match build_instance_image(ctx, es_image) {
+                    Ok(image) => Box::new(image),
+                    Err(e) => Box::new(e),
+                };
             } else {
                 use colored::*;
                 if es_image.frames.len() > 0 {
@@ -239,9 +931,23 @@ extern "C" fn signal_trap_handler(
                             .bold()
                             .red()
                     );
-                    es_image.print_backtrace_if_needed();
+                    es_image.print_backtrace_if_needed(&(*ctx.module).info);
                 }
-                // Just let the error propagate otherrwise
+
+                let kind = get_trap_kind(signum, &fault, code_base, msm.total_size);
+                let info = &(*ctx.module).info;
+                let num_imported_functions = info.imported_functions.len();
+                let mut details = build_trap_details(kind, &es_image, num_imported_functions);
+                if details.func_index.is_none() {
+                    details.func_index = msm
+                        .lookup_local_function_id(fault.ip as usize, code_base)
+                        .map(|id| FuncIndex::new(id + num_imported_functions));
+                }
+                details.func_name = details
+                    .func_index
+                    .and_then(|index| info.function_name(index))
+                    .map(|x| x.to_string());
+                unwind_result = Box::new(details);
             }
 
             true
@@ -253,6 +959,54 @@ extern "C" fn signal_trap_handler(
     }
 }
 
+/// Works out why generated code trapped.
+///
+/// Code that runs under these signal handlers follows each trapping `ud2` with a byte
+/// holding the `WasmTrapInfo` of the trap, and checks for division by zero explicitly,
+/// so a `SIGFPE` can only come from a division that overflows.
+unsafe fn get_trap_kind(
+    signum: ::nix::libc::c_int,
+    fault: &FaultInfo,
+    code_base: usize,
+    code_size: usize,
+) -> WasmTrapInfo {
+    match Signal::from_c_int(signum) {
+        Ok(SIGILL) => {
+            let ip = fault.ip as usize;
+            if ip >= code_base && ip + 2 < code_base + code_size {
+                WasmTrapInfo::from_u8(*((ip + 2) as *const u8))
+            } else {
+                WasmTrapInfo::Unknown
+            }
+        }
+        Ok(SIGFPE) => WasmTrapInfo::IntegerOverflow,
+        Ok(SIGSEGV) | Ok(SIGBUS) => WasmTrapInfo::MemoryOutOfBounds,
+        _ => WasmTrapInfo::Unknown,
+    }
+}
+
+fn build_trap_details(
+    kind: WasmTrapInfo,
+    es_image: &ExecutionStateImage,
+    num_imported_functions: usize,
+) -> TrapDetails {
+    let frames: Vec<TrapFrame> = es_image
+        .frames
+        .iter()
+        .map(|f| TrapFrame {
+            func_index: FuncIndex::new(f.local_function_id + num_imported_functions),
+            wasm_inst_offset: f.wasm_inst_offset,
+        })
+        .collect();
+    TrapDetails {
+        kind,
+        func_index: frames.first().map(|f| f.func_index),
+        func_name: None,
+        wasm_inst_offset: frames.first().map(|f| f.wasm_inst_offset),
+        frames,
+    }
+}
+
 extern "C" fn sigint_handler(
     _signum: ::nix::libc::c_int,
     _siginfo: *mut siginfo_t,
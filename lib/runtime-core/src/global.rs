@@ -1,6 +1,7 @@
 use crate::{
     export::Export,
     import::IsExport,
+    table::Reference,
     types::{GlobalDescriptor, Type, Value},
     vm,
 };
@@ -51,6 +52,7 @@ impl Global {
                 Value::F32(x) => x.to_bits() as u128,
                 Value::F64(x) => x.to_bits() as u128,
                 Value::V128(x) => x,
+                Value::AnyRef(x) | Value::FuncRef(x) => x.to_bits() as u128,
             },
         };
 
@@ -81,6 +83,7 @@ impl Global {
                         Value::F32(x) => x.to_bits() as u128,
                         Value::F64(x) => x.to_bits() as u128,
                         Value::V128(x) => x,
+                        Value::AnyRef(x) | Value::FuncRef(x) => x.to_bits() as u128,
                     },
                 };
                 *self.storage.borrow_mut() = local_global;
@@ -102,6 +105,8 @@ impl Global {
             Type::F32 => Value::F32(f32::from_bits(data as u32)),
             Type::F64 => Value::F64(f64::from_bits(data as u64)),
             Type::V128 => Value::V128(data),
+            Type::AnyRef => Value::AnyRef(Reference::from_bits(data as u64)),
+            Type::FuncRef => Value::FuncRef(Reference::from_bits(data as u64)),
         }
     }
 
@@ -1,7 +1,9 @@
 use crate::{
     backend::RunnableModule,
     backing::{ImportBacking, LocalBacking},
-    error::{CallError, CallResult, ResolveError, ResolveResult, Result, RuntimeError},
+    error::{
+        CallError, CallResult, ResolveError, ResolveResult, Result, RuntimeError, TrapDetails,
+    },
     export::{Context, Export, ExportIter, FuncPointer},
     global::Global,
     import::{ImportObject, LikeNamespace},
@@ -10,28 +12,76 @@ use crate::{
     module::{ExportIndex, Module, ModuleInfo, ModuleInner},
     sig_registry::SigRegistry,
     structures::TypedIndex,
-    table::Table,
+    table::{self, Reference, Table},
     typed_func::{Func, Wasm, WasmTrapInfo, WasmTypeList},
-    types::{FuncIndex, FuncSig, GlobalIndex, LocalOrImport, MemoryIndex, TableIndex, Type, Value},
+    types::{
+        FuncIndex, FuncSig, GlobalIndex, LocalFuncIndex, LocalOrImport, MemoryIndex, SigIndex,
+        TableIndex, Type, Value,
+    },
+    units::Pages,
     vm::{self, InternalField},
 };
+#[cfg(all(unix, target_arch = "x86_64"))]
+use crate::{
+    error::{ImageError, ResumeError},
+    fault::{ensure_sighandler, DirtyPageTracker, InterruptHandle, InterruptTarget},
+    state::{
+        x64::{
+            build_instance_image, build_instance_image_delta, check_instance_image,
+            invoke_call_return_on_stack, load_memory_and_globals, load_tables, memory_size,
+        },
+        ExecutionStateImage, InstanceImage, InstanceImageDelta,
+    },
+};
 use smallvec::{smallvec, SmallVec};
-use std::{mem, pin::Pin, ptr::NonNull, sync::Arc};
+use std::{mem, pin::Pin, ptr::NonNull, sync::Arc, time::Duration};
 
 pub(crate) struct InstanceInner {
     #[allow(dead_code)]
     pub(crate) backing: LocalBacking,
     import_backing: ImportBacking,
     pub(crate) vmctx: *mut vm::Ctx,
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    interrupt_target: Option<Arc<InterruptTarget>>,
 }
 
 impl Drop for InstanceInner {
     fn drop(&mut self) {
+        // References to the functions of this instance can't be used anymore.
+        table::release_references(self.vmctx);
+
+        // Stop tracking the memory before it can be unmapped, and keep interrupt
+        // handles from writing to the internal fields once they're gone.
+        #[cfg(all(unix, target_arch = "x86_64"))]
+        {
+            self.backing.dirty_pages = None;
+            if let Some(ref target) = self.interrupt_target {
+                target.detach();
+            }
+        }
+
         // Drop the vmctx.
         unsafe { Box::from_raw(self.vmctx) };
     }
 }
 
+/// Configuration for instantiating a [`Module`], passed to
+/// [`Module::instantiate_with_config`].
+///
+/// [`Module`]: ../module/struct.Module.html
+/// [`Module::instantiate_with_config`]: ../module/struct.Module.html#method.instantiate_with_config
+#[derive(Debug, Clone, Default)]
+pub struct InstanceConfig {
+    /// Caps the total number of pages of the memories the instance defines
+    /// and imports, independently of the maximums the module declares.
+    ///
+    /// Instantiation fails if the memories are already larger than the limit.
+    /// Afterwards, growing one of them past it, from WebAssembly or from the
+    /// host, fails with `GrowError::ExceededPageLimit`, which makes a
+    /// `memory.grow` instruction trap instead of returning `-1`.
+    pub memory_page_limit: Option<Pages>,
+}
+
 /// An instantiated WebAssembly module.
 ///
 /// An `Instance` represents a WebAssembly module that
@@ -47,7 +97,11 @@ pub struct Instance {
 }
 
 impl Instance {
-    pub(crate) fn new(module: Arc<ModuleInner>, imports: &ImportObject) -> Result<Instance> {
+    pub(crate) fn new(
+        module: Arc<ModuleInner>,
+        imports: &ImportObject,
+        config: InstanceConfig,
+    ) -> Result<Instance> {
         // We need the backing and import_backing to create a vm::Ctx, but we need
         // a vm::Ctx to create a backing and an import_backing. The solution is to create an
         // uninitialized vm::Ctx and then initialize it in-place.
@@ -55,12 +109,19 @@ impl Instance {
             Box::new(mem::MaybeUninit::<vm::Ctx>::zeroed());
 
         let import_backing = ImportBacking::new(&module, &imports, vmctx.as_mut_ptr())?;
-        let backing = LocalBacking::new(&module, &import_backing, vmctx.as_mut_ptr())?;
+        let backing = LocalBacking::new(
+            &module,
+            &import_backing,
+            config.memory_page_limit,
+            vmctx.as_mut_ptr(),
+        )?;
 
         let mut inner = Box::pin(InstanceInner {
             backing,
             import_backing,
             vmctx: vmctx.as_mut_ptr(),
+            #[cfg(all(unix, target_arch = "x86_64"))]
+            interrupt_target: None,
         });
 
         // Initialize the vm::Ctx in-place after the backing
@@ -298,7 +359,7 @@ impl Instance {
     ///
     /// # Note:
     /// This returns `CallResult<Vec<Value>>` in order to support
-    /// the future multi-value returns WebAssembly feature.
+    /// functions with multiple results (the multi-value feature).
     ///
     /// # Usage:
     /// ```
@@ -341,11 +402,35 @@ impl Instance {
             func_index,
             params,
             &mut results,
+            None,
         )?;
 
         Ok(results)
     }
 
+    /// Call an exported WebAssembly function like [`call`], stopping
+    /// it with [`RuntimeError::Timeout`] if it runs for longer than
+    /// `timeout`.
+    ///
+    /// Backends that don't poll the interrupt signal (LLVM and Cranelift)
+    /// observe the deadline through [`InterruptCheck`], which streaming
+    /// compilers add to them. Modules compiled without it fail with
+    /// [`TimeoutUnsupportedError`] without running.
+    ///
+    /// [`call`]: #method.call
+    /// [`RuntimeError::Timeout`]: ../error/enum.RuntimeError.html#variant.Timeout
+    /// [`InterruptCheck`]: ../fault/struct.InterruptCheck.html
+    /// [`TimeoutUnsupportedError`]: ../fault/struct.TimeoutUnsupportedError.html
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub fn call_with_timeout(
+        &self,
+        name: &str,
+        params: &[Value],
+        timeout: Duration,
+    ) -> CallResult<Vec<Value>> {
+        self.dyn_func(name)?.call_with_timeout(params, timeout)
+    }
+
     /// Returns an immutable reference to the
     /// [`Ctx`] used by this Instance.
     ///
@@ -362,6 +447,179 @@ impl Instance {
         unsafe { &mut *self.inner.vmctx }
     }
 
+    /// Returns an [`InterruptHandle`] that can be used from
+    /// another thread to stop this instance.
+    ///
+    /// Once a handle has been requested, the instance no longer
+    /// responds to the process-wide interrupt signal.
+    ///
+    /// Returns `None` if the module can't be stopped: it wasn't compiled
+    /// by singlepass nor with [`InterruptCheck`], e.g. because it was
+    /// loaded from a cache entry written by an older version.
+    ///
+    /// [`InterruptHandle`]: ../fault/struct.InterruptHandle.html
+    /// [`InterruptCheck`]: ../fault/struct.InterruptCheck.html
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub fn interrupt_handle(&mut self) -> Option<InterruptHandle> {
+        let info = &self.module.info;
+        if info.backend != crate::backend::Backend::Singlepass && !info.checks_interrupts {
+            return None;
+        }
+        let target = match self.inner.interrupt_target {
+            Some(ref target) => target.clone(),
+            None => {
+                let target = Arc::new(unsafe { InterruptTarget::new(self.inner.vmctx) });
+                self.context_mut().internal.interrupt_signal_mem = target.page_ptr();
+                self.inner.interrupt_target = Some(target.clone());
+                target
+            }
+        };
+        Some(InterruptHandle::new(target))
+    }
+
+    /// Captures the memory, globals and tables of this instance in an
+    /// [`InstanceImage`], along with the execution state of its last call
+    /// if that call was interrupted through an [`InterruptHandle`] or
+    /// timed out and the instance hasn't been called since.
+    ///
+    /// The image can be loaded into this or another instance of the same
+    /// module with [`resume`], which continues the interrupted call.
+    /// Execution state is only known to singlepass, and only when the module
+    /// is compiled with `track_state` set in its `CompilerConfig`.
+    ///
+    /// Only the first memory and the local globals and tables are part of
+    /// the image. Table elements are stored as function indices, but globals
+    /// holding references can't be, so instances with such globals can't be
+    /// captured.
+    ///
+    /// [`InstanceImage`]: ../state/struct.InstanceImage.html
+    /// [`InterruptHandle`]: ../fault/struct.InterruptHandle.html
+    /// [`resume`]: #method.resume
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub fn snapshot(&self) -> ::std::result::Result<InstanceImage, ImageError> {
+        let image = build_instance_image(self.context(), self.execution_state()?)?;
+        if let Some(ref tracker) = self.inner.backing.dirty_pages {
+            let vmctx = self.context();
+            unsafe { tracker.reset(vmctx.internal.memory_base, memory_size(vmctx)) };
+        }
+        Ok(image)
+    }
+
+    /// Starts recording which pages of the first memory of this instance
+    /// are written to, so that [`snapshot_delta`] can capture only those.
+    ///
+    /// Pages are kept read-only until they're first written to after a
+    /// snapshot, and the write is caught by the signal handlers of the
+    /// singlepass backend, so other backends aren't supported. Writes made
+    /// by the operating system, such as a `read` into the memory, would fail
+    /// on those pages instead, and must be announced with
+    /// [`Memory::prepare_os_write`] first.
+    ///
+    /// [`snapshot_delta`]: #method.snapshot_delta
+    /// [`Memory::prepare_os_write`]: ../memory/struct.Memory.html#method.prepare_os_write
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub fn track_dirty_pages(&mut self) -> ::std::result::Result<(), ImageError> {
+        if self.module.info.backend != crate::backend::Backend::Singlepass {
+            return Err(ImageError::DirtyPagesUnsupported(self.module.info.backend));
+        }
+        if self.inner.backing.dirty_pages.is_none() {
+            self.inner.backing.dirty_pages =
+                Some(DirtyPageTracker::new().ok_or(ImageError::TooManyDirtyPageTrackers)?);
+        }
+        Ok(())
+    }
+
+    /// Captures the changes made to this instance since the last
+    /// [`snapshot`] or `snapshot_delta`, in an [`InstanceImageDelta`]
+    /// that [`InstanceImage::apply_delta`] can bring an earlier image
+    /// up to date with.
+    ///
+    /// Only the pages of memory written to since then are part of the
+    /// delta. Recording them must have been started with
+    /// [`track_dirty_pages`] before the last snapshot was taken.
+    ///
+    /// [`snapshot`]: #method.snapshot
+    /// [`InstanceImageDelta`]: ../state/struct.InstanceImageDelta.html
+    /// [`InstanceImage::apply_delta`]: ../state/struct.InstanceImage.html#method.apply_delta
+    /// [`track_dirty_pages`]: #method.track_dirty_pages
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub fn snapshot_delta(&self) -> ::std::result::Result<InstanceImageDelta, ImageError> {
+        let tracker = match self.inner.backing.dirty_pages {
+            Some(ref tracker) => tracker,
+            None => return Err(ImageError::NoBaseImage),
+        };
+        let execution_state = self.execution_state()?;
+        unsafe { build_instance_image_delta(self.context(), tracker, execution_state) }
+    }
+
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    fn execution_state(&self) -> ::std::result::Result<ExecutionStateImage, ImageError> {
+        match self.inner.backing.interrupted_state {
+            Some(ref state) if state.frames.is_empty() => Err(ImageError::NoExecutionState),
+            Some(ref state) => Ok(state.clone()),
+            None => Ok(ExecutionStateImage { frames: vec![] }),
+        }
+    }
+
+    /// Loads an [`InstanceImage`] taken by [`snapshot`] into this instance
+    /// and continues the call it was taken during, returning the results
+    /// of that call.
+    ///
+    /// If the image doesn't hold the state of a call, only its memory,
+    /// globals and tables are loaded and no results are returned.
+    ///
+    /// [`InstanceImage`]: ../state/struct.InstanceImage.html
+    /// [`snapshot`]: #method.snapshot
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub fn resume(
+        &mut self,
+        image: InstanceImage,
+    ) -> ::std::result::Result<Vec<Value>, ResumeError> {
+        let runnable = &self.module.runnable_module;
+        let msm = runnable.get_module_state_map();
+        let vmctx = unsafe { &mut *self.inner.vmctx };
+
+        unsafe {
+            check_instance_image(msm.as_ref(), vmctx, &image)?;
+            load_tables(vmctx, &image.tables)?;
+        }
+        self.inner.backing.interrupted_state = None;
+
+        let local_function_id = match image.execution_state.frames.last() {
+            Some(frame) => frame.local_function_id,
+            None => {
+                unsafe { load_memory_and_globals(vmctx, &image) };
+                return Ok(vec![]);
+            }
+        };
+        let info = &self.module.info;
+        let func_index = LocalFuncIndex::new(local_function_id).convert_up(info);
+        let signature = &info.signatures[info.func_assoc[func_index]];
+
+        ensure_sighandler();
+        let ret = unsafe {
+            invoke_call_return_on_stack(
+                msm.as_ref().unwrap(),
+                runnable.get_code().unwrap().as_ptr() as usize,
+                image,
+                vmctx,
+                runnable.get_breakpoints(),
+            )
+        }
+        .map_err(RuntimeError::from_unwind_payload)?;
+
+        let mut results = Vec::with_capacity(signature.returns().len());
+        let num_return_slots = signature.return_slots();
+        if num_return_slots > 1 {
+            // `ret` points to the return buffer of the instance.
+            let raw = unsafe { ::std::slice::from_raw_parts(ret as *const u64, num_return_slots) };
+            raw_to_values(raw, signature.returns(), &mut results);
+        } else {
+            raw_to_values(&[ret], signature.returns(), &mut results);
+        }
+        Ok(results)
+    }
+
     /// Returns an iterator over all of the items
     /// exported from this instance.
     pub fn exports(&self) -> ExportIter {
@@ -518,7 +776,7 @@ impl LikeNamespace for Rc<Instance> {
 }
 
 #[must_use]
-fn call_func_with_index(
+pub(crate) fn call_func_with_index(
     info: &ModuleInfo,
     runnable: &dyn RunnableModule,
     import_backing: &ImportBacking,
@@ -526,31 +784,13 @@ fn call_func_with_index(
     func_index: FuncIndex,
     args: &[Value],
     rets: &mut Vec<Value>,
+    timeout: Option<Duration>,
 ) -> CallResult<()> {
-    rets.clear();
-
     let sig_index = *info
         .func_assoc
         .get(func_index)
         .expect("broken invariant, incorrect func index");
 
-    let signature = &info.signatures[sig_index];
-    let num_results = signature.returns().len();
-    let num_results = num_results
-        + signature
-            .returns()
-            .iter()
-            .filter(|&&ty| ty == Type::V128)
-            .count();
-    rets.reserve(num_results);
-
-    if !signature.check_param_value_types(args) {
-        Err(ResolveError::Signature {
-            expected: signature.clone(),
-            found: args.iter().map(|val| val.ty()).collect(),
-        })?
-    }
-
     let func_ptr = match func_index.local_or_import(info) {
         LocalOrImport::Local(local_func_index) => {
             runnable.get_func(info, local_func_index).unwrap()
@@ -567,6 +807,42 @@ fn call_func_with_index(
         }
     };
 
+    call_func_with_ptr(
+        info, runnable, func_ptr, ctx_ptr, sig_index, args, rets, timeout,
+    )
+}
+
+/// Calls the function at `func_ptr`, whose signature is `sig_index` in the
+/// module described by `info`, with `ctx_ptr` as its context.
+#[must_use]
+pub(crate) fn call_func_with_ptr(
+    info: &ModuleInfo,
+    runnable: &dyn RunnableModule,
+    func_ptr: NonNull<vm::Func>,
+    ctx_ptr: *mut vm::Ctx,
+    sig_index: SigIndex,
+    args: &[Value],
+    rets: &mut Vec<Value>,
+    timeout: Option<Duration>,
+) -> CallResult<()> {
+    rets.clear();
+
+    let signature = &info.signatures[sig_index];
+    let num_results = signature.return_slots();
+    rets.reserve(signature.returns().len());
+
+    if !signature.check_param_value_types(args) {
+        Err(ResolveError::Signature {
+            expected: signature.clone(),
+            found: args.iter().map(|val| val.ty()).collect(),
+        })?
+    }
+
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    unsafe {
+        crate::fault::discard_interrupted_state(ctx_ptr)
+    };
+
     let mut raw_args: SmallVec<[u64; 8]> = SmallVec::new();
     for v in args {
         match v {
@@ -582,6 +858,9 @@ fn call_func_with_index(
             Value::F64(f) => {
                 raw_args.push(f.to_bits() as u64);
             }
+            Value::AnyRef(r) | Value::FuncRef(r) => {
+                raw_args.push(r.to_bits());
+            }
             Value::V128(v) => {
                 let bytes = v.to_le_bytes();
                 let mut lo = [0u8; 8];
@@ -602,7 +881,7 @@ fn call_func_with_index(
         .get_trampoline(info, sig_index)
         .expect("wasm trampoline");
 
-    let run_wasm = |result_space: *mut u64| unsafe {
+    let invoke_wasm = |result_space: *mut u64| unsafe {
         let mut trap_info = WasmTrapInfo::Unknown;
         let mut user_error = None;
 
@@ -621,21 +900,19 @@ fn call_func_with_index(
             Ok(())
         } else {
             if let Some(data) = user_error {
-                Err(RuntimeError::Error { data })
+                Err(RuntimeError::from_unwind_payload(data))
             } else {
-                Err(RuntimeError::Trap {
-                    msg: trap_info.to_string().into(),
-                })
+                Err(RuntimeError::from_trap(TrapDetails::new(trap_info)))
             }
         }
     };
 
-    let raw_to_value = |raw, ty| match ty {
-        Type::I32 => Value::I32(raw as i32),
-        Type::I64 => Value::I64(raw as i64),
-        Type::F32 => Value::F32(f32::from_bits(raw as u32)),
-        Type::F64 => Value::F64(f64::from_bits(raw)),
-        Type::V128 => unreachable!("V128 does not map to any single value"),
+    let run_wasm = |result_space: *mut u64| match timeout {
+        #[cfg(all(unix, target_arch = "x86_64"))]
+        Some(timeout) => unsafe {
+            crate::fault::run_with_timeout(ctx_ptr, timeout, || invoke_wasm(result_space))
+        },
+        _ => invoke_wasm(result_space),
     };
 
     match signature.returns() {
@@ -648,14 +925,7 @@ fn call_func_with_index(
 
             run_wasm(result.as_mut_ptr())?;
 
-            let mut bytes = [0u8; 16];
-            let lo = result[0].to_le_bytes();
-            let hi = result[1].to_le_bytes();
-            for i in 0..8 {
-                bytes[i] = lo[i];
-                bytes[i + 8] = hi[i];
-            }
-            rets.push(Value::V128(u128::from_le_bytes(bytes)));
+            rets.push(raw_to_v128(result[0], result[1]));
             Ok(())
         }
         &[ty] => {
@@ -672,18 +942,52 @@ fn call_func_with_index(
 
             run_wasm(results.as_mut_ptr())?;
 
-            rets.extend(
-                results
-                    .iter()
-                    .zip(result_tys.iter())
-                    .map(|(&raw, &ty)| raw_to_value(raw, ty)),
-            );
+            raw_to_values(&results, result_tys, rets);
 
             Ok(())
         }
     }
 }
 
+fn raw_to_value(raw: u64, ty: Type) -> Value {
+    match ty {
+        Type::I32 => Value::I32(raw as i32),
+        Type::I64 => Value::I64(raw as i64),
+        Type::F32 => Value::F32(f32::from_bits(raw as u32)),
+        Type::F64 => Value::F64(f64::from_bits(raw)),
+        Type::V128 => unreachable!("V128 does not map to any single value"),
+        Type::AnyRef => Value::AnyRef(Reference::from_bits(raw)),
+        Type::FuncRef => Value::FuncRef(Reference::from_bits(raw)),
+    }
+}
+
+fn raw_to_v128(lo: u64, hi: u64) -> Value {
+    let mut bytes = [0u8; 16];
+    let lo = lo.to_le_bytes();
+    let hi = hi.to_le_bytes();
+    for i in 0..8 {
+        bytes[i] = lo[i];
+        bytes[i + 8] = hi[i];
+    }
+    Value::V128(u128::from_le_bytes(bytes))
+}
+
+/// Converts the `u64` slots that results of types `tys` are returned in to values.
+fn raw_to_values(raw: &[u64], tys: &[Type], rets: &mut Vec<Value>) {
+    let mut raw = raw.iter().cloned();
+    for &ty in tys {
+        let value = match ty {
+            Type::V128 => {
+                let lo = raw.next().unwrap();
+                let hi = raw.next().unwrap();
+                raw_to_v128(lo, hi)
+            }
+            _ => raw_to_value(raw.next().unwrap(), ty),
+        };
+        rets.push(value);
+    }
+}
+
 /// A representation of an exported WebAssembly function.
 pub struct DynFunc<'a> {
     pub(crate) signature: Arc<FuncSig>,
@@ -702,7 +1006,7 @@ impl<'a> DynFunc<'a> {
     ///
     /// # Note:
     /// This returns `CallResult<Vec<Value>>` in order to support
-    /// the future multi-value returns WebAssembly feature.
+    /// functions with multiple results (the multi-value feature).
     ///
     /// # Usage:
     /// ```
@@ -726,6 +1030,35 @@ impl<'a> DynFunc<'a> {
             self.func_index,
             params,
             &mut results,
+            None,
+        )?;
+
+        Ok(results)
+    }
+
+    /// Call an exported WebAssembly function like [`call`], stopping
+    /// it with [`RuntimeError::Timeout`] if it runs for longer than
+    /// `timeout`.
+    ///
+    /// [`call`]: #method.call
+    /// [`RuntimeError::Timeout`]: ../error/enum.RuntimeError.html#variant.Timeout
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub fn call_with_timeout(
+        &self,
+        params: &[Value],
+        timeout: Duration,
+    ) -> CallResult<Vec<Value>> {
+        let mut results = Vec::new();
+
+        call_func_with_index(
+            &self.module.info,
+            &*self.module.runnable_module,
+            &self.instance_inner.import_backing,
+            self.instance_inner.vmctx,
+            self.func_index,
+            params,
+            &mut results,
+            Some(timeout),
         )?;
 
         Ok(results)
@@ -66,6 +66,9 @@ pub use self::instance::{DynFunc, Instance};
 pub use self::module::Module;
 #[doc(inline)]
 pub use self::typed_func::Func;
+#[cfg(all(unix, target_arch = "x86_64"))]
+#[doc(inline)]
+pub use self::typed_func::DynamicFunc;
 use std::sync::Arc;
 
 pub use wasmparser;
@@ -137,10 +140,10 @@ pub fn validate_and_report_errors_with_features(
     let config = wasmparser::ValidatingParserConfig {
         operator_config: wasmparser::OperatorValidatorConfig {
             enable_simd: features.simd,
-            enable_bulk_memory: false,
-            enable_multi_value: false,
-            enable_reference_types: false,
-            enable_threads: false,
+            enable_bulk_memory: features.bulk_memory,
+            enable_multi_value: features.multi_value,
+            enable_reference_types: features.reference_types,
+            enable_threads: features.threads,
         },
         mutable_global_imports: true,
     };
@@ -150,6 +153,11 @@ pub fn validate_and_report_errors_with_features(
         match *state {
             wasmparser::ParserState::EndWasm => break Ok(()),
             wasmparser::ParserState::Error(e) => break Err(format!("{}", e)),
+            wasmparser::ParserState::CodeOperator(ref op) => {
+                if let Err(e) = codegen::check_operator_features(op, &features) {
+                    break Err(format!("{}", e));
+                }
+            }
             _ => {}
         }
     }
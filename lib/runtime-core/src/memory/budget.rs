@@ -0,0 +1,116 @@
+use crate::{error::GrowError, units::Pages};
+use parking_lot::Mutex;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Weak,
+};
+
+/// A cap on the total number of pages of a group of memories, such as the
+/// memories an instance defines and imports.
+///
+/// See `InstanceConfig::memory_page_limit`.
+pub(crate) struct PageBudget {
+    limit: Pages,
+    used: AtomicU64,
+}
+
+impl PageBudget {
+    /// Creates a budget of `limit` pages, `used` of which are already taken.
+    ///
+    /// Fails with `GrowError::ExceededPageLimit` if `used` exceeds the limit.
+    pub fn new(limit: Pages, used: u64) -> Result<Arc<Self>, GrowError> {
+        if used > limit.0 as u64 {
+            return Err(GrowError::ExceededPageLimit(
+                used as usize,
+                limit.0 as usize,
+            ));
+        }
+        Ok(Arc::new(PageBudget {
+            limit,
+            used: AtomicU64::new(used),
+        }))
+    }
+
+    fn reserve(&self, delta: Pages) -> Result<(), GrowError> {
+        let limit = self.limit.0 as u64;
+        let mut used = self.used.load(Ordering::SeqCst);
+        loop {
+            let new_used = used + delta.0 as u64;
+            if new_used > limit {
+                return Err(GrowError::ExceededPageLimit(
+                    new_used as usize,
+                    limit as usize,
+                ));
+            }
+            match self
+                .used
+                .compare_exchange(used, new_used, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => return Ok(()),
+                Err(current) => used = current,
+            }
+        }
+    }
+
+    fn release(&self, delta: Pages) {
+        self.used.fetch_sub(delta.0 as u64, Ordering::SeqCst);
+    }
+}
+
+/// The page budgets that the growth of a memory is charged to.
+///
+/// The budgets are owned by the instances that set them, so the ones of
+/// instances that have been dropped no longer apply.
+#[derive(Default)]
+pub(crate) struct PageBudgets {
+    budgets: Mutex<Vec<Weak<PageBudget>>>,
+}
+
+impl PageBudgets {
+    pub fn add(&self, budget: &Arc<PageBudget>) {
+        self.budgets.lock().push(Arc::downgrade(budget));
+    }
+
+    /// Charges `delta` pages to every budget.
+    ///
+    /// Nothing is charged if a budget would be exceeded. The charge is
+    /// given back when the returned `PageCharge` is dropped without being
+    /// committed, such as when the memory then fails to grow.
+    pub fn charge(&self, delta: Pages) -> Result<PageCharge, GrowError> {
+        let mut weak_budgets = self.budgets.lock();
+        let budgets: Vec<Arc<PageBudget>> = weak_budgets.iter().filter_map(Weak::upgrade).collect();
+        *weak_budgets = budgets.iter().map(Arc::downgrade).collect();
+
+        let mut charge = PageCharge {
+            budgets: Vec::with_capacity(budgets.len()),
+            delta,
+        };
+        for budget in budgets {
+            budget.reserve(delta)?;
+            charge.budgets.push(budget);
+        }
+        Ok(charge)
+    }
+}
+
+/// Pages charged to the budgets of a memory by `PageBudgets::charge`.
+#[must_use]
+pub(crate) struct PageCharge {
+    budgets: Vec<Arc<PageBudget>>,
+    delta: Pages,
+}
+
+impl PageCharge {
+    /// Keeps the pages charged once the memory has grown.
+    pub fn commit(mut self) {
+        self.budgets.clear();
+    }
+}
+
+impl Drop for PageCharge {
+    fn drop(&mut self) {
+        for budget in &self.budgets {
+            budget.release(self.delta);
+        }
+    }
+}
@@ -1,11 +1,13 @@
 use crate::error::GrowError;
 use crate::{
     error::CreationError,
+    memory::budget::{PageBudget, PageBudgets},
     sys,
     types::MemoryDescriptor,
     units::{Bytes, Pages},
     vm,
 };
+use std::sync::Arc;
 
 pub const DYNAMIC_GUARD_SIZE: usize = 4096;
 
@@ -26,6 +28,8 @@ pub struct DynamicMemory {
     memory: sys::Memory,
     current: Pages,
     max: Option<Pages>,
+    limit: Option<Pages>,
+    budgets: PageBudgets,
 }
 
 impl DynamicMemory {
@@ -52,6 +56,8 @@ impl DynamicMemory {
             memory,
             current: desc.minimum,
             max: desc.maximum,
+            limit: None,
+            budgets: PageBudgets::default(),
         });
         let storage_ptr: *mut DynamicMemory = &mut *storage;
 
@@ -66,6 +72,14 @@ impl DynamicMemory {
         self.current
     }
 
+    pub fn set_page_limit(&mut self, limit: Option<Pages>) {
+        self.limit = limit;
+    }
+
+    pub(crate) fn add_page_budget(&self, budget: &Arc<PageBudget>) {
+        self.budgets.add(budget);
+    }
+
     pub fn grow(&mut self, delta: Pages, local: &mut vm::LocalMemory) -> Result<Pages, GrowError> {
         if delta == Pages(0) {
             return Ok(self.current);
@@ -82,6 +96,17 @@ impl DynamicMemory {
             }
         }
 
+        if let Some(limit) = self.limit {
+            if new_pages > limit {
+                return Err(GrowError::ExceededPageLimit(
+                    new_pages.0 as usize,
+                    limit.0 as usize,
+                ));
+            }
+        }
+
+        let charge = self.budgets.charge(delta)?;
+
         let mut new_memory = sys::Memory::with_size(new_pages.bytes().0 + DYNAMIC_GUARD_SIZE)
             .map_err(|e| e.into())?;
 
@@ -94,6 +119,10 @@ impl DynamicMemory {
                 .copy_from_slice(&self.memory.as_slice()[..self.current.bytes().0]);
         }
 
+        // The old memory may be tracked for `Instance::snapshot_delta`.
+        #[cfg(all(unix, target_arch = "x86_64"))]
+        crate::fault::dirty_region_moved(self.memory.as_ptr());
+
         self.memory = new_memory; //The old memory gets dropped.
 
         local.base = self.memory.as_ptr();
@@ -101,6 +130,7 @@ impl DynamicMemory {
 
         let old_pages = self.current;
         self.current = new_pages;
+        charge.commit();
         Ok(old_pages)
     }
 
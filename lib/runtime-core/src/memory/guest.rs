@@ -0,0 +1,155 @@
+//! Passing buffers to an instance in memory allocated by the instance itself.
+
+use crate::{
+    error::GuestMemoryError,
+    memory::ptr::{length_prefixed_size, Array, WasmPtr},
+    types::Value,
+    vm::Ctx,
+};
+
+/// How to allocate and free memory in an instance, through functions it exports
+/// such as `malloc` and `free`.
+///
+/// Allocations are made in the first memory of the instance. An allocator
+/// returning a null pointer is taken to be out of memory.
+///
+/// # Usage:
+///
+/// ```
+/// # use wasmer_runtime_core::{
+/// #     error::GuestMemoryError,
+/// #     memory::{ptr::{Array, WasmPtr}, GuestAllocator},
+/// #     vm::Ctx,
+/// # };
+/// fn pass_name(ctx: &mut Ctx) -> Result<WasmPtr<u8, Array>, GuestMemoryError> {
+///     let allocator = GuestAllocator::new("malloc", "free");
+///     // The guest reads the length, then the name, from the returned pointer,
+///     // and frees it once done.
+///     allocator.write_length_prefixed(ctx, b"wasmer")
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct GuestAllocator {
+    alloc: String,
+    free: Option<String>,
+    sized_free: bool,
+}
+
+impl GuestAllocator {
+    /// Allocates with `alloc(size: i32) -> i32` and frees with `free(ptr: i32)`.
+    pub fn new(alloc: &str, free: &str) -> GuestAllocator {
+        GuestAllocator {
+            alloc: alloc.to_string(),
+            free: Some(free.to_string()),
+            sized_free: false,
+        }
+    }
+
+    /// Allocates with `alloc(size: i32) -> i32` and never frees, as suits bump
+    /// allocators.
+    pub fn without_free(alloc: &str) -> GuestAllocator {
+        GuestAllocator {
+            alloc: alloc.to_string(),
+            free: None,
+            sized_free: false,
+        }
+    }
+
+    /// Frees with `free(ptr: i32, size: i32)`, for allocators that need to be
+    /// told the size of the allocation being freed.
+    pub fn with_sized_free(mut self) -> GuestAllocator {
+        self.sized_free = true;
+        self
+    }
+
+    /// Allocates `size` bytes.
+    pub fn alloc(&self, ctx: &mut Ctx, size: u32) -> Result<WasmPtr<u8, Array>, GuestMemoryError> {
+        match ctx.call(&self.alloc, &[Value::I32(size as i32)])?[..] {
+            [Value::I32(0)] => Err(GuestMemoryError::AllocationFailed { size }),
+            [Value::I32(offset)] => Ok(WasmPtr::new(offset as u32)),
+            ref results => Err(GuestMemoryError::UnexpectedResults {
+                function: self.alloc.clone(),
+                results: results.to_vec(),
+            }),
+        }
+    }
+
+    /// Frees the `size` bytes at `ptr`, which were allocated with `alloc`.
+    ///
+    /// This does nothing if the allocator doesn't free memory.
+    pub fn free(
+        &self,
+        ctx: &mut Ctx,
+        ptr: WasmPtr<u8, Array>,
+        size: u32,
+    ) -> Result<(), GuestMemoryError> {
+        let free = match self.free {
+            Some(ref free) => free,
+            None => return Ok(()),
+        };
+        let ptr = Value::I32(ptr.offset() as i32);
+        let results = if self.sized_free {
+            ctx.call(free, &[ptr, Value::I32(size as i32)])?
+        } else {
+            ctx.call(free, &[ptr])?
+        };
+        if results.is_empty() {
+            Ok(())
+        } else {
+            Err(GuestMemoryError::UnexpectedResults {
+                function: free.clone(),
+                results,
+            })
+        }
+    }
+
+    /// Copies `bytes` into memory allocated for them, and returns a pointer to it.
+    pub fn write_bytes(
+        &self,
+        ctx: &mut Ctx,
+        bytes: &[u8],
+    ) -> Result<WasmPtr<u8, Array>, GuestMemoryError> {
+        if bytes.len() > u32::max_value() as usize {
+            return Err(GuestMemoryError::TooLarge);
+        }
+        let size = bytes.len() as u32;
+        let ptr = self.alloc(ctx, size)?;
+        if ptr.write_bytes(ctx.memory(0), bytes).is_none() {
+            self.free(ctx, ptr, size)?;
+            return Err(GuestMemoryError::OutOfBounds);
+        }
+        Ok(ptr)
+    }
+
+    /// Copies `bytes` into memory allocated for them, preceded by their length
+    /// as `WasmPtr::write_length_prefixed` does, and returns a pointer to it.
+    ///
+    /// The buffer can be freed with `free_length_prefixed`.
+    pub fn write_length_prefixed(
+        &self,
+        ctx: &mut Ctx,
+        bytes: &[u8],
+    ) -> Result<WasmPtr<u8, Array>, GuestMemoryError> {
+        let size = length_prefixed_size(bytes.len()).ok_or(GuestMemoryError::TooLarge)?;
+        let ptr = self.alloc(ctx, size)?;
+        if ptr.write_length_prefixed(ctx.memory(0), bytes).is_none() {
+            self.free(ctx, ptr, size)?;
+            return Err(GuestMemoryError::OutOfBounds);
+        }
+        Ok(ptr)
+    }
+
+    /// Frees a length-prefixed buffer at `ptr`, which was allocated with this
+    /// allocator, e.g. by `write_length_prefixed`.
+    pub fn free_length_prefixed(
+        &self,
+        ctx: &mut Ctx,
+        ptr: WasmPtr<u8, Array>,
+    ) -> Result<(), GuestMemoryError> {
+        let len = ptr
+            .read_length_prefix(ctx.memory(0))
+            .ok_or(GuestMemoryError::OutOfBounds)?;
+        let size = length_prefixed_size(len as usize).ok_or(GuestMemoryError::TooLarge)?;
+        self.free(ctx, ptr, size)
+    }
+}
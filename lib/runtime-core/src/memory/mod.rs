@@ -9,19 +9,25 @@ use crate::{
     vm,
 };
 use std::{
-    cell::{Cell, RefCell},
+    cell::{Cell, RefCell, UnsafeCell},
     fmt, mem,
     rc::Rc,
+    sync::Arc,
 };
 
+pub(crate) use self::budget::PageBudget;
 pub use self::dynamic::DynamicMemory;
+pub use self::guest::GuestAllocator;
 pub use self::static_::{SharedStaticMemory, StaticMemory};
-pub use self::view::{Atomically, MemoryView};
+pub use self::view::{Atomic, Atomically, MemoryView};
 
+mod budget;
 mod dynamic;
+mod guest;
 pub mod ptr;
 mod static_;
 mod view;
+pub(crate) mod wait;
 
 #[derive(Clone)]
 enum MemoryVariant {
@@ -110,6 +116,35 @@ impl Memory {
         }
     }
 
+    /// Caps the size, in wasm pages, that this memory can grow to,
+    /// independently of the maximum in its [`MemoryDescriptor`].
+    ///
+    /// Growing past the limit fails with `GrowError::ExceededPageLimit`.
+    /// When the growth is requested by a `memory.grow` instruction, the
+    /// WebAssembly code traps with that error instead of receiving `-1`.
+    /// Passing `None` removes the limit.
+    ///
+    /// To cap the total size of the memories of an instance, use
+    /// [`InstanceConfig::memory_page_limit`] instead.
+    ///
+    /// [`InstanceConfig::memory_page_limit`]: ../instance/struct.InstanceConfig.html#structfield.memory_page_limit
+    /// [`MemoryDescriptor`]: struct.MemoryDescriptor.html
+    pub fn set_page_limit(&self, limit: Option<Pages>) {
+        match &self.variant {
+            MemoryVariant::Unshared(unshared_mem) => unshared_mem.set_page_limit(limit),
+            MemoryVariant::Shared(shared_mem) => shared_mem.set_page_limit(limit),
+        }
+    }
+
+    /// Charges the growth of this memory to `budget` as well, for as long
+    /// as it is alive.
+    pub(crate) fn add_page_budget(&self, budget: &Arc<PageBudget>) {
+        match &self.variant {
+            MemoryVariant::Unshared(unshared_mem) => unshared_mem.add_page_budget(budget),
+            MemoryVariant::Shared(shared_mem) => shared_mem.add_page_budget(budget),
+        }
+    }
+
     /// Return a "view" of the currently accessible memory. By
     /// default, the view is unsyncronized, using regular memory
     /// accesses. You can force a memory view to use atomic accesses
@@ -151,20 +186,58 @@ impl Memory {
         unsafe { MemoryView::new(base as _, length as u32) }
     }
 
+    /// Prepares the `len` bytes at `offset` to be written to by the operating
+    /// system, such as by a `read` into the memory.
+    ///
+    /// While [`Instance::track_dirty_pages`] is on, pages are read-only until
+    /// they're first written to. Writes from WebAssembly or the host are caught
+    /// and recorded, but the operating system fails with `EFAULT` instead, so
+    /// this records the pages as written to and makes them writable up front.
+    /// It does nothing otherwise.
+    ///
+    /// [`Instance::track_dirty_pages`]: ../instance/struct.Instance.html#method.track_dirty_pages
+    pub fn prepare_os_write(&self, offset: usize, len: usize) {
+        #[cfg(all(unix, target_arch = "x86_64"))]
+        {
+            let bytes = self.size().bytes().0;
+            let len = len.min(bytes.saturating_sub(offset));
+            let vm::LocalMemory { base, .. } = unsafe { *self.vm_local_memory() };
+            unsafe { crate::fault::prepare_tracked_write(base as usize + offset, len) };
+        }
+        #[cfg(not(all(unix, target_arch = "x86_64")))]
+        {
+            let _ = (offset, len);
+        }
+    }
+
     /// Convert this memory to a shared memory if the shared flag
     /// is present in the description used to create it.
+    ///
+    /// Unlike `Memory`, a [`SharedMemory`] can be sent to other threads,
+    /// where it can be turned back into a `Memory` and imported by
+    /// other instances.
+    ///
+    /// [`SharedMemory`]: struct.SharedMemory.html
     pub fn shared(self) -> Option<SharedMemory> {
-        if self.desc.shared {
-            Some(SharedMemory { desc: self.desc })
-        } else {
-            None
+        match self.variant {
+            MemoryVariant::Shared(shared_mem) => Some(shared_mem),
+            MemoryVariant::Unshared(_) => None,
         }
     }
 
     pub(crate) fn vm_local_memory(&self) -> *mut vm::LocalMemory {
         match &self.variant {
             MemoryVariant::Unshared(unshared_mem) => unshared_mem.vm_local_memory(),
-            MemoryVariant::Shared(_) => unimplemented!(),
+            MemoryVariant::Shared(shared_mem) => shared_mem.vm_local_memory(),
+        }
+    }
+}
+
+impl From<SharedMemory> for Memory {
+    fn from(shared_mem: SharedMemory) -> Self {
+        Memory {
+            desc: shared_mem.desc,
+            variant: MemoryVariant::Shared(shared_mem),
         }
     }
 }
@@ -275,6 +348,26 @@ impl UnsharedMemory {
         }
     }
 
+    pub fn set_page_limit(&self, limit: Option<Pages>) {
+        let mut storage = self.internal.storage.borrow_mut();
+
+        match &mut *storage {
+            UnsharedMemoryStorage::Dynamic(dynamic_memory) => dynamic_memory.set_page_limit(limit),
+            UnsharedMemoryStorage::Static(static_memory) => static_memory.set_page_limit(limit),
+        }
+    }
+
+    pub(crate) fn add_page_budget(&self, budget: &Arc<PageBudget>) {
+        let storage = self.internal.storage.borrow();
+
+        match &*storage {
+            UnsharedMemoryStorage::Dynamic(dynamic_memory) => {
+                dynamic_memory.add_page_budget(budget)
+            }
+            UnsharedMemoryStorage::Static(static_memory) => static_memory.add_page_budget(budget),
+        }
+    }
+
     pub(crate) fn vm_local_memory(&self) -> *mut vm::LocalMemory {
         self.internal.local.as_ptr()
     }
@@ -288,28 +381,81 @@ impl Clone for UnsharedMemory {
     }
 }
 
+/// A linear memory that can be used by several instances,
+/// possibly running on different threads, at the same time.
 pub struct SharedMemory {
-    #[allow(dead_code)]
     desc: MemoryDescriptor,
+    internal: Arc<SharedMemoryInternal>,
+}
+
+struct SharedMemoryInternal {
+    memory: Box<SharedStaticMemory>,
+    local: UnsafeCell<vm::LocalMemory>,
 }
 
+// `local` is only written by `SharedStaticMemory::grow`, which holds the
+// memory's lock while doing so.
+unsafe impl Send for SharedMemoryInternal {}
+unsafe impl Sync for SharedMemoryInternal {}
+
 impl SharedMemory {
     fn new(desc: MemoryDescriptor) -> Result<Self, CreationError> {
-        Ok(Self { desc })
+        let mut local = vm::LocalMemory {
+            base: std::ptr::null_mut(),
+            bound: 0,
+            memory: std::ptr::null_mut(),
+        };
+
+        let memory = SharedStaticMemory::new(desc, &mut local)?;
+
+        Ok(Self {
+            desc,
+            internal: Arc::new(SharedMemoryInternal {
+                memory,
+                local: UnsafeCell::new(local),
+            }),
+        })
+    }
+
+    /// Return the [`MemoryDescriptor`] that this memory
+    /// was created with.
+    ///
+    /// [`MemoryDescriptor`]: struct.MemoryDescriptor.html
+    pub fn descriptor(&self) -> MemoryDescriptor {
+        self.desc
     }
 
-    pub fn grow(&self, _delta: Pages) -> Result<Pages, GrowError> {
-        unimplemented!()
+    pub fn grow(&self, delta: Pages) -> Result<Pages, GrowError> {
+        unsafe {
+            self.internal
+                .memory
+                .grow(delta, &mut *self.internal.local.get())
+        }
     }
 
     pub fn size(&self) -> Pages {
-        unimplemented!()
+        self.internal.memory.size()
+    }
+
+    pub fn set_page_limit(&self, limit: Option<Pages>) {
+        self.internal.memory.set_page_limit(limit)
+    }
+
+    pub(crate) fn add_page_budget(&self, budget: &Arc<PageBudget>) {
+        self.internal.memory.add_page_budget(budget)
+    }
+
+    pub(crate) fn vm_local_memory(&self) -> *mut vm::LocalMemory {
+        self.internal.local.get()
     }
 }
 
 impl Clone for SharedMemory {
     fn clone(&self) -> Self {
-        unimplemented!()
+        SharedMemory {
+            desc: self.desc,
+            internal: Arc::clone(&self.internal),
+        }
     }
 }
 
@@ -342,4 +488,55 @@ mod memory_tests {
         )
     }
 
+    #[test]
+    fn test_grow_past_page_limit() {
+        use crate::error::GrowError;
+
+        let memory = Memory::new(MemoryDescriptor {
+            minimum: Pages(1),
+            maximum: Some(Pages(20)),
+            shared: false,
+        })
+        .unwrap();
+        memory.set_page_limit(Some(Pages(4)));
+
+        assert_eq!(memory.grow(Pages(3)).unwrap(), Pages(1));
+        match memory.grow(Pages(1)) {
+            Err(GrowError::ExceededPageLimit(5, 4)) => {}
+            other => panic!("unexpected grow result: {:?}", other),
+        }
+        assert_eq!(memory.size(), Pages(4));
+
+        memory.set_page_limit(None);
+        assert_eq!(memory.grow(Pages(1)).unwrap(), Pages(4));
+    }
+
+    #[test]
+    fn test_shared_memory_grow() {
+        use crate::error::GrowError;
+
+        let memory = Memory::new(MemoryDescriptor {
+            minimum: Pages(1),
+            maximum: Some(Pages(4)),
+            shared: true,
+        })
+        .unwrap();
+        let shared = memory.clone().shared().unwrap();
+
+        let handle = std::thread::spawn(move || {
+            let memory = Memory::from(shared);
+            memory.view::<u8>()[0].set(42);
+            memory.grow(Pages(2)).unwrap()
+        });
+        assert_eq!(handle.join().unwrap(), Pages(1));
+
+        assert_eq!(memory.size(), Pages(3));
+        assert_eq!(memory.view::<u8>().len(), Pages(3).bytes().0);
+        assert_eq!(memory.view::<u8>()[0].get(), 42);
+        match memory.grow(Pages(2)) {
+            Err(GrowError::ExceededMaxPagesForMemory(5, 4)) => {}
+            other => panic!("unexpected grow result: {:?}", other),
+        }
+    }
+
 }
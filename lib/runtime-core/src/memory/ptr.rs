@@ -137,6 +137,75 @@ impl<T: Copy + ValueType> WasmPtr<T, Array> {
     }
 }
 
+/// The size of the length that prefixes buffers written by `write_length_prefixed`.
+const LENGTH_PREFIX_SIZE: u32 = 4;
+
+impl WasmPtr<u8, Array> {
+    /// Copies the `len` bytes the pointer points to out of `memory`.
+    ///
+    /// Returns `None` if they aren't all in bounds.
+    pub fn read_bytes(self, memory: &Memory, len: u32) -> Option<Vec<u8>> {
+        let view = memory.view::<u8>();
+        // Checked first, so that a bogus length can't make us allocate gigabytes.
+        if (self.offset as usize).checked_add(len as usize)? > view.len() {
+            return None;
+        }
+        let mut bytes = vec![0; len as usize];
+        view.read_bytes(self.offset as usize, &mut bytes)?;
+        Some(bytes)
+    }
+
+    /// Copies `bytes` into `memory`, where the pointer points to.
+    ///
+    /// Returns `None`, leaving the memory untouched, if they don't all fit.
+    pub fn write_bytes(self, memory: &Memory, bytes: &[u8]) -> Option<()> {
+        memory.view::<u8>().write_bytes(self.offset as usize, bytes)
+    }
+
+    /// Reads a length-prefixed buffer, as written by `write_length_prefixed`.
+    ///
+    /// Returns `None` if the buffer isn't entirely in bounds.
+    pub fn read_length_prefixed(self, memory: &Memory) -> Option<Vec<u8>> {
+        let len = self.read_length_prefix(memory)?;
+        WasmPtr::<u8, Array>::new(self.offset.checked_add(LENGTH_PREFIX_SIZE)?)
+            .read_bytes(memory, len)
+    }
+
+    /// Reads the length of the length-prefixed buffer the pointer points to.
+    pub(crate) fn read_length_prefix(self, memory: &Memory) -> Option<u32> {
+        let mut len = [0; LENGTH_PREFIX_SIZE as usize];
+        memory
+            .view::<u8>()
+            .read_bytes(self.offset as usize, &mut len)?;
+        Some(u32::from_le_bytes(len))
+    }
+
+    /// Writes `bytes` where the pointer points to, preceded by their length as
+    /// a little-endian `u32`, so that the buffer can be passed as a single
+    /// pointer. It takes `length_prefixed_size(bytes.len())` bytes of memory.
+    ///
+    /// Returns `None`, leaving the memory untouched, if the buffer doesn't fit.
+    pub fn write_length_prefixed(self, memory: &Memory, bytes: &[u8]) -> Option<()> {
+        let size = length_prefixed_size(bytes.len())?;
+        let view = memory.view::<u8>();
+        if (self.offset as usize).checked_add(size as usize)? > view.len() {
+            return None;
+        }
+        let len = (bytes.len() as u32).to_le_bytes();
+        view.write_bytes(self.offset as usize, &len)?;
+        view.write_bytes((self.offset + LENGTH_PREFIX_SIZE) as usize, bytes)
+    }
+}
+
+/// Returns the number of bytes `WasmPtr::write_length_prefixed` writes for a
+/// buffer of `len` bytes, or `None` if that doesn't fit in 32 bits.
+pub fn length_prefixed_size(len: usize) -> Option<u32> {
+    if len > u32::max_value() as usize {
+        return None;
+    }
+    (len as u32).checked_add(LENGTH_PREFIX_SIZE)
+}
+
 unsafe impl<T: Copy, Ty> WasmExternType for WasmPtr<T, Ty> {
     type Native = i32;
 
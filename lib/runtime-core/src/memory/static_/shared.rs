@@ -1,11 +1,142 @@
-use crate::sys;
+use crate::error::GrowError;
+use crate::{
+    error::CreationError,
+    memory::budget::{PageBudget, PageBudgets},
+    memory::static_::{SAFE_STATIC_GUARD_SIZE, SAFE_STATIC_HEAP_SIZE},
+    sys,
+    types::MemoryDescriptor,
+    units::Pages,
+    vm,
+};
 use parking_lot::Mutex;
-use std::sync::atomic::AtomicUsize;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
 
-// Remove this attribute once this is used.
-#[allow(dead_code)]
+/// This is an internal-only api.
+///
+/// A shared static memory reserves the same virtual memory as a
+/// [`StaticMemory`], so its base address never changes and it can be used
+/// by several threads at once. Growing it is serialized by a lock.
+///
+/// [`StaticMemory`]: struct.StaticMemory.html
 pub struct SharedStaticMemory {
-    memory: sys::Memory,
+    memory: Mutex<SharedStaticMemoryStorage>,
     current: AtomicUsize,
-    lock: Mutex<()>,
+    max: Pages,
+    budgets: PageBudgets,
+}
+
+struct SharedStaticMemoryStorage {
+    memory: sys::Memory,
+    limit: Option<Pages>,
+}
+
+impl SharedStaticMemory {
+    pub(in crate::memory) fn new(
+        desc: MemoryDescriptor,
+        local: &mut vm::LocalMemory,
+    ) -> Result<Box<Self>, CreationError> {
+        let max = desc.maximum.ok_or_else(|| {
+            CreationError::InvalidDescriptor(
+                "Max number of pages is required for shared memory".to_string(),
+            )
+        })?;
+
+        let memory = {
+            let mut memory = sys::Memory::with_size(SAFE_STATIC_HEAP_SIZE + SAFE_STATIC_GUARD_SIZE)
+                .map_err(|_| CreationError::UnableToCreateMemory)?;
+            if desc.minimum != Pages(0) {
+                unsafe {
+                    memory
+                        .protect(0..desc.minimum.bytes().0, sys::Protect::ReadWrite)
+                        .map_err(|_| CreationError::UnableToCreateMemory)?;
+                }
+            }
+
+            memory
+        };
+
+        let base = memory.as_ptr();
+        let storage = Box::new(SharedStaticMemory {
+            memory: Mutex::new(SharedStaticMemoryStorage {
+                memory,
+                limit: None,
+            }),
+            current: AtomicUsize::new(desc.minimum.0 as usize),
+            max,
+            budgets: PageBudgets::default(),
+        });
+        let storage_ptr: *const SharedStaticMemory = &*storage;
+
+        local.base = base;
+        local.bound = desc.minimum.bytes().0;
+        local.memory = storage_ptr as *mut ();
+
+        Ok(storage)
+    }
+
+    pub fn size(&self) -> Pages {
+        Pages(self.current.load(Ordering::SeqCst) as u32)
+    }
+
+    pub fn set_page_limit(&self, limit: Option<Pages>) {
+        self.memory.lock().limit = limit;
+    }
+
+    pub(crate) fn add_page_budget(&self, budget: &Arc<PageBudget>) {
+        self.budgets.add(budget);
+    }
+
+    /// Grows the memory by `delta` pages.
+    ///
+    /// The caller must make sure `local` is not concurrently written by
+    /// anything else; reads from other threads only ever observe a bound
+    /// that is backed by accessible memory.
+    pub fn grow(&self, delta: Pages, local: &mut vm::LocalMemory) -> Result<Pages, GrowError> {
+        let mut storage = self.memory.lock();
+
+        let old_pages = self.size();
+        if delta == Pages(0) {
+            return Ok(old_pages);
+        }
+
+        let new_pages = old_pages.checked_add(delta).map_err(|e| e.into())?;
+
+        if new_pages > self.max {
+            return Err(GrowError::ExceededMaxPagesForMemory(
+                new_pages.0 as usize,
+                self.max.0 as usize,
+            ));
+        }
+
+        if let Some(limit) = storage.limit {
+            if new_pages > limit {
+                return Err(GrowError::ExceededPageLimit(
+                    new_pages.0 as usize,
+                    limit.0 as usize,
+                ));
+            }
+        }
+
+        let charge = self.budgets.charge(delta)?;
+
+        let _ = unsafe {
+            storage
+                .memory
+                .protect(
+                    old_pages.bytes().0..new_pages.bytes().0,
+                    sys::Protect::ReadWrite,
+                )
+                .map_err(|e| e.into())
+        }?;
+
+        local.bound = new_pages.bytes().0;
+
+        self.current.store(new_pages.0 as usize, Ordering::SeqCst);
+        charge.commit();
+
+        Ok(old_pages)
+    }
 }
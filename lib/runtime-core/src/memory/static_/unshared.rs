@@ -1,12 +1,14 @@
 use crate::error::GrowError;
 use crate::{
     error::CreationError,
+    memory::budget::{PageBudget, PageBudgets},
     memory::static_::{SAFE_STATIC_GUARD_SIZE, SAFE_STATIC_HEAP_SIZE},
     sys,
     types::MemoryDescriptor,
     units::Pages,
     vm,
 };
+use std::sync::Arc;
 
 /// This is an internal-only api.
 ///
@@ -23,6 +25,8 @@ pub struct StaticMemory {
     memory: sys::Memory,
     current: Pages,
     max: Option<Pages>,
+    limit: Option<Pages>,
+    budgets: PageBudgets,
 }
 
 impl StaticMemory {
@@ -48,6 +52,8 @@ impl StaticMemory {
             memory,
             current: desc.minimum,
             max: desc.maximum,
+            limit: None,
+            budgets: PageBudgets::default(),
         });
         let storage_ptr: *mut StaticMemory = &mut *storage;
 
@@ -62,6 +68,14 @@ impl StaticMemory {
         self.current
     }
 
+    pub fn set_page_limit(&mut self, limit: Option<Pages>) {
+        self.limit = limit;
+    }
+
+    pub(crate) fn add_page_budget(&self, budget: &Arc<PageBudget>) {
+        self.budgets.add(budget);
+    }
+
     pub fn grow(&mut self, delta: Pages, local: &mut vm::LocalMemory) -> Result<Pages, GrowError> {
         if delta == Pages(0) {
             return Ok(self.current);
@@ -78,6 +92,17 @@ impl StaticMemory {
             }
         }
 
+        if let Some(limit) = self.limit {
+            if new_pages > limit {
+                return Err(GrowError::ExceededPageLimit(
+                    new_pages.0 as usize,
+                    limit.0 as usize,
+                ));
+            }
+        }
+
+        let charge = self.budgets.charge(delta)?;
+
         let _ = unsafe {
             self.memory
                 .protect(
@@ -92,6 +117,7 @@ impl StaticMemory {
         let old_pages = self.current;
 
         self.current = new_pages;
+        charge.commit();
 
         Ok(old_pages)
     }
@@ -3,7 +3,7 @@ use crate::types::ValueType;
 use std::sync::atomic::{
     AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicU16, AtomicU32, AtomicU64, AtomicU8,
 };
-use std::{cell::Cell, marker::PhantomData, ops::Deref, slice};
+use std::{cell::Cell, marker::PhantomData, ops::Deref, ptr, slice};
 
 pub trait Atomic {
     type Output;
@@ -64,6 +64,30 @@ where
     }
 }
 
+impl<'a> MemoryView<'a, u8> {
+    /// Copies the bytes at `offset` into `buf`.
+    ///
+    /// Returns `None`, leaving `buf` untouched, if they aren't all in the view.
+    pub fn read_bytes(&self, offset: usize, buf: &mut [u8]) -> Option<()> {
+        if offset.checked_add(buf.len())? > self.length {
+            return None;
+        }
+        unsafe { ptr::copy_nonoverlapping(self.ptr.add(offset), buf.as_mut_ptr(), buf.len()) };
+        Some(())
+    }
+
+    /// Copies `bytes` into the view at `offset`.
+    ///
+    /// Returns `None`, leaving the memory untouched, if they don't all fit.
+    pub fn write_bytes(&self, offset: usize, bytes: &[u8]) -> Option<()> {
+        if offset.checked_add(bytes.len())? > self.length {
+            return None;
+        }
+        unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr.add(offset), bytes.len()) };
+        Some(())
+    }
+}
+
 impl<'a, T: Atomic> MemoryView<'a, T> {
     pub fn atomically(&self) -> MemoryView<'a, T::Output, Atomically> {
         MemoryView {
@@ -0,0 +1,105 @@
+//! The wait queues behind the `atomic.wait` and `atomic.notify` operators.
+//!
+//! Waiters are keyed by the host address they wait on, so instances that
+//! share a memory also share its queues.
+
+use lazy_static::lazy_static;
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+lazy_static! {
+    static ref WAITERS: Mutex<HashMap<usize, VecDeque<Arc<Waiter>>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Default)]
+struct Waiter {
+    woken: AtomicBool,
+    condvar: Condvar,
+}
+
+/// The outcome of a wait, encoded as `atomic.wait` returns it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum WaitResult {
+    Woken = 0,
+    NotEqual = 1,
+    TimedOut = 2,
+}
+
+/// Blocks the current thread on `addr` until it is notified or `timeout`
+/// expires, unless `matches` returns `false`.
+///
+/// `matches` runs while the queues are locked, so a notification sent
+/// after the value at `addr` has been changed is never lost.
+pub(crate) fn wait(
+    addr: usize,
+    matches: impl FnOnce() -> bool,
+    timeout: Option<Duration>,
+) -> WaitResult {
+    let mut waiters = WAITERS.lock();
+    if !matches() {
+        return WaitResult::NotEqual;
+    }
+
+    let waiter = Arc::new(Waiter::default());
+    waiters
+        .entry(addr)
+        .or_insert_with(VecDeque::new)
+        .push_back(Arc::clone(&waiter));
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    while !waiter.woken.load(Ordering::SeqCst) {
+        match deadline {
+            Some(deadline) => {
+                let result = waiter.condvar.wait_until(&mut waiters, deadline);
+                if result.timed_out() {
+                    break;
+                }
+            }
+            None => waiter.condvar.wait(&mut waiters),
+        }
+    }
+
+    if waiter.woken.load(Ordering::SeqCst) {
+        return WaitResult::Woken;
+    }
+
+    if let Some(queue) = waiters.get_mut(&addr) {
+        queue.retain(|other| !Arc::ptr_eq(other, &waiter));
+        if queue.is_empty() {
+            waiters.remove(&addr);
+        }
+    }
+    WaitResult::TimedOut
+}
+
+/// Wakes up to `count` threads waiting on `addr`, in the order they
+/// started waiting, and returns how many were woken.
+pub(crate) fn notify(addr: usize, count: u32) -> u32 {
+    let mut waiters = WAITERS.lock();
+    let mut woken = 0;
+
+    if let Some(queue) = waiters.get_mut(&addr) {
+        while woken < count {
+            match queue.pop_front() {
+                Some(waiter) => {
+                    waiter.woken.store(true, Ordering::SeqCst);
+                    waiter.condvar.notify_one();
+                    woken += 1;
+                }
+                None => break,
+            }
+        }
+        if queue.is_empty() {
+            waiters.remove(&addr);
+        }
+    }
+
+    woken
+}
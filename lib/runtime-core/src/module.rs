@@ -1,14 +1,15 @@
 use crate::{
     backend::{Backend, RunnableModule},
-    cache::{Artifact, Error as CacheError},
+    cache::{Artifact, Error as CacheError, WasmHash},
     error,
     import::ImportObject,
+    instance::InstanceConfig,
     structures::{Map, TypedIndex},
     types::{
-        FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex, GlobalInit, ImportedFuncIndex,
-        ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex, Initializer,
-        LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryDescriptor, MemoryIndex,
-        SigIndex, TableDescriptor, TableIndex,
+        DataIndex, ElemIndex, FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex, GlobalInit,
+        ImportedFuncIndex, ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex,
+        Initializer, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryDescriptor,
+        MemoryIndex, SigIndex, TableDescriptor, TableIndex,
     },
     Instance,
 };
@@ -45,11 +46,24 @@ pub struct ModuleInfo {
     pub data_initializers: Vec<DataInitializer>,
     pub elem_initializers: Vec<TableInitializer>,
 
+    /// Passive data segments, which are only copied into memory by `memory.init`.
+    pub passive_data: HashMap<DataIndex, Vec<u8>>,
+    /// Passive element segments, which are only copied into a table by `table.init`.
+    pub passive_elements: HashMap<ElemIndex, Vec<FuncIndex>>,
+
     pub start_func: Option<FuncIndex>,
 
     pub func_assoc: Map<FuncIndex, SigIndex>,
     pub signatures: Map<SigIndex, FuncSig>,
     pub backend: Backend,
+    /// The hash of the wasm binary the module was compiled from.
+    pub wasm_hash: WasmHash,
+    /// The fingerprint of the middlewares the module was compiled with, or an
+    /// empty string if there were none. See `MiddlewareChain::fingerprint`.
+    pub middleware_fingerprint: String,
+    /// Whether the module was compiled with a middleware that stops it once
+    /// `fault::INTERRUPT_FIELD` is set. See `FunctionMiddleware::checks_interrupts`.
+    pub checks_interrupts: bool,
 
     pub namespace_table: StringTable<NamespaceIndex>,
     pub name_table: StringTable<NameIndex>,
@@ -58,6 +72,14 @@ pub struct ModuleInfo {
     pub em_symbol_map: Option<HashMap<u32, String>>,
 
     pub custom_sections: HashMap<String, Vec<u8>>,
+
+    /// Module name from the "name" custom section, if present.
+    pub module_name: Option<String>,
+    /// Function names from the "name" custom section, keyed by function index.
+    pub function_names: HashMap<FuncIndex, String>,
+    /// Local names from the "name" custom section, keyed by function index
+    /// and then by local index (parameters come first).
+    pub local_names: HashMap<FuncIndex, HashMap<u32, String>>,
 }
 
 impl ModuleInfo {
@@ -76,6 +98,70 @@ impl ModuleInfo {
         }
         Ok(())
     }
+
+    /// Decodes the "name" custom section into `module_name`, `function_names`
+    /// and `local_names`.
+    pub fn import_name_section(&mut self, wasm: &[u8]) -> crate::error::ParseResult<()> {
+        use wasmparser::{CustomSectionKind, Name, SectionCode};
+
+        let mut parser = wasmparser::ModuleReader::new(wasm)?;
+        while !parser.eof() {
+            let section = parser.read()?;
+            if let SectionCode::Custom {
+                kind: CustomSectionKind::Name,
+                ..
+            } = section.code
+            {
+                let mut reader = section.get_name_section_reader()?;
+                while !reader.eof() {
+                    match reader.read()? {
+                        Name::Module(name) => {
+                            self.module_name = Some(name.get_name()?.to_string());
+                        }
+                        Name::Function(names) => {
+                            let mut map = names.get_map()?;
+                            for _ in 0..map.get_count() {
+                                let naming = map.read()?;
+                                self.function_names.insert(
+                                    FuncIndex::new(naming.index as usize),
+                                    naming.name.to_string(),
+                                );
+                            }
+                        }
+                        Name::Local(names) => {
+                            let mut funcs = names.get_function_local_reader()?;
+                            for _ in 0..funcs.get_count() {
+                                let func = funcs.read()?;
+                                let mut map = func.get_map()?;
+                                let locals = self
+                                    .local_names
+                                    .entry(FuncIndex::new(func.func_index as usize))
+                                    .or_insert_with(HashMap::new);
+                                for _ in 0..map.get_count() {
+                                    let naming = map.read()?;
+                                    locals.insert(naming.index, naming.name.to_string());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the function at `index` from the "name" section.
+    pub fn function_name(&self, index: FuncIndex) -> Option<&str> {
+        self.function_names.get(&index).map(|x| x.as_str())
+    }
+
+    /// Returns the name of local `local_index` of the function at `index`.
+    pub fn local_name(&self, index: FuncIndex, local_index: u32) -> Option<&str> {
+        self.local_names
+            .get(&index)
+            .and_then(|x| x.get(&local_index))
+            .map(|x| x.as_str())
+    }
 }
 
 /// A compiled WebAssembly module.
@@ -117,7 +203,37 @@ impl Module {
     /// # }
     /// ```
     pub fn instantiate(&self, import_object: &ImportObject) -> error::Result<Instance> {
-        Instance::new(Arc::clone(&self.inner), import_object)
+        self.instantiate_with_config(import_object, InstanceConfig::default())
+    }
+
+    /// Instantiate a WebAssembly module with the provided [`ImportObject`]
+    /// and [`InstanceConfig`].
+    ///
+    /// [`ImportObject`]: struct.ImportObject.html
+    /// [`InstanceConfig`]: ../instance/struct.InstanceConfig.html
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::error::Result;
+    /// # use wasmer_runtime_core::Module;
+    /// # use wasmer_runtime_core::imports;
+    /// # use wasmer_runtime_core::instance::InstanceConfig;
+    /// # use wasmer_runtime_core::units::Pages;
+    /// # fn instantiate(module: &Module) -> Result<()> {
+    /// let config = InstanceConfig {
+    ///     memory_page_limit: Some(Pages(16)),
+    /// };
+    /// let instance = module.instantiate_with_config(&imports! {}, config)?;
+    /// // ...
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn instantiate_with_config(
+        &self,
+        import_object: &ImportObject,
+        config: InstanceConfig,
+    ) -> error::Result<Instance> {
+        Instance::new(Arc::clone(&self.inner), import_object, config)
     }
 
     pub fn cache(&self) -> Result<Artifact, CacheError> {
@@ -1,16 +1,18 @@
 use crate::codegen::*;
 use crate::{
     backend::{Backend, CompilerConfig, RunnableModule},
+    cache::WasmHash,
     error::CompileError,
     module::{
         DataInitializer, ExportIndex, ImportName, ModuleInfo, StringTable, StringTableBuilder,
         TableInitializer,
     },
     structures::{Map, TypedIndex},
+    table::Reference,
     types::{
-        ElementType, FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex, GlobalInit,
-        ImportedGlobalIndex, Initializer, MemoryDescriptor, MemoryIndex, SigIndex, TableDescriptor,
-        TableIndex, Type, Value,
+        DataIndex, ElemIndex, ElementType, FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex,
+        GlobalInit, ImportedGlobalIndex, Initializer, MemoryDescriptor, MemoryIndex, SigIndex,
+        TableDescriptor, TableIndex, Type, Value,
     },
     units::Pages,
 };
@@ -71,11 +73,17 @@ pub fn read_module<
         data_initializers: Vec::new(),
         elem_initializers: Vec::new(),
 
+        passive_data: HashMap::new(),
+        passive_elements: HashMap::new(),
+
         start_func: None,
 
         func_assoc: Map::new(),
         signatures: Map::new(),
         backend: backend,
+        wasm_hash: WasmHash::generate(wasm),
+        middleware_fingerprint: middlewares.fingerprint(),
+        checks_interrupts: middlewares.checks_interrupts(),
 
         namespace_table: StringTable::new(),
         name_table: StringTable::new(),
@@ -83,8 +91,17 @@ pub fn read_module<
         em_symbol_map: compiler_config.symbol_map.clone(),
 
         custom_sections: HashMap::new(),
+
+        module_name: None,
+        function_names: HashMap::new(),
+        local_names: HashMap::new(),
     }));
 
+    // The name section comes after the code section, so decode it up front to
+    // make names available to middlewares. A malformed name section is not an
+    // error; names are debug information only.
+    let _ = info.write().unwrap().import_name_section(wasm);
+
     let mut parser = wasmparser::ValidatingParser::new(
         wasm,
         Some(validating_parser_config(&compiler_config.features)),
@@ -93,6 +110,8 @@ pub fn read_module<
     let mut namespace_builder = Some(StringTableBuilder::new());
     let mut name_builder = Some(StringTableBuilder::new());
     let mut func_count: usize = 0;
+    let mut elem_count: usize = 0;
+    let mut data_count: usize = 0;
     let mut mcg_info_fed = false;
 
     loop {
@@ -123,9 +142,8 @@ pub fn read_module<
                             .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
                     }
                     ImportSectionEntryType::Table(table_ty) => {
-                        assert_eq!(table_ty.element_type, WpType::AnyFunc);
                         let table_desc = TableDescriptor {
-                            element: ElementType::Anyfunc,
+                            element: wp_type_to_element_type(table_ty.element_type)?,
                             minimum: table_ty.limits.initial,
                             maximum: table_ty.limits.maximum,
                         };
@@ -164,7 +182,7 @@ pub fn read_module<
             }
             ParserState::TableSectionEntry(table_ty) => {
                 let table_desc = TableDescriptor {
-                    element: ElementType::Anyfunc,
+                    element: wp_type_to_element_type(table_ty.element_type)?,
                     minimum: table_ty.limits.initial,
                     maximum: table_ty.limits.maximum,
                 };
@@ -251,6 +269,7 @@ pub fn read_module<
                             }
                         }
                         ParserState::CodeOperator(op) => {
+                            check_operator_features(op, &compiler_config.features)?;
                             if !body_begun {
                                 body_begun = true;
                                 fcg.begin_body(&info.read().unwrap())
@@ -317,6 +336,34 @@ pub fn read_module<
                 };
 
                 info.write().unwrap().elem_initializers.push(table_init);
+                elem_count += 1;
+            }
+            ParserState::BeginPassiveElementSectionEntry(_ty) => {
+                let mut elements: Option<Vec<FuncIndex>> = None;
+
+                loop {
+                    let state = parser.read();
+                    match *state {
+                        ParserState::Error(err) => return Err(LoadError::Parse(err)),
+                        ParserState::ElementSectionEntryBody(ref _elements) => {
+                            elements = Some(
+                                _elements
+                                    .iter()
+                                    .cloned()
+                                    .map(|index| FuncIndex::new(index as usize))
+                                    .collect(),
+                            );
+                        }
+                        ParserState::EndElementSectionEntry => break,
+                        _ => unreachable!(),
+                    }
+                }
+
+                info.write()
+                    .unwrap()
+                    .passive_elements
+                    .insert(ElemIndex::new(elem_count), elements.unwrap());
+                elem_count += 1;
             }
             ParserState::BeginActiveDataSectionEntry(memory_index) => {
                 let memory_index = MemoryIndex::new(memory_index as usize);
@@ -348,6 +395,30 @@ pub fn read_module<
                     data,
                 };
                 info.write().unwrap().data_initializers.push(data_init);
+                data_count += 1;
+            }
+            ParserState::BeginPassiveDataSectionEntry => {
+                let mut data: Vec<u8> = vec![];
+
+                loop {
+                    let state = parser.read();
+                    match *state {
+                        ParserState::Error(err) => return Err(LoadError::Parse(err)),
+                        ParserState::DataSectionEntryBodyChunk(chunk) => {
+                            data.extend_from_slice(chunk);
+                        }
+                        ParserState::BeginDataSectionEntryBody(_)
+                        | ParserState::EndDataSectionEntryBody => {}
+                        ParserState::EndDataSectionEntry => break,
+                        _ => unreachable!(),
+                    }
+                }
+
+                info.write()
+                    .unwrap()
+                    .passive_data
+                    .insert(DataIndex::new(data_count), data);
+                data_count += 1;
             }
             ParserState::BeginGlobalSectionEntry(ty) => {
                 let init = loop {
@@ -365,6 +436,13 @@ pub fn read_module<
                     mutable: ty.mutable,
                     ty: wp_type_to_type(ty.content_type)?,
                 };
+                // `ref.null` evaluates to an `anyref`, which `funcref` globals hold as well.
+                let init = match init {
+                    Initializer::Const(Value::AnyRef(reference)) if desc.ty == Type::FuncRef => {
+                        Initializer::Const(Value::FuncRef(reference))
+                    }
+                    init => init,
+                };
 
                 let global_init = GlobalInit { desc, init };
 
@@ -398,10 +476,20 @@ pub fn wp_type_to_type(ty: WpType) -> Result<Type, BinaryReaderError> {
         WpType::F32 => Type::F32,
         WpType::F64 => Type::F64,
         WpType::V128 => Type::V128,
+        WpType::AnyRef => Type::AnyRef,
+        WpType::AnyFunc => Type::FuncRef,
         _ => panic!("broken invariant, invalid type"),
     })
 }
 
+fn wp_type_to_element_type(ty: WpType) -> Result<ElementType, BinaryReaderError> {
+    Ok(match ty {
+        WpType::AnyFunc => ElementType::Anyfunc,
+        WpType::AnyRef => ElementType::AnyRef,
+        _ => panic!("broken invariant, invalid element type"),
+    })
+}
+
 pub fn type_to_wp_type(ty: Type) -> WpType {
     match ty {
         Type::I32 => WpType::I32,
@@ -409,6 +497,8 @@ pub fn type_to_wp_type(ty: Type) -> WpType {
         Type::F32 => WpType::F32,
         Type::F64 => WpType::F64,
         Type::V128 => WpType::V128,
+        Type::AnyRef => WpType::AnyRef,
+        Type::FuncRef => WpType::AnyFunc,
     }
 }
 
@@ -447,6 +537,7 @@ fn eval_init_expr(op: &Operator) -> Result<Initializer, BinaryReaderError> {
         Operator::V128Const { value } => {
             Initializer::Const(Value::V128(u128::from_le_bytes(*value.bytes())))
         }
+        Operator::RefNull => Initializer::Const(Value::AnyRef(Reference::null())),
         _ => {
             return Err(BinaryReaderError {
                 message: "init expr evaluation failed: unsupported opcode",
@@ -1,16 +1,25 @@
+use crate::backend::Backend;
+use crate::cache::WasmHash;
+use crate::error::ImageError;
+use crate::module::ModuleInfo;
+use crate::structures::TypedIndex;
+use crate::types::FuncIndex;
+use crate::units::{WASM_MAX_PAGES, WASM_PAGE_SIZE};
+use blake2b_simd::blake2bp;
+use std::cmp;
 use std::collections::BTreeMap;
 use std::ops::Bound::{Included, Unbounded};
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct RegisterIndex(pub usize);
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum WasmAbstractValue {
     Runtime,
     Const(u64),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MachineState {
     pub stack_values: Vec<MachineValue>,
     pub register_values: Vec<MachineValue>,
@@ -21,7 +30,7 @@ pub struct MachineState {
     pub wasm_inst_offset: usize,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct MachineStateDiff {
     pub last: Option<usize>,
     pub stack_push: Vec<MachineValue>,
@@ -35,7 +44,7 @@ pub struct MachineStateDiff {
     pub wasm_inst_offset: usize, // absolute value; not a diff.
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum MachineValue {
     Undefined,
     Vmctx,
@@ -46,7 +55,7 @@ pub enum MachineValue {
     WasmLocal(usize),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FunctionStateMap {
     pub initial: MachineState,
     pub local_function_id: usize,
@@ -60,20 +69,20 @@ pub struct FunctionStateMap {
     pub trappable_offsets: BTreeMap<usize, OffsetInfo>, /* suspend_offset -> info */
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum SuspendOffset {
     Loop(usize),
     Call(usize),
     Trappable(usize),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct OffsetInfo {
     pub diff_id: usize,
     pub activate_offset: usize,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ModuleStateMap {
     pub local_functions: BTreeMap<usize, FunctionStateMap>,
     pub total_size: usize,
@@ -94,12 +103,59 @@ pub struct ExecutionStateImage {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InstanceImage {
+    /// The hash of the module the image was taken from.
+    pub wasm_hash: WasmHash,
+    /// The backend that compiled the module the image was taken from.
+    pub backend: Backend,
+    /// The middlewares the module was compiled with, as described by
+    /// `ModuleInfo::middleware_fingerprint`. Their internal fields are part of
+    /// the execution state, and they may change the code execution resumes in.
+    pub middleware_fingerprint: String,
+    /// Stored separately by `to_bytes`, which can compress it.
+    #[serde(skip)]
     pub memory: Option<Vec<u8>>,
     pub globals: Vec<u128>,
+    /// The elements of each local table, as the index of the function they
+    /// refer to.
+    pub tables: Vec<Vec<Option<FuncIndex>>>,
+    pub execution_state: ExecutionStateImage,
+}
+
+/// The changes made to an instance since an earlier snapshot of it, as taken
+/// by `Instance::snapshot_delta`.
+///
+/// Applying the deltas taken after an image to it, in the order they were
+/// taken, gives the image a full snapshot would have given.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceImageDelta {
+    pub wasm_hash: WasmHash,
+    pub backend: Backend,
+    pub middleware_fingerprint: String,
+    /// The size of the memory when the earlier snapshot was taken.
+    pub base_memory_size: usize,
+    /// The size of the memory when the delta was taken.
+    pub memory_size: usize,
+    /// The ranges of memory that may have changed, as their offset and
+    /// their contents.
+    pub memory: Vec<(usize, Vec<u8>)>,
+    pub globals: Vec<u128>,
+    pub tables: Vec<Vec<Option<FuncIndex>>>,
     pub execution_state: ExecutionStateImage,
 }
 
 impl ModuleStateMap {
+    /// Returns the id of the local function whose code contains `ip`.
+    pub fn lookup_local_function_id(&self, ip: usize, base: usize) -> Option<usize> {
+        if ip < base || ip - base >= self.total_size {
+            None
+        } else {
+            self.local_functions
+                .range((Unbounded, Included(&(ip - base))))
+                .last()
+                .map(|(_, fsm)| fsm.local_function_id)
+        }
+    }
+
     #[warn(dead_code)]
     fn lookup_call_ip(&self, ip: usize, base: usize) -> Option<(&FunctionStateMap, MachineState)> {
         if ip < base || ip - base >= self.total_size {
@@ -277,12 +333,12 @@ impl MachineStateDiff {
 }
 
 impl ExecutionStateImage {
-    pub fn print_backtrace_if_needed(&self) {
+    pub fn print_backtrace_if_needed(&self, info: &ModuleInfo) {
         use std::env;
 
         if let Ok(x) = env::var("WASMER_BACKTRACE") {
             if x == "1" {
-                eprintln!("{}", self.colored_output());
+                eprintln!("{}", self.colored_output_with_info(info));
                 return;
             }
         }
@@ -291,6 +347,16 @@ impl ExecutionStateImage {
     }
 
     pub fn colored_output(&self) -> String {
+        self.format_colored(None)
+    }
+
+    /// Like `colored_output`, but labels frames and locals with names from the
+    /// module's "name" section where available.
+    pub fn colored_output_with_info(&self, info: &ModuleInfo) -> String {
+        self.format_colored(Some(info))
+    }
+
+    fn format_colored(&self, info: Option<&ModuleInfo>) -> String {
         use colored::*;
 
         fn join_strings(x: impl Iterator<Item = String>, sep: &str) -> String {
@@ -309,7 +375,10 @@ impl ExecutionStateImage {
             ret
         }
 
-        fn format_optional_u64_sequence(x: &[Option<u64>]) -> String {
+        fn format_optional_u64_sequence(
+            x: &[Option<u64>],
+            name_of: impl Fn(usize) -> Option<String>,
+        ) -> String {
             if x.len() == 0 {
                 "(empty)".into()
             } else {
@@ -317,7 +386,7 @@ impl ExecutionStateImage {
                     x.iter().enumerate().map(|(i, x)| {
                         format!(
                             "[{}] = {}",
-                            i,
+                            name_of(i).unwrap_or_else(|| format!("{}", i)),
                             x.map(|x| format!("{}", x))
                                 .unwrap_or_else(|| "?".to_string())
                                 .bold()
@@ -338,7 +407,19 @@ impl ExecutionStateImage {
             ret += &"Backtrace:".bold();
             ret += "\n";
             for (i, f) in self.frames.iter().enumerate() {
-                ret += &format!("* Frame {} @ Local function {}", i, f.local_function_id).bold();
+                let func_index = info.map(|info| {
+                    FuncIndex::new(f.local_function_id + info.imported_functions.len())
+                });
+                let func_name =
+                    info.and_then(|info| info.function_name(func_index.unwrap()));
+                ret += &match func_name {
+                    Some(name) => format!(
+                        "* Frame {} @ {} (local function {})",
+                        i, name, f.local_function_id
+                    ),
+                    None => format!("* Frame {} @ Local function {}", i, f.local_function_id),
+                }
+                .bold();
                 ret += "\n";
                 ret += &format!(
                     "  {} {}\n",
@@ -348,12 +429,17 @@ impl ExecutionStateImage {
                 ret += &format!(
                     "  {} {}\n",
                     "Locals:".bold().yellow(),
-                    format_optional_u64_sequence(&f.locals)
+                    format_optional_u64_sequence(&f.locals, |local| {
+                        info.and_then(|info| {
+                            info.local_name(func_index.unwrap(), local as u32)
+                                .map(|x| x.to_string())
+                        })
+                    })
                 );
                 ret += &format!(
                     "  {} {}\n\n",
                     "Stack:".bold().yellow(),
-                    format_optional_u64_sequence(&f.stack)
+                    format_optional_u64_sequence(&f.stack, |_| None)
                 );
             }
         }
@@ -362,30 +448,279 @@ impl ExecutionStateImage {
     }
 }
 
+/// The version of the file format written by `InstanceImage::to_bytes` and
+/// `InstanceImageDelta::to_bytes`.
+pub const IMAGE_FORMAT_VERSION: u32 = 2;
+static IMAGE_MAGIC: [u8; 8] = *b"WASMERIM";
+static DELTA_MAGIC: [u8; 8] = *b"WASMERID";
+/// The magic, the format version and the checksum of the rest of the file.
+const IMAGE_HEADER_SIZE: usize = 8 + 4 + 64;
+const ZERO_CHUNK_SIZE: usize = 4096;
+/// The largest memory an image can hold, which is the most a wasm memory can
+/// grow to.
+const MAX_MEMORY_SIZE: u64 = (WASM_PAGE_SIZE * WASM_MAX_PAGES) as u64;
+
+/// How the memory of an instance is stored in an image file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryCompression {
+    /// The memory is stored as is.
+    None,
+    /// Only the 4 KiB chunks of memory that aren't all zeroes are stored,
+    /// which keeps mostly empty memories small.
+    ZeroChunks,
+}
+
 impl InstanceImage {
-    pub fn from_bytes(input: &[u8]) -> Option<InstanceImage> {
-        use bincode::deserialize;
-        match deserialize(input) {
-            Ok(x) => Some(x),
-            Err(_) => None,
+    /// Decodes an image file written by `to_bytes`, checking its format
+    /// version and checksum.
+    pub fn from_bytes(input: &[u8]) -> Result<InstanceImage, ImageError> {
+        let body = read_image_file(&IMAGE_MAGIC, input)?;
+        let (compression, memory, mut image): (MemoryCompression, Option<Vec<u8>>, InstanceImage) =
+            bincode::deserialize(body).map_err(|e| ImageError::Malformed(e.to_string()))?;
+        image.memory = match memory {
+            Some(memory) => Some(decompress_memory(memory, compression)?),
+            None => None,
+        };
+        Ok(image)
+    }
+
+    /// Encodes the image as an image file, storing its memory with `compression`.
+    pub fn to_bytes(&self, compression: MemoryCompression) -> Result<Vec<u8>, ImageError> {
+        let memory = self
+            .memory
+            .as_ref()
+            .map(|memory| compress_memory(memory, compression));
+        let body = bincode::serialize(&(compression, memory, self))
+            .map_err(|e| ImageError::Encoding(e.to_string()))?;
+        Ok(write_image_file(&IMAGE_MAGIC, &body))
+    }
+
+    /// Checks that the image was taken from an instance of the module
+    /// described by `info`.
+    pub fn check_module(&self, info: &ModuleInfo) -> Result<(), ImageError> {
+        if self.wasm_hash != info.wasm_hash {
+            Err(ImageError::WrongModule {
+                expected: info.wasm_hash,
+                found: self.wasm_hash,
+            })
+        } else if self.backend != info.backend {
+            Err(ImageError::WrongBackend {
+                expected: info.backend,
+                found: self.backend,
+            })
+        } else if self.middleware_fingerprint != info.middleware_fingerprint {
+            Err(ImageError::WrongMiddlewares {
+                expected: info.middleware_fingerprint.clone(),
+                found: self.middleware_fingerprint.clone(),
+            })
+        } else {
+            Ok(())
         }
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        use bincode::serialize;
-        serialize(self).unwrap()
+    /// Brings the image up to date with `delta`, which must have been taken
+    /// right after the image, or after the last delta applied to it.
+    ///
+    /// The image is left untouched if `delta` doesn't apply to it.
+    pub fn apply_delta(&mut self, delta: InstanceImageDelta) -> Result<(), ImageError> {
+        if delta.wasm_hash != self.wasm_hash {
+            return Err(ImageError::WrongModule {
+                expected: self.wasm_hash,
+                found: delta.wasm_hash,
+            });
+        }
+        if delta.backend != self.backend {
+            return Err(ImageError::WrongBackend {
+                expected: self.backend,
+                found: delta.backend,
+            });
+        }
+        if delta.middleware_fingerprint != self.middleware_fingerprint {
+            return Err(ImageError::WrongMiddlewares {
+                expected: self.middleware_fingerprint.clone(),
+                found: delta.middleware_fingerprint,
+            });
+        }
+
+        let memory_size = self.memory.as_ref().map_or(0, Vec::len);
+        if delta.base_memory_size != memory_size {
+            return Err(ImageError::Mismatch(format!(
+                "the delta applies to {} bytes of memory, but the image has {}",
+                delta.base_memory_size, memory_size
+            )));
+        }
+        if delta.memory_size < memory_size
+            || delta.memory_size as u64 > MAX_MEMORY_SIZE
+            || (self.memory.is_none() && delta.memory_size > 0)
+        {
+            return Err(ImageError::Mismatch(format!(
+                "the memory of the image can't grow to {} bytes",
+                delta.memory_size
+            )));
+        }
+        if let Some(&(offset, _)) = delta.memory.iter().find(|&&(offset, ref bytes)| {
+            offset
+                .checked_add(bytes.len())
+                .map_or(true, |end| end > delta.memory_size)
+        }) {
+            return Err(ImageError::Malformed(format!(
+                "the memory changed at offset {} is out of bounds",
+                offset
+            )));
+        }
+
+        if let Some(ref mut memory) = self.memory {
+            memory.resize(delta.memory_size, 0);
+            for (offset, bytes) in delta.memory {
+                memory[offset..offset + bytes.len()].copy_from_slice(&bytes);
+            }
+        }
+        self.globals = delta.globals;
+        self.tables = delta.tables;
+        self.execution_state = delta.execution_state;
+        Ok(())
     }
 }
 
+impl InstanceImageDelta {
+    /// Decodes a delta file written by `to_bytes`, checking its format
+    /// version and checksum.
+    pub fn from_bytes(input: &[u8]) -> Result<InstanceImageDelta, ImageError> {
+        let body = read_image_file(&DELTA_MAGIC, input)?;
+        bincode::deserialize(body).map_err(|e| ImageError::Malformed(e.to_string()))
+    }
+
+    /// Encodes the delta as a delta file.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ImageError> {
+        let body = bincode::serialize(self).map_err(|e| ImageError::Encoding(e.to_string()))?;
+        Ok(write_image_file(&DELTA_MAGIC, &body))
+    }
+}
+
+/// Returns the body of the file `input`, after checking its header.
+fn read_image_file<'a>(magic: &[u8; 8], input: &'a [u8]) -> Result<&'a [u8], ImageError> {
+    if input.len() < magic.len() || input[..magic.len()] != magic[..] {
+        return Err(ImageError::InvalidMagic);
+    }
+    if input.len() < IMAGE_HEADER_SIZE {
+        return Err(ImageError::Malformed("the header is truncated".to_string()));
+    }
+    let mut version = [0u8; 4];
+    version.copy_from_slice(&input[8..12]);
+    let version = u32::from_le_bytes(version);
+    if version != IMAGE_FORMAT_VERSION {
+        return Err(ImageError::UnsupportedVersion(version));
+    }
+    let body = &input[IMAGE_HEADER_SIZE..];
+    if input[12..IMAGE_HEADER_SIZE] != checksum(body)[..] {
+        return Err(ImageError::ChecksumMismatch);
+    }
+    Ok(body)
+}
+
+fn write_image_file(magic: &[u8; 8], body: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(IMAGE_HEADER_SIZE + body.len());
+    bytes.extend_from_slice(magic);
+    bytes.extend_from_slice(&IMAGE_FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&checksum(body));
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+fn checksum(bytes: &[u8]) -> [u8; 64] {
+    let mut state = blake2bp::State::new();
+    state.update(bytes);
+
+    let mut checksum = [0u8; 64];
+    checksum.copy_from_slice(state.finalize().as_bytes());
+    checksum
+}
+
+fn compress_memory(memory: &[u8], compression: MemoryCompression) -> Vec<u8> {
+    match compression {
+        MemoryCompression::None => memory.to_vec(),
+        MemoryCompression::ZeroChunks => {
+            // The size of the memory, followed by the index and the contents of
+            // each chunk that isn't all zeroes.
+            let mut compressed = (memory.len() as u64).to_le_bytes().to_vec();
+            for (i, chunk) in memory.chunks(ZERO_CHUNK_SIZE).enumerate() {
+                if chunk.iter().any(|&byte| byte != 0) {
+                    compressed.extend_from_slice(&(i as u64).to_le_bytes());
+                    compressed.extend_from_slice(chunk);
+                }
+            }
+            compressed
+        }
+    }
+}
+
+fn decompress_memory(
+    compressed: Vec<u8>,
+    compression: MemoryCompression,
+) -> Result<Vec<u8>, ImageError> {
+    let truncated = || ImageError::Malformed("the memory is truncated".to_string());
+
+    match compression {
+        MemoryCompression::None if compressed.len() as u64 > MAX_MEMORY_SIZE => {
+            Err(ImageError::Malformed(format!(
+                "the memory size {} is larger than the largest wasm memory",
+                compressed.len()
+            )))
+        }
+        MemoryCompression::None => Ok(compressed),
+        MemoryCompression::ZeroChunks => {
+            let (len, mut rest) = read_u64(&compressed).ok_or_else(truncated)?;
+            // The size is checked before allocating, since it comes from the file.
+            if len > MAX_MEMORY_SIZE {
+                return Err(ImageError::Malformed(format!(
+                    "the memory size {} is larger than the largest wasm memory",
+                    len
+                )));
+            }
+            let mut memory = vec![0; len as usize];
+            while !rest.is_empty() {
+                let (index, chunk) = read_u64(rest).ok_or_else(truncated)?;
+                let start = (index as usize)
+                    .checked_mul(ZERO_CHUNK_SIZE)
+                    .filter(|&start| start < memory.len())
+                    .ok_or_else(|| {
+                        ImageError::Malformed(format!("memory chunk {} is out of bounds", index))
+                    })?;
+                let end = cmp::min(start + ZERO_CHUNK_SIZE, memory.len());
+                if chunk.len() < end - start {
+                    return Err(truncated());
+                }
+                memory[start..end].copy_from_slice(&chunk[..end - start]);
+                rest = &chunk[end - start..];
+            }
+            Ok(memory)
+        }
+    }
+}
+
+/// Splits a little-endian `u64` off the front of `bytes`.
+fn read_u64(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    if bytes.len() < 8 {
+        return None;
+    }
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[..8]);
+    Some((u64::from_le_bytes(value), &bytes[8..]))
+}
+
 #[cfg(all(unix, target_arch = "x86_64"))]
 pub mod x64 {
     use super::*;
+    use crate::backing::func_index_to_anyfunc;
     use crate::codegen::BreakpointMap;
-    use crate::fault::{catch_unsafe_unwind, run_on_alternative_stack};
+    use crate::error::ImageError;
+    use crate::fault::{catch_unsafe_unwind, run_on_alternative_stack, DirtyPageTracker};
     use crate::structures::TypedIndex;
-    use crate::types::LocalGlobalIndex;
-    use crate::vm::Ctx;
+    use crate::types::{LocalGlobalIndex, LocalTableIndex, Type};
+    use crate::units::WASM_PAGE_SIZE;
+    use crate::vm::{self, Ctx};
     use std::any::Any;
+    use std::collections::HashMap;
 
     pub fn new_machine_state() -> MachineState {
         MachineState {
@@ -606,6 +941,24 @@ pub mod x64 {
         stack[stack_offset] =
             known_registers[X64Register::XMM(XMM::XMM0).to_index().0].unwrap_or(0);
 
+        load_memory_and_globals(vmctx, &image);
+
+        drop(image); // free up host memory
+
+        catch_unsafe_unwind(
+            || {
+                run_on_alternative_stack(
+                    stack.as_mut_ptr().offset(stack.len() as isize),
+                    stack.as_mut_ptr().offset(stack_offset as isize),
+                )
+            },
+            breakpoints,
+        )
+    }
+
+    /// Copies the memory and the local globals of `image` into the instance
+    /// owning `vmctx`.
+    pub(crate) unsafe fn load_memory_and_globals(vmctx: &mut Ctx, image: &InstanceImage) {
         if let Some(ref memory) = image.memory {
             assert!(vmctx.internal.memory_bound <= memory.len());
 
@@ -632,24 +985,212 @@ pub mod x64 {
             (*(*vmctx.local_backing).globals[LocalGlobalIndex::new(i)].vm_local_global()).data =
                 image.globals[i];
         }
+    }
 
-        drop(image); // free up host memory
+    /// Fills the local tables of the instance owning `vmctx` with the functions
+    /// listed in `tables`, growing them as needed.
+    pub(crate) unsafe fn load_tables(
+        vmctx: &mut Ctx,
+        tables: &[Vec<Option<FuncIndex>>],
+    ) -> Result<(), ImageError> {
+        let module = &*vmctx.module;
+        let imports = &*vmctx.import_backing;
+        let vmctx_ptr = vmctx as *mut Ctx;
+
+        for (i, elements) in tables.iter().enumerate() {
+            let table = &(*vmctx.local_backing).tables[LocalTableIndex::new(i)];
+            let size = table.size() as usize;
+            if elements.len() > size {
+                table
+                    .grow((elements.len() - size) as u32)
+                    .map_err(|e| ImageError::Mismatch(e.to_string()))?;
+            }
+            table.anyfunc_direct_access_mut(|anyfuncs| {
+                for (anyfunc, element) in anyfuncs.iter_mut().zip(elements) {
+                    *anyfunc = match *element {
+                        Some(func_index) => {
+                            func_index_to_anyfunc(module, imports, vmctx_ptr, func_index)
+                        }
+                        None => vm::Anyfunc::null(),
+                    };
+                }
+            });
+        }
+        Ok(())
+    }
 
-        catch_unsafe_unwind(
-            || {
-                run_on_alternative_stack(
-                    stack.as_mut_ptr().offset(stack.len() as isize),
-                    stack.as_mut_ptr().offset(stack_offset as isize),
-                )
-            },
-            breakpoints,
-        )
+    /// Describes the local tables of the instance owning `vmctx` by the index of
+    /// the function each element refers to.
+    unsafe fn read_tables(vmctx: &Ctx) -> Result<Vec<Vec<Option<FuncIndex>>>, ImageError> {
+        let module = &*vmctx.module;
+        let imports = &*vmctx.import_backing;
+        let vmctx_ptr = vmctx as *const Ctx as *mut Ctx;
+
+        let functions: HashMap<(usize, usize), FuncIndex> = (0..module.info.func_assoc.len())
+            .map(|i| {
+                let func_index = FuncIndex::new(i);
+                let anyfunc = func_index_to_anyfunc(module, imports, vmctx_ptr, func_index);
+                ((anyfunc.func as usize, anyfunc.ctx as usize), func_index)
+            })
+            .collect();
+
+        (*vmctx.local_backing)
+            .tables
+            .iter()
+            .map(|(local_table_index, table)| {
+                table.anyfunc_direct_access_mut(|anyfuncs| {
+                    anyfuncs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, anyfunc)| {
+                            if anyfunc.func.is_null() {
+                                return Ok(None);
+                            }
+                            functions
+                                .get(&(anyfunc.func as usize, anyfunc.ctx as usize))
+                                .map(|&func_index| Some(func_index))
+                                .ok_or_else(|| ImageError::ForeignTableElement {
+                                    table: local_table_index.convert_up(&module.info),
+                                    index: i as u32,
+                                })
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                })
+            })
+            .collect()
+    }
+
+    /// Checks that `image` can be loaded into the instance owning `vmctx`, so
+    /// that loading it can't fail halfway.
+    pub(crate) unsafe fn check_instance_image(
+        msm: Option<&ModuleStateMap>,
+        vmctx: &Ctx,
+        image: &InstanceImage,
+    ) -> Result<(), ImageError> {
+        let info = &(*vmctx.module).info;
+        let mismatch = |msg: String| Err(ImageError::Mismatch(msg));
+
+        image.check_module(info)?;
+        check_globals(info)?;
+
+        match (&image.memory, vmctx.internal.memory_base.is_null()) {
+            (Some(memory), false) => {
+                if memory.len() % WASM_PAGE_SIZE != 0 {
+                    return mismatch(format!(
+                        "the memory size {} isn't a multiple of the page size",
+                        memory.len()
+                    ));
+                }
+                if memory.len() < vmctx.internal.memory_bound {
+                    return mismatch(format!(
+                        "the image has {} bytes of memory, but the instance already has {}",
+                        memory.len(),
+                        vmctx.internal.memory_bound
+                    ));
+                }
+                if let Some(maximum) = vmctx.memory(0).descriptor().maximum {
+                    if memory.len() > maximum.bytes().0 {
+                        return mismatch(format!(
+                            "the image has {} bytes of memory, but the instance allows at most {}",
+                            memory.len(),
+                            maximum.bytes().0
+                        ));
+                    }
+                }
+            }
+            (None, true) => {}
+            (Some(_), true) => return mismatch("the instance has no memory".to_string()),
+            (None, false) => return mismatch("the image has no memory".to_string()),
+        }
+
+        if image.globals.len() != info.globals.len() {
+            return mismatch(format!(
+                "the image has {} globals, but the instance has {}",
+                image.globals.len(),
+                info.globals.len()
+            ));
+        }
+
+        let local_tables = &(*vmctx.local_backing).tables;
+        if image.tables.len() != local_tables.len() {
+            return mismatch(format!(
+                "the image has {} tables, but the instance has {}",
+                image.tables.len(),
+                local_tables.len()
+            ));
+        }
+        for ((_, table), elements) in local_tables.iter().zip(&image.tables) {
+            let descriptor = table.descriptor();
+            if elements.len() < table.size() as usize
+                || descriptor
+                    .maximum
+                    .map_or(false, |maximum| elements.len() > maximum as usize)
+            {
+                return mismatch(format!(
+                    "a table of the image has {} elements, which the instance can't hold",
+                    elements.len()
+                ));
+            }
+            if let Some(func_index) = elements
+                .iter()
+                .filter_map(|&element| element)
+                .find(|func_index| func_index.index() >= info.func_assoc.len())
+            {
+                return mismatch(format!(
+                    "function {} doesn't exist in the instance",
+                    func_index.index()
+                ));
+            }
+        }
+
+        let frames = &image.execution_state.frames;
+        if frames.is_empty() {
+            return Ok(());
+        }
+        let msm = match msm {
+            Some(msm) => msm,
+            None => return Err(ImageError::NoExecutionState),
+        };
+        let local_functions: Vec<&FunctionStateMap> =
+            msm.local_functions.iter().map(|(_, v)| v).collect();
+        for f in frames {
+            let fsm = match local_functions.get(f.local_function_id) {
+                Some(fsm) => fsm,
+                None => {
+                    return mismatch(format!(
+                        "local function {} doesn't exist in the instance",
+                        f.local_function_id
+                    ))
+                }
+            };
+            let is_critical_point = if f.wasm_inst_offset == ::std::usize::MAX {
+                fsm.wasm_function_header_target_offset.is_some()
+            } else {
+                fsm.wasm_offset_to_target_offset
+                    .contains_key(&f.wasm_inst_offset)
+            };
+            if !is_critical_point {
+                return mismatch(format!(
+                    "offset {} of local function {} isn't a point execution can resume from",
+                    f.wasm_inst_offset, f.local_function_id
+                ));
+            }
+            if f.locals.len() != fsm.locals.len()
+                || f.stack.iter().chain(&f.locals).any(Option::is_none)
+            {
+                return mismatch(format!(
+                    "the frame of local function {} is incomplete",
+                    f.local_function_id
+                ));
+            }
+        }
+        Ok(())
     }
 
     pub fn build_instance_image(
-        vmctx: &mut Ctx,
+        vmctx: &Ctx,
         execution_state: ExecutionStateImage,
-    ) -> InstanceImage {
+    ) -> Result<InstanceImage, ImageError> {
         unsafe {
             let memory = if vmctx.internal.memory_base.is_null() {
                 None
@@ -663,21 +1204,95 @@ pub mod x64 {
                 )
             };
 
-            // FIXME: Imported globals
-            let globals_len = (*vmctx.module).info.globals.len();
-            let globals: Vec<u128> = (0..globals_len)
-                .map(|i| {
-                    (*vmctx.local_backing).globals[LocalGlobalIndex::new(i)]
-                        .get()
-                        .to_u128()
-                })
-                .collect();
-
-            InstanceImage {
+            Ok(InstanceImage {
+                wasm_hash: (*vmctx.module).info.wasm_hash,
+                backend: (*vmctx.module).info.backend,
+                middleware_fingerprint: (*vmctx.module).info.middleware_fingerprint.clone(),
                 memory: memory,
-                globals: globals,
+                globals: read_globals(vmctx)?,
+                tables: read_tables(vmctx)?,
                 execution_state: execution_state,
-            }
+            })
+        }
+    }
+
+    /// Captures the changes made to the instance owning `vmctx` since `tracker`
+    /// last started tracking its memory, and starts tracking it anew.
+    pub(crate) unsafe fn build_instance_image_delta(
+        vmctx: &Ctx,
+        tracker: &DirtyPageTracker,
+        execution_state: ExecutionStateImage,
+    ) -> Result<InstanceImageDelta, ImageError> {
+        // Done first, since they can fail.
+        let globals = read_globals(vmctx)?;
+        let tables = read_tables(vmctx)?;
+
+        let memory_size = memory_size(vmctx);
+        let (base_memory_size, ranges) = tracker
+            .take(vmctx.internal.memory_base, memory_size)
+            .ok_or(ImageError::NoBaseImage)?;
+        let memory: &[u8] = if memory_size == 0 {
+            &[]
+        } else {
+            ::std::slice::from_raw_parts(vmctx.internal.memory_base, memory_size)
+        };
+
+        Ok(InstanceImageDelta {
+            wasm_hash: (*vmctx.module).info.wasm_hash,
+            backend: (*vmctx.module).info.backend,
+            middleware_fingerprint: (*vmctx.module).info.middleware_fingerprint.clone(),
+            base_memory_size,
+            memory_size,
+            memory: ranges
+                .into_iter()
+                .map(|range| (range.start, memory[range].to_vec()))
+                .collect(),
+            globals,
+            tables,
+            execution_state,
+        })
+    }
+
+    /// The size of the first memory of the instance owning `vmctx`, or 0 if it
+    /// has none.
+    pub(crate) fn memory_size(vmctx: &Ctx) -> usize {
+        if vmctx.internal.memory_base.is_null() {
+            0
+        } else {
+            vmctx.internal.memory_bound
+        }
+    }
+
+    unsafe fn read_globals(vmctx: &Ctx) -> Result<Vec<u128>, ImageError> {
+        // FIXME: Imported globals
+        let info = &(*vmctx.module).info;
+        check_globals(info)?;
+        Ok((0..info.globals.len())
+            .map(|i| {
+                (*vmctx.local_backing).globals[LocalGlobalIndex::new(i)]
+                    .get()
+                    .to_u128()
+            })
+            .collect())
+    }
+
+    /// Checks that none of the local globals holds a reference.
+    ///
+    /// References are ids that only mean something in the running process, so
+    /// they can't be written to an image, and loading raw bits into such a
+    /// global would forge a reference.
+    fn check_globals(info: &ModuleInfo) -> Result<(), ImageError> {
+        match info
+            .globals
+            .iter()
+            .find(|(_, global)| match global.desc.ty {
+                Type::AnyRef | Type::FuncRef => true,
+                _ => false,
+            }) {
+            Some((local_global_index, _)) => Err(ImageError::ReferenceGlobal(
+                local_global_index.convert_up(info),
+            )),
+            None => Ok(()),
         }
     }
 
@@ -859,3 +1474,30 @@ pub mod x64 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_chunks_round_trip() {
+        let mut memory = vec![0; 3 * ZERO_CHUNK_SIZE + 10];
+        memory[ZERO_CHUNK_SIZE + 1] = 1;
+        memory[3 * ZERO_CHUNK_SIZE + 9] = 2;
+        let compressed = compress_memory(&memory, MemoryCompression::ZeroChunks);
+        assert_eq!(compressed.len(), 8 + 2 * 8 + ZERO_CHUNK_SIZE + 10);
+        assert_eq!(
+            decompress_memory(compressed, MemoryCompression::ZeroChunks),
+            Ok(memory)
+        );
+    }
+
+    #[test]
+    fn oversized_memory_is_rejected() {
+        let compressed = (MAX_MEMORY_SIZE + 1).to_le_bytes().to_vec();
+        match decompress_memory(compressed, MemoryCompression::ZeroChunks) {
+            Err(ImageError::Malformed(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -3,6 +3,7 @@ use crate::{
     instance::DynFunc,
     sig_registry::SigRegistry,
     structures::TypedIndex,
+    typed_func::{Func, Host, WasmTypeList},
     types::{FuncSig, TableDescriptor},
     vm,
 };
@@ -43,6 +44,21 @@ impl<'a> From<DynFunc<'a>> for Anyfunc<'a> {
     }
 }
 
+impl<'a, Args, Rets> From<Func<'a, Args, Rets, Host>> for Anyfunc<'a>
+where
+    Args: WasmTypeList,
+    Rets: WasmTypeList,
+{
+    fn from(function: Func<'a, Args, Rets, Host>) -> Self {
+        unsafe {
+            Anyfunc::new(
+                function.get_vm_func().as_ptr(),
+                FuncSig::new(function.params(), function.returns()),
+            )
+        }
+    }
+}
+
 pub struct AnyfuncTable {
     backing: Vec<vm::Anyfunc>,
     max: Option<u32>,
@@ -97,30 +113,34 @@ impl AnyfuncTable {
     }
 
     pub fn set(&mut self, index: u32, element: Anyfunc) -> Result<(), ()> {
-        if let Some(slot) = self.backing.get_mut(index as usize) {
-            let anyfunc = match element.inner {
-                AnyfuncInner::Host { ptr, signature } => {
-                    let sig_index = SigRegistry.lookup_sig_index(signature);
-                    let sig_id = vm::SigId(sig_index.index() as u32);
-
-                    vm::Anyfunc {
-                        func: ptr,
-                        ctx: ptr::null_mut(),
-                        sig_id,
-                    }
+        let anyfunc = match element.inner {
+            AnyfuncInner::Host { ptr, signature } => {
+                let sig_index = SigRegistry.lookup_sig_index(signature);
+                let sig_id = vm::SigId(sig_index.index() as u32);
+
+                vm::Anyfunc {
+                    func: ptr,
+                    ctx: ptr::null_mut(),
+                    sig_id,
                 }
-                AnyfuncInner::Managed(ref func) => {
-                    let sig_index = SigRegistry.lookup_sig_index(Arc::clone(&func.signature));
-                    let sig_id = vm::SigId(sig_index.index() as u32);
-
-                    vm::Anyfunc {
-                        func: func.raw(),
-                        ctx: func.instance_inner.vmctx,
-                        sig_id,
-                    }
+            }
+            AnyfuncInner::Managed(ref func) => {
+                let sig_index = SigRegistry.lookup_sig_index(Arc::clone(&func.signature));
+                let sig_id = vm::SigId(sig_index.index() as u32);
+
+                vm::Anyfunc {
+                    func: func.raw(),
+                    ctx: func.instance_inner.vmctx,
+                    sig_id,
                 }
-            };
+            }
+        };
 
+        self.set_raw(index, anyfunc)
+    }
+
+    pub fn set_raw(&mut self, index: u32, anyfunc: vm::Anyfunc) -> Result<(), ()> {
+        if let Some(slot) = self.backing.get_mut(index as usize) {
             *slot = anyfunc;
 
             Ok(())
@@ -2,24 +2,31 @@ use crate::{
     error::CreationError,
     export::Export,
     import::IsExport,
-    types::{ElementType, TableDescriptor},
+    types::{ElementType, TableDescriptor, Value},
     vm,
 };
 use std::{cell::RefCell, fmt, ptr, rc::Rc};
 
 mod anyfunc;
+mod reference;
 
 pub use self::anyfunc::Anyfunc;
 use self::anyfunc::AnyfuncTable;
+pub(crate) use self::reference::release_references;
+pub use self::reference::Reference;
 use crate::error::GrowError;
 
 pub enum Element<'a> {
     Anyfunc(Anyfunc<'a>),
+    Reference(Reference),
 }
 
 // #[derive(Debug)]
 pub enum TableStorage {
     /// This is intended to be a caller-checked Anyfunc.
+    ///
+    /// `anyref` tables use it as well, since the only references
+    /// there are besides null are functions.
     Anyfunc(Box<AnyfuncTable>),
 }
 
@@ -66,7 +73,9 @@ impl Table {
         };
 
         let storage = match desc.element {
-            ElementType::Anyfunc => TableStorage::Anyfunc(AnyfuncTable::new(desc, &mut local)?),
+            ElementType::Anyfunc | ElementType::AnyRef => {
+                TableStorage::Anyfunc(AnyfuncTable::new(desc, &mut local)?)
+            }
         };
 
         Ok(Self {
@@ -80,15 +89,34 @@ impl Table {
         self.desc
     }
 
+    /// Get the element at index, as a value of the table's element type.
+    ///
+    /// Returns `None` if the index is out of bounds.
+    pub fn get(&self, index: u32) -> Option<Value> {
+        let reference = match &mut *self.storage.borrow_mut() {
+            (TableStorage::Anyfunc(ref mut anyfunc_table), _) => anyfunc_table
+                .internal_buffer()
+                .get(index as usize)
+                .map(|&anyfunc| Reference::from_anyfunc(anyfunc))?,
+        };
+        Some(match self.desc.element {
+            ElementType::Anyfunc => Value::FuncRef(reference),
+            ElementType::AnyRef => Value::AnyRef(reference),
+        })
+    }
+
     /// Set the element at index.
+    ///
+    /// Fails if the index is out of bounds, or if the element is a dead
+    /// reference, i.e. to a function of an instance that was dropped.
     pub fn set(&self, index: u32, element: Element) -> Result<(), ()> {
         match &mut *self.storage.borrow_mut() {
-            (TableStorage::Anyfunc(ref mut anyfunc_table), _) => {
-                match element {
-                    Element::Anyfunc(anyfunc) => anyfunc_table.set(index, anyfunc),
-                    // _ => panic!("wrong element type for anyfunc table"),
+            (TableStorage::Anyfunc(ref mut anyfunc_table), _) => match element {
+                Element::Anyfunc(anyfunc) => anyfunc_table.set(index, anyfunc),
+                Element::Reference(reference) => {
+                    anyfunc_table.set_raw(index, reference.to_anyfunc().ok_or(())?)
                 }
-            }
+            },
         }
     }
 
@@ -126,6 +154,13 @@ impl Table {
     }
 }
 
+/// Grows the table behind `local` by `delta` null elements, returning its
+/// previous size.
+pub(crate) unsafe fn grow_local(local: &mut vm::LocalTable, delta: u32) -> Option<u32> {
+    let table = &mut *(local.table as *mut AnyfuncTable);
+    table.grow(delta, local)
+}
+
 impl IsExport for Table {
     fn to_export(&self) -> Export {
         Export::Table(self.clone())
@@ -153,7 +188,8 @@ impl fmt::Debug for Table {
 #[cfg(test)]
 mod table_tests {
 
-    use super::{ElementType, Table, TableDescriptor};
+    use super::{Element, ElementType, Reference, Table, TableDescriptor};
+    use crate::{types::Value, vm, Func};
 
     #[test]
     fn test_initial_table_size() {
@@ -166,4 +202,35 @@ mod table_tests {
         assert_eq!(table.size(), 10);
     }
 
+    #[test]
+    fn test_table_get_set() {
+        fn double(_ctx: &mut vm::Ctx, x: i32) -> i32 {
+            x * 2
+        }
+
+        let table = Table::new(TableDescriptor {
+            element: ElementType::AnyRef,
+            minimum: 2,
+            maximum: None,
+        })
+        .unwrap();
+        assert_eq!(table.get(0), Some(Value::AnyRef(Reference::null())));
+        assert_eq!(table.get(2), None);
+
+        table
+            .set(0, Element::Anyfunc(Func::new(double).into()))
+            .unwrap();
+        let reference = match table.get(0) {
+            Some(Value::AnyRef(reference)) => reference,
+            other => panic!("unexpected element: {:?}", other),
+        };
+        assert!(!reference.is_null());
+
+        table.set(1, Element::Reference(reference)).unwrap();
+        assert_eq!(table.get(1), Some(Value::AnyRef(reference)));
+        table.set(0, Element::Reference(Reference::null())).unwrap();
+        assert_eq!(table.get(0), Some(Value::AnyRef(Reference::null())));
+        assert!(table.set(2, Element::Reference(reference)).is_err());
+    }
+
 }
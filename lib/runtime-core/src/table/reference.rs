@@ -0,0 +1,140 @@
+//! Reference values, as held by `anyref` and `funcref` locals, globals and
+//! table elements.
+//!
+//! Tables store their elements inline, so a reference can't point into one.
+//! Instead, every function that a reference is taken to gets a record of its
+//! own, identified by a number that is never reused. The record lives until
+//! the instance owning its context is dropped, after which references to it
+//! are dead: they can still be passed around, but resolving one fails.
+
+use crate::vm;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+/// The function records referenced by `Reference`s.
+struct Records {
+    /// The id of the record of each function, keyed by the function, context
+    /// and signature it holds.
+    ids: HashMap<(usize, usize, u32), u64>,
+    /// The function held by each live record.
+    functions: HashMap<u64, vm::Anyfunc>,
+    /// The last id handed out. Zero stands for the null reference.
+    last_id: u64,
+}
+
+// The pointers held by the records are only dereferenced by compiled code.
+unsafe impl Send for Records {}
+
+lazy_static! {
+    static ref RECORDS: Mutex<Records> = Mutex::new(Records {
+        ids: HashMap::new(),
+        functions: HashMap::new(),
+        last_id: 0,
+    });
+}
+
+/// A reference value: either null or a function.
+///
+/// A reference to a function is dead once the instance that the function
+/// belongs to is dropped. Storing a dead reference in a table fails, and
+/// WebAssembly code that tries to use one traps with
+/// `WasmTrapInfo::DeadReference`. Only null references can be serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Reference(u64);
+
+impl Reference {
+    /// The null reference.
+    pub fn null() -> Self {
+        Reference(0)
+    }
+
+    pub fn is_null(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Creates a reference to the function held by a table element.
+    pub(crate) fn from_anyfunc(anyfunc: vm::Anyfunc) -> Self {
+        if anyfunc.func.is_null() {
+            return Reference::null();
+        }
+
+        let key = (
+            anyfunc.func as usize,
+            anyfunc.ctx as usize,
+            anyfunc.sig_id.0,
+        );
+        let mut records = RECORDS.lock();
+        if let Some(&id) = records.ids.get(&key) {
+            return Reference(id);
+        }
+        records.last_id += 1;
+        let id = records.last_id;
+        records.ids.insert(key, id);
+        records.functions.insert(id, anyfunc);
+        Reference(id)
+    }
+
+    /// Returns the table element this reference stands for, or `None` if the
+    /// reference is dead.
+    pub(crate) fn to_anyfunc(self) -> Option<vm::Anyfunc> {
+        if self.is_null() {
+            Some(vm::Anyfunc::null())
+        } else {
+            RECORDS.lock().functions.get(&self.0).cloned()
+        }
+    }
+
+    /// Converts the representation used by compiled code back to a reference.
+    ///
+    /// `bits` must have been produced by `to_bits`.
+    pub(crate) fn from_bits(bits: u64) -> Self {
+        Reference(bits)
+    }
+
+    /// The representation of this reference in compiled code.
+    pub(crate) fn to_bits(self) -> u64 {
+        self.0
+    }
+}
+
+/// Releases the records of the functions belonging to the instance that
+/// owns `ctx`, making the references to them dead.
+pub(crate) fn release_references(ctx: *mut vm::Ctx) {
+    let mut records = RECORDS.lock();
+    let Records { ids, functions, .. } = &mut *records;
+    ids.retain(|&(_, record_ctx, _), id| {
+        if record_ctx == ctx as usize {
+            functions.remove(id);
+            false
+        } else {
+            true
+        }
+    });
+}
+
+impl Serialize for Reference {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if self.is_null() {
+            serializer.serialize_unit()
+        } else {
+            Err(ser::Error::custom(
+                "a non-null reference can't be serialized",
+            ))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Reference {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        <()>::deserialize(deserializer)?;
+        Ok(Reference::null())
+    }
+}
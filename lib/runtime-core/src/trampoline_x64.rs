@@ -7,6 +7,7 @@
 //! Variadic functions are not supported because `rax` is used by the trampoline code.
 
 use crate::loader::CodeMemory;
+use crate::types::Type;
 use crate::vm::Ctx;
 use std::fmt;
 use std::{mem, slice};
@@ -152,10 +153,30 @@ impl TrampolineBufferBuilder {
         target: unsafe extern "C" fn(*const CallContext, *const u64) -> u64,
         context: *const CallContext,
         num_params: u32,
+    ) -> usize {
+        let params = vec![Type::I64; num_params as usize];
+        self.add_typed_callinfo_trampoline(target, context, &params, &[])
+    }
+
+    /// Adds a callinfo trampoline for a function with the given signature.
+    ///
+    /// Like `add_callinfo_trampoline`, but floating point parameters are read from the `xmm`
+    /// registers, and a single floating point return value is moved from `rax` to `xmm0`. Every
+    /// parameter takes one slot of the array, with floating point values stored by their bits.
+    ///
+    /// Note that variadic functions are not supported, and that this panics if a parameter is
+    /// `V128`; `DynamicFunc::new` rejects such signatures before getting here.
+    pub fn add_typed_callinfo_trampoline(
+        &mut self,
+        target: unsafe extern "C" fn(*const CallContext, *const u64) -> u64,
+        context: *const CallContext,
+        params: &[Type],
+        returns: &[Type],
     ) -> usize {
         let idx = self.offsets.len();
         self.offsets.push(self.code.len());
 
+        let num_params = params.len() as u32;
         let mut stack_offset: u32 = num_params.checked_mul(8).unwrap();
         if stack_offset % 16 == 0 {
             stack_offset += 8;
@@ -163,11 +184,18 @@ impl TrampolineBufferBuilder {
 
         self.code.extend_from_slice(&[0x48, 0x81, 0xec]); // sub ?, %rsp
         self.code.extend_from_slice(value_to_bytes(&stack_offset));
-        for i in 0..num_params {
-            match i {
-                0..=5 => {
+
+        let (mut int_params, mut float_params, mut stack_params) = (0u32, 0u32, 0u32);
+        for (i, ty) in (0u32..).zip(params) {
+            let is_float = match ty {
+                Type::F32 | Type::F64 => true,
+                Type::V128 => panic!("V128 parameters are not supported"),
+                _ => false,
+            };
+            match (is_float, int_params, float_params) {
+                (false, 0..=5, _) => {
                     // mov %?, ?(%rsp)
-                    let prefix: &[u8] = match i {
+                    let prefix: &[u8] = match int_params {
                         0 => &[0x48, 0x89, 0xbc, 0x24], // rdi
                         1 => &[0x48, 0x89, 0xb4, 0x24], // rsi
                         2 => &[0x48, 0x89, 0x94, 0x24], // rdx
@@ -176,16 +204,26 @@ impl TrampolineBufferBuilder {
                         5 => &[0x4c, 0x89, 0x8c, 0x24], // r9
                         _ => unreachable!(),
                     };
+                    int_params += 1;
                     self.code.extend_from_slice(prefix);
                     self.code.extend_from_slice(value_to_bytes(&(i * 8u32)));
                 }
+                (true, _, 0..=7) => {
+                    let modrm = 0x84 | ((float_params as u8) << 3);
+                    float_params += 1;
+                    self.code.extend_from_slice(&[
+                        0xf2, 0x0f, 0x11, modrm, 0x24, // movsd %xmm?, ?(%rsp)
+                    ]);
+                    self.code.extend_from_slice(value_to_bytes(&(i * 8u32)));
+                }
                 _ => {
                     self.code.extend_from_slice(&[
                         0x48, 0x8b, 0x84, 0x24, // mov ?(%rsp), %rax
                     ]);
                     self.code.extend_from_slice(value_to_bytes(
-                        &((i - 6) * 8u32 + stack_offset + 8/* ret addr */),
+                        &(stack_params * 8u32 + stack_offset + 8/* ret addr */),
                     ));
+                    stack_params += 1;
                     // mov %rax, ?(%rsp)
                     self.code.extend_from_slice(&[0x48, 0x89, 0x84, 0x24]);
                     self.code.extend_from_slice(value_to_bytes(&(i * 8u32)));
@@ -207,6 +245,14 @@ impl TrampolineBufferBuilder {
         self.code.extend_from_slice(&[
             0xff, 0xd0, // callq *%rax
         ]);
+        match returns {
+            [Type::F32] | [Type::F64] => {
+                self.code.extend_from_slice(&[
+                    0x66, 0x48, 0x0f, 0x6e, 0xc0, // movq %rax, %xmm0
+                ]);
+            }
+            _ => {}
+        }
         self.code.extend_from_slice(&[
             0x48, 0x81, 0xc4, // add ?, %rsp
         ]);
@@ -290,4 +336,64 @@ mod tests {
         };
         assert_eq!(ret, 136);
     }
+    #[test]
+    fn test_typed_callinfo_trampoline() {
+        fn param_type(i: usize) -> Type {
+            match i {
+                9 => Type::F32,
+                4..=12 => Type::F64,
+                _ => Type::I64,
+            }
+        }
+        unsafe extern "C" fn do_sum(_ctx: *const CallContext, args: *const u64) -> u64 {
+            let args: &[u64] = slice::from_raw_parts(args, 16);
+            let sum: f64 = args
+                .iter()
+                .enumerate()
+                .map(|(i, x)| match param_type(i) {
+                    Type::F32 => f32::from_bits(*x as u32) as f64,
+                    Type::F64 => f64::from_bits(*x),
+                    _ => *x as i64 as f64,
+                })
+                .sum();
+            sum.to_bits()
+        }
+        let params: Vec<_> = (0..16).map(param_type).collect();
+        let mut builder = TrampolineBufferBuilder::new();
+        let idx = builder.add_typed_callinfo_trampoline(
+            do_sum,
+            ::std::ptr::null(),
+            &params,
+            &[Type::F64],
+        );
+        let buf = builder.build();
+        let t = buf.get_trampoline(idx);
+        // The last floating point and integer parameters are passed on the stack.
+        let ret = unsafe {
+            mem::transmute::<
+                _,
+                extern "C" fn(
+                    i64,
+                    i64,
+                    i64,
+                    i64,
+                    f64,
+                    f64,
+                    f64,
+                    f64,
+                    f64,
+                    f32,
+                    f64,
+                    f64,
+                    f64,
+                    i64,
+                    i64,
+                    i64,
+                ) -> f64,
+            >(t)(
+                1, 2, 3, 4, 0.5, 0.25, 8.0, 16.0, 32.0, 0.125, 64.0, 128.0, 256.0, 512, 1024, -2048,
+            )
+        };
+        assert_eq!(ret, 2.875);
+    }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    error::RuntimeError,
+    error::{RuntimeError, TrapDetails},
     export::{Context, Export, FuncPointer},
     import::IsExport,
     types::{FuncSig, NativeWasmType, Type, WasmExternType},
@@ -16,16 +16,50 @@ use std::{
     sync::Arc,
 };
 
+#[cfg(all(unix, target_arch = "x86_64"))]
+use crate::{error::DynamicFuncError, table::Reference, types::Value};
+#[cfg(all(unix, target_arch = "x86_64"))]
+use std::{rc::Rc, time::Duration};
+
 #[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum WasmTrapInfo {
     Unreachable = 0,
     IncorrectCallIndirectSignature = 1,
     MemoryOutOfBounds = 2,
     CallIndirectOOB = 3,
     IllegalArithmetic = 4,
+    DivideByZero = 5,
+    IntegerOverflow = 6,
+    StackOverflow = 7,
+    TableOutOfBounds = 8,
+    MisalignedAtomicAccess = 9,
+    WaitOnUnsharedMemory = 10,
+    DeadReference = 11,
     Unknown,
 }
 
+impl WasmTrapInfo {
+    /// Decodes a trap kind from its discriminant, as stored in generated code.
+    pub fn from_u8(x: u8) -> WasmTrapInfo {
+        match x {
+            0 => WasmTrapInfo::Unreachable,
+            1 => WasmTrapInfo::IncorrectCallIndirectSignature,
+            2 => WasmTrapInfo::MemoryOutOfBounds,
+            3 => WasmTrapInfo::CallIndirectOOB,
+            4 => WasmTrapInfo::IllegalArithmetic,
+            5 => WasmTrapInfo::DivideByZero,
+            6 => WasmTrapInfo::IntegerOverflow,
+            7 => WasmTrapInfo::StackOverflow,
+            8 => WasmTrapInfo::TableOutOfBounds,
+            9 => WasmTrapInfo::MisalignedAtomicAccess,
+            10 => WasmTrapInfo::WaitOnUnsharedMemory,
+            11 => WasmTrapInfo::DeadReference,
+            _ => WasmTrapInfo::Unknown,
+        }
+    }
+}
+
 impl fmt::Display for WasmTrapInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -39,6 +73,13 @@ impl fmt::Display for WasmTrapInfo {
                 WasmTrapInfo::MemoryOutOfBounds => "memory out-of-bounds access",
                 WasmTrapInfo::CallIndirectOOB => "`call_indirect` out-of-bounds",
                 WasmTrapInfo::IllegalArithmetic => "illegal arithmetic operation",
+                WasmTrapInfo::DivideByZero => "integer divide by zero",
+                WasmTrapInfo::IntegerOverflow => "integer overflow",
+                WasmTrapInfo::StackOverflow => "call stack exhausted",
+                WasmTrapInfo::TableOutOfBounds => "table out-of-bounds access",
+                WasmTrapInfo::MisalignedAtomicAccess => "misaligned atomic access",
+                WasmTrapInfo::WaitOnUnsharedMemory => "atomic wait on unshared memory",
+                WasmTrapInfo::DeadReference => "reference to a function of a dropped instance",
                 WasmTrapInfo::Unknown => "unknown",
             }
         )
@@ -96,6 +137,7 @@ pub trait WasmTypeList {
     type CStruct;
     type RetArray: AsMut<[u64]>;
     fn from_ret_array(array: Self::RetArray) -> Self;
+    fn into_ret_array(self) -> Self::RetArray;
     fn empty_ret_array() -> Self::RetArray;
     fn from_c_struct(c_struct: Self::CStruct) -> Self;
     fn into_c_struct(self) -> Self::CStruct;
@@ -180,10 +222,6 @@ where
             _phantom: PhantomData,
         }
     }
-
-    pub fn get_vm_func(&self) -> NonNull<vm::Func> {
-        self.f
-    }
 }
 
 impl<'a, Args, Rets> Func<'a, Args, Rets, Host>
@@ -216,6 +254,10 @@ where
     pub fn returns(&self) -> &'static [Type] {
         Rets::types()
     }
+
+    pub fn get_vm_func(&self) -> NonNull<vm::Func> {
+        self.f
+    }
 }
 
 impl WasmTypeList for Infallible {
@@ -224,6 +266,9 @@ impl WasmTypeList for Infallible {
     fn from_ret_array(_: Self::RetArray) -> Self {
         unreachable!()
     }
+    fn into_ret_array(self) -> Self::RetArray {
+        unreachable!()
+    }
     fn empty_ret_array() -> Self::RetArray {
         unreachable!()
     }
@@ -255,6 +300,9 @@ impl<A: WasmExternType> WasmTypeList for (A,) {
             array[0],
         )),)
     }
+    fn into_ret_array(self) -> Self::RetArray {
+        [WasmExternType::to_native(self.0).to_binary()]
+    }
     fn empty_ret_array() -> Self::RetArray {
         [0u64]
     }
@@ -296,11 +344,9 @@ impl<A: WasmExternType> WasmTypeList for (A,) {
             Ok(Rets::from_ret_array(rets))
         } else {
             if let Some(data) = user_error {
-                Err(RuntimeError::Error { data })
+                Err(RuntimeError::from_unwind_payload(data))
             } else {
-                Err(RuntimeError::Trap {
-                    msg: trap.to_string().into(),
-                })
+                Err(RuntimeError::from_trap(TrapDetails::new(trap)))
             }
         }
     }
@@ -311,8 +357,19 @@ where
     Rets: WasmTypeList,
 {
     pub fn call(&self, a: A) -> Result<Rets, RuntimeError> {
+        #[cfg(all(unix, target_arch = "x86_64"))]
+        unsafe {
+            crate::fault::discard_interrupted_state(self.ctx)
+        };
         unsafe { <A as WasmTypeList>::call(a, self.f, self.inner, self.ctx) }
     }
+
+    /// Like `call`, but stops the function with `RuntimeError::Timeout` if it
+    /// runs for longer than `timeout`.
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub fn call_with_timeout(&self, a: A, timeout: Duration) -> Result<Rets, RuntimeError> {
+        unsafe { crate::fault::run_with_timeout(self.ctx, timeout, || self.call(a)) }
+    }
 }
 
 macro_rules! impl_traits {
@@ -328,6 +385,11 @@ macro_rules! impl_traits {
                 let [ $( $x ),* ] = array;
                 ( $( WasmExternType::from_native(NativeWasmType::from_binary($x)) ),* )
             }
+            fn into_ret_array(self) -> Self::RetArray {
+                #[allow(unused_parens, non_snake_case)]
+                let ( $( $x ),* ) = self;
+                [ $( WasmExternType::to_native($x).to_binary() ),* ]
+            }
             fn empty_ret_array() -> Self::RetArray {
                 [0; count_idents!( $( $x ),* )]
             }
@@ -357,9 +419,9 @@ macro_rules! impl_traits {
                     Ok(Rets::from_ret_array(rets))
                 } else {
                     if let Some(data) = user_error {
-                        Err(RuntimeError::Error { data })
+                        Err(RuntimeError::from_unwind_payload(data))
                     } else {
-                        Err(RuntimeError::Trap { msg: trap.to_string().into() })
+                        Err(RuntimeError::from_trap(TrapDetails::new(trap)))
                     }
                 }
             }
@@ -390,7 +452,40 @@ macro_rules! impl_traits {
                         }
                     }
 
-                    NonNull::new(wrap::<$( $x, )* Rets, Trap, Self> as *mut vm::Func).unwrap()
+                    /// Functions with more than one result write them to the return
+                    /// buffer of the `Ctx` and return a pointer to it.
+                    #[cfg_attr(nightly, unwind(allowed))]
+                    extern fn wrap_multi<$( $x: WasmExternType, )* Rets: WasmTypeList, Trap: TrapEarly<Rets>, FN: Fn( &mut Ctx $( ,$x )* ) -> Trap>( ctx: &mut Ctx $( ,$x: <$x as WasmExternType>::Native )* ) -> *const u64 {
+                        let f: FN = unsafe { mem::transmute_copy(&()) };
+
+                        let err = match panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                            f( ctx $( ,WasmExternType::from_native($x) )* ).report()
+                        })) {
+                            Ok(Ok(returns)) => {
+                                let mut rets = returns.into_ret_array();
+                                let rets = rets.as_mut();
+                                unsafe {
+                                    ptr::copy_nonoverlapping(rets.as_ptr(), ctx.internal.return_values, rets.len());
+                                }
+                                return ctx.internal.return_values;
+                            },
+                            Ok(Err(err)) => {
+                                let b: Box<_> = err.into();
+                                b as Box<dyn Any>
+                            },
+                            Err(err) => err,
+                        };
+
+                        unsafe {
+                            (&*ctx.module).runnable_module.do_early_trap(err)
+                        }
+                    }
+
+                    if Rets::types().len() > 1 {
+                        NonNull::new(wrap_multi::<$( $x, )* Rets, Trap, Self> as *mut vm::Func).unwrap()
+                    } else {
+                        NonNull::new(wrap::<$( $x, )* Rets, Trap, Self> as *mut vm::Func).unwrap()
+                    }
                 } else {
                     assert_eq!(mem::size_of::<Self>(), mem::size_of::<usize>(), "you cannot use a closure that captures state for `Func`.");
                     NonNull::new(unsafe {
@@ -406,9 +501,19 @@ macro_rules! impl_traits {
         {
             #[allow(non_snake_case)]
             pub fn call(&self, $( $x: $x, )* ) -> Result<Rets, RuntimeError> {
+                #[cfg(all(unix, target_arch = "x86_64"))]
+                unsafe { crate::fault::discard_interrupted_state(self.ctx) };
                 #[allow(unused_parens)]
                 unsafe { <( $( $x ),* ) as WasmTypeList>::call(( $($x),* ), self.f, self.inner, self.ctx) }
             }
+
+            /// Like `call`, but stops the function with `RuntimeError::Timeout` if it
+            /// runs for longer than `timeout`.
+            #[cfg(all(unix, target_arch = "x86_64"))]
+            #[allow(non_snake_case)]
+            pub fn call_with_timeout(&self, $( $x: $x, )* timeout: Duration) -> Result<Rets, RuntimeError> {
+                unsafe { crate::fault::run_with_timeout(self.ctx, timeout, || self.call($( $x ),* )) }
+            }
         }
     };
 }
@@ -455,6 +560,161 @@ where
     }
 }
 
+/// A host function whose signature is only known at runtime.
+///
+/// The function receives its arguments as `Value`s and must return values
+/// matching the results of its signature. Errors returned by the function
+/// are reported as traps, just like with `Func`.
+///
+/// ```
+/// # use wasmer_runtime_core::{imports, typed_func::DynamicFunc, types::{FuncSig, Type, Value}};
+/// let add = DynamicFunc::new(
+///     FuncSig::new(vec![Type::I32, Type::I32], vec![Type::I32]),
+///     |_ctx, args| match args {
+///         [Value::I32(a), Value::I32(b)] => Ok(vec![Value::I32(a + b)]),
+///         _ => Err("unexpected arguments"),
+///     },
+/// )
+/// .unwrap();
+/// let import_object = imports! {
+///     "env" => {
+///         "add" => add,
+///     },
+/// };
+/// ```
+#[cfg(all(unix, target_arch = "x86_64"))]
+pub struct DynamicFunc {
+    func: FuncPointer,
+    signature: Arc<FuncSig>,
+}
+
+#[cfg(all(unix, target_arch = "x86_64"))]
+type DynamicFn = dyn Fn(&mut Ctx, &[Value]) -> Result<Vec<Value>, Box<dyn Any>>;
+
+/// The state passed to the trampoline of a `DynamicFunc`.
+#[cfg(all(unix, target_arch = "x86_64"))]
+struct DynamicContext {
+    signature: Arc<FuncSig>,
+    f: Box<DynamicFn>,
+}
+
+#[cfg(all(unix, target_arch = "x86_64"))]
+impl DynamicFunc {
+    /// Creates a host function with the given signature.
+    ///
+    /// Returns an error if the signature contains `V128` values, which can't
+    /// be passed through the trampoline.
+    pub fn new<F, E>(signature: FuncSig, f: F) -> Result<DynamicFunc, DynamicFuncError>
+    where
+        F: Fn(&mut Ctx, &[Value]) -> Result<Vec<Value>, E> + 'static,
+        E: 'static,
+    {
+        use crate::trampoline::{CallContext, TrampolineBufferBuilder};
+
+        /// This is required for the llvm backend to be able to unwind through this function.
+        #[cfg_attr(nightly, unwind(allowed))]
+        unsafe extern "C" fn enter(context: *const CallContext, args: *const u64) -> u64 {
+            let context = &*(context as *const DynamicContext);
+            let params = context.signature.params();
+            let args = std::slice::from_raw_parts(args, 1 + params.len());
+            let ctx = &mut *(args[0] as *mut Ctx);
+            let args: Vec<_> = params
+                .iter()
+                .zip(&args[1..])
+                .map(|(&ty, &raw)| match ty {
+                    Type::I32 => Value::I32(raw as i32),
+                    Type::I64 => Value::I64(raw as i64),
+                    Type::F32 => Value::F32(f32::from_bits(raw as u32)),
+                    Type::F64 => Value::F64(f64::from_bits(raw)),
+                    Type::V128 => unreachable!("V128 does not map to any single value"),
+                    Type::AnyRef => Value::AnyRef(Reference::from_bits(raw)),
+                    Type::FuncRef => Value::FuncRef(Reference::from_bits(raw)),
+                })
+                .collect();
+
+            let returns = context.signature.returns();
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(|| (context.f)(ctx, &args)));
+            let err: Box<dyn Any> = match result {
+                Ok(Ok(results)) => {
+                    if results.iter().map(Value::ty).eq(returns.iter().cloned()) {
+                        let raw: Vec<u64> = results.iter().map(|x| x.to_u128() as u64).collect();
+                        return match raw[..] {
+                            [] => 0,
+                            [x] => x,
+                            // Multiple results are written to the return buffer of the `Ctx`.
+                            _ => {
+                                let return_values = ctx.internal.return_values;
+                                ptr::copy_nonoverlapping(raw.as_ptr(), return_values, raw.len());
+                                return_values as u64
+                            }
+                        };
+                    }
+                    Box::new(format!(
+                        "host function returned {:?}, expected {:?}",
+                        results, returns
+                    ))
+                }
+                Ok(Err(err)) => err,
+                Err(err) => err,
+            };
+
+            (&*ctx.module).runnable_module.do_early_trap(err)
+        }
+
+        if signature
+            .params()
+            .iter()
+            .chain(signature.returns())
+            .any(|&ty| ty == Type::V128)
+        {
+            return Err(DynamicFuncError::UnsupportedType(Type::V128));
+        }
+
+        let signature = Arc::new(signature);
+        let context = Box::new(DynamicContext {
+            signature: signature.clone(),
+            f: Box::new(move |ctx: &mut Ctx, args: &[Value]| {
+                f(ctx, args).map_err(|err| Box::new(err) as Box<dyn Any>)
+            }),
+        });
+
+        // The `Ctx` is passed as the first parameter.
+        let mut params = vec![Type::I64];
+        params.extend_from_slice(signature.params());
+        let mut builder = TrampolineBufferBuilder::new();
+        let idx = builder.add_typed_callinfo_trampoline(
+            enter,
+            &*context as *const DynamicContext as *const CallContext,
+            &params,
+            signature.returns(),
+        );
+        let trampolines = builder.build();
+
+        let func = unsafe {
+            FuncPointer::with_owner(
+                trampolines.get_trampoline(idx) as *const vm::Func,
+                Rc::new((trampolines, context)),
+            )
+        };
+        Ok(DynamicFunc { func, signature })
+    }
+
+    pub fn signature(&self) -> &FuncSig {
+        &self.signature
+    }
+}
+
+#[cfg(all(unix, target_arch = "x86_64"))]
+impl IsExport for DynamicFunc {
+    fn to_export(&self) -> Export {
+        Export::Function {
+            func: self.func.clone(),
+            ctx: Context::Internal,
+            signature: self.signature.clone(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
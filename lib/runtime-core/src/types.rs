@@ -1,6 +1,11 @@
-use crate::{memory::MemoryType, module::ModuleInfo, structures::TypedIndex, units::Pages};
+use crate::{
+    memory::MemoryType, module::ModuleInfo, structures::TypedIndex, table::Reference, units::Pages,
+};
 use std::borrow::Cow;
 
+/// Derives `ValueType` for `#[repr(C)]` structs, checking their layout.
+pub use wasmer_runtime_core_derive::ValueType;
+
 /// Represents a WebAssembly type.
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Type {
@@ -14,6 +19,10 @@ pub enum Type {
     F64,
     /// The `v128` type.
     V128,
+    /// The `anyref` type.
+    AnyRef,
+    /// The `funcref` type.
+    FuncRef,
 }
 
 impl std::fmt::Display for Type {
@@ -38,6 +47,10 @@ pub enum Value {
     F64(f64),
     /// The `v128` type.
     V128(u128),
+    /// The `anyref` type.
+    AnyRef(Reference),
+    /// The `funcref` type.
+    FuncRef(Reference),
 }
 
 impl Value {
@@ -48,6 +61,8 @@ impl Value {
             Value::F32(_) => Type::F32,
             Value::F64(_) => Type::F64,
             Value::V128(_) => Type::V128,
+            Value::AnyRef(_) => Type::AnyRef,
+            Value::FuncRef(_) => Type::FuncRef,
         }
     }
 
@@ -58,6 +73,7 @@ impl Value {
             Value::F32(x) => f32::to_bits(x) as u128,
             Value::F64(x) => f64::to_bits(x) as u128,
             Value::V128(x) => x,
+            Value::AnyRef(x) | Value::FuncRef(x) => x.to_bits() as u128,
         }
     }
 }
@@ -255,6 +271,10 @@ unsafe impl WasmExternType for f64 {
 //     fn swap(&self, other: Self::Primitive) -> Self::Primitive;
 // }
 
+/// Types that can be read from and written to memory as they are, e.g. through
+/// a `WasmPtr`: every bit pattern of their size must be a valid value.
+///
+/// Use `#[derive(ValueType)]` to implement it for a struct.
 pub unsafe trait ValueType: Copy
 where
     Self: Sized,
@@ -278,6 +298,8 @@ convert_value_impl!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
 pub enum ElementType {
     /// Any wasm function.
     Anyfunc,
+    /// Any reference, including functions.
+    AnyRef,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -385,6 +407,24 @@ impl FuncSig {
         &self.returns
     }
 
+    /// The number of `u64` slots needed to hold the results of this
+    /// signature. A `V128` result takes two slots, any other result one.
+    pub fn return_slots(&self) -> usize {
+        self.returns
+            .iter()
+            .map(|&ty| if ty == Type::V128 { 2 } else { 1 })
+            .sum()
+    }
+
+    /// The number of `u64` slots needed to hold the parameters of this
+    /// signature, counted the same way as `return_slots`.
+    pub fn param_slots(&self) -> usize {
+        self.params
+            .iter()
+            .map(|&ty| if ty == Type::V128 { 2 } else { 1 })
+            .sum()
+    }
+
     pub fn check_param_value_types(&self, params: &[Value]) -> bool {
         self.params.len() == params.len()
             && self
@@ -511,6 +551,10 @@ impl TypedIndex for SigIndex {
     }
 }
 
+// Indices of data and element segments, as used by the bulk memory operators.
+define_map_index!(DataIndex);
+define_map_index!(ElemIndex);
+
 pub enum LocalOrImport<T>
 where
     T: LocalImport,
@@ -1,15 +1,20 @@
 pub use crate::backing::{ImportBacking, LocalBacking, INTERNALS_SIZE};
 use crate::{
+    error::{CallResult, ResolveError, RuntimeError, TrapDetails},
+    instance::{call_func_with_index, call_func_with_ptr},
     memory::{Memory, MemoryType},
-    module::{ModuleInfo, ModuleInner},
+    module::{ExportIndex, ModuleInfo, ModuleInner},
+    sig_registry::SigRegistry,
     structures::TypedIndex,
-    types::{LocalOrImport, MemoryIndex},
+    typed_func::WasmTrapInfo,
+    types::{LocalOrImport, MemoryIndex, SigIndex, TableIndex, Value},
     vmcalls,
 };
 use std::{
     cell::UnsafeCell,
     ffi::c_void,
-    mem, ptr,
+    mem,
+    ptr::{self, NonNull},
     sync::atomic::{AtomicUsize, Ordering},
     sync::Once,
 };
@@ -116,6 +121,11 @@ pub struct InternalCtx {
     pub internals: *mut [u64; INTERNALS_SIZE], // TODO: Make this dynamic?
 
     pub interrupt_signal_mem: *mut u8,
+
+    /// A pointer to the buffer that functions with more than one result
+    /// write their results to. Such functions return a pointer to this
+    /// buffer instead of a value.
+    pub return_values: *mut u64,
 }
 
 static INTERNAL_FIELDS: AtomicUsize = AtomicUsize::new(0);
@@ -157,6 +167,34 @@ impl InternalField {
 pub struct Intrinsics {
     pub memory_grow: *const Func,
     pub memory_size: *const Func,
+
+    // Bulk memory operations. These don't depend on the type of the memory.
+    pub memory_copy: *const Func,
+    pub memory_fill: *const Func,
+    pub memory_init: *const Func,
+    pub data_drop: *const Func,
+    pub table_copy: *const Func,
+    pub table_init: *const Func,
+    pub elem_drop: *const Func,
+
+    /// Runs a middleware breakpoint handler, for backends that call it directly
+    /// instead of raising a trap.
+    pub breakpoint: *const Func,
+
+    // Threads proposal. These don't depend on the type of the memory either.
+    pub memory_atomic_wait32: *const Func,
+    pub memory_atomic_wait64: *const Func,
+    pub memory_atomic_notify: *const Func,
+
+    // Reference types proposal.
+    pub table_get: *const Func,
+    pub table_set: *const Func,
+    pub table_size: *const Func,
+    pub table_grow: *const Func,
+    pub table_fill: *const Func,
+
+    /// Unwinds with the error of a `codegen::InternalTrap`, given its code.
+    pub internal_trap: *const Func,
     /*pub memory_grow: unsafe extern "C" fn(
         ctx: &mut Ctx,
         memory_index: usize,
@@ -179,28 +217,126 @@ impl Intrinsics {
     pub fn offset_memory_size() -> u8 {
         (1 * ::std::mem::size_of::<usize>()) as u8
     }
+    pub fn offset_memory_copy() -> u8 {
+        (2 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_memory_fill() -> u8 {
+        (3 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_memory_init() -> u8 {
+        (4 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_data_drop() -> u8 {
+        (5 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_table_copy() -> u8 {
+        (6 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_table_init() -> u8 {
+        (7 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_elem_drop() -> u8 {
+        (8 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_breakpoint() -> u8 {
+        (9 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_memory_atomic_wait32() -> u8 {
+        (10 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_memory_atomic_wait64() -> u8 {
+        (11 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_memory_atomic_notify() -> u8 {
+        (12 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_table_get() -> u8 {
+        (13 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_table_set() -> u8 {
+        (14 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_table_size() -> u8 {
+        (15 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_table_grow() -> u8 {
+        (16 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_table_fill() -> u8 {
+        (17 * ::std::mem::size_of::<usize>()) as u8
+    }
+    pub fn offset_internal_trap() -> u8 {
+        (18 * ::std::mem::size_of::<usize>()) as u8
+    }
 }
 
-pub static INTRINSICS_LOCAL_STATIC_MEMORY: Intrinsics = Intrinsics {
-    memory_grow: vmcalls::local_static_memory_grow as _,
-    memory_size: vmcalls::local_static_memory_size as _,
-};
-pub static INTRINSICS_LOCAL_DYNAMIC_MEMORY: Intrinsics = Intrinsics {
-    memory_grow: vmcalls::local_dynamic_memory_grow as _,
-    memory_size: vmcalls::local_dynamic_memory_size as _,
-};
-pub static INTRINSICS_IMPORTED_STATIC_MEMORY: Intrinsics = Intrinsics {
-    memory_grow: vmcalls::imported_static_memory_grow as _,
-    memory_size: vmcalls::imported_static_memory_size as _,
-};
-pub static INTRINSICS_IMPORTED_DYNAMIC_MEMORY: Intrinsics = Intrinsics {
-    memory_grow: vmcalls::imported_dynamic_memory_grow as _,
-    memory_size: vmcalls::imported_dynamic_memory_size as _,
-};
+macro_rules! define_intrinsics {
+    ($name:ident, $memory_grow:path, $memory_size:path) => {
+        pub static $name: Intrinsics = Intrinsics {
+            memory_grow: $memory_grow as _,
+            memory_size: $memory_size as _,
+
+            memory_copy: vmcalls::memory_copy as _,
+            memory_fill: vmcalls::memory_fill as _,
+            memory_init: vmcalls::memory_init as _,
+            data_drop: vmcalls::data_drop as _,
+            table_copy: vmcalls::table_copy as _,
+            table_init: vmcalls::table_init as _,
+            elem_drop: vmcalls::elem_drop as _,
+
+            breakpoint: vmcalls::breakpoint as _,
+
+            memory_atomic_wait32: vmcalls::memory_atomic_wait32 as _,
+            memory_atomic_wait64: vmcalls::memory_atomic_wait64 as _,
+            memory_atomic_notify: vmcalls::memory_atomic_notify as _,
+
+            table_get: vmcalls::table_get as _,
+            table_set: vmcalls::table_set as _,
+            table_size: vmcalls::table_size as _,
+            table_grow: vmcalls::table_grow as _,
+            table_fill: vmcalls::table_fill as _,
+
+            internal_trap: vmcalls::internal_trap as _,
+        };
+    };
+}
+
+define_intrinsics!(
+    INTRINSICS_LOCAL_STATIC_MEMORY,
+    vmcalls::local_static_memory_grow,
+    vmcalls::local_static_memory_size
+);
+define_intrinsics!(
+    INTRINSICS_LOCAL_DYNAMIC_MEMORY,
+    vmcalls::local_dynamic_memory_grow,
+    vmcalls::local_dynamic_memory_size
+);
+define_intrinsics!(
+    INTRINSICS_IMPORTED_STATIC_MEMORY,
+    vmcalls::imported_static_memory_grow,
+    vmcalls::imported_static_memory_size
+);
+define_intrinsics!(
+    INTRINSICS_IMPORTED_DYNAMIC_MEMORY,
+    vmcalls::imported_dynamic_memory_grow,
+    vmcalls::imported_dynamic_memory_size
+);
+define_intrinsics!(
+    INTRINSICS_LOCAL_SHARED_STATIC_MEMORY,
+    vmcalls::local_shared_static_memory_grow,
+    vmcalls::local_shared_static_memory_size
+);
+define_intrinsics!(
+    INTRINSICS_IMPORTED_SHARED_STATIC_MEMORY,
+    vmcalls::imported_shared_static_memory_grow,
+    vmcalls::imported_shared_static_memory_size
+);
 
 fn get_intrinsics_for_module(m: &ModuleInfo) -> *const Intrinsics {
     if m.memories.len() == 0 && m.imported_memories.len() == 0 {
-        ::std::ptr::null()
+        // `memory.grow` and `memory.size` can't be used without a memory, but the
+        // table operations still are.
+        &INTRINSICS_LOCAL_DYNAMIC_MEMORY
     } else {
         match MemoryIndex::new(0).local_or_import(m) {
             LocalOrImport::Local(local_mem_index) => {
@@ -208,7 +344,7 @@ fn get_intrinsics_for_module(m: &ModuleInfo) -> *const Intrinsics {
                 match mem_desc.memory_type() {
                     MemoryType::Dynamic => &INTRINSICS_LOCAL_DYNAMIC_MEMORY,
                     MemoryType::Static => &INTRINSICS_LOCAL_STATIC_MEMORY,
-                    MemoryType::SharedStatic => unimplemented!(),
+                    MemoryType::SharedStatic => &INTRINSICS_LOCAL_SHARED_STATIC_MEMORY,
                 }
             }
             LocalOrImport::Import(import_mem_index) => {
@@ -216,7 +352,7 @@ fn get_intrinsics_for_module(m: &ModuleInfo) -> *const Intrinsics {
                 match mem_desc.memory_type() {
                     MemoryType::Dynamic => &INTRINSICS_IMPORTED_DYNAMIC_MEMORY,
                     MemoryType::Static => &INTRINSICS_IMPORTED_STATIC_MEMORY,
-                    MemoryType::SharedStatic => unimplemented!(),
+                    MemoryType::SharedStatic => &INTRINSICS_IMPORTED_SHARED_STATIC_MEMORY,
                 }
             }
         }
@@ -274,6 +410,8 @@ impl Ctx {
                 internals: &mut local_backing.internals.0,
 
                 interrupt_signal_mem: get_interrupt_signal_mem(),
+
+                return_values: local_backing.return_values.as_mut_ptr(),
             },
             local_functions: local_backing.local_functions.as_ptr(),
 
@@ -327,6 +465,8 @@ impl Ctx {
                 internals: &mut local_backing.internals.0,
 
                 interrupt_signal_mem: get_interrupt_signal_mem(),
+
+                return_values: local_backing.return_values.as_mut_ptr(),
             },
             local_functions: local_backing.local_functions.as_ptr(),
 
@@ -393,6 +533,133 @@ impl Ctx {
             (*self.internal.internals)[field.index()] = value;
         }
     }
+
+    /// Calls the exported function `name` of the instance this context
+    /// belongs to, e.g. the guest's allocator, from an imported function.
+    ///
+    /// Traps and errors raised during the call are returned. An imported
+    /// function that returns one of them as its own error makes the call
+    /// into the instance fail with it, as if it had been raised there.
+    ///
+    /// # Usage:
+    ///
+    /// ```
+    /// # use wasmer_runtime_core::{error::CallResult, types::Value, vm::Ctx};
+    /// fn alloc(ctx: &mut Ctx, size: i32) -> CallResult<i32> {
+    ///     match ctx.call("malloc", &[Value::I32(size)])?[..] {
+    ///         [Value::I32(ptr)] => Ok(ptr),
+    ///         _ => panic!("malloc has the wrong signature"),
+    ///     }
+    /// }
+    /// ```
+    pub fn call(&mut self, name: &str, params: &[Value]) -> CallResult<Vec<Value>> {
+        let module = unsafe { &*self.module };
+        let func_index = match module.info.exports.get(name) {
+            Some(&ExportIndex::Func(func_index)) => func_index,
+            Some(_) => {
+                return Err(ResolveError::ExportWrongType {
+                    name: name.to_string(),
+                }
+                .into())
+            }
+            None => {
+                return Err(ResolveError::ExportNotFound {
+                    name: name.to_string(),
+                }
+                .into())
+            }
+        };
+
+        let mut results = Vec::new();
+        call_func_with_index(
+            &module.info,
+            &*module.runnable_module,
+            unsafe { &*self.import_backing },
+            self,
+            func_index,
+            params,
+            &mut results,
+            None,
+        )?;
+        Ok(results)
+    }
+
+    /// Calls the function at `index` in the table `table_index` of the
+    /// instance this context belongs to, like `call_indirect` would.
+    ///
+    /// Calling an element that is out of bounds or null, or in a table the
+    /// instance doesn't have, traps with `CallIndirectOOB`. Elements whose signature isn't one of the module's
+    /// can't be called and trap with `IncorrectCallIndirectSignature`.
+    pub fn call_indirect(
+        &mut self,
+        table_index: u32,
+        index: u32,
+        params: &[Value],
+    ) -> CallResult<Vec<Value>> {
+        let module = unsafe { &*self.module };
+        let table_count = module.info.imported_tables.len() + module.info.tables.len();
+        if table_index as usize >= table_count {
+            return Err(
+                RuntimeError::from_trap(TrapDetails::new(WasmTrapInfo::CallIndirectOOB)).into(),
+            );
+        }
+        let table_index = TableIndex::new(table_index as usize);
+        let table = match table_index.local_or_import(&module.info) {
+            LocalOrImport::Local(local_table_index) => unsafe {
+                &(*self.local_backing).tables[local_table_index]
+            },
+            LocalOrImport::Import(import_table_index) => unsafe {
+                &(*self.import_backing).tables[import_table_index]
+            },
+        };
+        // The table isn't borrowed during the call, which may change it.
+        let anyfunc =
+            table.anyfunc_direct_access_mut(|elements| elements.get(index as usize).cloned());
+        let anyfunc = match anyfunc {
+            Some(anyfunc) if !anyfunc.func.is_null() => anyfunc,
+            _ => {
+                return Err(RuntimeError::from_trap(TrapDetails::new(
+                    WasmTrapInfo::CallIndirectOOB,
+                ))
+                .into())
+            }
+        };
+
+        let signature = SigRegistry.lookup_signature(SigIndex::new(anyfunc.sig_id.0 as usize));
+        let sig_index = match module
+            .info
+            .signatures
+            .iter()
+            .find(|&(_, sig)| *sig == *signature)
+        {
+            Some((sig_index, _)) => sig_index,
+            None => {
+                return Err(RuntimeError::from_trap(TrapDetails::new(
+                    WasmTrapInfo::IncorrectCallIndirectSignature,
+                ))
+                .into())
+            }
+        };
+        // Host functions put in the table run with the caller's context.
+        let ctx = if anyfunc.ctx.is_null() {
+            self as *mut Ctx
+        } else {
+            anyfunc.ctx
+        };
+
+        let mut results = Vec::new();
+        call_func_with_ptr(
+            &module.info,
+            &*module.runnable_module,
+            NonNull::new(anyfunc.func as *mut _).unwrap(),
+            ctx,
+            sig_index,
+            params,
+            &mut results,
+            None,
+        )?;
+        Ok(results)
+    }
 }
 
 #[doc(hidden)]
@@ -454,9 +721,13 @@ impl Ctx {
         13 * (mem::size_of::<usize>() as u8)
     }
 
-    pub fn offset_local_functions() -> u8 {
+    pub fn offset_return_values() -> u8 {
         14 * (mem::size_of::<usize>() as u8)
     }
+
+    pub fn offset_local_functions() -> u8 {
+        15 * (mem::size_of::<usize>() as u8)
+    }
 }
 
 enum InnerFunc {}
@@ -680,6 +951,11 @@ mod vm_offset_tests {
             offset_of!(InternalCtx => interrupt_signal_mem).get_byte_offset(),
         );
 
+        assert_eq!(
+            Ctx::offset_return_values() as usize,
+            offset_of!(InternalCtx => return_values).get_byte_offset(),
+        );
+
         assert_eq!(
             Ctx::offset_local_functions() as usize,
             offset_of!(Ctx => local_functions).get_byte_offset(),
@@ -806,6 +1082,8 @@ mod vm_ctx_tests {
             local_functions: Map::new().into_boxed_map(),
 
             internals: crate::backing::Internals([0; crate::backing::INTERNALS_SIZE]),
+
+            return_values: Vec::new().into_boxed_slice(),
         };
 
         let mut import_backing = ImportBacking {
@@ -847,7 +1125,7 @@ mod vm_ctx_tests {
     fn generate_module() -> ModuleInner {
         use super::Func;
         use crate::backend::{sys::Memory, Backend, CacheGen, RunnableModule};
-        use crate::cache::Error as CacheError;
+        use crate::cache::{Error as CacheError, WasmHash};
         use crate::typed_func::Wasm;
         use crate::types::{LocalFuncIndex, SigIndex};
         use indexmap::IndexMap;
@@ -896,11 +1174,17 @@ mod vm_ctx_tests {
                 data_initializers: Vec::new(),
                 elem_initializers: Vec::new(),
 
+                passive_data: HashMap::new(),
+                passive_elements: HashMap::new(),
+
                 start_func: None,
 
                 func_assoc: Map::new(),
                 signatures: Map::new(),
                 backend: Backend::Cranelift,
+                wasm_hash: WasmHash::generate(&[]),
+                middleware_fingerprint: String::new(),
+                checks_interrupts: false,
 
                 namespace_table: StringTable::new(),
                 name_table: StringTable::new(),
@@ -908,6 +1192,10 @@ mod vm_ctx_tests {
                 em_symbol_map: None,
 
                 custom_sections: HashMap::new(),
+
+                module_name: None,
+                function_names: HashMap::new(),
+                local_names: HashMap::new(),
             },
         }
     }
@@ -1,12 +1,40 @@
 #![allow(clippy::cast_ptr_alignment)]
 
 use crate::{
-    memory::{DynamicMemory, StaticMemory},
+    backing::func_index_to_anyfunc,
+    codegen::{BreakpointHandler, BreakpointInfo, InternalTrap},
+    error::{ExecutionLimitExceededError, GrowError, TrapDetails},
+    memory::{wait, DynamicMemory, SharedStaticMemory, StaticMemory},
     structures::TypedIndex,
-    types::{ImportedMemoryIndex, LocalMemoryIndex, LocalTableIndex},
+    table::{self, Reference},
+    typed_func::WasmTrapInfo,
+    types::{
+        DataIndex, ElemIndex, ImportedMemoryIndex, LocalMemoryIndex, LocalOrImport,
+        LocalTableIndex, MemoryIndex, TableIndex,
+    },
     units::Pages,
     vm,
 };
+use std::{
+    any::Any,
+    ptr, slice,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Converts the result of growing a memory into the value returned by `memory.grow`.
+///
+/// Exceeding the page limit set on a memory traps instead of returning `-1`, so
+/// that the embedder can tell it apart from an ordinary failed growth.
+unsafe fn grow_result(ctx: &vm::Ctx, result: Result<Pages, GrowError>) -> i32 {
+    match result {
+        Ok(old) => old.0 as i32,
+        Err(err @ GrowError::ExceededPageLimit(..)) => {
+            (&*ctx.module).runnable_module.do_early_trap(Box::new(err))
+        }
+        Err(_) => -1,
+    }
+}
 
 // +*****************************+
 // |       LOCAL MEMORIES        |
@@ -20,10 +48,7 @@ pub unsafe extern "C" fn local_static_memory_grow(
     let local_memory = *ctx.internal.memories.add(memory_index.index());
     let memory = (*local_memory).memory as *mut StaticMemory;
 
-    let ret = match (*memory).grow(delta, &mut *local_memory) {
-        Ok(old) => old.0 as i32,
-        Err(_) => -1,
-    };
+    let ret = grow_result(ctx, (*memory).grow(delta, &mut *local_memory));
 
     ctx.internal.memory_base = (*local_memory).base;
     ctx.internal.memory_bound = (*local_memory).bound;
@@ -49,10 +74,7 @@ pub unsafe extern "C" fn local_dynamic_memory_grow(
     let local_memory = *ctx.internal.memories.add(memory_index.index());
     let memory = (*local_memory).memory as *mut DynamicMemory;
 
-    let ret = match (*memory).grow(delta, &mut *local_memory) {
-        Ok(old) => old.0 as i32,
-        Err(_) => -1,
-    };
+    let ret = grow_result(ctx, (*memory).grow(delta, &mut *local_memory));
 
     ctx.internal.memory_base = (*local_memory).base;
     ctx.internal.memory_bound = (*local_memory).bound;
@@ -70,6 +92,32 @@ pub unsafe extern "C" fn local_dynamic_memory_size(
     (*memory).size()
 }
 
+pub unsafe extern "C" fn local_shared_static_memory_grow(
+    ctx: &mut vm::Ctx,
+    memory_index: LocalMemoryIndex,
+    delta: Pages,
+) -> i32 {
+    let local_memory = *ctx.internal.memories.add(memory_index.index());
+    let memory = (*local_memory).memory as *const SharedStaticMemory;
+
+    let ret = grow_result(ctx, (*memory).grow(delta, &mut *local_memory));
+
+    ctx.internal.memory_base = (*local_memory).base;
+    ctx.internal.memory_bound = (*local_memory).bound;
+
+    ret
+}
+
+pub unsafe extern "C" fn local_shared_static_memory_size(
+    ctx: &vm::Ctx,
+    memory_index: LocalMemoryIndex,
+) -> Pages {
+    let local_memory = *ctx.internal.memories.add(memory_index.index());
+    let memory = (*local_memory).memory as *const SharedStaticMemory;
+
+    (*memory).size()
+}
+
 // +*****************************+
 // |      IMPORTED MEMORIES      |
 // +****************************+
@@ -85,10 +133,7 @@ pub unsafe extern "C" fn imported_static_memory_grow(
         .add(import_memory_index.index());
     let memory = (*local_memory).memory as *mut StaticMemory;
 
-    let ret = match (*memory).grow(delta, &mut *local_memory) {
-        Ok(old) => old.0 as i32,
-        Err(_) => -1,
-    };
+    let ret = grow_result(ctx, (*memory).grow(delta, &mut *local_memory));
 
     ctx.internal.memory_base = (*local_memory).base;
     ctx.internal.memory_bound = (*local_memory).bound;
@@ -117,10 +162,7 @@ pub unsafe extern "C" fn imported_dynamic_memory_grow(
     let local_memory = *ctx.internal.imported_memories.add(memory_index.index());
     let memory = (*local_memory).memory as *mut DynamicMemory;
 
-    let ret = match (*memory).grow(delta, &mut *local_memory) {
-        Ok(old) => old.0 as i32,
-        Err(_) => -1,
-    };
+    let ret = grow_result(ctx, (*memory).grow(delta, &mut *local_memory));
 
     ctx.internal.memory_base = (*local_memory).base;
     ctx.internal.memory_bound = (*local_memory).bound;
@@ -138,6 +180,38 @@ pub unsafe extern "C" fn imported_dynamic_memory_size(
     (*memory).size()
 }
 
+pub unsafe extern "C" fn imported_shared_static_memory_grow(
+    ctx: &mut vm::Ctx,
+    import_memory_index: ImportedMemoryIndex,
+    delta: Pages,
+) -> i32 {
+    let local_memory = *ctx
+        .internal
+        .imported_memories
+        .add(import_memory_index.index());
+    let memory = (*local_memory).memory as *const SharedStaticMemory;
+
+    let ret = grow_result(ctx, (*memory).grow(delta, &mut *local_memory));
+
+    ctx.internal.memory_base = (*local_memory).base;
+    ctx.internal.memory_bound = (*local_memory).bound;
+
+    ret
+}
+
+pub unsafe extern "C" fn imported_shared_static_memory_size(
+    ctx: &vm::Ctx,
+    import_memory_index: ImportedMemoryIndex,
+) -> Pages {
+    let local_memory = *ctx
+        .internal
+        .imported_memories
+        .add(import_memory_index.index());
+    let memory = (*local_memory).memory as *const SharedStaticMemory;
+
+    (*memory).size()
+}
+
 // +*****************************+
 // |        LOCAL TABLES         |
 // +****************************+
@@ -147,14 +221,377 @@ pub unsafe extern "C" fn local_table_grow(
     table_index: LocalTableIndex,
     delta: u32,
 ) -> i32 {
-    let _ = table_index;
-    let _ = delta;
-    let _ = ctx;
-    unimplemented!()
+    match (*ctx.local_backing).tables[table_index].grow(delta) {
+        Ok(old) => old as i32,
+        Err(_) => -1,
+    }
 }
 
 pub unsafe extern "C" fn local_table_size(ctx: &vm::Ctx, table_index: LocalTableIndex) -> u32 {
-    let _ = table_index;
-    let _ = ctx;
-    unimplemented!()
+    (*ctx.local_backing).tables[table_index].size()
+}
+
+// +*****************************+
+// |     BULK MEMORY AND TABLE   |
+// +****************************+
+
+unsafe fn trap(ctx: &vm::Ctx, kind: WasmTrapInfo) -> ! {
+    (&*ctx.module)
+        .runnable_module
+        .do_early_trap(Box::new(TrapDetails::new(kind)))
+}
+
+/// Checks that `[start, start + len)` lies within `[0, bound)`.
+fn in_bounds(start: u32, len: u32, bound: usize) -> bool {
+    start as u64 + len as u64 <= bound as u64
+}
+
+unsafe fn memory_bytes(ctx: &vm::Ctx, memory_index: MemoryIndex) -> &mut [u8] {
+    let local_memory = match memory_index.local_or_import(&(*ctx.module).info) {
+        LocalOrImport::Local(local_memory_index) => {
+            *ctx.internal.memories.add(local_memory_index.index())
+        }
+        LocalOrImport::Import(import_memory_index) => *ctx
+            .internal
+            .imported_memories
+            .add(import_memory_index.index()),
+    };
+    slice::from_raw_parts_mut((*local_memory).base, (*local_memory).bound)
+}
+
+unsafe fn local_table(ctx: &vm::Ctx, table_index: TableIndex) -> *mut vm::LocalTable {
+    match table_index.local_or_import(&(*ctx.module).info) {
+        LocalOrImport::Local(local_table_index) => {
+            *ctx.internal.tables.add(local_table_index.index())
+        }
+        LocalOrImport::Import(import_table_index) => {
+            *ctx.internal.imported_tables.add(import_table_index.index())
+        }
+    }
+}
+
+unsafe fn table_elements(ctx: &vm::Ctx, table_index: TableIndex) -> &mut [vm::Anyfunc] {
+    let local_table = local_table(ctx, table_index);
+    slice::from_raw_parts_mut(
+        (*local_table).base as *mut vm::Anyfunc,
+        (*local_table).count,
+    )
+}
+
+pub unsafe extern "C" fn memory_copy(
+    ctx: &mut vm::Ctx,
+    memory_index: MemoryIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let memory = memory_bytes(ctx, memory_index);
+    if !in_bounds(src, len, memory.len()) || !in_bounds(dst, len, memory.len()) {
+        trap(ctx, WasmTrapInfo::MemoryOutOfBounds);
+    }
+    ptr::copy(
+        memory.as_ptr().add(src as usize),
+        memory.as_mut_ptr().add(dst as usize),
+        len as usize,
+    );
+}
+
+pub unsafe extern "C" fn memory_fill(
+    ctx: &mut vm::Ctx,
+    memory_index: MemoryIndex,
+    dst: u32,
+    value: u32,
+    len: u32,
+) {
+    let memory = memory_bytes(ctx, memory_index);
+    if !in_bounds(dst, len, memory.len()) {
+        trap(ctx, WasmTrapInfo::MemoryOutOfBounds);
+    }
+    ptr::write_bytes(
+        memory.as_mut_ptr().add(dst as usize),
+        value as u8,
+        len as usize,
+    );
+}
+
+/// Copies part of a passive data segment into memory. Active segments and
+/// dropped passive segments behave like empty segments.
+pub unsafe extern "C" fn memory_init(
+    ctx: &mut vm::Ctx,
+    memory_index: MemoryIndex,
+    segment: DataIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let data: &[u8] = if (*ctx.local_backing).passive_data.contains(&segment) {
+        &(*ctx.module).info.passive_data[&segment]
+    } else {
+        &[]
+    };
+    let memory = memory_bytes(ctx, memory_index);
+    if !in_bounds(src, len, data.len()) || !in_bounds(dst, len, memory.len()) {
+        trap(ctx, WasmTrapInfo::MemoryOutOfBounds);
+    }
+    memory[dst as usize..][..len as usize].copy_from_slice(&data[src as usize..][..len as usize]);
+}
+
+pub unsafe extern "C" fn data_drop(ctx: &mut vm::Ctx, segment: DataIndex) {
+    (*ctx.local_backing).passive_data.remove(&segment);
+}
+
+pub unsafe extern "C" fn table_copy(
+    ctx: &mut vm::Ctx,
+    table_index: TableIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let elements = table_elements(ctx, table_index);
+    if !in_bounds(src, len, elements.len()) || !in_bounds(dst, len, elements.len()) {
+        trap(ctx, WasmTrapInfo::TableOutOfBounds);
+    }
+    ptr::copy(
+        elements.as_ptr().add(src as usize),
+        elements.as_mut_ptr().add(dst as usize),
+        len as usize,
+    );
+}
+
+/// Copies part of a passive element segment into a table. Active segments and
+/// dropped passive segments behave like empty segments.
+pub unsafe extern "C" fn table_init(
+    ctx: &mut vm::Ctx,
+    table_index: TableIndex,
+    segment: ElemIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let module = &*ctx.module;
+    let segment_elements: &[_] = if (*ctx.local_backing).passive_elements.contains(&segment) {
+        &module.info.passive_elements[&segment]
+    } else {
+        &[]
+    };
+    let elements = table_elements(ctx, table_index);
+    if !in_bounds(src, len, segment_elements.len()) || !in_bounds(dst, len, elements.len()) {
+        trap(ctx, WasmTrapInfo::TableOutOfBounds);
+    }
+    let vmctx = ctx as *mut vm::Ctx;
+    for i in 0..len as usize {
+        elements[dst as usize + i] = func_index_to_anyfunc(
+            module,
+            &*ctx.import_backing,
+            vmctx,
+            segment_elements[src as usize + i],
+        );
+    }
+}
+
+pub unsafe extern "C" fn elem_drop(ctx: &mut vm::Ctx, segment: ElemIndex) {
+    (*ctx.local_backing).passive_elements.remove(&segment);
+}
+
+// +*****************************+
+// |      ATOMIC WAIT/NOTIFY     |
+// +****************************+
+
+/// Returns the address accessed by an atomic operator on `addr` with a
+/// static `offset`, trapping if it is out of bounds or not aligned to `size`.
+unsafe fn atomic_address(
+    ctx: &vm::Ctx,
+    memory_index: MemoryIndex,
+    offset: u32,
+    addr: u32,
+    size: u32,
+) -> *mut u8 {
+    let memory = memory_bytes(ctx, memory_index);
+    let effective = addr as u64 + offset as u64;
+    if effective + size as u64 > memory.len() as u64 {
+        trap(ctx, WasmTrapInfo::MemoryOutOfBounds);
+    }
+    if effective % size as u64 != 0 {
+        trap(ctx, WasmTrapInfo::MisalignedAtomicAccess);
+    }
+    memory.as_mut_ptr().add(effective as usize)
+}
+
+unsafe fn memory_is_shared(ctx: &vm::Ctx, memory_index: MemoryIndex) -> bool {
+    let info = &(*ctx.module).info;
+    match memory_index.local_or_import(info) {
+        LocalOrImport::Local(local_memory_index) => info.memories[local_memory_index].shared,
+        LocalOrImport::Import(import_memory_index) => {
+            info.imported_memories[import_memory_index].1.shared
+        }
+    }
+}
+
+/// A negative timeout, in nanoseconds, means waiting forever.
+fn wait_timeout(timeout: i64) -> Option<Duration> {
+    if timeout < 0 {
+        None
+    } else {
+        Some(Duration::from_nanos(timeout as u64))
+    }
+}
+
+pub unsafe extern "C" fn memory_atomic_wait32(
+    ctx: &mut vm::Ctx,
+    memory_index: MemoryIndex,
+    offset: u32,
+    addr: u32,
+    expected: u32,
+    timeout: i64,
+) -> u32 {
+    let ptr = atomic_address(ctx, memory_index, offset, addr, 4);
+    if !memory_is_shared(ctx, memory_index) {
+        trap(ctx, WasmTrapInfo::WaitOnUnsharedMemory);
+    }
+    let value = &*(ptr as *const AtomicU32);
+    wait::wait(
+        ptr as usize,
+        || value.load(Ordering::SeqCst) == expected,
+        wait_timeout(timeout),
+    ) as u32
+}
+
+pub unsafe extern "C" fn memory_atomic_wait64(
+    ctx: &mut vm::Ctx,
+    memory_index: MemoryIndex,
+    offset: u32,
+    addr: u32,
+    expected: u64,
+    timeout: i64,
+) -> u32 {
+    let ptr = atomic_address(ctx, memory_index, offset, addr, 8);
+    if !memory_is_shared(ctx, memory_index) {
+        trap(ctx, WasmTrapInfo::WaitOnUnsharedMemory);
+    }
+    let value = &*(ptr as *const AtomicU64);
+    wait::wait(
+        ptr as usize,
+        || value.load(Ordering::SeqCst) == expected,
+        wait_timeout(timeout),
+    ) as u32
+}
+
+/// Wakes up to `count` threads waiting on the address. Unshared memories
+/// can't have waiters, so notifying them always returns 0.
+pub unsafe extern "C" fn memory_atomic_notify(
+    ctx: &mut vm::Ctx,
+    memory_index: MemoryIndex,
+    offset: u32,
+    addr: u32,
+    count: u32,
+) -> u32 {
+    let ptr = atomic_address(ctx, memory_index, offset, addr, 4);
+    if !memory_is_shared(ctx, memory_index) {
+        return 0;
+    }
+    wait::notify(ptr as usize, count)
+}
+
+// +*****************************+
+// |       REFERENCE TYPES       |
+// +****************************+
+
+/// Returns the table element that the reference `bits` stands for, trapping if
+/// the reference is dead.
+unsafe fn reference_to_anyfunc(ctx: &vm::Ctx, bits: u64) -> vm::Anyfunc {
+    match Reference::from_bits(bits).to_anyfunc() {
+        Some(anyfunc) => anyfunc,
+        None => trap(ctx, WasmTrapInfo::DeadReference),
+    }
+}
+
+pub unsafe extern "C" fn table_get(ctx: &mut vm::Ctx, table_index: TableIndex, index: u32) -> u64 {
+    match table_elements(ctx, table_index).get(index as usize) {
+        Some(&anyfunc) => Reference::from_anyfunc(anyfunc).to_bits(),
+        None => trap(ctx, WasmTrapInfo::TableOutOfBounds),
+    }
+}
+
+pub unsafe extern "C" fn table_set(
+    ctx: &mut vm::Ctx,
+    table_index: TableIndex,
+    index: u32,
+    value: u64,
+) {
+    let anyfunc = reference_to_anyfunc(ctx, value);
+    match table_elements(ctx, table_index).get_mut(index as usize) {
+        Some(element) => *element = anyfunc,
+        None => trap(ctx, WasmTrapInfo::TableOutOfBounds),
+    }
+}
+
+pub unsafe extern "C" fn table_size(ctx: &mut vm::Ctx, table_index: TableIndex) -> u32 {
+    (*local_table(ctx, table_index)).count as u32
+}
+
+/// Grows a table by `delta` elements set to `value`, returning the previous
+/// size or `-1` if the table can't grow that much.
+pub unsafe extern "C" fn table_grow(
+    ctx: &mut vm::Ctx,
+    table_index: TableIndex,
+    value: u64,
+    delta: u32,
+) -> i32 {
+    let element = reference_to_anyfunc(ctx, value);
+    let old = match table::grow_local(&mut *local_table(ctx, table_index), delta) {
+        Some(old) => old,
+        None => return -1,
+    };
+    for slot in &mut table_elements(ctx, table_index)[old as usize..] {
+        *slot = element;
+    }
+    old as i32
+}
+
+pub unsafe extern "C" fn table_fill(
+    ctx: &mut vm::Ctx,
+    table_index: TableIndex,
+    dst: u32,
+    value: u64,
+    len: u32,
+) {
+    let elements = table_elements(ctx, table_index);
+    if !in_bounds(dst, len, elements.len()) {
+        trap(ctx, WasmTrapInfo::TableOutOfBounds);
+    }
+    let element = reference_to_anyfunc(ctx, value);
+    for slot in &mut elements[dst as usize..][..len as usize] {
+        *slot = element;
+    }
+}
+
+// +*****************************+
+// |          BREAKPOINT         |
+// +****************************+
+
+/// Runs a breakpoint handler emitted by a middleware, unwinding with the error
+/// it returns, if any.
+pub unsafe extern "C" fn breakpoint(ctx: &mut vm::Ctx, handler: *const BreakpointHandler) {
+    if let Err(e) = (*handler)(BreakpointInfo { fault: None }) {
+        (&*ctx.module).runnable_module.do_early_trap(e)
+    }
+}
+
+// +*****************************+
+// |        INTERNAL TRAP        |
+// +****************************+
+
+/// Unwinds with the error of the `InternalTrap` whose code is `code`, as raised by
+/// a middleware.
+pub unsafe extern "C" fn internal_trap(ctx: &mut vm::Ctx, code: u32) {
+    let error: Box<dyn Any> = match InternalTrap::from_code(code) {
+        #[cfg(all(unix, target_arch = "x86_64"))]
+        Some(InternalTrap::Interrupted) => {
+            crate::fault::clear_interrupt(ctx);
+            Box::new(crate::fault::ExecutionInterrupted)
+        }
+        Some(InternalTrap::ExecutionLimitExceeded) => Box::new(ExecutionLimitExceededError),
+        _ => Box::new(TrapDetails::new(WasmTrapInfo::Unknown)),
+    };
+    (&*ctx.module).runnable_module.do_early_trap(error)
 }
@@ -89,7 +89,18 @@ impl Cache for FileSystemCache {
     }
 
     fn load_with_backend(&self, key: WasmHash, backend: Backend) -> Result<Module, CacheError> {
-        let filename = key.encode();
+        self.load_with_fingerprint(key, backend, "")
+    }
+
+    fn load_with_fingerprint(
+        &self,
+        key: WasmHash,
+        backend: Backend,
+        middleware_fingerprint: &str,
+    ) -> Result<Module, CacheError> {
+        let filename = key
+            .with_middleware_fingerprint(middleware_fingerprint)
+            .encode();
         let mut new_path_buf = self.path.clone();
         new_path_buf.push(backend.to_string());
         new_path_buf.push(filename);
@@ -97,6 +108,13 @@ impl Cache for FileSystemCache {
         let mmap = unsafe { Mmap::map(&file)? };
 
         let serialized_cache = Artifact::deserialize(&mmap[..])?;
+        let found = &serialized_cache.info().middleware_fingerprint;
+        if found != middleware_fingerprint {
+            return Err(CacheError::MiddlewareMismatch {
+                expected: middleware_fingerprint.to_string(),
+                found: found.clone(),
+            });
+        }
         unsafe {
             wasmer_runtime_core::load_cache_with(
                 serialized_cache,
@@ -108,7 +126,10 @@ impl Cache for FileSystemCache {
     }
 
     fn store(&mut self, key: WasmHash, module: Module) -> Result<(), CacheError> {
-        let filename = key.encode();
+        // Modules compiled with middlewares are stored apart from the others.
+        let filename = key
+            .with_middleware_fingerprint(&module.info().middleware_fingerprint)
+            .encode();
         let backend_str = module.info().backend.to_string();
         let mut new_path_buf = self.path.clone();
         new_path_buf.push(backend_str);
@@ -174,4 +195,228 @@ mod tests {
         assert_eq!(value, 43);
     }
 
+    #[test]
+    fn test_cache_without_fingerprints() {
+        struct NoFingerprints;
+
+        impl Cache for NoFingerprints {
+            type LoadError = CacheError;
+            type StoreError = CacheError;
+
+            fn load(&self, _key: WasmHash) -> Result<Module, CacheError> {
+                Err(CacheError::Unknown("load".to_string()))
+            }
+
+            fn load_with_backend(
+                &self,
+                _key: WasmHash,
+                _backend: Backend,
+            ) -> Result<Module, CacheError> {
+                Err(CacheError::Unknown("load_with_backend".to_string()))
+            }
+
+            fn store(&mut self, _key: WasmHash, _module: Module) -> Result<(), CacheError> {
+                Ok(())
+            }
+        }
+
+        let key = WasmHash::generate(b"");
+        match NoFingerprints.load_with_fingerprint(key, Backend::default(), "") {
+            Err(CacheError::Unknown(ref method)) if method == "load_with_backend" => {}
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+        match NoFingerprints.load_with_fingerprint(key, Backend::default(), "metering") {
+            Err(CacheError::MiddlewareMismatch { ref expected, .. }) if expected == "metering" => {}
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "singlepass"))]
+mod singlepass_tests {
+
+    use super::*;
+
+    #[test]
+    fn test_file_system_cache_run_singlepass() {
+        use crate::{compile_with, imports, Func};
+        use wabt::wat2wasm;
+        use wasmer_singlepass_backend::SinglePassCompiler;
+
+        static WAT: &'static str = r#"
+            (module
+              (type $t0 (func (param i32) (result i32)))
+              (func $add_one (export "add_one") (type $t0) (param $p0 i32) (result i32)
+                get_local $p0
+                call $add_one_inner)
+              (func $add_one_inner (type $t0) (param $p0 i32) (result i32)
+                get_local $p0
+                i32.const 1
+                i32.add))
+        "#;
+
+        let wasm = wat2wasm(WAT).unwrap();
+
+        let module = compile_with(&wasm, &SinglePassCompiler::new()).unwrap();
+
+        let cache_dir = tempfile::tempdir().unwrap();
+
+        let mut fs_cache = unsafe {
+            FileSystemCache::new(cache_dir.path())
+                .map_err(|e| format!("Cache error: {:?}", e))
+                .unwrap()
+        };
+        // store module
+        let key = WasmHash::generate(&wasm);
+        fs_cache.store(key, module.clone()).unwrap();
+
+        // load module
+        let cached_module = fs_cache.load_with_backend(key, Backend::Singlepass).unwrap();
+
+        let import_object = imports! {};
+        let instance = cached_module.instantiate(&import_object).unwrap();
+        let add_one: Func<i32, i32> = instance.func("add_one").unwrap();
+
+        let value = add_one.call(42).unwrap();
+
+        // verify it works
+        assert_eq!(value, 43);
+    }
+
+    #[test]
+    fn test_file_system_cache_middleware_fingerprint() {
+        use crate::{compile_with, error::RuntimeError, imports, Func};
+        use wabt::wat2wasm;
+        use wasmer_middleware_common::metering::{
+            set_execution_limit, set_points_used, ExecutionLimitExceededError, Metering,
+        };
+        use wasmer_runtime_core::{
+            backend::Compiler,
+            codegen::{MiddlewareChain, StreamingCompiler},
+        };
+        use wasmer_singlepass_backend::ModuleCodeGenerator as SinglePassMCG;
+
+        fn metering_compiler<F: Fn() -> Metering + 'static>(metering: F) -> impl Compiler {
+            let c: StreamingCompiler<SinglePassMCG, _, _, _, _> =
+                StreamingCompiler::new(move || {
+                    let mut chain = MiddlewareChain::new();
+                    chain.push(metering());
+                    chain
+                });
+            c
+        }
+
+        static WAT: &'static str = r#"
+            (module
+              (func (export "one") (result i32)
+                i32.const 1))
+        "#;
+
+        let wasm = wat2wasm(WAT).unwrap();
+        let key = WasmHash::generate(&wasm);
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut fs_cache = unsafe { FileSystemCache::new(cache_dir.path()).unwrap() };
+
+        let compiler = metering_compiler(|| Metering::new(100));
+        let fingerprint = compiler.middleware_fingerprint();
+        let module = compile_with(&wasm, &compiler).unwrap();
+        fs_cache.store(key, module).unwrap();
+
+        // Modules compiled with middlewares are only found with the same fingerprint.
+        assert!(fs_cache
+            .load_with_backend(key, Backend::Singlepass)
+            .is_err());
+        let other_fingerprint =
+            metering_compiler(|| Metering::with_cost_function(100, "two", |_| 2))
+                .middleware_fingerprint();
+        assert!(fs_cache
+            .load_with_fingerprint(key, Backend::Singlepass, &other_fingerprint)
+            .is_err());
+
+        // The limit is set per instance, so it doesn't keep the module from being found.
+        let limit_fingerprint = metering_compiler(|| Metering::new(1)).middleware_fingerprint();
+        assert_eq!(limit_fingerprint, fingerprint);
+        let cached_module = fs_cache
+            .load_with_fingerprint(key, Backend::Singlepass, &limit_fingerprint)
+            .unwrap();
+        let mut instance = cached_module.instantiate(&imports! {}).unwrap();
+        set_execution_limit(&mut instance, 1000);
+        set_points_used(&mut instance, 0);
+        {
+            let one: Func<(), i32> = instance.func("one").unwrap();
+            assert_eq!(one.call(), Ok(1));
+        }
+        set_execution_limit(&mut instance, 0);
+        set_points_used(&mut instance, 0);
+        let one: Func<(), i32> = instance.func("one").unwrap();
+        match one.call() {
+            Err(RuntimeError::Error { data }) => {
+                assert!(data.downcast_ref::<ExecutionLimitExceededError>().is_some());
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Artifacts compiled with other middlewares are refused even under the right key.
+        let backend_dir = cache_dir.path().join(Backend::Singlepass.to_string());
+        std::fs::copy(
+            backend_dir.join(key.with_middleware_fingerprint(&fingerprint).encode()),
+            backend_dir.join(key.with_middleware_fingerprint(&other_fingerprint).encode()),
+        )
+        .unwrap();
+        match fs_cache.load_with_fingerprint(key, Backend::Singlepass, &other_fingerprint) {
+            Err(CacheError::MiddlewareMismatch { expected, found }) => {
+                assert_eq!(expected, other_fingerprint);
+                assert_eq!(found, fingerprint);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_modules_with_breakpoints_are_not_cached() {
+        use crate::compile_with;
+        use wabt::wat2wasm;
+        use wasmer_runtime_core::{
+            codegen::{
+                Event, EventSink, FunctionMiddleware, InternalEvent, MiddlewareChain,
+                StreamingCompiler,
+            },
+            module::ModuleInfo,
+        };
+        use wasmer_singlepass_backend::ModuleCodeGenerator as SinglePassMCG;
+
+        struct Breakpoints;
+
+        impl FunctionMiddleware for Breakpoints {
+            type Error = String;
+            fn feed_event<'a, 'b: 'a>(
+                &mut self,
+                op: Event<'a, 'b>,
+                _module_info: &ModuleInfo,
+                sink: &mut EventSink<'a, 'b>,
+            ) -> Result<(), Self::Error> {
+                if let Event::Internal(InternalEvent::FunctionBegin(_)) = op {
+                    sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(|_| {
+                        Ok(())
+                    }))));
+                }
+                sink.push(op);
+                Ok(())
+            }
+        }
+
+        let wasm = wat2wasm("(module (func (export \"nop\")))").unwrap();
+        let compiler: StreamingCompiler<SinglePassMCG, _, _, _, _> = StreamingCompiler::new(|| {
+            let mut chain = MiddlewareChain::new();
+            chain.push(Breakpoints);
+            chain
+        });
+        let module = compile_with(&wasm, &compiler).unwrap();
+
+        // The breakpoint handlers can't be restored from an artifact.
+        match module.cache() {
+            Err(CacheError::SerializeError(_)) => {}
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
 }
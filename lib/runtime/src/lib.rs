@@ -86,9 +86,11 @@
 pub use wasmer_runtime_core::backend::Backend;
 pub use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
 pub use wasmer_runtime_core::export::Export;
+#[cfg(all(unix, target_arch = "x86_64"))]
+pub use wasmer_runtime_core::fault::InterruptHandle;
 pub use wasmer_runtime_core::global::Global;
 pub use wasmer_runtime_core::import::ImportObject;
-pub use wasmer_runtime_core::instance::{DynFunc, Instance};
+pub use wasmer_runtime_core::instance::{DynFunc, Instance, InstanceConfig};
 pub use wasmer_runtime_core::memory::Memory;
 pub use wasmer_runtime_core::module::Module;
 pub use wasmer_runtime_core::table::Table;
@@ -96,11 +98,15 @@ pub use wasmer_runtime_core::types::Value;
 pub use wasmer_runtime_core::vm::Ctx;
 
 pub use wasmer_runtime_core::Func;
+#[cfg(all(unix, target_arch = "x86_64"))]
+pub use wasmer_runtime_core::DynamicFunc;
 pub use wasmer_runtime_core::{compile_with, validate};
 pub use wasmer_runtime_core::{func, imports};
 
 pub mod memory {
-    pub use wasmer_runtime_core::memory::{Atomically, Memory, MemoryView};
+    pub use wasmer_runtime_core::memory::{
+        ptr, Atomic, Atomically, GuestAllocator, Memory, MemoryView, SharedMemory,
+    };
 }
 
 pub mod wasm {
@@ -108,13 +114,14 @@ pub mod wasm {
     pub use wasmer_runtime_core::global::Global;
     pub use wasmer_runtime_core::table::Table;
     pub use wasmer_runtime_core::types::{
-        FuncSig, GlobalDescriptor, MemoryDescriptor, TableDescriptor, Type, Value,
+        FuncSig, GlobalDescriptor, MemoryDescriptor, TableDescriptor, Type, Value, ValueType,
     };
 }
 
 pub mod error {
     pub use wasmer_runtime_core::cache::Error as CacheError;
     pub use wasmer_runtime_core::error::*;
+    pub use wasmer_runtime_core::typed_func::WasmTrapInfo;
 }
 
 pub mod units {
@@ -0,0 +1,140 @@
+#[macro_use]
+mod utils;
+
+use utils::Proposal;
+use wasmer_runtime::{imports, Backend, Instance};
+
+static WAT: &'static str = r#"
+    (module
+    (type $ret_i32 (func (result i32)))
+    (memory 1)
+    (data (i32.const 0) "\01\02\03\04")
+    (data passive "hello")
+    (table 4 funcref)
+    (elem (i32.const 0) $one)
+    (elem passive funcref (ref.func $two) (ref.func $three))
+    (func $one (result i32)
+        i32.const 1
+    )
+    (func $two (result i32)
+        i32.const 2
+    )
+    (func $three (result i32)
+        i32.const 3
+    )
+    (func (export "load8") (param i32) (result i32)
+        get_local 0
+        i32.load8_u
+    )
+    (func (export "memory_copy") (param i32 i32 i32)
+        get_local 0
+        get_local 1
+        get_local 2
+        memory.copy
+    )
+    (func (export "memory_fill") (param i32 i32 i32)
+        get_local 0
+        get_local 1
+        get_local 2
+        memory.fill
+    )
+    (func (export "memory_init") (param i32 i32 i32)
+        get_local 0
+        get_local 1
+        get_local 2
+        memory.init 1
+    )
+    (func (export "data_drop")
+        data.drop 1
+    )
+    (func (export "table_copy") (param i32 i32 i32)
+        get_local 0
+        get_local 1
+        get_local 2
+        table.copy
+    )
+    (func (export "table_init") (param i32 i32 i32)
+        get_local 0
+        get_local 1
+        get_local 2
+        table.init 1
+    )
+    (func (export "elem_drop")
+        elem.drop 1
+    )
+    (func (export "call") (param i32) (result i32)
+        get_local 0
+        call_indirect (type $ret_i32)
+    )
+    )
+"#;
+
+fn check_memory_ops(instance: &Instance) {
+    let load8 = instance.func::<i32, i32>("load8").unwrap();
+    let memory_copy = instance.func::<(i32, i32, i32), ()>("memory_copy").unwrap();
+    let memory_fill = instance.func::<(i32, i32, i32), ()>("memory_fill").unwrap();
+    let memory_init = instance.func::<(i32, i32, i32), ()>("memory_init").unwrap();
+    let data_drop = instance.func::<(), ()>("data_drop").unwrap();
+
+    // Overlapping copies behave like `memmove`.
+    memory_copy.call(1, 0, 3).unwrap();
+    assert_eq!(load8.call(0), Ok(1));
+    assert_eq!(load8.call(1), Ok(1));
+    assert_eq!(load8.call(3), Ok(3));
+    assert!(memory_copy.call(65535, 0, 2).is_err());
+
+    memory_fill.call(10, 0xff, 2).unwrap();
+    assert_eq!(load8.call(10), Ok(0xff));
+    assert_eq!(load8.call(11), Ok(0xff));
+    assert_eq!(load8.call(12), Ok(0));
+    assert!(memory_fill.call(65536, 0, 1).is_err());
+
+    memory_init.call(100, 1, 3).unwrap();
+    assert_eq!(load8.call(100), Ok(b'e' as i32));
+    assert_eq!(load8.call(102), Ok(b'l' as i32));
+    assert!(memory_init.call(100, 3, 3).is_err());
+
+    // A dropped segment behaves like an empty one.
+    data_drop.call().unwrap();
+    data_drop.call().unwrap();
+    memory_init.call(100, 0, 0).unwrap();
+    assert!(memory_init.call(100, 0, 1).is_err());
+}
+
+fn check_table_ops(instance: &Instance) {
+    let call = instance.func::<i32, i32>("call").unwrap();
+    let table_copy = instance.func::<(i32, i32, i32), ()>("table_copy").unwrap();
+    let table_init = instance.func::<(i32, i32, i32), ()>("table_init").unwrap();
+    let elem_drop = instance.func::<(), ()>("elem_drop").unwrap();
+
+    assert_eq!(call.call(0), Ok(1));
+    assert!(call.call(1).is_err());
+
+    table_init.call(1, 0, 2).unwrap();
+    assert_eq!(call.call(1), Ok(2));
+    assert_eq!(call.call(2), Ok(3));
+    assert!(table_init.call(3, 0, 2).is_err());
+
+    table_copy.call(3, 1, 1).unwrap();
+    assert_eq!(call.call(3), Ok(2));
+    assert!(table_copy.call(3, 0, 2).is_err());
+
+    elem_drop.call().unwrap();
+    table_init.call(0, 0, 0).unwrap();
+    assert!(table_init.call(0, 0, 1).is_err());
+}
+
+fn check_bulk_memory(backend: Backend) {
+    let wasm = Proposal::BulkMemory.wat2wasm(WAT);
+    let module = Proposal::BulkMemory.compile(&wasm, backend).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    check_memory_ops(&instance);
+    check_table_ops(&instance);
+}
+
+#[test]
+fn bulk_memory_requires_feature() {
+    assert!(wasmer_runtime::compile(&Proposal::BulkMemory.wat2wasm(WAT)).is_err());
+}
+
+backend_tests!(bulk_memory, check_bulk_memory);
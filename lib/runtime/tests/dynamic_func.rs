@@ -0,0 +1,134 @@
+#![cfg(all(unix, target_arch = "x86_64"))]
+
+use std::{cell::Cell, rc::Rc};
+use wasmer_runtime::{
+    error::{DynamicFuncError, RuntimeError},
+    imports, DynamicFunc, Value,
+};
+use wasmer_runtime_core::types::{FuncSig, Type};
+
+static WAT: &'static str = r#"
+    (module
+    (import "env" "sum" (func $sum (param i32 i64 f32 f64 i32 i32 i32 i32 f64) (result f64)))
+    (import "env" "count" (func $count))
+    (import "env" "fail" (func $fail (param i32) (result i32)))
+    (func (export "sum") (result f64)
+        i32.const 1
+        i64.const 2
+        f32.const 0.5
+        f64.const 0.25
+        i32.const 3
+        i32.const 4
+        i32.const 5
+        i32.const -6
+        f64.const 8
+        call $sum
+    )
+    (func (export "count")
+        call $count
+        call $count
+    )
+    (func (export "fail") (param i32) (result i32)
+        get_local 0
+        call $fail
+    )
+    )
+"#;
+
+#[test]
+fn dynamic_func_imports() {
+    let wasm = wabt::wat2wasm(WAT).unwrap();
+    let module = wasmer_runtime::compile(&wasm).unwrap();
+
+    let counter = Rc::new(Cell::new(0));
+    let count = {
+        let counter = counter.clone();
+        DynamicFunc::new(FuncSig::new(vec![], vec![]), move |_ctx, args| {
+            assert!(args.is_empty());
+            counter.set(counter.get() + 1);
+            Ok::<_, ()>(vec![])
+        })
+        .unwrap()
+    };
+    let sum = DynamicFunc::new(
+        FuncSig::new(
+            vec![
+                Type::I32,
+                Type::I64,
+                Type::F32,
+                Type::F64,
+                Type::I32,
+                Type::I32,
+                Type::I32,
+                Type::I32,
+                Type::F64,
+            ],
+            vec![Type::F64],
+        ),
+        |_ctx, args| {
+            let sum = args
+                .iter()
+                .map(|arg| match *arg {
+                    Value::I32(x) => x as f64,
+                    Value::I64(x) => x as f64,
+                    Value::F32(x) => x as f64,
+                    Value::F64(x) => x,
+                    _ => unreachable!(),
+                })
+                .sum();
+            Ok::<_, ()>(vec![Value::F64(sum)])
+        },
+    )
+    .unwrap();
+    let fail = DynamicFunc::new(
+        FuncSig::new(vec![Type::I32], vec![Type::I32]),
+        |_ctx, args| match args {
+            [Value::I32(0)] => Err("zero".to_string()),
+            [Value::I32(1)] => Ok(vec![Value::I64(1)]),
+            [Value::I32(x)] => Ok(vec![Value::I32(x * 2)]),
+            _ => unreachable!(),
+        },
+    )
+    .unwrap();
+
+    let instance = module
+        .instantiate(&imports! {
+            "env" => {
+                "sum" => sum,
+                "count" => count,
+                "fail" => fail,
+            },
+        })
+        .unwrap();
+
+    let sum = instance.func::<(), f64>("sum").unwrap();
+    assert_eq!(sum.call(), Ok(17.75));
+
+    instance.func::<(), ()>("count").unwrap().call().unwrap();
+    assert_eq!(counter.get(), 2);
+
+    let fail = instance.func::<i32, i32>("fail").unwrap();
+    assert_eq!(fail.call(21), Ok(42));
+    match fail.call(0) {
+        Err(RuntimeError::Error { data }) => {
+            assert_eq!(data.downcast_ref::<String>().unwrap(), "zero")
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+    // Results that don't match the signature are reported as errors.
+    assert!(fail.call(1).is_err());
+}
+
+#[test]
+fn dynamic_func_rejects_v128() {
+    let signatures = vec![
+        FuncSig::new(vec![Type::V128], vec![]),
+        FuncSig::new(vec![Type::I32], vec![Type::V128]),
+    ];
+    for signature in signatures {
+        match DynamicFunc::new(signature, |_ctx, _args| Ok::<_, ()>(vec![])) {
+            Err(DynamicFuncError::UnsupportedType(Type::V128)) => {}
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use wasmer_runtime::{imports, Module};
+use wasmer_runtime_core::backend::{CompilerConfig, Features};
+
+static WAT: &'static str = r#"
+    (module
+    (func (export "extend8") (param i32) (result i32)
+        get_local 0
+        i32.extend8_s
+    )
+    (func (export "trunc_sat") (param f32) (result i32)
+        get_local 0
+        i32.trunc_sat_f32_s
+    )
+    )
+"#;
+
+fn wasm() -> Vec<u8> {
+    let mut features = wabt::Features::new();
+    features.enable_sign_extension();
+    features.enable_sat_float_to_int();
+    wabt::wat2wasm_with_features(WAT, features).unwrap()
+}
+
+fn compile(features: Features) -> Result<Module, wasmer_runtime::error::CompileError> {
+    wasmer_runtime::compile_with_config(
+        &wasm(),
+        CompilerConfig {
+            features,
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn sign_extension_and_sat_float_to_int_enabled_by_default() {
+    let module = wasmer_runtime::compile(&wasm()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let extend8 = instance.func::<i32, i32>("extend8").unwrap();
+    let trunc_sat = instance.func::<f32, i32>("trunc_sat").unwrap();
+
+    assert_eq!(extend8.call(0x80), Ok(-128));
+    assert_eq!(trunc_sat.call(3e9), Ok(std::i32::MAX));
+    assert_eq!(trunc_sat.call(std::f32::NAN), Ok(0));
+}
+
+#[test]
+fn sign_extension_can_be_disabled() {
+    assert!(compile(Features {
+        sign_extension: false,
+        ..Default::default()
+    })
+    .is_err());
+}
+
+#[test]
+fn sat_float_to_int_can_be_disabled() {
+    assert!(compile(Features {
+        sat_float_to_int: false,
+        ..Default::default()
+    })
+    .is_err());
+}
@@ -0,0 +1,240 @@
+use wasmer_runtime::{
+    compile,
+    error::GuestMemoryError,
+    imports,
+    memory::{
+        ptr::{Array, Item, WasmPtr},
+        GuestAllocator,
+    },
+    wasm::ValueType,
+    Func, Instance,
+};
+
+static WAT: &'static str = r#"
+    (module
+    (memory (export "memory") 1)
+    (global $next (mut i32) (i32.const 1024))
+    (global $frees (mut i32) (i32.const 0))
+    (global $freed_size (mut i32) (i32.const 0))
+    (func (export "alloc") (param $size i32) (result i32)
+        (local $ptr i32)
+        get_local $size
+        i32.const 60000
+        i32.gt_u
+        if
+            i32.const 0
+            return
+        end
+        get_global $next
+        tee_local $ptr
+        get_local $size
+        i32.add
+        set_global $next
+        get_local $ptr
+    )
+    (func (export "free") (param i32)
+        get_global $frees
+        i32.const 1
+        i32.add
+        set_global $frees
+    )
+    (func (export "sized_free") (param i32 i32)
+        get_local 1
+        set_global $freed_size
+    )
+    (func (export "no_result") (param i32))
+    (func (export "frees") (result i32)
+        get_global $frees
+    )
+    (func (export "freed_size") (result i32)
+        get_global $freed_size
+    )
+    (func (export "sum") (param $ptr i32) (result i32)
+        (local $end i32)
+        (local $sum i32)
+        get_local $ptr
+        get_local $ptr
+        i32.load
+        i32.add
+        i32.const 4
+        i32.add
+        set_local $end
+        get_local $ptr
+        i32.const 4
+        i32.add
+        set_local $ptr
+        block $done
+            loop $continue
+                get_local $ptr
+                get_local $end
+                i32.ge_u
+                br_if $done
+                get_local $sum
+                get_local $ptr
+                i32.load8_u
+                i32.add
+                set_local $sum
+                get_local $ptr
+                i32.const 1
+                i32.add
+                set_local $ptr
+                br $continue
+            end
+        end
+        get_local $sum
+    )
+    )
+"#;
+
+fn instantiate() -> Instance {
+    let wasm = wabt::wat2wasm(WAT).unwrap();
+    compile(&wasm).unwrap().instantiate(&imports! {}).unwrap()
+}
+
+#[derive(ValueType, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+struct Point {
+    x: i32,
+    y: i32,
+    tag: u16,
+    flags: u16,
+}
+
+#[test]
+fn bulk_reads_and_writes_are_bounds_checked() {
+    let instance = instantiate();
+    let memory = instance.context().memory(0);
+    let view = memory.view::<u8>();
+    assert_eq!(view.len(), 65536);
+
+    assert_eq!(view.write_bytes(100, b"hello"), Some(()));
+    let mut buf = [0; 5];
+    assert_eq!(view.read_bytes(100, &mut buf), Some(()));
+    assert_eq!(&buf, b"hello");
+    assert_eq!(view.write_bytes(65534, b"abc"), None);
+    assert_eq!(view.read_bytes(65534, &mut buf), None);
+    assert_eq!(view.read_bytes(usize::max_value(), &mut buf), None);
+
+    let ptr: WasmPtr<u8, Array> = WasmPtr::new(100);
+    assert_eq!(ptr.read_bytes(memory, 5), Some(b"hello".to_vec()));
+    assert_eq!(WasmPtr::<u8, Array>::new(65535).read_bytes(memory, 2), None);
+    assert_eq!(ptr.read_bytes(memory, u32::max_value()), None);
+    // Nothing is written when the buffer doesn't fit.
+    assert_eq!(
+        WasmPtr::<u8, Array>::new(65534).write_bytes(memory, b"xyz"),
+        None
+    );
+    assert_eq!(view[65534].get(), 0);
+}
+
+#[test]
+fn length_prefixed_buffers() {
+    let instance = instantiate();
+    let memory = instance.context().memory(0);
+
+    let ptr: WasmPtr<u8, Array> = WasmPtr::new(200);
+    assert_eq!(ptr.write_length_prefixed(memory, b"wasmer"), Some(()));
+    assert_eq!(ptr.read_length_prefixed(memory), Some(b"wasmer".to_vec()));
+    assert_eq!(ptr.read_bytes(memory, 4), Some(vec![6, 0, 0, 0]));
+
+    let end: WasmPtr<u8, Array> = WasmPtr::new(65530);
+    assert_eq!(end.write_length_prefixed(memory, b"wasmer"), None);
+    assert_eq!(end.read_length_prefixed(memory), Some(vec![]));
+    assert_eq!(end.write_length_prefixed(memory, b"ab"), Some(()));
+    // The length points past the end of the memory.
+    memory.view::<u8>()[65530].set(3);
+    assert_eq!(end.read_length_prefixed(memory), None);
+}
+
+#[test]
+fn guest_allocator() {
+    let mut instance = instantiate();
+    let allocator = GuestAllocator::new("alloc", "free");
+
+    let ptr = allocator
+        .write_length_prefixed(instance.context_mut(), &[1, 2, 3, 250])
+        .unwrap();
+    assert_eq!(ptr.offset(), 1024);
+    let sum: Func<WasmPtr<u8, Array>, i32> = instance.func("sum").unwrap();
+    assert_eq!(sum.call(ptr), Ok(256));
+    allocator
+        .free_length_prefixed(instance.context_mut(), ptr)
+        .unwrap();
+
+    let ptr = allocator
+        .write_bytes(instance.context_mut(), b"bytes")
+        .unwrap();
+    assert_eq!(ptr.offset(), 1032);
+    assert_eq!(
+        ptr.read_bytes(instance.context().memory(0), 5),
+        Some(b"bytes".to_vec())
+    );
+    allocator.free(instance.context_mut(), ptr, 5).unwrap();
+    let frees: Func<(), i32> = instance.func("frees").unwrap();
+    assert_eq!(frees.call(), Ok(2));
+
+    match allocator.write_bytes(instance.context_mut(), &[0; 60001]) {
+        Err(GuestMemoryError::AllocationFailed { size: 60001 }) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn guest_allocator_protocols() {
+    let mut instance = instantiate();
+
+    let allocator = GuestAllocator::new("alloc", "sized_free").with_sized_free();
+    let ptr = allocator
+        .write_length_prefixed(instance.context_mut(), b"abc")
+        .unwrap();
+    allocator
+        .free_length_prefixed(instance.context_mut(), ptr)
+        .unwrap();
+    let freed_size: Func<(), i32> = instance.func("freed_size").unwrap();
+    assert_eq!(freed_size.call(), Ok(7));
+
+    let allocator = GuestAllocator::without_free("alloc");
+    let ptr = allocator
+        .write_bytes(instance.context_mut(), b"abc")
+        .unwrap();
+    allocator.free(instance.context_mut(), ptr, 3).unwrap();
+    let frees: Func<(), i32> = instance.func("frees").unwrap();
+    assert_eq!(frees.call(), Ok(0));
+
+    let allocator = GuestAllocator::without_free("no_result");
+    match allocator.alloc(instance.context_mut(), 3) {
+        Err(GuestMemoryError::UnexpectedResults { ref results, .. }) if results.is_empty() => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let allocator = GuestAllocator::without_free("missing");
+    match allocator.alloc(instance.context_mut(), 3) {
+        Err(GuestMemoryError::Call(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn derived_value_types() {
+    let instance = instantiate();
+    let memory = instance.context().memory(0);
+    let point = Point {
+        x: -1,
+        y: 2,
+        tag: 3,
+        flags: 4,
+    };
+
+    let ptr: WasmPtr<Point, Item> = WasmPtr::new(64);
+    ptr.deref(memory).unwrap().set(point);
+    assert_eq!(ptr.deref(memory).unwrap().get(), point);
+
+    let bytes = WasmPtr::<u8, Array>::new(64)
+        .read_bytes(memory, 12)
+        .unwrap();
+    assert_eq!(bytes, [255, 255, 255, 255, 2, 0, 0, 0, 3, 0, 4, 0]);
+
+    let points: WasmPtr<Point, Array> = WasmPtr::new(64);
+    let cells = points.deref(memory, 0, 2).unwrap();
+    assert_eq!(cells[0].get(), point);
+    assert_eq!(cells[1].get().x, 0);
+}
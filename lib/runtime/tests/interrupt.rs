@@ -0,0 +1,84 @@
+#![cfg(all(
+    unix,
+    target_arch = "x86_64",
+    any(feature = "singlepass", feature = "cranelift", feature = "llvm")
+))]
+
+use std::{thread, time::Duration};
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    compile_with_config_with, compiler_for_backend, error::RuntimeError, imports, Backend, Func,
+};
+
+static WAT: &'static str = r#"
+    (module
+    (type (;0;) (func))
+    (type (;1;) (func (result i32)))
+    (func $spin (type 0)
+        loop
+            br 0
+        end
+    )
+    (func $answer (type 1) (result i32)
+        i32.const 42
+    )
+    (export "spin" (func $spin))
+    (export "answer" (func $answer))
+    )
+"#;
+
+fn interrupt_handle_stops_instance(backend: Backend) {
+    let wasm = wat2wasm(WAT).unwrap();
+    let compiler = compiler_for_backend(backend).unwrap();
+    let module = compile_with_config_with(&wasm, Default::default(), &*compiler).unwrap();
+
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+    let handle = instance.interrupt_handle().unwrap();
+
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        handle.interrupt();
+    });
+
+    {
+        let spin: Func<(), ()> = instance.func("spin").unwrap();
+        match spin.call() {
+            Err(RuntimeError::Interrupted) => {}
+            Err(other) => panic!("unexpected error: {:?}", other),
+            Ok(()) => panic!("infinite loop returned"),
+        }
+        interrupter.join().unwrap();
+
+        // The instance can be called again after being interrupted.
+        let answer: Func<(), i32> = instance.func("answer").unwrap();
+        assert_eq!(answer.call().unwrap(), 42);
+    }
+
+    // An interrupt requested while the instance isn't running stops the
+    // next call, even one made with a timeout.
+    instance.interrupt_handle().unwrap().interrupt();
+    let spin: Func<(), ()> = instance.func("spin").unwrap();
+    match spin.call_with_timeout(Duration::from_secs(10)) {
+        Err(RuntimeError::Interrupted) => {}
+        Err(other) => panic!("unexpected error: {:?}", other),
+        Ok(()) => panic!("infinite loop returned"),
+    }
+}
+
+#[cfg(feature = "singlepass")]
+#[test]
+fn interrupt_handle_stops_instance_singlepass() {
+    interrupt_handle_stops_instance(Backend::Singlepass);
+}
+
+#[cfg(feature = "cranelift")]
+#[test]
+fn interrupt_handle_stops_instance_cranelift() {
+    interrupt_handle_stops_instance(Backend::Cranelift);
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn interrupt_handle_stops_instance_llvm() {
+    interrupt_handle_stops_instance(Backend::LLVM);
+}
@@ -0,0 +1,158 @@
+#[test]
+fn memory_page_limit() {
+    use wabt::wat2wasm;
+    use wasmer_runtime::{
+        compile, error::GrowError, error::RuntimeError, imports, units::Pages, Func,
+    };
+
+    static WAT: &'static str = r#"
+        (module
+        (type (;0;) (func (param i32) (result i32)))
+        (func $grow (type 0) (param i32) (result i32)
+            get_local 0
+            memory.grow
+        )
+        (memory 1 100)
+        (export "grow" (func $grow))
+        )
+    "#;
+
+    let wasm = wat2wasm(WAT).unwrap();
+
+    let module = compile(&wasm).unwrap();
+
+    let instance = module.instantiate(&imports! {}).unwrap();
+    instance.context().memory(0).set_page_limit(Some(Pages(4)));
+
+    let grow: Func<i32, i32> = instance.func("grow").unwrap();
+
+    // Growing within the limit behaves as usual.
+    assert_eq!(grow.call(3).unwrap(), 1);
+
+    // Growing past the declared maximum still returns -1.
+    assert_eq!(grow.call(1000).unwrap(), -1);
+
+    // Growing past the page limit traps with a distinguishable error.
+    if let Err(RuntimeError::Error { data }) = grow.call(1) {
+        match data.downcast::<GrowError>() {
+            Ok(ref err) => match **err {
+                GrowError::ExceededPageLimit(5, 4) => {}
+                ref other => panic!("unexpected grow error: {:?}", other),
+            },
+            Err(_) => panic!("trap data is not a GrowError"),
+        }
+    } else {
+        panic!("didn't return RuntimeError::Error")
+    }
+
+    assert_eq!(instance.context().memory(0).size(), Pages(4));
+}
+
+#[test]
+fn instance_memory_page_limit() {
+    use wabt::wat2wasm;
+    use wasmer_runtime::{
+        compile, error::GrowError, error::RuntimeError, imports, units::Pages, Func, InstanceConfig,
+    };
+
+    static WAT: &'static str = r#"
+        (module
+        (type (;0;) (func (param i32) (result i32)))
+        (func $grow (type 0) (param i32) (result i32)
+            get_local 0
+            memory.grow
+        )
+        (memory 2 100)
+        (export "grow" (func $grow))
+        )
+    "#;
+
+    let wasm = wat2wasm(WAT).unwrap();
+
+    let module = compile(&wasm).unwrap();
+
+    // The limit is chosen before the instance is created.
+    let config = InstanceConfig {
+        memory_page_limit: Some(Pages(4)),
+    };
+    let instance = module
+        .instantiate_with_config(&imports! {}, config)
+        .unwrap();
+
+    let grow: Func<i32, i32> = instance.func("grow").unwrap();
+    assert_eq!(grow.call(2).unwrap(), 2);
+
+    match grow.call(1) {
+        Err(RuntimeError::Error { data }) => match data.downcast::<GrowError>() {
+            Ok(ref err) => match **err {
+                GrowError::ExceededPageLimit(5, 4) => {}
+                ref other => panic!("unexpected grow error: {:?}", other),
+            },
+            Err(_) => panic!("trap data is not a GrowError"),
+        },
+        _ => panic!("didn't return RuntimeError::Error"),
+    }
+
+    // Growing from the host is capped as well.
+    match instance.context().memory(0).grow(Pages(1)) {
+        Err(GrowError::ExceededPageLimit(5, 4)) => {}
+        other => panic!("unexpected grow result: {:?}", other),
+    }
+    assert_eq!(instance.context().memory(0).size(), Pages(4));
+
+    // Memories that are already too large can't be instantiated.
+    let config = InstanceConfig {
+        memory_page_limit: Some(Pages(1)),
+    };
+    assert!(module
+        .instantiate_with_config(&imports! {}, config)
+        .is_err());
+}
+
+#[test]
+fn imported_memory_page_limit() {
+    use wabt::wat2wasm;
+    use wasmer_runtime::{
+        compile, error::GrowError, imports, units::Pages, wasm::MemoryDescriptor, InstanceConfig,
+        Memory,
+    };
+
+    static WAT: &'static str = r#"
+        (module
+        (import "env" "memory" (memory 1))
+        )
+    "#;
+
+    let wasm = wat2wasm(WAT).unwrap();
+
+    let module = compile(&wasm).unwrap();
+
+    let memory = Memory::new(MemoryDescriptor {
+        minimum: Pages(1),
+        maximum: None,
+        shared: false,
+    })
+    .unwrap();
+    let import_object = imports! {
+        "env" => {
+            "memory" => memory.clone(),
+        },
+    };
+    let config = InstanceConfig {
+        memory_page_limit: Some(Pages(2)),
+    };
+    let instance = module
+        .instantiate_with_config(&import_object, config)
+        .unwrap();
+
+    // Imported memories count towards the limit, whoever grows them.
+    assert_eq!(memory.grow(Pages(1)).unwrap(), Pages(1));
+    match memory.grow(Pages(1)) {
+        Err(GrowError::ExceededPageLimit(3, 2)) => {}
+        other => panic!("unexpected grow result: {:?}", other),
+    }
+
+    // The limit no longer applies once the instance is dropped.
+    drop(instance);
+    assert_eq!(memory.grow(Pages(1)).unwrap(), Pages(2));
+}
@@ -0,0 +1,133 @@
+#[macro_use]
+mod utils;
+
+use utils::Proposal;
+
+static WAT: &'static str = r#"
+    (module
+    (import "env" "host_swap" (func $host_swap (param i32 i32) (result i32 i32)))
+    (func $swap (param i32 i32) (result i32 i32)
+        get_local 1
+        get_local 0
+    )
+    (func $sub_swapped (param i32 i32) (result i32)
+        get_local 0
+        get_local 1
+        call $swap
+        i32.sub
+    )
+    (func $call_host_swap (param i32 i32) (result i32 i32)
+        get_local 0
+        get_local 1
+        call $host_swap
+    )
+    (func $choose (param i32 i32 i32) (result i32 i32)
+        get_local 0
+        get_local 1
+        get_local 2
+        if (param i32 i32) (result i32 i32)
+            call $swap
+        else
+            i32.const 10
+            i32.add
+        end
+    )
+    (func $sum_to (param $n i32) (result i32)
+        i32.const 0
+        loop (param i32) (result i32)
+            get_local $n
+            i32.add
+            get_local $n
+            i32.const 1
+            i32.sub
+            tee_local $n
+            br_if 0
+        end
+    )
+    (func $block_params (param i32 i32) (result i32 i32 i64)
+        get_local 0
+        get_local 1
+        block (param i32 i32) (result i32 i32 i64)
+            i64.const 7
+        end
+    )
+    (func $pick (param i32) (result i32 i32)
+        block (result i32 i32)
+            block (result i32 i32)
+                i32.const 1
+                i32.const 2
+                get_local 0
+                br_table 0 1
+            end
+            i32.const 10
+            i32.add
+            return
+        end
+    )
+    (export "swap" (func $swap))
+    (export "sub_swapped" (func $sub_swapped))
+    (export "call_host_swap" (func $call_host_swap))
+    (export "choose" (func $choose))
+    (export "sum_to" (func $sum_to))
+    (export "block_params" (func $block_params))
+    (export "pick" (func $pick))
+    )
+"#;
+
+#[cfg(any(feature = "singlepass", feature = "llvm", feature = "cranelift"))]
+fn check_multi_value(backend: wasmer_runtime::Backend) {
+    use wasmer_runtime::{func, imports, Ctx, Value};
+
+    fn host_swap(_ctx: &mut Ctx, a: i32, b: i32) -> (i32, i32) {
+        (b, a)
+    }
+
+    let import_object = imports! {
+        "env" => {
+            "host_swap" => func!(host_swap),
+        },
+    };
+    let wasm = Proposal::MultiValue.wat2wasm(WAT);
+    let module = Proposal::MultiValue.compile(&wasm, backend).unwrap();
+    let instance = module.instantiate(&import_object).unwrap();
+
+    let swap = instance.func::<(i32, i32), (i32, i32)>("swap").unwrap();
+    assert_eq!(swap.call(1, 2), Ok((2, 1)));
+
+    assert_eq!(
+        instance
+            .dyn_func("swap")
+            .unwrap()
+            .call(&[Value::I32(3), Value::I32(4)])
+            .unwrap(),
+        vec![Value::I32(4), Value::I32(3)]
+    );
+
+    let sub_swapped = instance.func::<(i32, i32), i32>("sub_swapped").unwrap();
+    assert_eq!(sub_swapped.call(1, 5), Ok(4));
+
+    let call_host_swap = instance
+        .func::<(i32, i32), (i32, i32)>("call_host_swap")
+        .unwrap();
+    assert_eq!(call_host_swap.call(5, 6), Ok((6, 5)));
+
+    let choose = instance
+        .func::<(i32, i32, i32), (i32, i32)>("choose")
+        .unwrap();
+    assert_eq!(choose.call(1, 2, 1), Ok((2, 1)));
+    assert_eq!(choose.call(1, 2, 0), Ok((1, 12)));
+
+    let sum_to = instance.func::<i32, i32>("sum_to").unwrap();
+    assert_eq!(sum_to.call(4), Ok(10));
+
+    let block_params = instance
+        .func::<(i32, i32), (i32, i32, i64)>("block_params")
+        .unwrap();
+    assert_eq!(block_params.call(8, 9), Ok((8, 9, 7)));
+
+    let pick = instance.func::<i32, (i32, i32)>("pick").unwrap();
+    assert_eq!(pick.call(0), Ok((1, 12)));
+    assert_eq!(pick.call(1), Ok((1, 2)));
+}
+
+backend_tests!(multi_value, check_multi_value);
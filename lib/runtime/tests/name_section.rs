@@ -0,0 +1,63 @@
+use wabt::Wat2Wasm;
+use wasmer_runtime::Module;
+use wasmer_runtime_core::{structures::TypedIndex, types::FuncIndex};
+
+static WAT: &'static str = r#"
+    (module
+    (type (;0;) (func (param i32 i32) (result i32)))
+    (func $my_div (type 0) (param $lhs i32) (param $rhs i32) (result i32)
+        get_local $lhs
+        get_local $rhs
+        i32.div_s
+    )
+    (export "div" (func $my_div))
+    )
+"#;
+
+fn wasm_with_names() -> Vec<u8> {
+    Wat2Wasm::new()
+        .write_debug_names(true)
+        .convert(WAT)
+        .unwrap()
+        .as_ref()
+        .to_vec()
+}
+
+fn check_names(module: &Module) {
+    let info = module.info();
+    assert_eq!(info.function_name(FuncIndex::new(0)), Some("my_div"));
+    assert_eq!(info.local_name(FuncIndex::new(0), 0), Some("lhs"));
+    assert_eq!(info.local_name(FuncIndex::new(0), 1), Some("rhs"));
+    assert_eq!(info.local_name(FuncIndex::new(0), 2), None);
+}
+
+#[test]
+fn name_section_is_parsed() {
+    let module = wasmer_runtime::compile(&wasm_with_names()).unwrap();
+    check_names(&module);
+}
+
+#[cfg(feature = "singlepass")]
+#[test]
+fn trap_message_uses_function_name() {
+    use wasmer_runtime::{
+        compile_with_config_with, compiler_for_backend,
+        error::{CallError, RuntimeError},
+        imports, Backend, Value,
+    };
+
+    let compiler = compiler_for_backend(Backend::Singlepass).unwrap();
+    let module =
+        compile_with_config_with(&wasm_with_names(), Default::default(), &*compiler).unwrap();
+    check_names(&module);
+
+    let instance = module.instantiate(&imports! {}).unwrap();
+    match instance.call("div", &[Value::I32(1), Value::I32(0)]) {
+        Err(CallError::Runtime(RuntimeError::Trap { msg, details })) => {
+            assert_eq!(details.func_name.as_ref().map(|x| x.as_str()), Some("my_div"));
+            assert!(msg.contains("my_div"), "unexpected trap message: {}", msg);
+        }
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(_) => panic!("div didn't trap"),
+    }
+}
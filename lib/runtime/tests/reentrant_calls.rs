@@ -0,0 +1,136 @@
+use wasmer_runtime::{
+    compile,
+    error::{CallError, CallResult, RuntimeError, WasmTrapInfo},
+    func, imports, Ctx, Func, Instance, Value,
+};
+
+static WAT: &'static str = r#"
+    (module
+    (import "env" "call_export" (func $call_export (param i32) (result i32)))
+    (import "env" "call_element" (func $call_element (param i32 i32) (result i32)))
+    (import "env" "call_trap" (func $call_trap))
+    (table 3 funcref)
+    (elem (i32.const 0) $double $triple)
+    (func $double (export "double") (param i32) (result i32)
+        get_local 0
+        i32.const 2
+        i32.mul
+    )
+    (func $triple (param i32) (result i32)
+        get_local 0
+        i32.const 3
+        i32.mul
+    )
+    (func (export "trap")
+        unreachable
+    )
+    (func (export "run_export") (param i32) (result i32)
+        get_local 0
+        call $call_export
+        i32.const 1
+        i32.add
+    )
+    (func (export "run_element") (param i32 i32) (result i32)
+        get_local 0
+        get_local 1
+        call $call_element
+    )
+    (func (export "run_trap")
+        call $call_trap
+    )
+    )
+"#;
+
+fn call_export(ctx: &mut Ctx, x: i32) -> CallResult<i32> {
+    match ctx.call("double", &[Value::I32(x)])?[..] {
+        [Value::I32(result)] => Ok(result),
+        ref other => panic!("unexpected results: {:?}", other),
+    }
+}
+
+fn call_element(ctx: &mut Ctx, index: i32, x: i32) -> CallResult<i32> {
+    match ctx.call_indirect(0, index as u32, &[Value::I32(x)])?[..] {
+        [Value::I32(result)] => Ok(result),
+        ref other => panic!("unexpected results: {:?}", other),
+    }
+}
+
+fn call_trap(ctx: &mut Ctx) -> CallResult<()> {
+    ctx.call("trap", &[])?;
+    Ok(())
+}
+
+fn instantiate() -> Instance {
+    let wasm = wabt::wat2wasm(WAT).unwrap();
+    compile(&wasm)
+        .unwrap()
+        .instantiate(&imports! {
+            "env" => {
+                "call_export" => func!(call_export),
+                "call_element" => func!(call_element),
+                "call_trap" => func!(call_trap),
+            },
+        })
+        .unwrap()
+}
+
+fn assert_trap(result: Result<impl std::fmt::Debug, RuntimeError>, kind: WasmTrapInfo) {
+    match result {
+        Err(RuntimeError::Trap { ref details, .. }) if details.kind == kind => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn imports_call_exports() {
+    let instance = instantiate();
+    let run: Func<i32, i32> = instance.func("run_export").unwrap();
+    assert_eq!(run.call(20), Ok(41));
+}
+
+#[test]
+fn imports_call_table_elements() {
+    let instance = instantiate();
+    let run: Func<(i32, i32), i32> = instance.func("run_element").unwrap();
+    assert_eq!(run.call(0, 5), Ok(10));
+    assert_eq!(run.call(1, 5), Ok(15));
+    // The last element is null.
+    assert_trap(run.call(2, 5), WasmTrapInfo::CallIndirectOOB);
+    assert_trap(run.call(3, 5), WasmTrapInfo::CallIndirectOOB);
+}
+
+#[test]
+fn missing_tables_trap() {
+    let mut instance = instantiate();
+    match instance.context_mut().call_indirect(1, 0, &[Value::I32(5)]) {
+        Err(CallError::Runtime(RuntimeError::Trap { ref details, .. }))
+            if details.kind == WasmTrapInfo::CallIndirectOOB => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn traps_propagate_through_imports() {
+    let instance = instantiate();
+    let run: Func<(), ()> = instance.func("run_trap").unwrap();
+    assert_trap(run.call(), WasmTrapInfo::Unreachable);
+
+    // The instance can still be called afterwards.
+    let run: Func<i32, i32> = instance.func("run_export").unwrap();
+    assert_eq!(run.call(1), Ok(3));
+}
+
+#[test]
+fn exports_are_resolved_by_name() {
+    let mut instance = instantiate();
+    let ctx = instance.context_mut();
+    match ctx.call("missing", &[]) {
+        Err(CallError::Resolve(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    // Calls from the host outside of any import work too.
+    assert_eq!(
+        ctx.call("double", &[Value::I32(4)]).unwrap(),
+        vec![Value::I32(8)]
+    );
+}
@@ -0,0 +1,205 @@
+#[macro_use]
+mod utils;
+
+use utils::Proposal;
+use wasmer_runtime::{imports, Backend, Export, Instance, Module, Value};
+
+static WAT: &'static str = r#"
+    (module
+    (type $ret_i32 (func (result i32)))
+    (table $funcs (export "funcs") 2 funcref)
+    (table $more 2 funcref)
+    (table $refs 2 anyref)
+    (elem 0 (i32.const 0) $one)
+    (elem 1 (i32.const 1) $two)
+    (func $one (result i32)
+        i32.const 1
+    )
+    (func $two (result i32)
+        i32.const 2
+    )
+    (func (export "call") (param i32) (result i32)
+        get_local 0
+        call_indirect $funcs (type $ret_i32)
+    )
+    (func (export "call_more") (param i32) (result i32)
+        get_local 0
+        call_indirect $more (type $ret_i32)
+    )
+    (func (export "copy") (param i32 i32)
+        get_local 0
+        get_local 1
+        table.get $funcs
+        table.set $refs
+    )
+    (func (export "get") (param i32) (result anyref)
+        get_local 0
+        table.get $refs
+    )
+    (func (export "is_null") (param i32) (result i32)
+        get_local 0
+        table.get $refs
+        ref.is_null
+    )
+    (func (export "set") (param i32 anyref)
+        get_local 0
+        get_local 1
+        table.set $refs
+    )
+    (func (export "clear") (param i32)
+        get_local 0
+        ref.null
+        table.set $refs
+    )
+    (func (export "size") (result i32)
+        table.size $refs
+    )
+    (func (export "grow") (param i32) (result i32)
+        ref.null
+        get_local 0
+        table.grow $refs
+    )
+    (func (export "fill") (param i32 i32)
+        get_local 0
+        i32.const 0
+        table.get $funcs
+        get_local 1
+        table.fill $refs
+    )
+    )
+"#;
+
+fn check_reference_ops(instance: &Instance) {
+    let call_more = instance.func::<i32, i32>("call_more").unwrap();
+    let copy = instance.func::<(i32, i32), ()>("copy").unwrap();
+    let is_null = instance.func::<i32, i32>("is_null").unwrap();
+    let clear = instance.func::<i32, ()>("clear").unwrap();
+    let size = instance.func::<(), i32>("size").unwrap();
+    let grow = instance.func::<i32, i32>("grow").unwrap();
+    let fill = instance.func::<(i32, i32), ()>("fill").unwrap();
+
+    // Each table is independent of the others.
+    assert!(call_more.call(0).is_err());
+    assert_eq!(call_more.call(1), Ok(2));
+
+    assert_eq!(is_null.call(0), Ok(1));
+    copy.call(0, 0).unwrap();
+    assert_eq!(is_null.call(0), Ok(0));
+    copy.call(1, 1).unwrap();
+    assert_eq!(is_null.call(1), Ok(1));
+    assert!(copy.call(2, 0).is_err());
+
+    // The same function always gives the same reference.
+    let get = instance.dyn_func("get").unwrap();
+    let reference = get.call(&[Value::I32(0)]).unwrap();
+    assert_eq!(get.call(&[Value::I32(0)]).unwrap(), reference);
+    match reference[..] {
+        [Value::AnyRef(reference)] => assert!(!reference.is_null()),
+        ref other => panic!("unexpected results: {:?}", other),
+    }
+
+    clear.call(0).unwrap();
+    assert_eq!(is_null.call(0), Ok(1));
+
+    // `table.grow` returns the old size, or -1 if the table can't grow.
+    assert_eq!(size.call(), Ok(2));
+    assert_eq!(grow.call(2), Ok(2));
+    assert_eq!(size.call(), Ok(4));
+    assert_eq!(grow.call(-1), Ok(-1));
+    assert_eq!(is_null.call(3), Ok(1));
+
+    fill.call(1, 3).unwrap();
+    assert_eq!(is_null.call(0), Ok(1));
+    assert_eq!(is_null.call(1), Ok(0));
+    assert_eq!(is_null.call(3), Ok(0));
+    assert!(fill.call(3, 2).is_err());
+}
+
+fn check_host_table(instance: &Instance) {
+    use wasmer_runtime_core::{table::Element, vm::Ctx, Func};
+
+    fn seven(_ctx: &mut Ctx) -> i32 {
+        7
+    }
+
+    let call = instance.func::<i32, i32>("call").unwrap();
+    let table = funcs_table(instance);
+
+    let one = match table.get(0) {
+        Some(Value::FuncRef(reference)) => reference,
+        other => panic!("unexpected element: {:?}", other),
+    };
+    assert!(!one.is_null());
+    assert_eq!(table.get(2), None);
+
+    // Host functions can be stored in a table and called indirectly.
+    table
+        .set(1, Element::Anyfunc(Func::new(seven).into()))
+        .unwrap();
+    assert_eq!(call.call(1), Ok(7));
+
+    table.set(1, Element::Reference(one)).unwrap();
+    assert_eq!(call.call(1), Ok(1));
+    assert!(table.set(2, Element::Reference(one)).is_err());
+}
+
+fn funcs_table(instance: &Instance) -> wasmer_runtime_core::table::Table {
+    instance
+        .exports()
+        .find_map(|(name, export)| match export {
+            Export::Table(table) if name == "funcs" => Some(table),
+            _ => None,
+        })
+        .unwrap()
+}
+
+fn check_dead_references(module: &Module) {
+    use wasmer_runtime::error::{CallError, RuntimeError};
+    use wasmer_runtime_core::{table::Element, typed_func::WasmTrapInfo};
+
+    let reference = {
+        let instance = module.instantiate(&imports! {}).unwrap();
+        match funcs_table(&instance).get(0) {
+            Some(Value::FuncRef(reference)) => reference,
+            other => panic!("unexpected element: {:?}", other),
+        }
+    };
+
+    // The instance the function belongs to is gone.
+    let instance = module.instantiate(&imports! {}).unwrap();
+    assert!(funcs_table(&instance)
+        .set(0, Element::Reference(reference))
+        .is_err());
+
+    let set = instance.dyn_func("set").unwrap();
+    match set.call(&[Value::I32(0), Value::AnyRef(reference)]) {
+        Err(CallError::Runtime(RuntimeError::Trap { ref details, .. }))
+            if details.kind == WasmTrapInfo::DeadReference => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+fn check_reference_types(backend: Backend) {
+    let wasm = Proposal::ReferenceTypes.wat2wasm(WAT);
+    let module = Proposal::ReferenceTypes.compile(&wasm, backend).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    check_reference_ops(&instance);
+    check_host_table(&instance);
+    check_dead_references(&module);
+}
+
+#[test]
+fn reference_types_requires_feature() {
+    assert!(wasmer_runtime::compile(&Proposal::ReferenceTypes.wat2wasm(WAT)).is_err());
+}
+
+backend_tests!(reference_types, check_reference_types, [singlepass, llvm]);
+
+#[cfg(feature = "cranelift")]
+#[test]
+fn reference_types_unsupported_cranelift() {
+    let wasm = Proposal::ReferenceTypes.wat2wasm(WAT);
+    assert!(Proposal::ReferenceTypes
+        .compile(&wasm, Backend::Cranelift)
+        .is_err());
+}
@@ -0,0 +1,294 @@
+#[macro_use]
+mod utils;
+
+use utils::Proposal;
+use wasmer_runtime::{imports, Backend, Instance, Value};
+
+static WAT: &'static str = r#"
+    (module
+    (memory 1)
+    (global $g (mut v128) (v128.const i32x4 1 2 3 4))
+    (func $add (param v128 v128) (result v128)
+        get_local 0
+        get_local 1
+        i32x4.add
+    )
+    (func (export "add") (param v128 v128) (result v128)
+        get_local 0
+        get_local 1
+        call $add
+    )
+    (func (export "mul8") (param v128 v128) (result v128)
+        get_local 0
+        get_local 1
+        i8x16.mul
+    )
+    (func (export "sub_sat") (param v128 v128) (result v128)
+        get_local 0
+        get_local 1
+        i8x16.sub_saturate_u
+    )
+    (func (export "lt_u") (param v128 v128) (result v128)
+        get_local 0
+        get_local 1
+        i32x4.lt_u
+    )
+    (func (export "shr_s8") (param v128 i32) (result v128)
+        get_local 0
+        get_local 1
+        i8x16.shr_s
+    )
+    (func (export "shr_s64") (param v128 i32) (result v128)
+        get_local 0
+        get_local 1
+        i64x2.shr_s
+    )
+    (func (export "shuffle") (param v128 v128) (result v128)
+        get_local 0
+        get_local 1
+        v8x16.shuffle 0 16 1 17 2 18 3 19 4 20 5 21 6 22 7 23
+    )
+    (func (export "swizzle") (param v128 v128) (result v128)
+        get_local 0
+        get_local 1
+        v8x16.swizzle
+    )
+    (func (export "splat") (param i32) (result v128)
+        get_local 0
+        i16x8.splat
+    )
+    (func (export "extract") (param v128) (result i32)
+        get_local 0
+        i8x16.extract_lane_s 15
+    )
+    (func (export "replace") (param v128 f64) (result v128)
+        get_local 0
+        get_local 1
+        f64x2.replace_lane 1
+    )
+    (func (export "min") (param v128 v128) (result v128)
+        get_local 0
+        get_local 1
+        f32x4.min
+    )
+    (func (export "trunc") (param v128) (result v128)
+        get_local 0
+        i32x4.trunc_sat_f32x4_u
+    )
+    (func (export "all_true") (param v128) (result i32)
+        get_local 0
+        i16x8.all_true
+    )
+    (func (export "select") (param v128 v128 i32) (result v128)
+        (local v128)
+        get_local 0
+        set_local 3
+        get_local 3
+        get_local 1
+        get_local 2
+        select
+    )
+    (func (export "block") (param v128) (result v128)
+        block (result v128)
+            get_local 0
+            br 0
+        end
+    )
+    (func (export "global") (param v128) (result v128)
+        get_global $g
+        get_local 0
+        set_global $g
+    )
+    (func (export "store") (param i32 v128)
+        get_local 0
+        get_local 1
+        v128.store
+    )
+    (func (export "load") (param i32) (result v128)
+        get_local 0
+        v128.load
+    )
+    (func (export "load_splat") (param i32) (result v128)
+        get_local 0
+        i32x4.load_splat
+    )
+    (func (export "const") (result v128)
+        v128.const i64x2 0x0123456789abcdef -1
+    )
+    )
+"#;
+
+fn i32x4(lanes: [u32; 4]) -> u128 {
+    lanes
+        .iter()
+        .rev()
+        .fold(0, |acc, &lane| (acc << 32) | lane as u128)
+}
+
+fn f32x4(lanes: [f32; 4]) -> u128 {
+    i32x4([
+        lanes[0].to_bits(),
+        lanes[1].to_bits(),
+        lanes[2].to_bits(),
+        lanes[3].to_bits(),
+    ])
+}
+
+fn call(instance: &Instance, name: &str, args: &[Value]) -> Value {
+    match instance.dyn_func(name).unwrap().call(args).unwrap()[..] {
+        [ref result] => result.clone(),
+        ref other => panic!("unexpected results: {:?}", other),
+    }
+}
+
+fn check_simd(backend: Backend) {
+    let wasm = Proposal::Simd.wat2wasm(WAT);
+    let module = Proposal::Simd.compile(&wasm, backend).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let v128 = |name, args: &[Value]| match call(&instance, name, args) {
+        Value::V128(v) => v,
+        other => panic!("unexpected result: {:?}", other),
+    };
+    let scalar = |name, args: &[Value]| match call(&instance, name, args) {
+        Value::I32(v) => v,
+        other => panic!("unexpected result: {:?}", other),
+    };
+
+    let a = i32x4([1, 0xffff_ffff, 0x8000_0000, 7]);
+    let b = i32x4([2, 1, 0x7fff_ffff, 7]);
+
+    // Lanes wrap around independently.
+    assert_eq!(
+        v128("add", &[Value::V128(a), Value::V128(b)]),
+        i32x4([3, 0, 0xffff_ffff, 14])
+    );
+    assert_eq!(
+        v128("lt_u", &[Value::V128(a), Value::V128(b)]),
+        i32x4([0xffff_ffff, 0, 0, 0])
+    );
+    assert_eq!(
+        v128("mul8", &[Value::V128(0x1003), Value::V128(0x8010)]),
+        0x0030
+    );
+    assert_eq!(
+        v128("sub_sat", &[Value::V128(0x0510), Value::V128(0x0708)]),
+        0x0008
+    );
+    assert_eq!(
+        v128("shr_s8", &[Value::V128(0x80_40), Value::I32(9)]),
+        0xc0_20
+    );
+    assert_eq!(
+        v128(
+            "shr_s64",
+            &[Value::V128(1 << 127 | 1 << 64 | 8), Value::I32(66)]
+        ),
+        0xe000_0000_0000_0000 << 64 | 2
+    );
+
+    // Interleaves the low bytes of both operands.
+    assert_eq!(
+        v128("shuffle", &[Value::V128(0x0201), Value::V128(0x0403)]),
+        0x0402_0301
+    );
+    // Out of range indices select 0.
+    assert_eq!(
+        v128(
+            "swizzle",
+            &[Value::V128(0x30_2010), Value::V128(!0 << 24 | 0x10_0201)]
+        ),
+        0x3020
+    );
+
+    assert_eq!(
+        v128("splat", &[Value::I32(0x1_2345)]),
+        0x2345_2345_2345_2345_2345_2345_2345_2345
+    );
+    assert_eq!(scalar("extract", &[Value::V128(0xff << 120)]), -1);
+    assert_eq!(
+        v128("replace", &[Value::V128(!0), Value::F64(1.5)]),
+        (1.5f64.to_bits() as u128) << 64 | 0xffff_ffff_ffff_ffff
+    );
+
+    // NaNs and negative zero propagate.
+    let min = v128(
+        "min",
+        &[
+            Value::V128(f32x4([1.0, 0.0, std::f32::NAN, -3.0])),
+            Value::V128(f32x4([2.0, -0.0, 1.0, -4.0])),
+        ],
+    );
+    let lane = |i: u32| f32::from_bits((min >> (i * 32)) as u32);
+    assert_eq!(lane(0), 1.0);
+    assert_eq!(lane(1).to_bits(), (-0.0f32).to_bits());
+    assert!(lane(2).is_nan());
+    assert_eq!(lane(3), -4.0);
+    assert_eq!(
+        v128(
+            "trunc",
+            &[Value::V128(f32x4([-1.0, 3e9, 5e9, std::f32::NAN]))]
+        ),
+        i32x4([0, 3_000_000_000, 0xffff_ffff, 0])
+    );
+
+    assert_eq!(scalar("all_true", &[Value::V128(!0)]), 1);
+    assert_eq!(scalar("all_true", &[Value::V128(!0 ^ 0xffff << 64)]), 0);
+
+    // Values travel through locals, globals, blocks and `select`.
+    assert_eq!(
+        v128("select", &[Value::V128(a), Value::V128(b), Value::I32(1)]),
+        a
+    );
+    assert_eq!(
+        v128("select", &[Value::V128(a), Value::V128(b), Value::I32(0)]),
+        b
+    );
+    assert_eq!(v128("block", &[Value::V128(a)]), a);
+    assert_eq!(v128("global", &[Value::V128(a)]), i32x4([1, 2, 3, 4]));
+    assert_eq!(v128("global", &[Value::V128(b)]), a);
+
+    // Memory accesses don't need to be aligned.
+    instance
+        .dyn_func("store")
+        .unwrap()
+        .call(&[Value::I32(3), Value::V128(a)])
+        .unwrap();
+    assert_eq!(v128("load", &[Value::I32(3)]), a);
+    assert_eq!(
+        v128("load_splat", &[Value::I32(7)]),
+        i32x4([0xffff_ffff; 4])
+    );
+    assert!(instance
+        .dyn_func("load")
+        .unwrap()
+        .call(&[Value::I32(65530)])
+        .is_err());
+
+    assert_eq!(
+        v128("const", &[]),
+        0xffff_ffff_ffff_ffff_0123_4567_89ab_cdef
+    );
+}
+
+#[test]
+fn simd_requires_feature() {
+    assert!(wasmer_runtime::compile(&Proposal::Simd.wat2wasm(WAT)).is_err());
+}
+
+backend_tests!(simd, check_simd, [singlepass, llvm]);
+
+#[cfg(feature = "cranelift")]
+#[test]
+fn simd_unsupported_cranelift() {
+    let compile = |wasm: &[u8]| Proposal::Simd.compile(wasm, Backend::Cranelift);
+    match compile(&Proposal::Simd.wat2wasm(WAT)) {
+        Err(err) => assert!(format!("{:?}", err).contains("does not support SIMD")),
+        Ok(_) => panic!("the cranelift backend compiled a SIMD module"),
+    }
+
+    // Only modules that use SIMD are refused, not every module compiled with it enabled.
+    let wasm = wabt::wat2wasm(r#"(module (func (export "one") (result i32) i32.const 1))"#);
+    assert!(compile(&wasm.unwrap()).is_ok());
+    let wasm = Proposal::Simd.wat2wasm(r#"(module (func (local v128)))"#);
+    assert!(compile(&wasm).is_err());
+}
@@ -0,0 +1,436 @@
+#![cfg(all(feature = "singlepass", unix, target_arch = "x86_64"))]
+
+use std::time::Duration;
+use wasmer_runtime::{
+    compile_with_config_with, compiler_for_backend,
+    error::{ImageError, ResumeError, RuntimeError},
+    func, imports, Backend, Ctx, Export, Func, Instance, Module, Table, Value,
+};
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    state::{InstanceImage, InstanceImageDelta, MemoryCompression},
+    structures::TypedIndex,
+    table::Element,
+};
+
+static WAT: &'static str = r#"
+    (module
+    (type $ret_i32 (func (result i32)))
+    (import "env" "done" (func $done (param i32) (result i32)))
+    (memory 1)
+    (global $count (mut i32) (i32.const 0))
+    (table $table (export "table") 2 funcref)
+    (elem (i32.const 0) $one $two)
+    (func $one (result i32)
+        i32.const 1
+    )
+    (func $two (result i32)
+        i32.const 2
+    )
+    (func (export "run") (result i32)
+        (local $i i32)
+        loop $continue
+            get_local $i
+            i32.const 1
+            i32.add
+            set_local $i
+            get_global $count
+            i32.const 2
+            i32.add
+            set_global $count
+            get_local $i
+            call $done
+            i32.eqz
+            br_if $continue
+        end
+        i32.const 0
+        get_local $i
+        i32.store
+        get_local $i
+        i32.const 10
+        i32.mul
+        i32.const 0
+        call_indirect (type $ret_i32)
+        i32.add
+    )
+    (func (export "count") (result i32)
+        get_global $count
+    )
+    (func (export "load") (result i32)
+        i32.const 0
+        i32.load
+    )
+    )
+"#;
+
+fn compile(track_state: bool) -> Module {
+    let wasm = wabt::wat2wasm(WAT).unwrap();
+    let compiler = compiler_for_backend(Backend::Singlepass).unwrap();
+    compile_with_config_with(
+        &wasm,
+        CompilerConfig {
+            track_state,
+            ..Default::default()
+        },
+        &*compiler,
+    )
+    .unwrap()
+}
+
+fn never(_ctx: &mut Ctx, _i: i32) -> i32 {
+    0
+}
+
+fn always(_ctx: &mut Ctx, _i: i32) -> i32 {
+    1
+}
+
+fn instantiate(module: &Module, finish: bool) -> Instance {
+    let done = if finish { func!(always) } else { func!(never) };
+    module
+        .instantiate(&imports! {
+            "env" => {
+                "done" => done,
+            },
+        })
+        .unwrap()
+}
+
+fn table(instance: &Instance) -> Table {
+    instance
+        .exports()
+        .find_map(|(name, export)| match export {
+            Export::Table(table) if name == "table" => Some(table),
+            _ => None,
+        })
+        .unwrap()
+}
+
+fn run_until_timeout(instance: &Instance) {
+    let run: Func<(), i32> = instance.func("run").unwrap();
+    match run.call_with_timeout(Duration::from_millis(100)) {
+        Err(RuntimeError::Timeout) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn resume_interrupted_call() {
+    let module = compile(true);
+    let instance = instantiate(&module, false);
+
+    // Calls through the first element now reach `$two`.
+    let table = table(&instance);
+    match table.get(1) {
+        Some(Value::FuncRef(two)) => table.set(0, Element::Reference(two)).unwrap(),
+        other => panic!("unexpected element: {:?}", other),
+    }
+
+    run_until_timeout(&instance);
+    let image = instance.snapshot().unwrap();
+    assert!(!image.execution_state.frames.is_empty());
+
+    // Images can be stored and continued in another instance.
+    let bytes = image.to_bytes(MemoryCompression::ZeroChunks).unwrap();
+    let image = InstanceImage::from_bytes(&bytes).unwrap();
+    let mut resumed = instantiate(&module, true);
+    let result = match resumed.resume(image).unwrap()[..] {
+        [Value::I32(result)] => result,
+        ref other => panic!("unexpected results: {:?}", other),
+    };
+
+    // The loop ends after one more iteration, then calls `$two`.
+    assert_eq!(result % 10, 2);
+    let iterations = result / 10;
+    assert!(iterations > 1);
+    let count: Func<(), i32> = resumed.func("count").unwrap();
+    let load: Func<(), i32> = resumed.func("load").unwrap();
+    assert_eq!(count.call(), Ok(2 * iterations));
+    assert_eq!(load.call(), Ok(iterations));
+}
+
+#[test]
+fn resume_idle_instance() {
+    let module = compile(false);
+    let instance = instantiate(&module, true);
+    let run: Func<(), i32> = instance.func("run").unwrap();
+    assert_eq!(run.call(), Ok(11));
+
+    let image = instance.snapshot().unwrap();
+    assert!(image.execution_state.frames.is_empty());
+
+    // Only memory, globals and tables are loaded.
+    let mut resumed = instantiate(&module, false);
+    assert_eq!(resumed.resume(image).unwrap(), vec![]);
+    let count: Func<(), i32> = resumed.func("count").unwrap();
+    let load: Func<(), i32> = resumed.func("load").unwrap();
+    assert_eq!(count.call(), Ok(2));
+    assert_eq!(load.call(), Ok(1));
+}
+
+#[test]
+fn snapshot_requires_execution_state() {
+    let module = compile(false);
+    let instance = instantiate(&module, false);
+
+    run_until_timeout(&instance);
+    assert_eq!(
+        instance.snapshot().unwrap_err(),
+        ImageError::NoExecutionState
+    );
+
+    // Calling the instance again leaves nothing to resume.
+    let count: Func<(), i32> = instance.func("count").unwrap();
+    assert!(count.call().is_ok());
+    let image = instance.snapshot().unwrap();
+    assert!(image.execution_state.frames.is_empty());
+}
+
+#[test]
+fn snapshot_rejects_foreign_table_elements() {
+    fn seven(_ctx: &mut Ctx) -> i32 {
+        7
+    }
+
+    let module = compile(false);
+    let instance = instantiate(&module, true);
+    table(&instance)
+        .set(1, Element::Anyfunc(Func::new(seven).into()))
+        .unwrap();
+
+    match instance.snapshot() {
+        Err(ImageError::ForeignTableElement { index: 1, .. }) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn snapshot_rejects_reference_globals() {
+    let mut features = wabt::Features::new();
+    features.enable_reference_types();
+    let wasm = wabt::wat2wasm_with_features(
+        "(module (global (mut i32) (i32.const 0)) (global (mut anyref) (ref.null)))",
+        features,
+    )
+    .unwrap();
+    let compiler = compiler_for_backend(Backend::Singlepass).unwrap();
+    let module = compile_with_config_with(
+        &wasm,
+        CompilerConfig {
+            features: Features {
+                reference_types: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        &*compiler,
+    )
+    .unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+
+    match instance.snapshot() {
+        Err(ImageError::ReferenceGlobal(global)) => assert_eq!(global.index(), 1),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // Images of the module can't be loaded either, since that would write raw
+    // bits into the global.
+    let mut image = instantiate(&compile(false), true).snapshot().unwrap();
+    image.wasm_hash = module.info().wasm_hash;
+    image.memory = None;
+    image.globals = vec![0, 1];
+    image.tables = vec![];
+    match instance.resume(image) {
+        Err(ResumeError::Image(ImageError::ReferenceGlobal(_))) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn resume_rejects_other_modules() {
+    let module = compile(false);
+    let image = instantiate(&module, true).snapshot().unwrap();
+
+    let wasm = wabt::wat2wasm("(module (memory 1))").unwrap();
+    let compiler = compiler_for_backend(Backend::Singlepass).unwrap();
+    let other = compile_with_config_with(&wasm, Default::default(), &*compiler).unwrap();
+    let mut instance = other.instantiate(&imports! {}).unwrap();
+
+    match instance.resume(image.clone()) {
+        Err(ResumeError::Image(ImageError::WrongModule { .. })) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // Images of the module compiled with other middlewares are rejected too.
+    let mut instance = instantiate(&module, true);
+    let mut metered = image;
+    metered.middleware_fingerprint = "metering".to_string();
+    match instance.resume(metered) {
+        Err(ResumeError::Image(ImageError::WrongMiddlewares { ref found, .. }))
+            if found == "metering" => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn image_format_round_trip() {
+    let module = compile(false);
+    let instance = instantiate(&module, true);
+    let run: Func<(), i32> = instance.func("run").unwrap();
+    assert_eq!(run.call(), Ok(11));
+    let image = instance.snapshot().unwrap();
+
+    let plain = image.to_bytes(MemoryCompression::None).unwrap();
+    let compressed = image.to_bytes(MemoryCompression::ZeroChunks).unwrap();
+    // Only the first chunk of the memory isn't empty.
+    assert!(compressed.len() < plain.len() - 60000);
+    for bytes in &[plain, compressed] {
+        let decoded = InstanceImage::from_bytes(bytes).unwrap();
+        assert_eq!(decoded.memory, image.memory);
+        assert_eq!(decoded.wasm_hash, image.wasm_hash);
+        assert_eq!(decoded.backend, Backend::Singlepass);
+    }
+}
+
+#[test]
+fn image_format_rejects_invalid_files() {
+    let module = compile(false);
+    let image = instantiate(&module, true).snapshot().unwrap();
+    let bytes = image.to_bytes(MemoryCompression::ZeroChunks).unwrap();
+
+    let mut corrupted = bytes.clone();
+    *corrupted.last_mut().unwrap() ^= 1;
+    assert_eq!(
+        InstanceImage::from_bytes(&corrupted).unwrap_err(),
+        ImageError::ChecksumMismatch
+    );
+
+    let mut newer = bytes.clone();
+    newer[8] += 1;
+    match InstanceImage::from_bytes(&newer) {
+        Err(ImageError::UnsupportedVersion(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    assert_eq!(
+        InstanceImage::from_bytes(&bytes[1..]).unwrap_err(),
+        ImageError::InvalidMagic
+    );
+    match InstanceImage::from_bytes(&bytes[..20]) {
+        Err(ImageError::Malformed(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn incremental_snapshots() {
+    let module = compile(false);
+    let mut instance = instantiate(&module, true);
+    instance.track_dirty_pages().unwrap();
+    assert_eq!(
+        instance.snapshot_delta().unwrap_err(),
+        ImageError::NoBaseImage
+    );
+    let mut image = instance.snapshot().unwrap();
+
+    let run: Func<(), i32> = instance.func("run").unwrap();
+    assert_eq!(run.call(), Ok(11));
+    // Writes from the host are recorded too.
+    instance.context().memory(0).view::<u8>()[40000].set(7);
+
+    let delta = instance.snapshot_delta().unwrap();
+    assert_eq!(delta.base_memory_size, 65536);
+    assert_eq!(delta.memory_size, 65536);
+    let changed: usize = delta.memory.iter().map(|(_, bytes)| bytes.len()).sum();
+    assert!(changed < 65536 / 2);
+    let delta = InstanceImageDelta::from_bytes(&delta.to_bytes().unwrap()).unwrap();
+    image.apply_delta(delta).unwrap();
+
+    let unchanged = instance.snapshot_delta().unwrap();
+    assert!(unchanged.memory.is_empty());
+    image.apply_delta(unchanged).unwrap();
+
+    let full = instance.snapshot().unwrap();
+    assert_eq!(image.memory, full.memory);
+    assert_eq!(image.globals, full.globals);
+
+    // The rebuilt image resumes like the full one.
+    let mut resumed = instantiate(&module, false);
+    assert_eq!(resumed.resume(image).unwrap(), vec![]);
+    let count: Func<(), i32> = resumed.func("count").unwrap();
+    let load: Func<(), i32> = resumed.func("load").unwrap();
+    assert_eq!(count.call(), Ok(2));
+    assert_eq!(load.call(), Ok(1));
+    assert_eq!(resumed.context().memory(0).view::<u8>()[40000].get(), 7);
+}
+
+#[test]
+fn deltas_must_follow_their_image() {
+    let module = compile(false);
+    let mut instance = instantiate(&module, true);
+    instance.track_dirty_pages().unwrap();
+    let mut image = instance.snapshot().unwrap();
+    let delta = instance.snapshot_delta().unwrap();
+
+    let mut other = compile_with_config_with(
+        &wabt::wat2wasm("(module (memory 2))").unwrap(),
+        Default::default(),
+        &*compiler_for_backend(Backend::Singlepass).unwrap(),
+    )
+    .unwrap()
+    .instantiate(&imports! {})
+    .unwrap();
+    other.track_dirty_pages().unwrap();
+    other.snapshot().unwrap();
+    match image.apply_delta(other.snapshot_delta().unwrap()) {
+        Err(ImageError::WrongModule { .. }) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let mut shrunk = delta.clone();
+    shrunk.base_memory_size = 0;
+    match image.apply_delta(shrunk) {
+        Err(ImageError::Mismatch(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    image.apply_delta(delta).unwrap();
+}
+
+#[test]
+fn os_writes_to_tracked_memory() {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    let module = compile(false);
+    let mut instance = instantiate(&module, true);
+    instance.track_dirty_pages().unwrap();
+    instance.snapshot().unwrap();
+
+    let mut file = tempfile::tempfile().unwrap();
+    file.write_all(b"wasm").unwrap();
+    file.seek(SeekFrom::Start(0)).unwrap();
+
+    let memory = instance.context().memory(0);
+    memory.prepare_os_write(50000, 4);
+    let cells = &memory.view::<u8>()[50000..50004];
+    // The kernel writes to the memory directly, without faulting.
+    let bytes = unsafe { &mut *(cells as *const [_] as *mut [_] as *mut [u8]) };
+    file.read_exact(bytes).unwrap();
+
+    let delta = instance.snapshot_delta().unwrap();
+    let (offset, bytes) = &delta.memory[0];
+    assert_eq!(&bytes[50000 - offset..50004 - offset], b"wasm");
+}
+
+#[cfg(feature = "cranelift")]
+#[test]
+fn dirty_page_tracking_requires_singlepass() {
+    let wasm = wabt::wat2wasm("(module (memory 1))").unwrap();
+    let compiler = compiler_for_backend(Backend::Cranelift).unwrap();
+    let module = compile_with_config_with(&wasm, Default::default(), &*compiler).unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+
+    assert_eq!(
+        instance.track_dirty_pages(),
+        Err(ImageError::DirtyPagesUnsupported(Backend::Cranelift))
+    );
+}
@@ -0,0 +1,194 @@
+#[macro_use]
+mod utils;
+
+use utils::Proposal;
+
+static WAT: &'static str = r#"
+    (module
+    (import "env" "memory" (memory 1 4 shared))
+    (func (export "load") (param i32) (result i32)
+        get_local 0
+        i32.atomic.load
+    )
+    (func (export "store8") (param i32 i32)
+        get_local 0
+        get_local 1
+        i32.atomic.store8
+    )
+    (func (export "load8") (param i32) (result i32)
+        get_local 0
+        i32.atomic.load8_u
+    )
+    (func (export "add") (param i32 i32) (result i32)
+        get_local 0
+        get_local 1
+        i32.atomic.rmw.add
+    )
+    (func (export "sub64") (param i32 i64) (result i64)
+        get_local 0
+        get_local 1
+        i64.atomic.rmw.sub
+    )
+    (func (export "or") (param i32 i32) (result i32)
+        get_local 0
+        get_local 1
+        i32.atomic.rmw.or
+    )
+    (func (export "xchg") (param i32 i32) (result i32)
+        get_local 0
+        get_local 1
+        i32.atomic.rmw.xchg
+    )
+    (func (export "cmpxchg") (param i32 i32 i32) (result i32)
+        get_local 0
+        get_local 1
+        get_local 2
+        i32.atomic.rmw.cmpxchg
+    )
+    (func (export "wait") (param i32 i32 i64) (result i32)
+        get_local 0
+        get_local 1
+        get_local 2
+        i32.atomic.wait
+    )
+    (func (export "notify") (param i32 i32) (result i32)
+        get_local 0
+        get_local 1
+        atomic.notify
+    )
+    (func (export "grow") (param i32) (result i32)
+        get_local 0
+        memory.grow
+    )
+    (func (export "size") (result i32)
+        memory.size
+    )
+    )
+"#;
+
+#[cfg(any(feature = "singlepass", feature = "llvm"))]
+fn check_threads(backend: wasmer_runtime::Backend) {
+    use std::{sync::atomic::Ordering, thread, time::Duration};
+    use wasmer_runtime::{
+        imports, memory::SharedMemory, units::Pages, wasm::MemoryDescriptor, Instance, Memory,
+    };
+
+    fn instantiate(backend: wasmer_runtime::Backend, memory: SharedMemory) -> Instance {
+        let wasm = Proposal::Threads.wat2wasm(WAT);
+        let module = Proposal::Threads.compile(&wasm, backend).unwrap();
+        module
+            .instantiate(&imports! {
+                "env" => {
+                    "memory" => Memory::from(memory),
+                },
+            })
+            .unwrap()
+    }
+
+    let memory = Memory::new(MemoryDescriptor {
+        minimum: Pages(1),
+        maximum: Some(Pages(4)),
+        shared: true,
+    })
+    .unwrap();
+    let shared = memory.clone().shared().unwrap();
+    let instance = instantiate(backend, shared.clone());
+
+    let load = instance.func::<i32, i32>("load").unwrap();
+    let store8 = instance.func::<(i32, i32), ()>("store8").unwrap();
+    let load8 = instance.func::<i32, i32>("load8").unwrap();
+    let add = instance.func::<(i32, i32), i32>("add").unwrap();
+    let sub64 = instance.func::<(i32, i64), i64>("sub64").unwrap();
+    let or = instance.func::<(i32, i32), i32>("or").unwrap();
+    let xchg = instance.func::<(i32, i32), i32>("xchg").unwrap();
+    let cmpxchg = instance.func::<(i32, i32, i32), i32>("cmpxchg").unwrap();
+    let wait = instance.func::<(i32, i32, i64), i32>("wait").unwrap();
+    let notify = instance.func::<(i32, i32), i32>("notify").unwrap();
+
+    // Read-modify-write operators return the old value.
+    assert_eq!(add.call(0, 5), Ok(0));
+    assert_eq!(add.call(0, 2), Ok(5));
+    assert_eq!(or.call(0, 0x10), Ok(7));
+    assert_eq!(xchg.call(0, 3), Ok(0x17));
+    assert_eq!(cmpxchg.call(0, 4, 9), Ok(3));
+    assert_eq!(cmpxchg.call(0, 3, 9), Ok(3));
+    assert_eq!(load.call(0), Ok(9));
+    assert_eq!(sub64.call(8, 1), Ok(0));
+    assert_eq!(sub64.call(8, 1), Ok(-1));
+    store8.call(16, 0x1ff).unwrap();
+    assert_eq!(load8.call(16), Ok(0xff));
+    assert_eq!(load8.call(17), Ok(0));
+
+    // Atomic accesses must be naturally aligned.
+    assert!(load.call(2).is_err());
+    assert!(add.call(65536, 1).is_err());
+
+    // The host sees the same memory, atomically.
+    assert_eq!(
+        memory.view::<u32>().atomically()[0].load(Ordering::SeqCst),
+        9
+    );
+
+    // Concurrent increments from instances on other threads aren't lost.
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let instance = instantiate(backend, shared);
+                let add = instance.func::<(i32, i32), i32>("add").unwrap();
+                for _ in 0..1000 {
+                    add.call(32, 1).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(load.call(32), Ok(4000));
+
+    // `wait` returns 1 if the value doesn't match and 2 on timeout.
+    assert_eq!(wait.call(64, 1, -1), Ok(1));
+    assert_eq!(wait.call(64, 0, 1_000_000), Ok(2));
+    assert_eq!(notify.call(64, 1), Ok(0));
+
+    // A notified waiter returns 0.
+    let waiter = {
+        let shared = shared.clone();
+        thread::spawn(move || {
+            let instance = instantiate(backend, shared);
+            let wait = instance.func::<(i32, i32, i64), i32>("wait").unwrap();
+            wait.call(64, 0, -1).unwrap()
+        })
+    };
+    while notify.call(64, 1).unwrap() == 0 {
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(waiter.join().unwrap(), 0);
+
+    // Growing the memory from one instance is visible in the others.
+    let grow = instance.func::<i32, i32>("grow").unwrap();
+    let size = instance.func::<(), i32>("size").unwrap();
+    let other = instantiate(backend, shared.clone());
+    assert_eq!(grow.call(2), Ok(1));
+    assert_eq!(other.func::<(), i32>("size").unwrap().call(), Ok(3));
+    assert_eq!(size.call(), Ok(3));
+    assert_eq!(memory.size(), Pages(3));
+    assert_eq!(grow.call(2), Ok(-1));
+}
+
+#[test]
+fn threads_requires_feature() {
+    assert!(wasmer_runtime::compile(&Proposal::Threads.wat2wasm(WAT)).is_err());
+}
+
+backend_tests!(threads, check_threads, [singlepass, llvm]);
+
+#[cfg(feature = "cranelift")]
+#[test]
+fn threads_unsupported_cranelift() {
+    let wasm = Proposal::Threads.wat2wasm(WAT);
+    assert!(Proposal::Threads
+        .compile(&wasm, wasmer_runtime::Backend::Cranelift)
+        .is_err());
+}
@@ -0,0 +1,81 @@
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    error::{CallError, RuntimeError, WasmTrapInfo},
+    imports, Module, Value,
+};
+
+static WAT: &'static str = r#"
+    (module
+    (type (;0;) (func (param i32 i32) (result i32)))
+    (type (;1;) (func))
+    (func $div (type 0) (param i32 i32) (result i32)
+        get_local 0
+        get_local 1
+        i32.div_s
+    )
+    (func $call_div (type 0) (param i32 i32) (result i32)
+        get_local 0
+        get_local 1
+        call $div
+    )
+    (func $unreachable (type 1)
+        unreachable
+    )
+    (export "div" (func $div))
+    (export "call_div" (func $call_div))
+    (export "unreachable" (func $unreachable))
+    )
+"#;
+
+fn trap_kind(module: &Module, name: &str, params: &[Value]) -> WasmTrapInfo {
+    let instance = module.instantiate(&imports! {}).unwrap();
+    match instance.call(name, params) {
+        Err(CallError::Runtime(RuntimeError::Trap { details, .. })) => details.kind,
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(_) => panic!("call to {} didn't trap", name),
+    }
+}
+
+fn check_trap_kinds(module: &Module) {
+    assert_eq!(
+        trap_kind(module, "div", &[Value::I32(1), Value::I32(0)]),
+        WasmTrapInfo::DivideByZero
+    );
+    assert_eq!(
+        trap_kind(module, "div", &[Value::I32(i32::min_value()), Value::I32(-1)]),
+        WasmTrapInfo::IntegerOverflow
+    );
+    assert_eq!(trap_kind(module, "unreachable", &[]), WasmTrapInfo::Unreachable);
+}
+
+#[test]
+fn trap_kinds() {
+    let wasm = wat2wasm(WAT).unwrap();
+    let module = wasmer_runtime::compile(&wasm).unwrap();
+    check_trap_kinds(&module);
+}
+
+#[cfg(feature = "singlepass")]
+#[test]
+fn trap_frames_singlepass() {
+    use wasmer_runtime::{compile_with_config_with, compiler_for_backend, Backend};
+
+    let wasm = wat2wasm(WAT).unwrap();
+    let compiler = compiler_for_backend(Backend::Singlepass).unwrap();
+    let module = compile_with_config_with(&wasm, Default::default(), &*compiler).unwrap();
+    check_trap_kinds(&module);
+
+    let instance = module.instantiate(&imports! {}).unwrap();
+    match instance.call("call_div", &[Value::I32(1), Value::I32(0)]) {
+        Err(CallError::Runtime(RuntimeError::Trap { details, .. })) => {
+            use wasmer_runtime_core::structures::TypedIndex;
+
+            assert_eq!(details.kind, WasmTrapInfo::DivideByZero);
+            assert_eq!(details.func_index.map(|f| f.index()), Some(0));
+            let frames: Vec<usize> = details.frames.iter().map(|f| f.func_index.index()).collect();
+            assert_eq!(frames, vec![0, 1]);
+        }
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(_) => panic!("call_div didn't trap"),
+    }
+}
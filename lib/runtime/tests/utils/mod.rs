@@ -0,0 +1,90 @@
+//! Helpers shared by the tests of the WebAssembly proposals.
+
+#![allow(dead_code)]
+
+use wasmer_runtime::{compiler_for_backend, error::CompileError, Backend, Module};
+use wasmer_runtime_core::backend::{CompilerConfig, Features};
+
+/// A WebAssembly proposal, which has to be enabled both to assemble and to
+/// compile the modules that use it.
+#[derive(Clone, Copy)]
+pub enum Proposal {
+    MultiValue,
+    BulkMemory,
+    ReferenceTypes,
+    Simd,
+    Threads,
+}
+
+impl Proposal {
+    /// Assembles `wat` with the proposal enabled.
+    pub fn wat2wasm(self, wat: &str) -> Vec<u8> {
+        let mut features = wabt::Features::new();
+        match self {
+            Proposal::MultiValue => features.enable_multi_value(),
+            Proposal::BulkMemory => features.enable_bulk_memory(),
+            Proposal::ReferenceTypes => features.enable_reference_types(),
+            Proposal::Simd => features.enable_simd(),
+            Proposal::Threads => features.enable_threads(),
+        }
+        wabt::wat2wasm_with_features(wat, features).unwrap()
+    }
+
+    /// Compiles `wasm` with the compiler of `backend` and the proposal enabled.
+    pub fn compile(self, wasm: &[u8], backend: Backend) -> Result<Module, CompileError> {
+        let mut features = Features::default();
+        match self {
+            Proposal::MultiValue => features.multi_value = true,
+            Proposal::BulkMemory => features.bulk_memory = true,
+            Proposal::ReferenceTypes => features.reference_types = true,
+            Proposal::Simd => features.simd = true,
+            Proposal::Threads => features.threads = true,
+        }
+        let compiler = compiler_for_backend(backend).unwrap();
+        wasmer_runtime::compile_with_config_with(
+            wasm,
+            CompilerConfig {
+                features,
+                ..Default::default()
+            },
+            &*compiler,
+        )
+    }
+}
+
+/// Generates a `$name` module with a test for each of the listed backends,
+/// or all of them, that calls `$check` with the backend.
+///
+/// The test of a backend is only built when the crate feature of the same
+/// name is enabled.
+macro_rules! backend_tests {
+    (@test singlepass, $check:ident) => {
+        #[cfg(feature = "singlepass")]
+        #[test]
+        fn singlepass() {
+            super::$check(wasmer_runtime::Backend::Singlepass);
+        }
+    };
+    (@test llvm, $check:ident) => {
+        #[cfg(feature = "llvm")]
+        #[test]
+        fn llvm() {
+            super::$check(wasmer_runtime::Backend::LLVM);
+        }
+    };
+    (@test cranelift, $check:ident) => {
+        #[cfg(feature = "cranelift")]
+        #[test]
+        fn cranelift() {
+            super::$check(wasmer_runtime::Backend::Cranelift);
+        }
+    };
+    ($name:ident, $check:ident, [$($backend:ident),*]) => {
+        mod $name {
+            $(backend_tests!(@test $backend, $check);)*
+        }
+    };
+    ($name:ident, $check:ident) => {
+        backend_tests!($name, $check, [singlepass, llvm, cranelift]);
+    };
+}
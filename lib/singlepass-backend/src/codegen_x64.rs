@@ -4,9 +4,7 @@
 use crate::emitter_x64::*;
 use crate::machine::*;
 use crate::protect_unix;
-use dynasmrt::{
-    x64::Assembler, AssemblyOffset, DynamicLabel, DynasmApi, DynasmLabelApi, ExecutableBuffer,
-};
+use dynasmrt::{x64::Assembler, AssemblyOffset, DynamicLabel, DynasmApi, DynasmLabelApi};
 use smallvec::SmallVec;
 use std::ptr::NonNull;
 use std::{
@@ -16,7 +14,8 @@ use std::{
 };
 use wasmer_runtime_core::{
     backend::{
-        sys::Memory, Backend, CacheGen, CompilerConfig, MemoryBoundCheckMode, RunnableModule, Token,
+        sys::{Memory, Protect},
+        Backend, CacheGen, CompilerConfig, MemoryBoundCheckMode, RunnableModule, Token,
     },
     cache::{Artifact, Error as CacheError},
     codegen::*,
@@ -27,7 +26,7 @@ use wasmer_runtime_core::{
         ModuleStateMap, OffsetInfo, SuspendOffset, WasmAbstractValue,
     },
     structures::{Map, TypedIndex},
-    typed_func::Wasm,
+    typed_func::{Wasm, WasmTrapInfo},
     types::{
         FuncIndex, FuncSig, GlobalIndex, LocalFuncIndex, LocalOrImport, MemoryIndex, SigIndex,
         TableIndex, Type,
@@ -154,12 +153,16 @@ pub struct X64FunctionCode {
     returns: SmallVec<[WpType; 1]>,
     locals: Vec<Location>,
     num_params: usize,
-    num_locals: usize,
+    local_types: Vec<WpType>,
     value_stack: Vec<Location>,
     control_stack: Vec<ControlFrame>,
     machine: Machine,
     unreachable_depth: usize,
 
+    /// Frame slots that values pass through when a block with parameters
+    /// or more than one result is entered or left.
+    value_transfer_area: Vec<Location>,
+
     config: Arc<CodegenConfig>,
 }
 
@@ -171,10 +174,7 @@ unsafe impl Send for FuncPtr {}
 unsafe impl Sync for FuncPtr {}
 
 pub struct X64ExecutionContext {
-    #[allow(dead_code)]
-    code: ExecutableBuffer,
-    #[allow(dead_code)]
-    functions: Vec<X64FunctionCode>,
+    code: Arc<Memory>,
     function_pointers: Vec<FuncPtr>,
     function_offsets: Vec<AssemblyOffset>,
     signatures: Arc<Map<SigIndex, FuncSig>>,
@@ -183,17 +183,91 @@ pub struct X64ExecutionContext {
     msm: ModuleStateMap,
 }
 
+/// Backend metadata stored next to the machine code in a cache `Artifact`.
+///
+/// Breakpoint handlers are arbitrary closures and cannot be serialized, so modules
+/// with breakpoints are never cached. Middlewares that only unwind with an error,
+/// such as metering, raise an `InternalTrap` instead and keep modules cacheable.
+#[derive(Serialize, Deserialize)]
+struct CacheImage {
+    function_offsets: Vec<usize>,
+    func_import_count: usize,
+    msm: ModuleStateMap,
+}
+
+struct SinglepassCache {
+    metadata: Box<[u8]>,
+    code: Arc<Memory>,
+    has_breakpoints: bool,
+}
+
+impl CacheGen for SinglepassCache {
+    fn generate_cache(&self) -> Result<(Box<[u8]>, Memory), CacheError> {
+        if self.has_breakpoints {
+            return Err(CacheError::SerializeError(
+                "modules with breakpoints cannot be cached".to_string(),
+            ));
+        }
+        // Clone the memory to a new location. This could take a long time,
+        // depending on the throughput of your memcpy implementation.
+        Ok((self.metadata.clone(), (*self.code).clone()))
+    }
+}
+
+impl X64ExecutionContext {
+    fn new(
+        code: Arc<Memory>,
+        function_offsets: Vec<AssemblyOffset>,
+        signatures: Arc<Map<SigIndex, FuncSig>>,
+        breakpoints: BreakpointMap,
+        func_import_count: usize,
+        msm: ModuleStateMap,
+    ) -> X64ExecutionContext {
+        let function_pointers = function_offsets
+            .iter()
+            .map(|offset| FuncPtr(unsafe { code.as_ptr().add(offset.0) } as _))
+            .collect();
+        X64ExecutionContext {
+            code,
+            function_pointers,
+            function_offsets,
+            signatures,
+            breakpoints,
+            func_import_count,
+            msm,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ControlFrame {
     pub label: DynamicLabel,
     pub loop_like: bool,
     pub if_else: IfElseState,
+    pub params: SmallVec<[WpType; 1]>,
     pub returns: SmallVec<[WpType; 1]>,
     pub value_stack_depth: usize,
     pub state: MachineState,
     pub state_diff_id: usize,
 }
 
+impl ControlFrame {
+    /// The number of values carried by a branch to this frame.
+    fn branch_arity(&self) -> usize {
+        if self.loop_like {
+            self.params.len()
+        } else {
+            self.returns.len()
+        }
+    }
+
+    /// Whether values flow into this frame through the value transfer area
+    /// rather than `RAX`.
+    fn uses_transfer_area(&self) -> bool {
+        self.params.len() > 0 || self.returns.len() > 1 || self.returns.contains(&WpType::V128)
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum IfElseState {
     None,
@@ -222,7 +296,6 @@ impl RunnableModule for X64ExecutionContext {
 
     fn get_trampoline(&self, _: &ModuleInfo, sig_index: SigIndex) -> Option<Wasm> {
         use std::ffi::c_void;
-        use wasmer_runtime_core::typed_func::WasmTrapInfo;
 
         unsafe extern "C" fn invoke(
             _trampoline: unsafe extern "C" fn(
@@ -237,16 +310,14 @@ impl RunnableModule for X64ExecutionContext {
             rets: *mut u64,
             trap_info: *mut WasmTrapInfo,
             user_error: *mut Option<Box<dyn Any>>,
-            num_params_plus_one: Option<NonNull<c_void>>,
+            invoke_env: Option<NonNull<c_void>>,
         ) -> bool {
             let rm: &Box<dyn RunnableModule> = &(&*(*ctx).module).runnable_module;
             let execution_context =
                 ::std::mem::transmute_copy::<&dyn RunnableModule, &X64ExecutionContext>(&&**rm);
 
-            let args = ::std::slice::from_raw_parts(
-                args,
-                num_params_plus_one.unwrap().as_ptr() as usize - 1,
-            );
+            let (num_params, num_return_slots) = decode_invoke_env(invoke_env);
+            let args = ::std::slice::from_raw_parts(args, num_params);
             let args_reverse: SmallVec<[u64; 8]> = args.iter().cloned().rev().collect();
             let ret = match protect_unix::call_protected(
                 || {
@@ -261,7 +332,16 @@ impl RunnableModule for X64ExecutionContext {
             ) {
                 Ok(x) => {
                     if !rets.is_null() {
-                        *rets = x;
+                        if num_return_slots > 0 {
+                            // `x` points to the return buffer of the callee's `Ctx`.
+                            ::std::ptr::copy_nonoverlapping(
+                                x as *const u64,
+                                rets,
+                                num_return_slots,
+                            );
+                        } else {
+                            *rets = x;
+                        }
                     }
                     true
                 }
@@ -289,7 +369,7 @@ impl RunnableModule for X64ExecutionContext {
             Wasm::from_raw_parts(
                 dummy_trampoline,
                 invoke,
-                NonNull::new((self.signatures.get(sig_index).unwrap().params().len() + 1) as _), // +1 to keep it non-zero
+                encode_invoke_env(self.signatures.get(sig_index).unwrap()),
             )
         })
     }
@@ -300,7 +380,7 @@ impl RunnableModule for X64ExecutionContext {
     }
 
     fn get_code(&self) -> Option<&[u8]> {
-        Some(&self.code)
+        Some(unsafe { &self.code.as_slice()[..self.msm.total_size] })
     }
 
     fn get_offsets(&self) -> Option<Vec<usize>> {
@@ -308,9 +388,29 @@ impl RunnableModule for X64ExecutionContext {
     }
 }
 
+/// Packs what `invoke` needs to know about a signature into its environment pointer.
+///
+/// The low 32 bits hold the number of parameter slots plus one, to keep the pointer
+/// non-zero. The high 32 bits hold the number of return slots for functions whose
+/// results take more than one slot, and zero otherwise.
+fn encode_invoke_env(sig: &FuncSig) -> Option<NonNull<std::ffi::c_void>> {
+    let num_return_slots = if sig.return_slots() > 1 {
+        sig.return_slots()
+    } else {
+        0
+    };
+    NonNull::new(((sig.param_slots() + 1) | (num_return_slots << 32)) as _)
+}
+
+/// Reverses `encode_invoke_env`, returning the number of parameter and return slots.
+fn decode_invoke_env(env: Option<NonNull<std::ffi::c_void>>) -> (usize, usize) {
+    let env = env.unwrap().as_ptr() as usize;
+    ((env & 0xffff_ffff) - 1, env >> 32)
+}
+
 #[derive(Debug)]
 pub struct CodegenError {
-    pub message: &'static str,
+    pub message: String,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -318,6 +418,8 @@ struct CodegenConfig {
     memory_bound_check_mode: MemoryBoundCheckMode,
     enforce_stack_check: bool,
     track_state: bool,
+    multi_value: bool,
+    simd: bool,
 }
 
 impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
@@ -389,11 +491,12 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
             returns: smallvec![],
             locals: vec![],
             num_params: 0,
-            num_locals: 0,
+            local_types: vec![],
             value_stack: vec![],
             control_stack: vec![],
             machine,
             unreachable_depth: 0,
+            value_transfer_area: vec![],
             config: self.config.as_ref().unwrap().clone(),
         };
         self.functions.push(code);
@@ -420,7 +523,21 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
         let total_size = assembler.get_offset().0;
         let output = assembler.finalize().unwrap();
 
-        let mut out_labels: Vec<FuncPtr> = vec![];
+        // Move the generated code out of the assembler's buffer into memory we own,
+        // so that it can be written to and restored from the cache.
+        let mut code = Memory::with_size_protect(total_size.max(1), Protect::ReadWrite)
+            .map_err(|_| CodegenError {
+                message: "cannot allocate code memory".to_string(),
+            })?;
+        unsafe {
+            code.as_slice_mut()[..total_size].copy_from_slice(&output[..total_size]);
+            code.protect(.., Protect::ReadExec).map_err(|_| CodegenError {
+                message: "cannot make code memory executable".to_string(),
+            })?;
+        }
+        let code = Arc::new(code);
+        drop(output);
+
         let mut out_offsets: Vec<AssemblyOffset> = vec![];
 
         for i in 0..function_labels.len() {
@@ -428,7 +545,7 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
                 Some(x) => x,
                 None => {
                     return Err(CodegenError {
-                        message: "label not found",
+                        message: "label not found".to_string(),
                     });
                 }
             };
@@ -436,18 +553,19 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
                 Some(x) => x,
                 None => {
                     return Err(CodegenError {
-                        message: "offset is none",
+                        message: "offset is none".to_string(),
                     });
                 }
             };
-            out_labels.push(FuncPtr(output.ptr(*offset) as _));
             out_offsets.push(*offset);
         }
 
+        let code_base = code.as_ptr() as usize;
+        let has_breakpoints = !breakpoints.is_empty();
         let breakpoints: Arc<HashMap<_, _>> = Arc::new(
             breakpoints
                 .into_iter()
-                .map(|(offset, f)| (output.ptr(offset) as usize, f))
+                .map(|(offset, f)| (code_base + offset.0, f))
                 .collect(),
         );
 
@@ -457,29 +575,34 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
             .map(|x| (x.offset, x.fsm.clone()))
             .collect();
 
-        struct Placeholder;
-        impl CacheGen for Placeholder {
-            fn generate_cache(&self) -> Result<(Box<[u8]>, Memory), CacheError> {
-                Err(CacheError::Unknown(
-                    "the singlepass backend doesn't support caching yet".to_string(),
-                ))
-            }
-        }
+        let msm = ModuleStateMap {
+            local_functions: local_function_maps,
+            total_size,
+        };
+
+        let cache_image = CacheImage {
+            function_offsets: out_offsets.iter().map(|x| x.0).collect(),
+            func_import_count: self.func_import_count,
+            msm: msm.clone(),
+        };
+        let metadata = bincode::serialize(&cache_image).map_err(|_| CodegenError {
+            message: "cannot serialize cache image".to_string(),
+        })?;
+
         Ok((
-            X64ExecutionContext {
-                code: output,
-                functions: self.functions,
-                signatures: self.signatures.as_ref().unwrap().clone(),
-                breakpoints: breakpoints,
-                func_import_count: self.func_import_count,
-                function_pointers: out_labels,
-                function_offsets: out_offsets,
-                msm: ModuleStateMap {
-                    local_functions: local_function_maps,
-                    total_size,
-                },
-            },
-            Box::new(Placeholder),
+            X64ExecutionContext::new(
+                code.clone(),
+                out_offsets,
+                self.signatures.as_ref().unwrap().clone(),
+                breakpoints,
+                self.func_import_count,
+                msm,
+            ),
+            Box::new(SinglepassCache {
+                metadata: metadata.into_boxed_slice(),
+                code,
+                has_breakpoints,
+            }),
         ))
     }
 
@@ -535,13 +658,49 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
             memory_bound_check_mode: config.memory_bound_check_mode,
             enforce_stack_check: config.enforce_stack_check,
             track_state: config.track_state,
+            multi_value: config.features.multi_value,
+            simd: config.features.simd,
         }));
         Ok(())
     }
-    unsafe fn from_cache(_artifact: Artifact, _: Token) -> Result<ModuleInner, CacheError> {
-        Err(CacheError::Unknown(
-            "the singlepass compiler API doesn't support caching yet".to_string(),
-        ))
+    unsafe fn from_cache(artifact: Artifact, _: Token) -> Result<ModuleInner, CacheError> {
+        let (info, metadata, mut code) = artifact.consume();
+
+        let cache_image: CacheImage = bincode::deserialize(&metadata)
+            .map_err(|e| CacheError::DeserializeError(e.to_string()))?;
+        if code.size() < cache_image.msm.total_size {
+            return Err(CacheError::DeserializeError(
+                "code memory is smaller than the module state map".to_string(),
+            ));
+        }
+        if code.protection() != Protect::ReadExec {
+            code.protect(.., Protect::ReadExec)
+                .map_err(|e| CacheError::Unknown(format!("{:?}", e)))?;
+        }
+        let code = Arc::new(code);
+
+        let execution_context = X64ExecutionContext::new(
+            code.clone(),
+            cache_image
+                .function_offsets
+                .iter()
+                .map(|&x| AssemblyOffset(x))
+                .collect(),
+            Arc::new(info.signatures.clone()),
+            Arc::new(HashMap::new()),
+            cache_image.func_import_count,
+            cache_image.msm,
+        );
+
+        Ok(ModuleInner {
+            runnable_module: Box::new(execution_context),
+            cache_gen: Box::new(SinglepassCache {
+                metadata,
+                code,
+                has_breakpoints: false,
+            }),
+            info,
+        })
     }
 }
 
@@ -576,17 +735,25 @@ impl X64FunctionCode {
         control_stack: &mut [ControlFrame],
     ) {
         m.state.wasm_stack_private_depth += 1;
-        match loc {
+        let divisor = match loc {
             Location::Imm64(_) | Location::Imm32(_) => {
                 a.emit_mov(sz, loc, Location::GPR(GPR::RCX)); // must not be used during div (rax, rdx)
-                Self::mark_trappable(a, m, fsm, control_stack);
-                op(a, sz, Location::GPR(GPR::RCX));
-            }
-            _ => {
-                Self::mark_trappable(a, m, fsm, control_stack);
-                op(a, sz, loc);
+                Location::GPR(GPR::RCX)
             }
-        }
+            _ => loc,
+        };
+
+        // Check for division by zero explicitly, so that the `SIGFPE` raised by `div`/`idiv`
+        // always means an overflow.
+        let nonzero = a.get_label();
+        a.emit_cmp(sz, Location::Imm32(0), divisor);
+        a.emit_jmp(Condition::NotEqual, nonzero);
+        Self::mark_trappable(a, m, fsm, control_stack);
+        a.emit_trap(WasmTrapInfo::DivideByZero);
+        a.emit_label(nonzero);
+
+        Self::mark_trappable(a, m, fsm, control_stack);
+        op(a, sz, divisor);
         m.state.wasm_stack_private_depth -= 1;
     }
 
@@ -700,6 +867,22 @@ impl X64FunctionCode {
         }
     }
 
+    /// Moves a value of type `ty` from `src` to `dst`, as two halves if it is a `V128`.
+    fn emit_relaxed_move(
+        a: &mut Assembler,
+        m: &mut Machine,
+        ty: WpType,
+        src: Location,
+        dst: Location,
+    ) {
+        for (src, dst) in value_halves(src, ty)
+            .into_iter()
+            .zip(value_halves(dst, ty).into_iter())
+        {
+            Self::emit_relaxed_binop(a, m, Assembler::emit_mov, Size::S64, src, dst);
+        }
+    }
+
     /// Moves `src1` and `src2` to valid locations and possibly adds a layer of indirection for `dst` for AVX instructions.
     fn emit_relaxed_avx(
         a: &mut Assembler,
@@ -1160,6 +1343,178 @@ impl X64FunctionCode {
         Self::emit_relaxed_avx(a, m, f, loc, loc, ret);
     }
 
+    /// Pops a `V128` value off the virtual stack into a temporary XMM register.
+    fn emit_v128_pop(a: &mut Assembler, m: &mut Machine, value_stack: &mut Vec<Location>) -> XMM {
+        let loc = get_location_released(a, m, value_stack.pop().unwrap());
+        let value = m.acquire_temp_xmm().unwrap();
+        a.emit_movdqu(v128_operand(loc), XMMOrMemory::XMM(value));
+        value
+    }
+
+    /// Pushes the `V128` value in the temporary XMM register `value` onto the virtual stack,
+    /// releasing the register.
+    fn emit_v128_push(
+        a: &mut Assembler,
+        m: &mut Machine,
+        value_stack: &mut Vec<Location>,
+        value: XMM,
+    ) {
+        let ret = m.acquire_locations(
+            a,
+            &[(WpType::V128, MachineValue::WasmStack(value_stack.len()))],
+            false,
+        )[0];
+        a.emit_movdqu(XMMOrMemory::XMM(value), v128_operand(ret));
+        m.release_temp_xmm(value);
+        value_stack.push(ret);
+    }
+
+    /// Pushes the scalar of type `ty` in `value` onto the virtual stack.
+    fn emit_push_gpr(
+        a: &mut Assembler,
+        m: &mut Machine,
+        value_stack: &mut Vec<Location>,
+        ty: WpType,
+        value: GPR,
+    ) {
+        let ret = m.acquire_locations(
+            a,
+            &[(ty, MachineValue::WasmStack(value_stack.len()))],
+            false,
+        )[0];
+        let sz = match ty {
+            WpType::I64 | WpType::F64 => Size::S64,
+            _ => Size::S32,
+        };
+        a.emit_mov(sz, Location::GPR(value), ret);
+        value_stack.push(ret);
+    }
+
+    /// Loads the `V128` constant `value` into `dst`.
+    fn emit_v128_const(a: &mut Assembler, m: &mut Machine, value: u128, dst: XMM) {
+        let tmp = m.acquire_temp_gpr().unwrap();
+        a.emit_mov(Size::S64, Location::Imm64(value as u64), Location::GPR(tmp));
+        a.emit_mov(Size::S64, Location::GPR(tmp), Location::XMM(dst));
+        a.emit_mov(
+            Size::S64,
+            Location::Imm64((value >> 64) as u64),
+            Location::GPR(tmp),
+        );
+        a.emit_pinsr(Size::S64, tmp, 1, dst);
+        m.release_temp_gpr(tmp);
+    }
+
+    /// Fills every lane of `dst` with the low `sz` bits of `src`.
+    fn emit_v128_splat(a: &mut Assembler, m: &mut Machine, sz: Size, src: GPR, dst: XMM) {
+        match sz {
+            Size::S8 => {
+                let zero = m.acquire_temp_xmm().unwrap();
+                a.emit_mov(Size::S32, Location::GPR(src), Location::XMM(dst));
+                a.emit_vpxor(zero, XMMOrMemory::XMM(zero), zero);
+                a.emit_vpshufb(dst, XMMOrMemory::XMM(zero), dst);
+                m.release_temp_xmm(zero);
+            }
+            Size::S16 => {
+                a.emit_mov(Size::S32, Location::GPR(src), Location::XMM(dst));
+                a.emit_pshuflw(0, XMMOrMemory::XMM(dst), dst);
+                a.emit_pshufd(0, XMMOrMemory::XMM(dst), dst);
+            }
+            Size::S32 => {
+                a.emit_mov(Size::S32, Location::GPR(src), Location::XMM(dst));
+                a.emit_pshufd(0, XMMOrMemory::XMM(dst), dst);
+            }
+            Size::S64 => {
+                a.emit_mov(Size::S64, Location::GPR(src), Location::XMM(dst));
+                a.emit_vpunpcklqdq(dst, XMMOrMemory::XMM(dst), dst);
+            }
+        }
+    }
+
+    /// Inverts every bit of `value`.
+    fn emit_v128_not(a: &mut Assembler, m: &mut Machine, value: XMM) {
+        let ones = m.acquire_temp_xmm().unwrap();
+        a.emit_vpcmpeqd(ones, XMMOrMemory::XMM(ones), ones);
+        a.emit_vpxor(value, XMMOrMemory::XMM(ones), value);
+        m.release_temp_xmm(ones);
+    }
+
+    /// `V128` binary operation with both operands popped from the virtual stack.
+    fn emit_v128_binop(
+        a: &mut Assembler,
+        m: &mut Machine,
+        value_stack: &mut Vec<Location>,
+        f: fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+    ) {
+        Self::emit_v128_binop_base(a, m, value_stack, |a, _, lhs, rhs| {
+            f(a, lhs, XMMOrMemory::XMM(rhs), lhs)
+        })
+    }
+
+    /// `V128` binary operation with both operands popped from the virtual stack.
+    ///
+    /// `f` gets the operands in registers and leaves the result in the first one.
+    fn emit_v128_binop_base<F: FnOnce(&mut Assembler, &mut Machine, XMM, XMM)>(
+        a: &mut Assembler,
+        m: &mut Machine,
+        value_stack: &mut Vec<Location>,
+        f: F,
+    ) {
+        let rhs = Self::emit_v128_pop(a, m, value_stack);
+        let lhs = Self::emit_v128_pop(a, m, value_stack);
+        f(a, m, lhs, rhs);
+        m.release_temp_xmm(rhs);
+        Self::emit_v128_push(a, m, value_stack, lhs);
+    }
+
+    /// `V128` shift by the count popped from the virtual stack, taken modulo the
+    /// `lane_bits` width of the lanes.
+    fn emit_v128_shift(
+        a: &mut Assembler,
+        m: &mut Machine,
+        value_stack: &mut Vec<Location>,
+        lane_bits: u32,
+        f: fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+    ) {
+        let count = get_location_released(a, m, value_stack.pop().unwrap());
+        let value = Self::emit_v128_pop(a, m, value_stack);
+        let tmp = m.acquire_temp_xmm().unwrap();
+
+        a.emit_mov(Size::S32, count, Location::GPR(GPR::RCX));
+        a.emit_and(
+            Size::S32,
+            Location::Imm32(lane_bits - 1),
+            Location::GPR(GPR::RCX),
+        );
+        a.emit_mov(Size::S32, Location::GPR(GPR::RCX), Location::XMM(tmp));
+        f(a, value, XMMOrMemory::XMM(tmp), value);
+
+        m.release_temp_xmm(tmp);
+        Self::emit_v128_push(a, m, value_stack, value);
+    }
+
+    /// Converts the unsigned integer in `src` to a double in `dst`, clobbering `src`.
+    fn emit_convert_u64_to_f64(a: &mut Assembler, m: &mut Machine, src: GPR, dst: XMM) {
+        let tmp = m.acquire_temp_gpr().unwrap();
+
+        let do_convert = a.get_label();
+        let end_convert = a.get_label();
+
+        a.emit_test_gpr_64(src);
+        a.emit_jmp(Condition::Signed, do_convert);
+        a.emit_vcvtsi2sd_64(dst, GPROrMemory::GPR(src), dst);
+        a.emit_jmp(Condition::None, end_convert);
+        a.emit_label(do_convert);
+        a.emit_mov(Size::S64, Location::GPR(src), Location::GPR(tmp));
+        a.emit_and(Size::S64, Location::Imm32(1), Location::GPR(tmp));
+        a.emit_shr(Size::S64, Location::Imm8(1), Location::GPR(src));
+        a.emit_or(Size::S64, Location::GPR(tmp), Location::GPR(src));
+        a.emit_vcvtsi2sd_64(dst, GPROrMemory::GPR(src), dst);
+        a.emit_vaddsd(dst, XMMOrMemory::XMM(dst), dst);
+        a.emit_label(end_convert);
+
+        m.release_temp_gpr(tmp);
+    }
+
     /// Emits a System V call sequence.
     ///
     /// This function must not use RAX before `cb` is called.
@@ -1400,6 +1755,184 @@ impl X64FunctionCode {
         Self::emit_call_sysv(a, m, |a| a.emit_call_label(label), params, state_context)
     }
 
+    /// Emits a call to a runtime intrinsic that returns nothing.
+    ///
+    /// `immediates` are passed before the top `num_operands` values of the value stack,
+    /// which are popped in order.
+    fn emit_call_intrinsic(
+        a: &mut Assembler,
+        m: &mut Machine,
+        value_stack: &mut Vec<Location>,
+        intrinsic_offset: u8,
+        immediates: &[u32],
+        num_operands: usize,
+    ) {
+        let operands = value_stack.split_off(value_stack.len() - num_operands);
+
+        m.release_locations_only_regs(&operands);
+
+        a.emit_mov(
+            Size::S64,
+            Location::Memory(
+                Machine::get_vmctx_reg(),
+                vm::Ctx::offset_intrinsics() as i32,
+            ),
+            Location::GPR(GPR::RAX),
+        );
+        a.emit_mov(
+            Size::S64,
+            Location::Memory(GPR::RAX, intrinsic_offset as i32),
+            Location::GPR(GPR::RAX),
+        );
+
+        m.release_locations_only_osr_state(num_operands);
+
+        Self::emit_call_sysv(
+            a,
+            m,
+            |a| {
+                a.emit_call_location(Location::GPR(GPR::RAX));
+            },
+            immediates
+                .iter()
+                .map(|&x| Location::Imm32(x))
+                .chain(operands.iter().cloned()),
+            None,
+        );
+
+        m.release_locations_only_stack(a, &operands);
+    }
+
+    /// Pushes the results of a call onto the value stack.
+    ///
+    /// A function with a single result returns it in `RAX`. A function with more than
+    /// one result, or a `V128` result, writes them to the return buffer of its `Ctx`
+    /// and returns a pointer to that buffer in `RAX`.
+    fn emit_call_results(
+        a: &mut Assembler,
+        m: &mut Machine,
+        value_stack: &mut Vec<Location>,
+        return_types: &[WpType],
+    ) {
+        if return_types.len() == 0 {
+            return;
+        }
+        let tys: SmallVec<[(WpType, MachineValue); 1]> = return_types
+            .iter()
+            .enumerate()
+            .map(|(i, &ty)| (ty, MachineValue::WasmStack(value_stack.len() + i)))
+            .collect();
+        let rets = m.acquire_locations(a, &tys, false);
+        if rets.len() == 1 && return_types[0] != WpType::V128 {
+            a.emit_mov(Size::S64, Location::GPR(GPR::RAX), rets[0]);
+        } else {
+            let slots = rets
+                .iter()
+                .zip(return_types.iter())
+                .flat_map(|(&ret, &ty)| value_halves(ret, ty));
+            for (i, ret) in slots.enumerate() {
+                a.emit_mov(
+                    Size::S64,
+                    Location::Memory(GPR::RAX, (i * 8) as i32),
+                    Location::GPR(GPR::RCX),
+                );
+                a.emit_mov(Size::S64, Location::GPR(GPR::RCX), ret);
+            }
+        }
+        value_stack.extend(rets);
+    }
+
+    /// Returns the parameter and result types of a block.
+    fn get_block_type(
+        signatures: &Map<SigIndex, FuncSig>,
+        ty: WpTypeOrFuncType,
+    ) -> (SmallVec<[WpType; 1]>, SmallVec<[WpType; 1]>) {
+        match ty {
+            WpTypeOrFuncType::Type(WpType::EmptyBlockType) => (smallvec![], smallvec![]),
+            WpTypeOrFuncType::Type(inner_ty) => (smallvec![], smallvec![inner_ty]),
+            WpTypeOrFuncType::FuncType(index) => {
+                let sig = signatures.get(SigIndex::new(index as usize)).unwrap();
+                (
+                    sig.params().iter().cloned().map(type_to_wp_type).collect(),
+                    sig.returns().iter().cloned().map(type_to_wp_type).collect(),
+                )
+            }
+        }
+    }
+
+    /// Moves the top `n` values of the value stack to where the code at a merge point
+    /// expects them: the value transfer area if `via_transfer_area` is set, `RAX` otherwise.
+    fn emit_values_out(
+        a: &mut Assembler,
+        m: &mut Machine,
+        value_stack: &[Location],
+        n: usize,
+        via_transfer_area: bool,
+        transfer_area: &[Location],
+    ) {
+        let values = &value_stack[value_stack.len() - n..];
+        if via_transfer_area {
+            let halves: SmallVec<[Location; 2]> = values
+                .iter()
+                .flat_map(|&value| {
+                    let ty = if m.is_v128(value) {
+                        WpType::V128
+                    } else {
+                        WpType::I64
+                    };
+                    value_halves(value, ty)
+                })
+                .collect();
+            for (value, slot) in halves.iter().zip(transfer_area.iter()) {
+                Self::emit_relaxed_binop(a, m, Assembler::emit_mov, Size::S64, *value, *slot);
+            }
+        } else if n > 0 {
+            assert_eq!(n, 1);
+            Self::emit_relaxed_binop(
+                a,
+                m,
+                Assembler::emit_mov,
+                Size::S64,
+                values[0],
+                Location::GPR(GPR::RAX),
+            );
+        }
+    }
+
+    /// Pushes values of types `tys` onto the value stack, taking them from where
+    /// `emit_values_out` put them.
+    fn emit_values_in(
+        a: &mut Assembler,
+        m: &mut Machine,
+        value_stack: &mut Vec<Location>,
+        tys: &[WpType],
+        via_transfer_area: bool,
+        transfer_area: &[Location],
+    ) {
+        if tys.len() == 0 {
+            return;
+        }
+        let tys: SmallVec<[(WpType, MachineValue); 1]> = tys
+            .iter()
+            .enumerate()
+            .map(|(i, &ty)| (ty, MachineValue::WasmStack(value_stack.len() + i)))
+            .collect();
+        let locs = m.acquire_locations(a, &tys, false);
+        if via_transfer_area {
+            let halves = locs
+                .iter()
+                .zip(tys.iter())
+                .flat_map(|(&loc, &(ty, _))| value_halves(loc, ty));
+            for (loc, slot) in halves.zip(transfer_area.iter()) {
+                Self::emit_relaxed_binop(a, m, Assembler::emit_mov, Size::S64, *slot, loc);
+            }
+        } else {
+            assert_eq!(locs.len(), 1);
+            a.emit_mov(Size::S64, Location::GPR(GPR::RAX), locs[0]);
+        }
+        value_stack.extend(locs);
+    }
+
     /// Emits a memory operation.
     fn emit_memory_op<F: FnOnce(&mut Assembler, &mut Machine, GPR)>(
         module_info: &ModuleInfo,
@@ -1476,7 +2009,7 @@ impl X64FunctionCode {
             // Trap if the end address of the requested area is above that of the linear memory.
             a.emit_add(Size::S64, Location::GPR(tmp_base), Location::GPR(tmp_addr));
             a.emit_cmp(Size::S64, Location::GPR(tmp_bound), Location::GPR(tmp_addr));
-            a.emit_conditional_trap(Condition::Above);
+            a.emit_conditional_trap(Condition::Above, WasmTrapInfo::MemoryOutOfBounds);
         }
 
         m.release_temp_gpr(tmp_bound);
@@ -1496,6 +2029,57 @@ impl X64FunctionCode {
         m.release_temp_gpr(tmp_addr);
     }
 
+    /// Like `emit_memory_op`, but also traps if the address isn't aligned to
+    /// `value_size`, as atomic accesses must be.
+    fn emit_atomic_memory_op<F: FnOnce(&mut Assembler, &mut Machine, GPR)>(
+        module_info: &ModuleInfo,
+        config: &CodegenConfig,
+        a: &mut Assembler,
+        m: &mut Machine,
+        addr: Location,
+        offset: usize,
+        value_size: usize,
+        cb: F,
+    ) {
+        Self::emit_memory_op(
+            module_info,
+            config,
+            a,
+            m,
+            addr,
+            offset,
+            value_size,
+            |a, m, addr| {
+                if value_size > 1 {
+                    let tmp = m.acquire_temp_gpr().unwrap();
+                    a.emit_mov(Size::S64, Location::GPR(addr), Location::GPR(tmp));
+                    a.emit_and(
+                        Size::S64,
+                        Location::Imm32(value_size as u32 - 1),
+                        Location::GPR(tmp),
+                    );
+                    a.emit_conditional_trap(
+                        Condition::NotEqual,
+                        WasmTrapInfo::MisalignedAtomicAccess,
+                    );
+                    m.release_temp_gpr(tmp);
+                }
+                cb(a, m, addr);
+            },
+        );
+    }
+
+    /// Zero-extends the `sz` low bits of `reg` to 64 bits.
+    fn emit_zero_extend(a: &mut Assembler, sz: Size, reg: GPR) {
+        match sz {
+            Size::S8 | Size::S16 => {
+                a.emit_movzx(sz, Location::GPR(reg), Size::S64, Location::GPR(reg))
+            }
+            Size::S32 => a.emit_mov(Size::S32, Location::GPR(reg), Location::GPR(reg)),
+            Size::S64 => {}
+        }
+    }
+
     // Checks for underflow/overflow/nan before IxxTrunc{U/S}F32.
     fn emit_f32_int_conv_check(
         a: &mut Assembler,
@@ -1504,12 +2088,31 @@ impl X64FunctionCode {
         lower_bound: f32,
         upper_bound: f32,
     ) {
-        let lower_bound = f32::to_bits(lower_bound);
-        let upper_bound = f32::to_bits(upper_bound);
-
         let trap = a.get_label();
         let end = a.get_label();
 
+        Self::emit_f32_int_conv_check_labels(a, m, reg, lower_bound, upper_bound, trap, trap, trap);
+        a.emit_jmp(Condition::None, end);
+        a.emit_label(trap);
+        a.emit_trap(WasmTrapInfo::IllegalArithmetic);
+        a.emit_label(end);
+    }
+
+    // Jumps to `underflow`, `overflow` or `nan` if `reg` is out of range for IxxTrunc{U/S}F32.
+    #[allow(clippy::too_many_arguments)]
+    fn emit_f32_int_conv_check_labels(
+        a: &mut Assembler,
+        m: &mut Machine,
+        reg: XMM,
+        lower_bound: f32,
+        upper_bound: f32,
+        underflow: DynamicLabel,
+        overflow: DynamicLabel,
+        nan: DynamicLabel,
+    ) {
+        let lower_bound = f32::to_bits(lower_bound);
+        let upper_bound = f32::to_bits(upper_bound);
+
         let tmp = m.acquire_temp_gpr().unwrap();
         let tmp_x = m.acquire_temp_xmm().unwrap();
 
@@ -1519,7 +2122,7 @@ impl X64FunctionCode {
         a.emit_vcmpless(reg, XMMOrMemory::XMM(tmp_x), tmp_x);
         a.emit_mov(Size::S32, Location::XMM(tmp_x), Location::GPR(tmp));
         a.emit_cmp(Size::S32, Location::Imm32(0), Location::GPR(tmp));
-        a.emit_jmp(Condition::NotEqual, trap);
+        a.emit_jmp(Condition::NotEqual, underflow);
 
         // Overflow.
         a.emit_mov(Size::S32, Location::Imm32(upper_bound), Location::GPR(tmp));
@@ -1527,18 +2130,13 @@ impl X64FunctionCode {
         a.emit_vcmpgess(reg, XMMOrMemory::XMM(tmp_x), tmp_x);
         a.emit_mov(Size::S32, Location::XMM(tmp_x), Location::GPR(tmp));
         a.emit_cmp(Size::S32, Location::Imm32(0), Location::GPR(tmp));
-        a.emit_jmp(Condition::NotEqual, trap);
+        a.emit_jmp(Condition::NotEqual, overflow);
 
         // NaN.
         a.emit_vcmpeqss(reg, XMMOrMemory::XMM(reg), tmp_x);
         a.emit_mov(Size::S32, Location::XMM(tmp_x), Location::GPR(tmp));
         a.emit_cmp(Size::S32, Location::Imm32(0), Location::GPR(tmp));
-        a.emit_jmp(Condition::Equal, trap);
-
-        a.emit_jmp(Condition::None, end);
-        a.emit_label(trap);
-        a.emit_ud2();
-        a.emit_label(end);
+        a.emit_jmp(Condition::Equal, nan);
 
         m.release_temp_xmm(tmp_x);
         m.release_temp_gpr(tmp);
@@ -1552,12 +2150,31 @@ impl X64FunctionCode {
         lower_bound: f64,
         upper_bound: f64,
     ) {
-        let lower_bound = f64::to_bits(lower_bound);
-        let upper_bound = f64::to_bits(upper_bound);
-
         let trap = a.get_label();
         let end = a.get_label();
 
+        Self::emit_f64_int_conv_check_labels(a, m, reg, lower_bound, upper_bound, trap, trap, trap);
+        a.emit_jmp(Condition::None, end);
+        a.emit_label(trap);
+        a.emit_trap(WasmTrapInfo::IllegalArithmetic);
+        a.emit_label(end);
+    }
+
+    // Jumps to `underflow`, `overflow` or `nan` if `reg` is out of range for IxxTrunc{U/S}F64.
+    #[allow(clippy::too_many_arguments)]
+    fn emit_f64_int_conv_check_labels(
+        a: &mut Assembler,
+        m: &mut Machine,
+        reg: XMM,
+        lower_bound: f64,
+        upper_bound: f64,
+        underflow: DynamicLabel,
+        overflow: DynamicLabel,
+        nan: DynamicLabel,
+    ) {
+        let lower_bound = f64::to_bits(lower_bound);
+        let upper_bound = f64::to_bits(upper_bound);
+
         let tmp = m.acquire_temp_gpr().unwrap();
         let tmp_x = m.acquire_temp_xmm().unwrap();
 
@@ -1567,7 +2184,7 @@ impl X64FunctionCode {
         a.emit_vcmplesd(reg, XMMOrMemory::XMM(tmp_x), tmp_x);
         a.emit_mov(Size::S32, Location::XMM(tmp_x), Location::GPR(tmp));
         a.emit_cmp(Size::S32, Location::Imm32(0), Location::GPR(tmp));
-        a.emit_jmp(Condition::NotEqual, trap);
+        a.emit_jmp(Condition::NotEqual, underflow);
 
         // Overflow.
         a.emit_mov(Size::S64, Location::Imm64(upper_bound), Location::GPR(tmp));
@@ -1575,21 +2192,155 @@ impl X64FunctionCode {
         a.emit_vcmpgesd(reg, XMMOrMemory::XMM(tmp_x), tmp_x);
         a.emit_mov(Size::S32, Location::XMM(tmp_x), Location::GPR(tmp));
         a.emit_cmp(Size::S32, Location::Imm32(0), Location::GPR(tmp));
-        a.emit_jmp(Condition::NotEqual, trap);
+        a.emit_jmp(Condition::NotEqual, overflow);
 
         // NaN.
         a.emit_vcmpeqsd(reg, XMMOrMemory::XMM(reg), tmp_x);
         a.emit_mov(Size::S32, Location::XMM(tmp_x), Location::GPR(tmp));
         a.emit_cmp(Size::S32, Location::Imm32(0), Location::GPR(tmp));
-        a.emit_jmp(Condition::Equal, trap);
+        a.emit_jmp(Condition::Equal, nan);
+
+        m.release_temp_xmm(tmp_x);
+        m.release_temp_gpr(tmp);
+    }
+
+    /// Emits IxxTrunc{U/S}SatFxx, which clamps out-of-range inputs to the
+    /// bounds of the result type and converts NaN to zero instead of trapping.
+    fn emit_trunc_sat(
+        a: &mut Assembler,
+        m: &mut Machine,
+        float_sz: Size,
+        int_sz: Size,
+        signed: bool,
+        loc: Location,
+        ret: Location,
+    ) {
+        let (lower_bound, upper_bound) = match (float_sz, int_sz, signed) {
+            (_, Size::S32, false) => (-1.0, 4294967296.0),
+            (Size::S32, Size::S32, true) => (-2147483904.0, 2147483648.0),
+            (_, Size::S32, true) => (-2147483649.0, 2147483648.0),
+            (_, _, false) => (-1.0, 18446744073709551616.0),
+            (Size::S32, _, true) => (-9223373136366403584.0, 9223372036854775808.0),
+            (_, _, true) => (-9223372036854777856.0, 9223372036854775808.0),
+        };
+        let (min, max) = match (int_sz, signed) {
+            (Size::S32, true) => (std::i32::MIN as u32 as u64, std::i32::MAX as u64),
+            (Size::S32, false) => (0, std::u32::MAX as u64),
+            (_, true) => (std::i64::MIN as u64, std::i64::MAX as u64),
+            (_, false) => (0, std::u64::MAX),
+        };
+
+        let tmp_out = m.acquire_temp_gpr().unwrap();
+        let tmp_in = m.acquire_temp_xmm().unwrap();
+
+        Self::emit_relaxed_binop(
+            a,
+            m,
+            Assembler::emit_mov,
+            float_sz,
+            loc,
+            Location::XMM(tmp_in),
+        );
+
+        let underflow = a.get_label();
+        let overflow = a.get_label();
+        let nan = a.get_label();
+        let end = a.get_label();
+        if float_sz == Size::S32 {
+            Self::emit_f32_int_conv_check_labels(
+                a,
+                m,
+                tmp_in,
+                lower_bound as f32,
+                upper_bound as f32,
+                underflow,
+                overflow,
+                nan,
+            );
+        } else {
+            Self::emit_f64_int_conv_check_labels(
+                a,
+                m,
+                tmp_in,
+                lower_bound,
+                upper_bound,
+                underflow,
+                overflow,
+                nan,
+            );
+        }
+
+        let cvtt: fn(&mut Assembler, XMMOrMemory, GPR) = match (float_sz, int_sz) {
+            (Size::S32, Size::S32) if signed => Assembler::emit_cvttss2si_32,
+            (Size::S32, _) => Assembler::emit_cvttss2si_64,
+            (_, Size::S32) if signed => Assembler::emit_cvttsd2si_32,
+            (_, _) => Assembler::emit_cvttsd2si_64,
+        };
+        if int_sz == Size::S64 && !signed {
+            // Inputs of 2^63 and above are biased into the signed range, and
+            // the sign bit is restored after the conversion.
+            let tmp = m.acquire_temp_gpr().unwrap();
+            let tmp_x1 = m.acquire_temp_xmm().unwrap();
+            let tmp_x2 = m.acquire_temp_xmm().unwrap();
+
+            if float_sz == Size::S32 {
+                a.emit_mov(
+                    Size::S32,
+                    Location::Imm32(f32::to_bits(9223372036854775808.0)),
+                    Location::GPR(tmp),
+                );
+                a.emit_mov(Size::S32, Location::GPR(tmp), Location::XMM(tmp_x1));
+                a.emit_mov(Size::S32, Location::XMM(tmp_in), Location::XMM(tmp_x2));
+                a.emit_vsubss(tmp_in, XMMOrMemory::XMM(tmp_x1), tmp_in);
+            } else {
+                a.emit_mov(
+                    Size::S64,
+                    Location::Imm64(f64::to_bits(9223372036854775808.0)),
+                    Location::GPR(tmp),
+                );
+                a.emit_mov(Size::S64, Location::GPR(tmp), Location::XMM(tmp_x1));
+                a.emit_mov(Size::S64, Location::XMM(tmp_in), Location::XMM(tmp_x2));
+                a.emit_vsubsd(tmp_in, XMMOrMemory::XMM(tmp_x1), tmp_in);
+            }
+            cvtt(a, XMMOrMemory::XMM(tmp_in), tmp_out);
+            a.emit_mov(
+                Size::S64,
+                Location::Imm64(0x8000000000000000u64),
+                Location::GPR(tmp),
+            );
+            a.emit_xor(Size::S64, Location::GPR(tmp_out), Location::GPR(tmp));
+            cvtt(a, XMMOrMemory::XMM(tmp_x2), tmp_out);
+            if float_sz == Size::S32 {
+                a.emit_ucomiss(XMMOrMemory::XMM(tmp_x1), tmp_x2);
+            } else {
+                a.emit_ucomisd(XMMOrMemory::XMM(tmp_x1), tmp_x2);
+            }
+            a.emit_cmovae_gpr_64(tmp, tmp_out);
 
+            m.release_temp_xmm(tmp_x2);
+            m.release_temp_xmm(tmp_x1);
+            m.release_temp_gpr(tmp);
+        } else {
+            cvtt(a, XMMOrMemory::XMM(tmp_in), tmp_out);
+        }
         a.emit_jmp(Condition::None, end);
-        a.emit_label(trap);
-        a.emit_ud2();
+
+        a.emit_label(underflow);
+        a.emit_mov(Size::S64, Location::Imm64(min), Location::GPR(tmp_out));
+        a.emit_jmp(Condition::None, end);
+
+        a.emit_label(overflow);
+        a.emit_mov(Size::S64, Location::Imm64(max), Location::GPR(tmp_out));
+        a.emit_jmp(Condition::None, end);
+
+        a.emit_label(nan);
+        a.emit_mov(Size::S64, Location::Imm64(0), Location::GPR(tmp_out));
+
         a.emit_label(end);
+        a.emit_mov(int_sz, Location::GPR(tmp_out), ret);
 
-        m.release_temp_xmm(tmp_x);
-        m.release_temp_gpr(tmp);
+        m.release_temp_xmm(tmp_in);
+        m.release_temp_gpr(tmp_out);
     }
 
     pub fn get_state_diff(
@@ -1617,14 +2368,14 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
         Ok(())
     }
 
-    fn feed_param(&mut self, _ty: WpType) -> Result<(), CodegenError> {
+    fn feed_param(&mut self, ty: WpType) -> Result<(), CodegenError> {
         self.num_params += 1;
-        self.num_locals += 1;
+        self.local_types.push(ty);
         Ok(())
     }
 
-    fn feed_local(&mut self, _ty: WpType, n: usize) -> Result<(), CodegenError> {
-        self.num_locals += n;
+    fn feed_local(&mut self, ty: WpType, n: usize) -> Result<(), CodegenError> {
+        self.local_types.extend(::std::iter::repeat(ty).take(n));
         Ok(())
     }
 
@@ -1643,12 +2394,31 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 ),
                 Location::GPR(GPR::RSP),
             );
-            a.emit_conditional_trap(Condition::Below);
+            a.emit_conditional_trap(Condition::Below, WasmTrapInfo::StackOverflow);
         }
 
         self.locals = self
             .machine
-            .init_locals(a, self.num_locals, self.num_params);
+            .init_locals(a, &self.local_types, self.num_params);
+
+        // Blocks can only have parameters or more than one result with multi-value,
+        // and their arity is bounded by that of the largest signature. A block with
+        // a single `V128` result needs two slots.
+        let transfer_slots = if self.config.multi_value {
+            self.signatures
+                .iter()
+                .map(|(_, sig)| sig.param_slots().max(sig.return_slots()))
+                .max()
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        let transfer_slots = if self.config.simd {
+            transfer_slots.max(2)
+        } else {
+            transfer_slots
+        };
+        self.value_transfer_area = self.machine.reserve_fixed_slots(a, transfer_slots);
 
         self.machine.state.register_values
             [X64Register::GPR(Machine::get_vmctx_reg()).to_index().0] = MachineValue::Vmctx;
@@ -1674,6 +2444,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             label: a.get_label(),
             loop_like: false,
             if_else: IfElseState::None,
+            params: smallvec![],
             returns: self.returns.clone(),
             value_stack_depth: 0,
             state: self.machine.state.clone(),
@@ -1775,6 +2546,14 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                             .unwrap()
                             .insert(a.get_offset(), callback);
                     }
+                    InternalEvent::Trap(trap) => Self::emit_call_intrinsic(
+                        a,
+                        &mut self.machine,
+                        &mut self.value_stack,
+                        vm::Intrinsics::offset_internal_trap(),
+                        &[trap as u32],
+                        0,
+                    ),
                     InternalEvent::FunctionBegin(_) | InternalEvent::FunctionEnd => {}
                     InternalEvent::GetInternal(idx) => {
                         let idx = idx as usize;
@@ -1854,61 +2633,43 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
 
                 let tmp = self.machine.acquire_temp_gpr().unwrap();
 
-                let loc = match GlobalIndex::new(global_index).local_or_import(module_info) {
-                    LocalOrImport::Local(local_index) => {
-                        a.emit_mov(
-                            Size::S64,
-                            Location::Memory(
-                                Machine::get_vmctx_reg(),
-                                vm::Ctx::offset_globals() as i32,
-                            ),
-                            Location::GPR(tmp),
-                        );
-                        a.emit_mov(
-                            Size::S64,
-                            Location::Memory(tmp, (local_index.index() as i32) * 8),
-                            Location::GPR(tmp),
-                        );
-                        self.machine.acquire_locations(
-                            a,
-                            &[(
-                                type_to_wp_type(module_info.globals[local_index].desc.ty),
-                                MachineValue::WasmStack(self.value_stack.len()),
-                            )],
-                            false,
-                        )[0]
-                    }
-                    LocalOrImport::Import(import_index) => {
-                        a.emit_mov(
-                            Size::S64,
-                            Location::Memory(
-                                Machine::get_vmctx_reg(),
-                                vm::Ctx::offset_imported_globals() as i32,
-                            ),
-                            Location::GPR(tmp),
-                        );
-                        a.emit_mov(
-                            Size::S64,
-                            Location::Memory(tmp, (import_index.index() as i32) * 8),
-                            Location::GPR(tmp),
-                        );
-                        self.machine.acquire_locations(
-                            a,
-                            &[(
-                                type_to_wp_type(module_info.imported_globals[import_index].1.ty),
-                                MachineValue::WasmStack(self.value_stack.len()),
-                            )],
-                            false,
-                        )[0]
-                    }
-                };
+                let (globals_offset, index, ty) =
+                    match GlobalIndex::new(global_index).local_or_import(module_info) {
+                        LocalOrImport::Local(local_index) => (
+                            vm::Ctx::offset_globals(),
+                            local_index.index(),
+                            module_info.globals[local_index].desc.ty,
+                        ),
+                        LocalOrImport::Import(import_index) => (
+                            vm::Ctx::offset_imported_globals(),
+                            import_index.index(),
+                            module_info.imported_globals[import_index].1.ty,
+                        ),
+                    };
+                a.emit_mov(
+                    Size::S64,
+                    Location::Memory(Machine::get_vmctx_reg(), globals_offset as i32),
+                    Location::GPR(tmp),
+                );
+                a.emit_mov(
+                    Size::S64,
+                    Location::Memory(tmp, (index as i32) * 8),
+                    Location::GPR(tmp),
+                );
+                let loc = self.machine.acquire_locations(
+                    a,
+                    &[(
+                        type_to_wp_type(ty),
+                        MachineValue::WasmStack(self.value_stack.len()),
+                    )],
+                    false,
+                )[0];
                 self.value_stack.push(loc);
 
-                Self::emit_relaxed_binop(
+                Self::emit_relaxed_move(
                     a,
                     &mut self.machine,
-                    Assembler::emit_mov,
-                    Size::S64,
+                    type_to_wp_type(ty),
                     Location::Memory(tmp, LocalGlobal::offset_data() as i32),
                     loc,
                 );
@@ -1917,6 +2678,12 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             }
             Operator::SetGlobal { global_index } => {
                 let mut global_index = global_index as usize;
+                let ty = match GlobalIndex::new(global_index).local_or_import(module_info) {
+                    LocalOrImport::Local(local_index) => module_info.globals[local_index].desc.ty,
+                    LocalOrImport::Import(import_index) => {
+                        module_info.imported_globals[import_index].1.ty
+                    }
+                };
                 let loc =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
 
@@ -1948,11 +2715,10 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Location::Memory(tmp, (global_index as i32) * 8),
                     Location::GPR(tmp),
                 );
-                Self::emit_relaxed_binop(
+                Self::emit_relaxed_move(
                     a,
                     &mut self.machine,
-                    Assembler::emit_mov,
-                    Size::S64,
+                    type_to_wp_type(ty),
                     loc,
                     Location::Memory(tmp, LocalGlobal::offset_data() as i32),
                 );
@@ -1961,19 +2727,16 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             }
             Operator::GetLocal { local_index } => {
                 let local_index = local_index as usize;
+                let ty = match self.local_types[local_index] {
+                    WpType::V128 => WpType::V128,
+                    _ => WpType::I64,
+                };
                 let ret = self.machine.acquire_locations(
                     a,
-                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    &[(ty, MachineValue::WasmStack(self.value_stack.len()))],
                     false,
                 )[0];
-                Self::emit_relaxed_binop(
-                    a,
-                    &mut self.machine,
-                    Assembler::emit_mov,
-                    Size::S64,
-                    self.locals[local_index],
-                    ret,
-                );
+                Self::emit_relaxed_move(a, &mut self.machine, ty, self.locals[local_index], ret);
                 self.value_stack.push(ret);
             }
             Operator::SetLocal { local_index } => {
@@ -1981,11 +2744,10 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 let loc =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
 
-                Self::emit_relaxed_binop(
+                Self::emit_relaxed_move(
                     a,
                     &mut self.machine,
-                    Assembler::emit_mov,
-                    Size::S64,
+                    self.local_types[local_index],
                     loc,
                     self.locals[local_index],
                 );
@@ -1994,11 +2756,10 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 let local_index = local_index as usize;
                 let loc = *self.value_stack.last().unwrap();
 
-                Self::emit_relaxed_binop(
+                Self::emit_relaxed_move(
                     a,
                     &mut self.machine,
-                    Assembler::emit_mov,
-                    Size::S64,
+                    self.local_types[local_index],
                     loc,
                     self.locals[local_index],
                 );
@@ -2477,7 +3238,8 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 &mut self.value_stack,
                 Condition::NotEqual,
             ),
-            Operator::I64Eqz => Self::emit_cmpop_i64_dynamic_b(
+            // References are 64-bit values, with null being zero.
+            Operator::I64Eqz | Operator::RefIsNull => Self::emit_cmpop_i64_dynamic_b(
                 a,
                 &mut self.machine,
                 &mut self.value_stack,
@@ -2632,6 +3394,101 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     ret,
                 );
             }
+            Operator::I32Extend8S => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_relaxed_zx_sx(
+                    a,
+                    &mut self.machine,
+                    Assembler::emit_movsx,
+                    Size::S8,
+                    loc,
+                    Size::S32,
+                    ret,
+                );
+            }
+            Operator::I32Extend16S => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_relaxed_zx_sx(
+                    a,
+                    &mut self.machine,
+                    Assembler::emit_movsx,
+                    Size::S16,
+                    loc,
+                    Size::S32,
+                    ret,
+                );
+            }
+            Operator::I64Extend8S => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_relaxed_zx_sx(
+                    a,
+                    &mut self.machine,
+                    Assembler::emit_movsx,
+                    Size::S8,
+                    loc,
+                    Size::S64,
+                    ret,
+                );
+            }
+            Operator::I64Extend16S => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_relaxed_zx_sx(
+                    a,
+                    &mut self.machine,
+                    Assembler::emit_movsx,
+                    Size::S16,
+                    loc,
+                    Size::S64,
+                    ret,
+                );
+            }
+            Operator::I64Extend32S => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_relaxed_zx_sx(
+                    a,
+                    &mut self.machine,
+                    Assembler::emit_movsx,
+                    Size::S32,
+                    loc,
+                    Size::S64,
+                    ret,
+                );
+            }
 
             Operator::F32Const { value } => {
                 self.value_stack.push(Location::Imm32(value.bits()));
@@ -3438,31 +4295,127 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 self.machine.release_temp_gpr(tmp_out);
             }
 
-            Operator::F32ConvertSI32 => {
+            Operator::I32TruncSSatF32 => {
                 let loc =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
                 let ret = self.machine.acquire_locations(
                     a,
-                    &[(WpType::F32, MachineValue::WasmStack(self.value_stack.len()))],
+                    &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
                     false,
                 )[0];
                 self.value_stack.push(ret);
-                let tmp_out = self.machine.acquire_temp_xmm().unwrap();
-                let tmp_in = self.machine.acquire_temp_gpr().unwrap();
-
-                a.emit_mov(Size::S32, loc, Location::GPR(tmp_in));
-                a.emit_vcvtsi2ss_32(tmp_out, GPROrMemory::GPR(tmp_in), tmp_out);
-                a.emit_mov(Size::S32, Location::XMM(tmp_out), ret);
-
-                self.machine.release_temp_gpr(tmp_in);
-                self.machine.release_temp_xmm(tmp_out);
+                Self::emit_trunc_sat(a, &mut self.machine, Size::S32, Size::S32, true, loc, ret);
             }
-            Operator::F32ConvertUI32 => {
+
+            Operator::I32TruncUSatF32 => {
                 let loc =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
                 let ret = self.machine.acquire_locations(
                     a,
-                    &[(WpType::F32, MachineValue::WasmStack(self.value_stack.len()))],
+                    &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_trunc_sat(a, &mut self.machine, Size::S32, Size::S32, false, loc, ret);
+            }
+
+            Operator::I32TruncSSatF64 => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_trunc_sat(a, &mut self.machine, Size::S64, Size::S32, true, loc, ret);
+            }
+
+            Operator::I32TruncUSatF64 => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_trunc_sat(a, &mut self.machine, Size::S64, Size::S32, false, loc, ret);
+            }
+
+            Operator::I64TruncSSatF32 => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_trunc_sat(a, &mut self.machine, Size::S32, Size::S64, true, loc, ret);
+            }
+
+            Operator::I64TruncUSatF32 => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_trunc_sat(a, &mut self.machine, Size::S32, Size::S64, false, loc, ret);
+            }
+
+            Operator::I64TruncSSatF64 => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_trunc_sat(a, &mut self.machine, Size::S64, Size::S64, true, loc, ret);
+            }
+
+            Operator::I64TruncUSatF64 => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_trunc_sat(a, &mut self.machine, Size::S64, Size::S64, false, loc, ret);
+            }
+
+            Operator::F32ConvertSI32 => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::F32, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                let tmp_out = self.machine.acquire_temp_xmm().unwrap();
+                let tmp_in = self.machine.acquire_temp_gpr().unwrap();
+
+                a.emit_mov(Size::S32, loc, Location::GPR(tmp_in));
+                a.emit_vcvtsi2ss_32(tmp_out, GPROrMemory::GPR(tmp_in), tmp_out);
+                a.emit_mov(Size::S32, Location::XMM(tmp_out), ret);
+
+                self.machine.release_temp_gpr(tmp_in);
+                self.machine.release_temp_xmm(tmp_out);
+            }
+            Operator::F32ConvertUI32 => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::F32, MachineValue::WasmStack(self.value_stack.len()))],
                     false,
                 )[0];
                 self.value_stack.push(ret);
@@ -3599,27 +4552,11 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 self.value_stack.push(ret);
                 let tmp_out = self.machine.acquire_temp_xmm().unwrap();
                 let tmp_in = self.machine.acquire_temp_gpr().unwrap();
-                let tmp = self.machine.acquire_temp_gpr().unwrap();
-
-                let do_convert = a.get_label();
-                let end_convert = a.get_label();
 
                 a.emit_mov(Size::S64, loc, Location::GPR(tmp_in));
-                a.emit_test_gpr_64(tmp_in);
-                a.emit_jmp(Condition::Signed, do_convert);
-                a.emit_vcvtsi2sd_64(tmp_out, GPROrMemory::GPR(tmp_in), tmp_out);
-                a.emit_jmp(Condition::None, end_convert);
-                a.emit_label(do_convert);
-                a.emit_mov(Size::S64, Location::GPR(tmp_in), Location::GPR(tmp));
-                a.emit_and(Size::S64, Location::Imm32(1), Location::GPR(tmp));
-                a.emit_shr(Size::S64, Location::Imm8(1), Location::GPR(tmp_in));
-                a.emit_or(Size::S64, Location::GPR(tmp), Location::GPR(tmp_in));
-                a.emit_vcvtsi2sd_64(tmp_out, GPROrMemory::GPR(tmp_in), tmp_out);
-                a.emit_vaddsd(tmp_out, XMMOrMemory::XMM(tmp_out), tmp_out);
-                a.emit_label(end_convert);
+                Self::emit_convert_u64_to_f64(a, &mut self.machine, tmp_in, tmp_out);
                 a.emit_mov(Size::S64, Location::XMM(tmp_out), ret);
 
-                self.machine.release_temp_gpr(tmp);
                 self.machine.release_temp_gpr(tmp_in);
                 self.machine.release_temp_xmm(tmp_out);
             }
@@ -3655,27 +4592,18 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     a,
                     &mut self.machine,
                     label,
-                    params.iter().map(|x| *x),
+                    params
+                        .iter()
+                        .zip(param_types.iter())
+                        .flat_map(|(&param, &ty)| value_halves(param, ty)),
                     Some((&mut self.fsm, &mut self.control_stack)),
                 );
 
                 self.machine.release_locations_only_stack(a, &params);
 
-                if return_types.len() > 0 {
-                    let ret = self.machine.acquire_locations(
-                        a,
-                        &[(
-                            return_types[0],
-                            MachineValue::WasmStack(self.value_stack.len()),
-                        )],
-                        false,
-                    )[0];
-                    self.value_stack.push(ret);
-                    a.emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
-                }
+                Self::emit_call_results(a, &mut self.machine, &mut self.value_stack, &return_types);
             }
             Operator::CallIndirect { index, table_index } => {
-                assert_eq!(table_index, 0);
                 let sig = self.signatures.get(SigIndex::new(index as usize)).unwrap();
                 let param_types: SmallVec<[WpType; 8]> =
                     sig.params().iter().cloned().map(type_to_wp_type).collect();
@@ -3695,20 +4623,24 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 let table_count = self.machine.acquire_temp_gpr().unwrap();
                 let sigidx = self.machine.acquire_temp_gpr().unwrap();
 
+                let (tables_offset, table_index) =
+                    match TableIndex::new(table_index as usize).local_or_import(module_info) {
+                        LocalOrImport::Local(local_table_index) => {
+                            (vm::Ctx::offset_tables(), local_table_index.index())
+                        }
+                        LocalOrImport::Import(import_table_index) => (
+                            vm::Ctx::offset_imported_tables(),
+                            import_table_index.index(),
+                        ),
+                    };
                 a.emit_mov(
                     Size::S64,
-                    Location::Memory(
-                        Machine::get_vmctx_reg(),
-                        match TableIndex::new(0).local_or_import(module_info) {
-                            LocalOrImport::Local(_) => vm::Ctx::offset_tables(),
-                            LocalOrImport::Import(_) => vm::Ctx::offset_imported_tables(),
-                        } as i32,
-                    ),
+                    Location::Memory(Machine::get_vmctx_reg(), tables_offset as i32),
                     Location::GPR(table_base),
                 );
                 a.emit_mov(
                     Size::S64,
-                    Location::Memory(table_base, 0),
+                    Location::Memory(table_base, (table_index * 8) as i32),
                     Location::GPR(table_base),
                 );
                 a.emit_mov(
@@ -3722,7 +4654,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Location::GPR(table_base),
                 );
                 a.emit_cmp(Size::S32, func_index, Location::GPR(table_count));
-                a.emit_conditional_trap(Condition::BelowEqual);
+                a.emit_conditional_trap(Condition::BelowEqual, WasmTrapInfo::CallIndirectOOB);
                 a.emit_mov(Size::S64, func_index, Location::GPR(table_count));
                 a.emit_imul_imm32_gpr64(vm::Anyfunc::size() as u32, table_count);
                 a.emit_add(
@@ -3748,7 +4680,10 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Location::GPR(sigidx),
                     Location::Memory(table_count, (vm::Anyfunc::offset_sig_id() as usize) as i32),
                 );
-                a.emit_conditional_trap(Condition::NotEqual);
+                a.emit_conditional_trap(
+                    Condition::NotEqual,
+                    WasmTrapInfo::IncorrectCallIndirectSignature,
+                );
 
                 self.machine.release_temp_gpr(sigidx);
                 self.machine.release_temp_gpr(table_count);
@@ -3768,29 +4703,36 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     a,
                     &mut self.machine,
                     |a| {
+                        // Call the function with its own context, unless it is a host
+                        // function without one.
+                        let use_caller_ctx = a.get_label();
+                        a.emit_cmp(
+                            Size::S64,
+                            Location::Imm32(0),
+                            Location::Memory(GPR::RAX, vm::Anyfunc::offset_vmctx() as i32),
+                        );
+                        a.emit_jmp(Condition::Equal, use_caller_ctx);
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(GPR::RAX, vm::Anyfunc::offset_vmctx() as i32),
+                            Machine::get_param_location(0),
+                        );
+                        a.emit_label(use_caller_ctx);
                         a.emit_call_location(Location::Memory(
                             GPR::RAX,
                             (vm::Anyfunc::offset_func() as usize) as i32,
                         ));
                     },
-                    params.iter().map(|x| *x),
+                    params
+                        .iter()
+                        .zip(param_types.iter())
+                        .flat_map(|(&param, &ty)| value_halves(param, ty)),
                     Some((&mut self.fsm, &mut self.control_stack)),
                 );
 
                 self.machine.release_locations_only_stack(a, &params);
 
-                if return_types.len() > 0 {
-                    let ret = self.machine.acquire_locations(
-                        a,
-                        &[(
-                            return_types[0],
-                            MachineValue::WasmStack(self.value_stack.len()),
-                        )],
-                        false,
-                    )[0];
-                    self.value_stack.push(ret);
-                    a.emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
-                }
+                Self::emit_call_results(a, &mut self.machine, &mut self.value_stack, &return_types);
             }
             Operator::If { ty } => {
                 let label_end = a.get_label();
@@ -3799,15 +4741,31 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 let cond =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
 
+                let (params, returns) = Self::get_block_type(&self.signatures, ty);
+
+                // Both arms start with the block parameters, so keep them in the value
+                // transfer area until it is known which arm runs.
+                if params.len() > 0 {
+                    Self::emit_values_out(
+                        a,
+                        &mut self.machine,
+                        &self.value_stack,
+                        params.len(),
+                        true,
+                        &self.value_transfer_area,
+                    );
+                    let released = self
+                        .value_stack
+                        .split_off(self.value_stack.len() - params.len());
+                    self.machine.release_locations(a, &released);
+                }
+
                 let frame = ControlFrame {
                     label: label_end,
                     loop_like: false,
                     if_else: IfElseState::If(label_else),
-                    returns: match ty {
-                        WpTypeOrFuncType::Type(WpType::EmptyBlockType) => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => panic!("multi-value returns not yet implemented"),
-                    },
+                    params: params.clone(),
+                    returns,
                     value_stack_depth: self.value_stack.len(),
                     state: self.machine.state.clone(),
                     state_diff_id: Self::get_state_diff(
@@ -3826,19 +4784,27 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     cond,
                 );
                 a.emit_jmp(Condition::Equal, label_else);
+
+                Self::emit_values_in(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    &params,
+                    true,
+                    &self.value_transfer_area,
+                );
             }
             Operator::Else => {
                 let mut frame = self.control_stack.last_mut().unwrap();
 
-                if !was_unreachable && frame.returns.len() > 0 {
-                    let loc = *self.value_stack.last().unwrap();
-                    Self::emit_relaxed_binop(
+                if !was_unreachable {
+                    Self::emit_values_out(
                         a,
                         &mut self.machine,
-                        Assembler::emit_mov,
-                        Size::S64,
-                        loc,
-                        Location::GPR(GPR::RAX),
+                        &self.value_stack,
+                        frame.returns.len(),
+                        frame.uses_transfer_area(),
+                        &self.value_transfer_area,
                     );
                 }
 
@@ -3854,17 +4820,31 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     }
                     _ => unreachable!(),
                 }
+
+                Self::emit_values_in(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    &frame.params,
+                    true,
+                    &self.value_transfer_area,
+                );
             }
             Operator::Select => {
                 let cond =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ty = if self.machine.is_v128(*self.value_stack.last().unwrap()) {
+                    WpType::V128
+                } else {
+                    WpType::I64
+                };
                 let v_b =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
                 let v_a =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
                 let ret = self.machine.acquire_locations(
                     a,
-                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    &[(ty, MachineValue::WasmStack(self.value_stack.len()))],
                     false,
                 )[0];
                 self.value_stack.push(ret);
@@ -3882,40 +4862,24 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 );
                 a.emit_jmp(Condition::Equal, zero_label);
                 if v_a != ret {
-                    Self::emit_relaxed_binop(
-                        a,
-                        &mut self.machine,
-                        Assembler::emit_mov,
-                        Size::S64,
-                        v_a,
-                        ret,
-                    );
+                    Self::emit_relaxed_move(a, &mut self.machine, ty, v_a, ret);
                 }
                 a.emit_jmp(Condition::None, end_label);
                 a.emit_label(zero_label);
                 if v_b != ret {
-                    Self::emit_relaxed_binop(
-                        a,
-                        &mut self.machine,
-                        Assembler::emit_mov,
-                        Size::S64,
-                        v_b,
-                        ret,
-                    );
+                    Self::emit_relaxed_move(a, &mut self.machine, ty, v_b, ret);
                 }
                 a.emit_label(end_label);
             }
             Operator::Block { ty } => {
+                let (params, returns) = Self::get_block_type(&self.signatures, ty);
                 let frame = ControlFrame {
                     label: a.get_label(),
                     loop_like: false,
                     if_else: IfElseState::None,
-                    returns: match ty {
-                        WpTypeOrFuncType::Type(WpType::EmptyBlockType) => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => panic!("multi-value returns not yet implemented"),
-                    },
-                    value_stack_depth: self.value_stack.len(),
+                    value_stack_depth: self.value_stack.len() - params.len(),
+                    params,
+                    returns,
                     state: self.machine.state.clone(),
                     state_diff_id: Self::get_state_diff(
                         &self.machine,
@@ -3927,25 +4891,54 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             }
             Operator::Loop { ty } => {
                 let label = a.get_label();
-                let state_diff_id =
+                let (params, returns) = Self::get_block_type(&self.signatures, ty);
+
+                // Branches to the loop header carry the loop parameters. Entering the
+                // loop passes them the same way, through the value transfer area.
+                if params.len() > 0 {
+                    Self::emit_values_out(
+                        a,
+                        &mut self.machine,
+                        &self.value_stack,
+                        params.len(),
+                        true,
+                        &self.value_transfer_area,
+                    );
+                    let released = self
+                        .value_stack
+                        .split_off(self.value_stack.len() - params.len());
+                    self.machine.release_locations(a, &released);
+                }
+
+                let mut state_diff_id =
                     Self::get_state_diff(&self.machine, &mut self.fsm, &mut self.control_stack);
-                let activate_offset = a.get_offset().0;
 
                 self.control_stack.push(ControlFrame {
                     label: label,
                     loop_like: true,
                     if_else: IfElseState::None,
-                    returns: match ty {
-                        WpTypeOrFuncType::Type(WpType::EmptyBlockType) => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => panic!("multi-value returns not yet implemented"),
-                    },
+                    params: params.clone(),
+                    returns,
                     value_stack_depth: self.value_stack.len(),
                     state: self.machine.state.clone(),
                     state_diff_id,
                 });
                 a.emit_label(label);
 
+                if params.len() > 0 {
+                    Self::emit_values_in(
+                        a,
+                        &mut self.machine,
+                        &mut self.value_stack,
+                        &params,
+                        true,
+                        &self.value_transfer_area,
+                    );
+                    state_diff_id =
+                        Self::get_state_diff(&self.machine, &mut self.fsm, &mut self.control_stack);
+                }
+                let activate_offset = a.get_offset().0;
+
                 // Check interrupt signal without branching
                 a.emit_mov(
                     Size::S64,
@@ -4048,6 +5041,126 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 self.value_stack.push(ret);
                 a.emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
             }
+            Operator::MemoryCopy => Self::emit_call_intrinsic(
+                a,
+                &mut self.machine,
+                &mut self.value_stack,
+                vm::Intrinsics::offset_memory_copy(),
+                &[0],
+                3,
+            ),
+            Operator::MemoryFill => Self::emit_call_intrinsic(
+                a,
+                &mut self.machine,
+                &mut self.value_stack,
+                vm::Intrinsics::offset_memory_fill(),
+                &[0],
+                3,
+            ),
+            Operator::MemoryInit { segment } => Self::emit_call_intrinsic(
+                a,
+                &mut self.machine,
+                &mut self.value_stack,
+                vm::Intrinsics::offset_memory_init(),
+                &[0, segment],
+                3,
+            ),
+            Operator::DataDrop { segment } => Self::emit_call_intrinsic(
+                a,
+                &mut self.machine,
+                &mut self.value_stack,
+                vm::Intrinsics::offset_data_drop(),
+                &[segment],
+                0,
+            ),
+            Operator::TableCopy => Self::emit_call_intrinsic(
+                a,
+                &mut self.machine,
+                &mut self.value_stack,
+                vm::Intrinsics::offset_table_copy(),
+                &[0],
+                3,
+            ),
+            Operator::TableInit { segment } => Self::emit_call_intrinsic(
+                a,
+                &mut self.machine,
+                &mut self.value_stack,
+                vm::Intrinsics::offset_table_init(),
+                &[0, segment],
+                3,
+            ),
+            Operator::ElemDrop { segment } => Self::emit_call_intrinsic(
+                a,
+                &mut self.machine,
+                &mut self.value_stack,
+                vm::Intrinsics::offset_elem_drop(),
+                &[segment],
+                0,
+            ),
+            Operator::RefNull => {
+                self.value_stack.push(Location::Imm64(0));
+                self.machine
+                    .state
+                    .wasm_stack
+                    .push(WasmAbstractValue::Const(0));
+            }
+            Operator::TableGet { table } => {
+                Self::emit_call_intrinsic(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    vm::Intrinsics::offset_table_get(),
+                    &[table],
+                    1,
+                );
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(
+                        WpType::AnyRef,
+                        MachineValue::WasmStack(self.value_stack.len()),
+                    )],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                a.emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
+            }
+            Operator::TableSet { table } => Self::emit_call_intrinsic(
+                a,
+                &mut self.machine,
+                &mut self.value_stack,
+                vm::Intrinsics::offset_table_set(),
+                &[table],
+                2,
+            ),
+            Operator::TableSize { table } | Operator::TableGrow { table } => {
+                let (intrinsic_offset, num_operands) = match *op {
+                    Operator::TableSize { .. } => (vm::Intrinsics::offset_table_size(), 0),
+                    _ => (vm::Intrinsics::offset_table_grow(), 2),
+                };
+                Self::emit_call_intrinsic(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    intrinsic_offset,
+                    &[table],
+                    num_operands,
+                );
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                a.emit_mov(Size::S32, Location::GPR(GPR::RAX), ret);
+            }
+            Operator::TableFill { table } => Self::emit_call_intrinsic(
+                a,
+                &mut self.machine,
+                &mut self.value_stack,
+                vm::Intrinsics::offset_table_fill(),
+                &[table],
+                3,
+            ),
             Operator::I32Load { ref memarg } => {
                 let target =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
@@ -4724,23 +5837,19 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             }
             Operator::Unreachable => {
                 Self::mark_trappable(a, &self.machine, &mut self.fsm, &mut self.control_stack);
-                a.emit_ud2();
+                a.emit_trap(WasmTrapInfo::Unreachable);
                 self.unreachable_depth = 1;
             }
             Operator::Return => {
                 let frame = &self.control_stack[0];
-                if frame.returns.len() > 0 {
-                    assert_eq!(frame.returns.len(), 1);
-                    let loc = *self.value_stack.last().unwrap();
-                    Self::emit_relaxed_binop(
-                        a,
-                        &mut self.machine,
-                        Assembler::emit_mov,
-                        Size::S64,
-                        loc,
-                        Location::GPR(GPR::RAX),
-                    );
-                }
+                Self::emit_values_out(
+                    a,
+                    &mut self.machine,
+                    &self.value_stack,
+                    frame.returns.len(),
+                    frame.uses_transfer_area(),
+                    &self.value_transfer_area,
+                );
                 let released = &self.value_stack[frame.value_stack_depth..];
                 self.machine.release_locations_keep_state(a, released);
                 a.emit_jmp(Condition::None, frame.label);
@@ -4749,11 +5858,14 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             Operator::Br { relative_depth } => {
                 let frame =
                     &self.control_stack[self.control_stack.len() - 1 - (relative_depth as usize)];
-                if !frame.loop_like && frame.returns.len() > 0 {
-                    assert_eq!(frame.returns.len(), 1);
-                    let loc = *self.value_stack.last().unwrap();
-                    a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                }
+                Self::emit_values_out(
+                    a,
+                    &mut self.machine,
+                    &self.value_stack,
+                    frame.branch_arity(),
+                    frame.uses_transfer_area(),
+                    &self.value_transfer_area,
+                );
                 let released = &self.value_stack[frame.value_stack_depth..];
                 self.machine.release_locations_keep_state(a, released);
                 a.emit_jmp(Condition::None, frame.label);
@@ -4775,12 +5887,15 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
 
                 let frame =
                     &self.control_stack[self.control_stack.len() - 1 - (relative_depth as usize)];
-                if !frame.loop_like && frame.returns.len() > 0 {
-                    assert_eq!(frame.returns.len(), 1);
-                    let loc = *self.value_stack.last().unwrap();
-                    a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                }
-                let released = &self.value_stack[frame.value_stack_depth..];
+                Self::emit_values_out(
+                    a,
+                    &mut self.machine,
+                    &self.value_stack,
+                    frame.branch_arity(),
+                    frame.uses_transfer_area(),
+                    &self.value_transfer_area,
+                );
+                let released = &self.value_stack[frame.value_stack_depth..];
                 self.machine.release_locations_keep_state(a, released);
                 a.emit_jmp(Condition::None, frame.label);
 
@@ -4815,11 +5930,14 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     table.push(label);
                     let frame =
                         &self.control_stack[self.control_stack.len() - 1 - (*target as usize)];
-                    if !frame.loop_like && frame.returns.len() > 0 {
-                        assert_eq!(frame.returns.len(), 1);
-                        let loc = *self.value_stack.last().unwrap();
-                        a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                    }
+                    Self::emit_values_out(
+                        a,
+                        &mut self.machine,
+                        &self.value_stack,
+                        frame.branch_arity(),
+                        frame.uses_transfer_area(),
+                        &self.value_transfer_area,
+                    );
                     let released = &self.value_stack[frame.value_stack_depth..];
                     self.machine.release_locations_keep_state(a, released);
                     a.emit_jmp(Condition::None, frame.label);
@@ -4829,11 +5947,14 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 {
                     let frame = &self.control_stack
                         [self.control_stack.len() - 1 - (default_target as usize)];
-                    if !frame.loop_like && frame.returns.len() > 0 {
-                        assert_eq!(frame.returns.len(), 1);
-                        let loc = *self.value_stack.last().unwrap();
-                        a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                    }
+                    Self::emit_values_out(
+                        a,
+                        &mut self.machine,
+                        &self.value_stack,
+                        frame.branch_arity(),
+                        frame.uses_transfer_area(),
+                        &self.value_transfer_area,
+                    );
                     let released = &self.value_stack[frame.value_stack_depth..];
                     self.machine.release_locations_keep_state(a, released);
                     a.emit_jmp(Condition::None, frame.label);
@@ -4851,20 +5972,47 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             Operator::End => {
                 let frame = self.control_stack.pop().unwrap();
 
-                if !was_unreachable && frame.returns.len() > 0 {
-                    let loc = *self.value_stack.last().unwrap();
-                    Self::emit_relaxed_binop(
+                if !was_unreachable {
+                    Self::emit_values_out(
                         a,
                         &mut self.machine,
-                        Assembler::emit_mov,
-                        Size::S64,
-                        loc,
-                        Location::GPR(GPR::RAX),
+                        &self.value_stack,
+                        frame.returns.len(),
+                        frame.uses_transfer_area(),
+                        &self.value_transfer_area,
                     );
                 }
 
                 if self.control_stack.len() == 0 {
                     a.emit_label(frame.label);
+                    if frame.uses_transfer_area() {
+                        // Copy the results to the return buffer and return a pointer to it.
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(
+                                Machine::get_vmctx_reg(),
+                                vm::Ctx::offset_return_values() as i32,
+                            ),
+                            Location::GPR(GPR::RAX),
+                        );
+                        let num_slots: usize = frame
+                            .returns
+                            .iter()
+                            .map(|&ty| if ty == WpType::V128 { 2 } else { 1 })
+                            .sum();
+                        for i in 0..num_slots {
+                            a.emit_mov(
+                                Size::S64,
+                                self.value_transfer_area[i],
+                                Location::GPR(GPR::RCX),
+                            );
+                            a.emit_mov(
+                                Size::S64,
+                                Location::GPR(GPR::RCX),
+                                Location::Memory(GPR::RAX, (i * 8) as i32),
+                            );
+                        }
+                    }
                     self.machine.finalize_locals(a, &self.locals);
                     a.emit_mov(Size::S64, Location::GPR(GPR::RBP), Location::GPR(GPR::RSP));
                     a.emit_pop(Size::S64, Location::GPR(GPR::RBP));
@@ -4882,23 +6030,1340 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                         a.emit_label(label);
                     }
 
-                    if frame.returns.len() > 0 {
-                        assert_eq!(frame.returns.len(), 1);
-                        let loc = self.machine.acquire_locations(
+                    Self::emit_values_in(
+                        a,
+                        &mut self.machine,
+                        &mut self.value_stack,
+                        &frame.returns,
+                        frame.uses_transfer_area(),
+                        &self.value_transfer_area,
+                    );
+                }
+            }
+            Operator::I32AtomicLoad { ref memarg }
+            | Operator::I64AtomicLoad { ref memarg }
+            | Operator::I32AtomicLoad8U { ref memarg }
+            | Operator::I32AtomicLoad16U { ref memarg }
+            | Operator::I64AtomicLoad8U { ref memarg }
+            | Operator::I64AtomicLoad16U { ref memarg }
+            | Operator::I64AtomicLoad32U { ref memarg } => {
+                let (sz, ty) = atomic_access(op);
+                let target =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(ty, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+
+                Self::emit_atomic_memory_op(
+                    module_info,
+                    &self.config,
+                    a,
+                    &mut self.machine,
+                    target,
+                    memarg.offset as usize,
+                    size_bytes(sz),
+                    |a, m, addr| {
+                        let value = m.acquire_temp_gpr().unwrap();
+                        match sz {
+                            Size::S8 | Size::S16 => a.emit_movzx(
+                                sz,
+                                Location::Memory(addr, 0),
+                                Size::S64,
+                                Location::GPR(value),
+                            ),
+                            _ => a.emit_mov(sz, Location::Memory(addr, 0), Location::GPR(value)),
+                        }
+                        a.emit_mov(wp_type_size(ty), Location::GPR(value), ret);
+                        m.release_temp_gpr(value);
+                    },
+                );
+            }
+            // Stores use `xchg`, which is implicitly locked, so that plain loads
+            // are sequentially consistent with them.
+            Operator::I32AtomicStore { ref memarg }
+            | Operator::I64AtomicStore { ref memarg }
+            | Operator::I32AtomicStore8 { ref memarg }
+            | Operator::I32AtomicStore16 { ref memarg }
+            | Operator::I64AtomicStore8 { ref memarg }
+            | Operator::I64AtomicStore16 { ref memarg }
+            | Operator::I64AtomicStore32 { ref memarg } => {
+                let (sz, ty) = atomic_access(op);
+                let target_value =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let target_addr =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+
+                Self::emit_atomic_memory_op(
+                    module_info,
+                    &self.config,
+                    a,
+                    &mut self.machine,
+                    target_addr,
+                    memarg.offset as usize,
+                    size_bytes(sz),
+                    |a, m, addr| {
+                        let value = m.acquire_temp_gpr().unwrap();
+                        a.emit_mov(wp_type_size(ty), target_value, Location::GPR(value));
+                        a.emit_xchg(sz, Location::GPR(value), Location::Memory(addr, 0));
+                        m.release_temp_gpr(value);
+                    },
+                );
+            }
+            Operator::I32AtomicRmwAdd { ref memarg }
+            | Operator::I64AtomicRmwAdd { ref memarg }
+            | Operator::I32AtomicRmw8UAdd { ref memarg }
+            | Operator::I32AtomicRmw16UAdd { ref memarg }
+            | Operator::I64AtomicRmw8UAdd { ref memarg }
+            | Operator::I64AtomicRmw16UAdd { ref memarg }
+            | Operator::I64AtomicRmw32UAdd { ref memarg }
+            | Operator::I32AtomicRmwSub { ref memarg }
+            | Operator::I64AtomicRmwSub { ref memarg }
+            | Operator::I32AtomicRmw8USub { ref memarg }
+            | Operator::I32AtomicRmw16USub { ref memarg }
+            | Operator::I64AtomicRmw8USub { ref memarg }
+            | Operator::I64AtomicRmw16USub { ref memarg }
+            | Operator::I64AtomicRmw32USub { ref memarg }
+            | Operator::I32AtomicRmwXchg { ref memarg }
+            | Operator::I64AtomicRmwXchg { ref memarg }
+            | Operator::I32AtomicRmw8UXchg { ref memarg }
+            | Operator::I32AtomicRmw16UXchg { ref memarg }
+            | Operator::I64AtomicRmw8UXchg { ref memarg }
+            | Operator::I64AtomicRmw16UXchg { ref memarg }
+            | Operator::I64AtomicRmw32UXchg { ref memarg } => {
+                let (sz, ty) = atomic_access(op);
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let target =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(ty, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+
+                Self::emit_atomic_memory_op(
+                    module_info,
+                    &self.config,
+                    a,
+                    &mut self.machine,
+                    target,
+                    memarg.offset as usize,
+                    size_bytes(sz),
+                    |a, m, addr| {
+                        let value = m.acquire_temp_gpr().unwrap();
+                        a.emit_mov(wp_type_size(ty), loc, Location::GPR(value));
+                        match *op {
+                            Operator::I32AtomicRmwSub { .. }
+                            | Operator::I64AtomicRmwSub { .. }
+                            | Operator::I32AtomicRmw8USub { .. }
+                            | Operator::I32AtomicRmw16USub { .. }
+                            | Operator::I64AtomicRmw8USub { .. }
+                            | Operator::I64AtomicRmw16USub { .. }
+                            | Operator::I64AtomicRmw32USub { .. } => {
+                                a.emit_neg(wp_type_size(ty), Location::GPR(value));
+                                a.emit_lock_xadd(
+                                    sz,
+                                    Location::GPR(value),
+                                    Location::Memory(addr, 0),
+                                );
+                            }
+                            Operator::I32AtomicRmwXchg { .. }
+                            | Operator::I64AtomicRmwXchg { .. }
+                            | Operator::I32AtomicRmw8UXchg { .. }
+                            | Operator::I32AtomicRmw16UXchg { .. }
+                            | Operator::I64AtomicRmw8UXchg { .. }
+                            | Operator::I64AtomicRmw16UXchg { .. }
+                            | Operator::I64AtomicRmw32UXchg { .. } => {
+                                a.emit_xchg(sz, Location::GPR(value), Location::Memory(addr, 0));
+                            }
+                            _ => {
+                                a.emit_lock_xadd(
+                                    sz,
+                                    Location::GPR(value),
+                                    Location::Memory(addr, 0),
+                                );
+                            }
+                        }
+                        Self::emit_zero_extend(a, sz, value);
+                        a.emit_mov(wp_type_size(ty), Location::GPR(value), ret);
+                        m.release_temp_gpr(value);
+                    },
+                );
+            }
+            // x86 has no instruction returning the old value for these, so they
+            // retry a `cmpxchg` until no other thread changed the memory in between.
+            Operator::I32AtomicRmwAnd { ref memarg }
+            | Operator::I64AtomicRmwAnd { ref memarg }
+            | Operator::I32AtomicRmw8UAnd { ref memarg }
+            | Operator::I32AtomicRmw16UAnd { ref memarg }
+            | Operator::I64AtomicRmw8UAnd { ref memarg }
+            | Operator::I64AtomicRmw16UAnd { ref memarg }
+            | Operator::I64AtomicRmw32UAnd { ref memarg }
+            | Operator::I32AtomicRmwOr { ref memarg }
+            | Operator::I64AtomicRmwOr { ref memarg }
+            | Operator::I32AtomicRmw8UOr { ref memarg }
+            | Operator::I32AtomicRmw16UOr { ref memarg }
+            | Operator::I64AtomicRmw8UOr { ref memarg }
+            | Operator::I64AtomicRmw16UOr { ref memarg }
+            | Operator::I64AtomicRmw32UOr { ref memarg }
+            | Operator::I32AtomicRmwXor { ref memarg }
+            | Operator::I64AtomicRmwXor { ref memarg }
+            | Operator::I32AtomicRmw8UXor { ref memarg }
+            | Operator::I32AtomicRmw16UXor { ref memarg }
+            | Operator::I64AtomicRmw8UXor { ref memarg }
+            | Operator::I64AtomicRmw16UXor { ref memarg }
+            | Operator::I64AtomicRmw32UXor { ref memarg } => {
+                let (sz, ty) = atomic_access(op);
+                let binop: fn(&mut Assembler, Size, Location, Location) = match *op {
+                    Operator::I32AtomicRmwAnd { .. }
+                    | Operator::I64AtomicRmwAnd { .. }
+                    | Operator::I32AtomicRmw8UAnd { .. }
+                    | Operator::I32AtomicRmw16UAnd { .. }
+                    | Operator::I64AtomicRmw8UAnd { .. }
+                    | Operator::I64AtomicRmw16UAnd { .. }
+                    | Operator::I64AtomicRmw32UAnd { .. } => Assembler::emit_and,
+                    Operator::I32AtomicRmwOr { .. }
+                    | Operator::I64AtomicRmwOr { .. }
+                    | Operator::I32AtomicRmw8UOr { .. }
+                    | Operator::I32AtomicRmw16UOr { .. }
+                    | Operator::I64AtomicRmw8UOr { .. }
+                    | Operator::I64AtomicRmw16UOr { .. }
+                    | Operator::I64AtomicRmw32UOr { .. } => Assembler::emit_or,
+                    _ => Assembler::emit_xor,
+                };
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let target =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(ty, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+
+                Self::emit_atomic_memory_op(
+                    module_info,
+                    &self.config,
+                    a,
+                    &mut self.machine,
+                    target,
+                    memarg.offset as usize,
+                    size_bytes(sz),
+                    |a, m, addr| {
+                        // `cmpxchg` compares with `RAX`, which `emit_memory_op` always
+                        // hands out first, so the address has to move elsewhere.
+                        debug_assert_eq!(addr, GPR::RAX);
+                        let value = m.acquire_temp_gpr().unwrap();
+                        let tmp_addr = m.acquire_temp_gpr().unwrap();
+                        a.emit_mov(Size::S64, Location::GPR(addr), Location::GPR(tmp_addr));
+                        match sz {
+                            Size::S8 | Size::S16 => a.emit_movzx(
+                                sz,
+                                Location::Memory(tmp_addr, 0),
+                                Size::S64,
+                                Location::GPR(GPR::RAX),
+                            ),
+                            _ => a.emit_mov(
+                                sz,
+                                Location::Memory(tmp_addr, 0),
+                                Location::GPR(GPR::RAX),
+                            ),
+                        }
+                        let retry = a.get_label();
+                        a.emit_label(retry);
+                        a.emit_mov(wp_type_size(ty), loc, Location::GPR(value));
+                        binop(
                             a,
-                            &[(
-                                frame.returns[0],
-                                MachineValue::WasmStack(self.value_stack.len()),
-                            )],
-                            false,
-                        )[0];
-                        a.emit_mov(Size::S64, Location::GPR(GPR::RAX), loc);
-                        self.value_stack.push(loc);
+                            wp_type_size(ty),
+                            Location::GPR(GPR::RAX),
+                            Location::GPR(value),
+                        );
+                        a.emit_lock_cmpxchg(
+                            sz,
+                            Location::GPR(value),
+                            Location::Memory(tmp_addr, 0),
+                        );
+                        a.emit_jmp(Condition::NotEqual, retry);
+                        a.emit_mov(wp_type_size(ty), Location::GPR(GPR::RAX), ret);
+                        m.release_temp_gpr(tmp_addr);
+                        m.release_temp_gpr(value);
+                    },
+                );
+            }
+            Operator::I32AtomicRmwCmpxchg { ref memarg }
+            | Operator::I64AtomicRmwCmpxchg { ref memarg }
+            | Operator::I32AtomicRmw8UCmpxchg { ref memarg }
+            | Operator::I32AtomicRmw16UCmpxchg { ref memarg }
+            | Operator::I64AtomicRmw8UCmpxchg { ref memarg }
+            | Operator::I64AtomicRmw16UCmpxchg { ref memarg }
+            | Operator::I64AtomicRmw32UCmpxchg { ref memarg } => {
+                let (sz, ty) = atomic_access(op);
+                let replacement =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let expected =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let target =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(ty, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+
+                Self::emit_atomic_memory_op(
+                    module_info,
+                    &self.config,
+                    a,
+                    &mut self.machine,
+                    target,
+                    memarg.offset as usize,
+                    size_bytes(sz),
+                    |a, m, addr| {
+                        // See above for why the address moves out of `RAX`.
+                        debug_assert_eq!(addr, GPR::RAX);
+                        let value = m.acquire_temp_gpr().unwrap();
+                        let tmp_addr = m.acquire_temp_gpr().unwrap();
+                        a.emit_mov(Size::S64, Location::GPR(addr), Location::GPR(tmp_addr));
+                        a.emit_mov(wp_type_size(ty), expected, Location::GPR(GPR::RAX));
+                        a.emit_mov(wp_type_size(ty), replacement, Location::GPR(value));
+                        a.emit_lock_cmpxchg(
+                            sz,
+                            Location::GPR(value),
+                            Location::Memory(tmp_addr, 0),
+                        );
+                        Self::emit_zero_extend(a, sz, GPR::RAX);
+                        a.emit_mov(wp_type_size(ty), Location::GPR(GPR::RAX), ret);
+                        m.release_temp_gpr(tmp_addr);
+                        m.release_temp_gpr(value);
+                    },
+                );
+            }
+            Operator::I32Wait { ref memarg } | Operator::I64Wait { ref memarg } => {
+                let intrinsic_offset = match *op {
+                    Operator::I32Wait { .. } => vm::Intrinsics::offset_memory_atomic_wait32(),
+                    _ => vm::Intrinsics::offset_memory_atomic_wait64(),
+                };
+                Self::emit_call_intrinsic(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    intrinsic_offset,
+                    &[0, memarg.offset],
+                    3,
+                );
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                a.emit_mov(Size::S32, Location::GPR(GPR::RAX), ret);
+            }
+            Operator::Wake { ref memarg } => {
+                Self::emit_call_intrinsic(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    vm::Intrinsics::offset_memory_atomic_notify(),
+                    &[0, memarg.offset],
+                    2,
+                );
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                a.emit_mov(Size::S32, Location::GPR(GPR::RAX), ret);
+            }
+            Operator::V128Const { value } => {
+                let value = u128::from_le_bytes(*value.bytes());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(
+                        WpType::V128,
+                        MachineValue::WasmStack(self.value_stack.len()),
+                    )],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                Self::emit_relaxed_binop(
+                    a,
+                    &mut self.machine,
+                    Assembler::emit_mov,
+                    Size::S64,
+                    Location::Imm64(value as u64),
+                    ret,
+                );
+                Self::emit_relaxed_binop(
+                    a,
+                    &mut self.machine,
+                    Assembler::emit_mov,
+                    Size::S64,
+                    Location::Imm64((value >> 64) as u64),
+                    Machine::get_high_half(ret),
+                );
+            }
+            Operator::V128Load { ref memarg } => {
+                let target =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(
+                        WpType::V128,
+                        MachineValue::WasmStack(self.value_stack.len()),
+                    )],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+
+                Self::emit_memory_op(
+                    module_info,
+                    &self.config,
+                    a,
+                    &mut self.machine,
+                    target,
+                    memarg.offset as usize,
+                    16,
+                    |a, m, addr| {
+                        let tmp = m.acquire_temp_xmm().unwrap();
+                        a.emit_movdqu(XMMOrMemory::Memory(addr, 0), XMMOrMemory::XMM(tmp));
+                        a.emit_movdqu(XMMOrMemory::XMM(tmp), v128_operand(ret));
+                        m.release_temp_xmm(tmp);
+                    },
+                );
+            }
+            Operator::V128Store { ref memarg } => {
+                let target_value =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let target_addr =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+
+                Self::emit_memory_op(
+                    module_info,
+                    &self.config,
+                    a,
+                    &mut self.machine,
+                    target_addr,
+                    memarg.offset as usize,
+                    16,
+                    |a, m, addr| {
+                        let tmp = m.acquire_temp_xmm().unwrap();
+                        a.emit_movdqu(v128_operand(target_value), XMMOrMemory::XMM(tmp));
+                        a.emit_movdqu(XMMOrMemory::XMM(tmp), XMMOrMemory::Memory(addr, 0));
+                        m.release_temp_xmm(tmp);
+                    },
+                );
+            }
+            Operator::I8x16LoadSplat { ref memarg }
+            | Operator::I16x8LoadSplat { ref memarg }
+            | Operator::I32x4LoadSplat { ref memarg }
+            | Operator::I64x2LoadSplat { ref memarg } => {
+                let sz = match *op {
+                    Operator::I8x16LoadSplat { .. } => Size::S8,
+                    Operator::I16x8LoadSplat { .. } => Size::S16,
+                    Operator::I32x4LoadSplat { .. } => Size::S32,
+                    _ => Size::S64,
+                };
+                let target =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(
+                        WpType::V128,
+                        MachineValue::WasmStack(self.value_stack.len()),
+                    )],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+
+                Self::emit_memory_op(
+                    module_info,
+                    &self.config,
+                    a,
+                    &mut self.machine,
+                    target,
+                    memarg.offset as usize,
+                    size_bytes(sz),
+                    |a, m, addr| {
+                        let tmp = m.acquire_temp_gpr().unwrap();
+                        let value = m.acquire_temp_xmm().unwrap();
+                        match sz {
+                            Size::S8 | Size::S16 => a.emit_movzx(
+                                sz,
+                                Location::Memory(addr, 0),
+                                Size::S32,
+                                Location::GPR(tmp),
+                            ),
+                            _ => a.emit_mov(sz, Location::Memory(addr, 0), Location::GPR(tmp)),
+                        }
+                        Self::emit_v128_splat(a, m, sz, tmp, value);
+                        a.emit_movdqu(XMMOrMemory::XMM(value), v128_operand(ret));
+                        m.release_temp_xmm(value);
+                        m.release_temp_gpr(tmp);
+                    },
+                );
+            }
+            Operator::I8x16Splat
+            | Operator::I16x8Splat
+            | Operator::I32x4Splat
+            | Operator::I64x2Splat
+            | Operator::F32x4Splat
+            | Operator::F64x2Splat => {
+                let sz = match *op {
+                    Operator::I8x16Splat => Size::S8,
+                    Operator::I16x8Splat => Size::S16,
+                    Operator::I32x4Splat | Operator::F32x4Splat => Size::S32,
+                    _ => Size::S64,
+                };
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let tmp = self.machine.acquire_temp_gpr().unwrap();
+                let value = self.machine.acquire_temp_xmm().unwrap();
+
+                let mov_sz = if sz == Size::S64 {
+                    Size::S64
+                } else {
+                    Size::S32
+                };
+                a.emit_mov(mov_sz, loc, Location::GPR(tmp));
+                Self::emit_v128_splat(a, &mut self.machine, sz, tmp, value);
+
+                self.machine.release_temp_gpr(tmp);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, value);
+            }
+            Operator::I8x16ExtractLaneS { lane }
+            | Operator::I8x16ExtractLaneU { lane }
+            | Operator::I16x8ExtractLaneS { lane }
+            | Operator::I16x8ExtractLaneU { lane }
+            | Operator::I32x4ExtractLane { lane }
+            | Operator::I64x2ExtractLane { lane }
+            | Operator::F32x4ExtractLane { lane }
+            | Operator::F64x2ExtractLane { lane } => {
+                let (sz, ty) = match *op {
+                    Operator::I8x16ExtractLaneS { .. } | Operator::I8x16ExtractLaneU { .. } => {
+                        (Size::S8, WpType::I32)
+                    }
+                    Operator::I16x8ExtractLaneS { .. } | Operator::I16x8ExtractLaneU { .. } => {
+                        (Size::S16, WpType::I32)
+                    }
+                    Operator::I32x4ExtractLane { .. } => (Size::S32, WpType::I32),
+                    Operator::I64x2ExtractLane { .. } => (Size::S64, WpType::I64),
+                    Operator::F32x4ExtractLane { .. } => (Size::S32, WpType::F32),
+                    _ => (Size::S64, WpType::F64),
+                };
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let tmp = self.machine.acquire_temp_gpr().unwrap();
+
+                a.emit_pextr(sz, value, lane, tmp);
+                match *op {
+                    Operator::I8x16ExtractLaneS { .. } | Operator::I16x8ExtractLaneS { .. } => {
+                        a.emit_movsx(sz, Location::GPR(tmp), Size::S32, Location::GPR(tmp));
+                    }
+                    _ => {}
+                }
+
+                self.machine.release_temp_xmm(value);
+                Self::emit_push_gpr(a, &mut self.machine, &mut self.value_stack, ty, tmp);
+                self.machine.release_temp_gpr(tmp);
+            }
+            Operator::I8x16ReplaceLane { lane }
+            | Operator::I16x8ReplaceLane { lane }
+            | Operator::I32x4ReplaceLane { lane }
+            | Operator::I64x2ReplaceLane { lane }
+            | Operator::F32x4ReplaceLane { lane }
+            | Operator::F64x2ReplaceLane { lane } => {
+                let sz = match *op {
+                    Operator::I8x16ReplaceLane { .. } => Size::S8,
+                    Operator::I16x8ReplaceLane { .. } => Size::S16,
+                    Operator::I32x4ReplaceLane { .. } | Operator::F32x4ReplaceLane { .. } => {
+                        Size::S32
+                    }
+                    _ => Size::S64,
+                };
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let tmp = self.machine.acquire_temp_gpr().unwrap();
+                let mov_sz = if sz == Size::S64 {
+                    Size::S64
+                } else {
+                    Size::S32
+                };
+                a.emit_mov(mov_sz, loc, Location::GPR(tmp));
+
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                a.emit_pinsr(sz, tmp, lane, value);
+
+                self.machine.release_temp_gpr(tmp);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, value);
+            }
+            Operator::V8x16Shuffle { lanes } => {
+                // Each operand is shuffled separately, zeroing the lanes taken from the other.
+                let mut lhs_mask = [0x80u8; 16];
+                let mut rhs_mask = [0x80u8; 16];
+                for (i, &lane) in lanes.iter().enumerate() {
+                    if lane < 16 {
+                        lhs_mask[i] = lane;
+                    } else {
+                        rhs_mask[i] = lane - 16;
+                    }
+                }
+
+                Self::emit_v128_binop_base(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    |a, m, lhs, rhs| {
+                        let mask = m.acquire_temp_xmm().unwrap();
+                        Self::emit_v128_const(a, m, u128::from_le_bytes(lhs_mask), mask);
+                        a.emit_vpshufb(lhs, XMMOrMemory::XMM(mask), lhs);
+                        Self::emit_v128_const(a, m, u128::from_le_bytes(rhs_mask), mask);
+                        a.emit_vpshufb(rhs, XMMOrMemory::XMM(mask), rhs);
+                        a.emit_vpor(lhs, XMMOrMemory::XMM(rhs), lhs);
+                        m.release_temp_xmm(mask);
+                    },
+                );
+            }
+            Operator::V8x16Swizzle => {
+                // `pshufb` zeroes the lanes whose index has the top bit set, so out of
+                // range indices are saturated into that range.
+                Self::emit_v128_binop_base(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    |a, m, lhs, rhs| {
+                        let tmp = m.acquire_temp_xmm().unwrap();
+                        Self::emit_v128_const(a, m, 0x7070_7070_7070_7070_7070_7070_7070_7070, tmp);
+                        a.emit_vpaddusb(rhs, XMMOrMemory::XMM(tmp), rhs);
+                        a.emit_vpshufb(lhs, XMMOrMemory::XMM(rhs), lhs);
+                        m.release_temp_xmm(tmp);
+                    },
+                );
+            }
+            Operator::I8x16Add
+            | Operator::I16x8Add
+            | Operator::I32x4Add
+            | Operator::I64x2Add
+            | Operator::I8x16Sub
+            | Operator::I16x8Sub
+            | Operator::I32x4Sub
+            | Operator::I64x2Sub
+            | Operator::I16x8Mul
+            | Operator::I32x4Mul
+            | Operator::I8x16AddSaturateS
+            | Operator::I8x16AddSaturateU
+            | Operator::I16x8AddSaturateS
+            | Operator::I16x8AddSaturateU
+            | Operator::I8x16SubSaturateS
+            | Operator::I8x16SubSaturateU
+            | Operator::I16x8SubSaturateS
+            | Operator::I16x8SubSaturateU
+            | Operator::V128And
+            | Operator::V128Or
+            | Operator::V128Xor
+            | Operator::I8x16Eq
+            | Operator::I16x8Eq
+            | Operator::I32x4Eq
+            | Operator::I8x16GtS
+            | Operator::I16x8GtS
+            | Operator::I32x4GtS
+            | Operator::F32x4Add
+            | Operator::F64x2Add
+            | Operator::F32x4Sub
+            | Operator::F64x2Sub
+            | Operator::F32x4Mul
+            | Operator::F64x2Mul
+            | Operator::F32x4Div
+            | Operator::F64x2Div
+            | Operator::F32x4Eq
+            | Operator::F64x2Eq
+            | Operator::F32x4Ne
+            | Operator::F64x2Ne
+            | Operator::F32x4Lt
+            | Operator::F64x2Lt
+            | Operator::F32x4Le
+            | Operator::F64x2Le => {
+                let binop: fn(&mut Assembler, XMM, XMMOrMemory, XMM) = match *op {
+                    Operator::I8x16Add => Assembler::emit_vpaddb,
+                    Operator::I16x8Add => Assembler::emit_vpaddw,
+                    Operator::I32x4Add => Assembler::emit_vpaddd,
+                    Operator::I64x2Add => Assembler::emit_vpaddq,
+                    Operator::I8x16Sub => Assembler::emit_vpsubb,
+                    Operator::I16x8Sub => Assembler::emit_vpsubw,
+                    Operator::I32x4Sub => Assembler::emit_vpsubd,
+                    Operator::I64x2Sub => Assembler::emit_vpsubq,
+                    Operator::I16x8Mul => Assembler::emit_vpmullw,
+                    Operator::I32x4Mul => Assembler::emit_vpmulld,
+                    Operator::I8x16AddSaturateS => Assembler::emit_vpaddsb,
+                    Operator::I8x16AddSaturateU => Assembler::emit_vpaddusb,
+                    Operator::I16x8AddSaturateS => Assembler::emit_vpaddsw,
+                    Operator::I16x8AddSaturateU => Assembler::emit_vpaddusw,
+                    Operator::I8x16SubSaturateS => Assembler::emit_vpsubsb,
+                    Operator::I8x16SubSaturateU => Assembler::emit_vpsubusb,
+                    Operator::I16x8SubSaturateS => Assembler::emit_vpsubsw,
+                    Operator::I16x8SubSaturateU => Assembler::emit_vpsubusw,
+                    Operator::V128And => Assembler::emit_vpand,
+                    Operator::V128Or => Assembler::emit_vpor,
+                    Operator::V128Xor => Assembler::emit_vpxor,
+                    Operator::I8x16Eq => Assembler::emit_vpcmpeqb,
+                    Operator::I16x8Eq => Assembler::emit_vpcmpeqw,
+                    Operator::I32x4Eq => Assembler::emit_vpcmpeqd,
+                    Operator::I8x16GtS => Assembler::emit_vpcmpgtb,
+                    Operator::I16x8GtS => Assembler::emit_vpcmpgtw,
+                    Operator::I32x4GtS => Assembler::emit_vpcmpgtd,
+                    Operator::F32x4Add => Assembler::emit_vaddps,
+                    Operator::F64x2Add => Assembler::emit_vaddpd,
+                    Operator::F32x4Sub => Assembler::emit_vsubps,
+                    Operator::F64x2Sub => Assembler::emit_vsubpd,
+                    Operator::F32x4Mul => Assembler::emit_vmulps,
+                    Operator::F64x2Mul => Assembler::emit_vmulpd,
+                    Operator::F32x4Div => Assembler::emit_vdivps,
+                    Operator::F64x2Div => Assembler::emit_vdivpd,
+                    Operator::F32x4Eq => Assembler::emit_vcmpeqps,
+                    Operator::F64x2Eq => Assembler::emit_vcmpeqpd,
+                    Operator::F32x4Ne => Assembler::emit_vcmpneqps,
+                    Operator::F64x2Ne => Assembler::emit_vcmpneqpd,
+                    Operator::F32x4Lt => Assembler::emit_vcmpltps,
+                    Operator::F64x2Lt => Assembler::emit_vcmpltpd,
+                    Operator::F32x4Le => Assembler::emit_vcmpleps,
+                    _ => Assembler::emit_vcmplepd,
+                };
+                Self::emit_v128_binop(a, &mut self.machine, &mut self.value_stack, binop);
+            }
+            Operator::I8x16Mul => {
+                // There is no 8-bit multiplication, so the even and odd bytes are
+                // multiplied separately as 16-bit lanes.
+                Self::emit_v128_binop_base(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    |a, m, lhs, rhs| {
+                        let odd = m.acquire_temp_xmm().unwrap();
+                        a.emit_movdqu(XMMOrMemory::XMM(lhs), XMMOrMemory::XMM(odd));
+                        a.emit_psrlw_imm(8, odd);
+                        a.emit_vpmullw(lhs, XMMOrMemory::XMM(rhs), lhs);
+                        a.emit_psllw_imm(8, lhs);
+                        a.emit_psrlw_imm(8, lhs);
+                        a.emit_psrlw_imm(8, rhs);
+                        a.emit_vpmullw(odd, XMMOrMemory::XMM(rhs), odd);
+                        a.emit_psllw_imm(8, odd);
+                        a.emit_vpor(lhs, XMMOrMemory::XMM(odd), lhs);
+                        m.release_temp_xmm(odd);
+                    },
+                );
+            }
+            Operator::I8x16Ne | Operator::I16x8Ne | Operator::I32x4Ne => {
+                let cmpeq: fn(&mut Assembler, XMM, XMMOrMemory, XMM) = match *op {
+                    Operator::I8x16Ne => Assembler::emit_vpcmpeqb,
+                    Operator::I16x8Ne => Assembler::emit_vpcmpeqw,
+                    _ => Assembler::emit_vpcmpeqd,
+                };
+                Self::emit_v128_binop_base(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    |a, m, lhs, rhs| {
+                        cmpeq(a, lhs, XMMOrMemory::XMM(rhs), lhs);
+                        Self::emit_v128_not(a, m, lhs);
+                    },
+                );
+            }
+            Operator::I8x16LtS
+            | Operator::I16x8LtS
+            | Operator::I32x4LtS
+            | Operator::F32x4Gt
+            | Operator::F64x2Gt
+            | Operator::F32x4Ge
+            | Operator::F64x2Ge => {
+                // These are computed with the operands swapped, e.g. `a < b` as `b > a`.
+                let cmp: fn(&mut Assembler, XMM, XMMOrMemory, XMM) = match *op {
+                    Operator::I8x16LtS => Assembler::emit_vpcmpgtb,
+                    Operator::I16x8LtS => Assembler::emit_vpcmpgtw,
+                    Operator::I32x4LtS => Assembler::emit_vpcmpgtd,
+                    Operator::F32x4Gt => Assembler::emit_vcmpltps,
+                    Operator::F64x2Gt => Assembler::emit_vcmpltpd,
+                    Operator::F32x4Ge => Assembler::emit_vcmpleps,
+                    _ => Assembler::emit_vcmplepd,
+                };
+                Self::emit_v128_binop_base(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    |a, _, lhs, rhs| cmp(a, rhs, XMMOrMemory::XMM(lhs), lhs),
+                );
+            }
+            Operator::I8x16GeS
+            | Operator::I8x16GeU
+            | Operator::I16x8GeS
+            | Operator::I16x8GeU
+            | Operator::I32x4GeS
+            | Operator::I32x4GeU
+            | Operator::I8x16LeS
+            | Operator::I8x16LeU
+            | Operator::I16x8LeS
+            | Operator::I16x8LeU
+            | Operator::I32x4LeS
+            | Operator::I32x4LeU
+            | Operator::I8x16GtU
+            | Operator::I16x8GtU
+            | Operator::I32x4GtU
+            | Operator::I8x16LtU
+            | Operator::I16x8LtU
+            | Operator::I32x4LtU => {
+                // `a >= b` holds exactly when `max(a, b) == a`, and `a <= b` when
+                // `min(a, b) == a`. The strict unsigned comparisons are their negations.
+                type Binop = fn(&mut Assembler, XMM, XMMOrMemory, XMM);
+                let (minmax, cmpeq): (Binop, Binop) = match *op {
+                    Operator::I8x16GeS => (Assembler::emit_vpmaxsb, Assembler::emit_vpcmpeqb),
+                    Operator::I8x16GeU | Operator::I8x16LtU => {
+                        (Assembler::emit_vpmaxub, Assembler::emit_vpcmpeqb)
+                    }
+                    Operator::I16x8GeS => (Assembler::emit_vpmaxsw, Assembler::emit_vpcmpeqw),
+                    Operator::I16x8GeU | Operator::I16x8LtU => {
+                        (Assembler::emit_vpmaxuw, Assembler::emit_vpcmpeqw)
+                    }
+                    Operator::I32x4GeS => (Assembler::emit_vpmaxsd, Assembler::emit_vpcmpeqd),
+                    Operator::I32x4GeU | Operator::I32x4LtU => {
+                        (Assembler::emit_vpmaxud, Assembler::emit_vpcmpeqd)
+                    }
+                    Operator::I8x16LeS => (Assembler::emit_vpminsb, Assembler::emit_vpcmpeqb),
+                    Operator::I8x16LeU | Operator::I8x16GtU => {
+                        (Assembler::emit_vpminub, Assembler::emit_vpcmpeqb)
+                    }
+                    Operator::I16x8LeS => (Assembler::emit_vpminsw, Assembler::emit_vpcmpeqw),
+                    Operator::I16x8LeU | Operator::I16x8GtU => {
+                        (Assembler::emit_vpminuw, Assembler::emit_vpcmpeqw)
+                    }
+                    Operator::I32x4LeS => (Assembler::emit_vpminsd, Assembler::emit_vpcmpeqd),
+                    _ => (Assembler::emit_vpminud, Assembler::emit_vpcmpeqd),
+                };
+                let negate = match *op {
+                    Operator::I8x16GtU
+                    | Operator::I16x8GtU
+                    | Operator::I32x4GtU
+                    | Operator::I8x16LtU
+                    | Operator::I16x8LtU
+                    | Operator::I32x4LtU => true,
+                    _ => false,
+                };
+                Self::emit_v128_binop_base(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    |a, m, lhs, rhs| {
+                        minmax(a, lhs, XMMOrMemory::XMM(rhs), rhs);
+                        cmpeq(a, lhs, XMMOrMemory::XMM(rhs), lhs);
+                        if negate {
+                            Self::emit_v128_not(a, m, lhs);
+                        }
+                    },
+                );
+            }
+            Operator::F32x4Min | Operator::F64x2Min => {
+                // `minps` returns its second operand if either is NaN or both are zero,
+                // so the minimum is taken both ways and merged to propagate NaNs and -0.
+                // NaNs are then made canonical by clearing their payload.
+                let (min, cmpunord, shift, payload_bits): (
+                    fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+                    fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+                    fn(&mut Assembler, u8, XMM),
+                    u8,
+                ) = match *op {
+                    Operator::F32x4Min => (
+                        Assembler::emit_vminps,
+                        Assembler::emit_vcmpunordps,
+                        Assembler::emit_psrld_imm,
+                        10,
+                    ),
+                    _ => (
+                        Assembler::emit_vminpd,
+                        Assembler::emit_vcmpunordpd,
+                        Assembler::emit_psrlq_imm,
+                        13,
+                    ),
+                };
+                Self::emit_v128_binop_base(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    |a, m, lhs, rhs| {
+                        let tmp = m.acquire_temp_xmm().unwrap();
+                        min(a, lhs, XMMOrMemory::XMM(rhs), tmp);
+                        min(a, rhs, XMMOrMemory::XMM(lhs), lhs);
+                        a.emit_vpor(tmp, XMMOrMemory::XMM(lhs), tmp);
+                        cmpunord(a, lhs, XMMOrMemory::XMM(tmp), lhs);
+                        a.emit_vpor(tmp, XMMOrMemory::XMM(lhs), tmp);
+                        shift(a, payload_bits, lhs);
+                        a.emit_vpandn(lhs, XMMOrMemory::XMM(tmp), lhs);
+                        m.release_temp_xmm(tmp);
+                    },
+                );
+            }
+            Operator::F32x4Max | Operator::F64x2Max => {
+                // Like `F32x4Min`, but the results of both orders only differ in the sign
+                // of zeros and in NaNs, which are propagated by subtracting the difference.
+                let (max, sub, cmpunord, shift, payload_bits): (
+                    fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+                    fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+                    fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+                    fn(&mut Assembler, u8, XMM),
+                    u8,
+                ) = match *op {
+                    Operator::F32x4Max => (
+                        Assembler::emit_vmaxps,
+                        Assembler::emit_vsubps,
+                        Assembler::emit_vcmpunordps,
+                        Assembler::emit_psrld_imm,
+                        10,
+                    ),
+                    _ => (
+                        Assembler::emit_vmaxpd,
+                        Assembler::emit_vsubpd,
+                        Assembler::emit_vcmpunordpd,
+                        Assembler::emit_psrlq_imm,
+                        13,
+                    ),
+                };
+                Self::emit_v128_binop_base(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    |a, m, lhs, rhs| {
+                        let tmp = m.acquire_temp_xmm().unwrap();
+                        max(a, lhs, XMMOrMemory::XMM(rhs), tmp);
+                        max(a, rhs, XMMOrMemory::XMM(lhs), lhs);
+                        a.emit_vpxor(lhs, XMMOrMemory::XMM(tmp), lhs);
+                        a.emit_vpor(tmp, XMMOrMemory::XMM(lhs), tmp);
+                        sub(a, tmp, XMMOrMemory::XMM(lhs), tmp);
+                        cmpunord(a, lhs, XMMOrMemory::XMM(tmp), lhs);
+                        shift(a, payload_bits, lhs);
+                        a.emit_vpandn(lhs, XMMOrMemory::XMM(tmp), lhs);
+                        m.release_temp_xmm(tmp);
+                    },
+                );
+            }
+            Operator::V128Not => {
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                Self::emit_v128_not(a, &mut self.machine, value);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, value);
+            }
+            Operator::V128Bitselect => {
+                let mask = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let rhs = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let lhs = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+
+                a.emit_vpand(lhs, XMMOrMemory::XMM(mask), lhs);
+                a.emit_vpandn(mask, XMMOrMemory::XMM(rhs), mask);
+                a.emit_vpor(lhs, XMMOrMemory::XMM(mask), lhs);
+
+                self.machine.release_temp_xmm(mask);
+                self.machine.release_temp_xmm(rhs);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, lhs);
+            }
+            Operator::I8x16Neg | Operator::I16x8Neg | Operator::I32x4Neg | Operator::I64x2Neg => {
+                let sub: fn(&mut Assembler, XMM, XMMOrMemory, XMM) = match *op {
+                    Operator::I8x16Neg => Assembler::emit_vpsubb,
+                    Operator::I16x8Neg => Assembler::emit_vpsubw,
+                    Operator::I32x4Neg => Assembler::emit_vpsubd,
+                    _ => Assembler::emit_vpsubq,
+                };
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let ret = self.machine.acquire_temp_xmm().unwrap();
+
+                a.emit_vpxor(ret, XMMOrMemory::XMM(ret), ret);
+                sub(a, ret, XMMOrMemory::XMM(value), ret);
+
+                self.machine.release_temp_xmm(value);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, ret);
+            }
+            Operator::F32x4Abs | Operator::F64x2Abs | Operator::F32x4Neg | Operator::F64x2Neg => {
+                // Clears or flips the sign bits.
+                let (mask, binop): (u128, fn(&mut Assembler, XMM, XMMOrMemory, XMM)) = match *op {
+                    Operator::F32x4Abs => (
+                        0x7fff_ffff_7fff_ffff_7fff_ffff_7fff_ffff,
+                        Assembler::emit_vpand,
+                    ),
+                    Operator::F64x2Abs => (
+                        0x7fff_ffff_ffff_ffff_7fff_ffff_ffff_ffff,
+                        Assembler::emit_vpand,
+                    ),
+                    Operator::F32x4Neg => (
+                        0x8000_0000_8000_0000_8000_0000_8000_0000,
+                        Assembler::emit_vpxor,
+                    ),
+                    _ => (
+                        0x8000_0000_0000_0000_8000_0000_0000_0000,
+                        Assembler::emit_vpxor,
+                    ),
+                };
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let tmp = self.machine.acquire_temp_xmm().unwrap();
+
+                Self::emit_v128_const(a, &mut self.machine, mask, tmp);
+                binop(a, value, XMMOrMemory::XMM(tmp), value);
+
+                self.machine.release_temp_xmm(tmp);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, value);
+            }
+            Operator::F32x4Sqrt | Operator::F64x2Sqrt | Operator::F32x4ConvertSI32x4 => {
+                let unop: fn(&mut Assembler, XMMOrMemory, XMM) = match *op {
+                    Operator::F32x4Sqrt => Assembler::emit_sqrtps,
+                    Operator::F64x2Sqrt => Assembler::emit_sqrtpd,
+                    _ => Assembler::emit_cvtdq2ps,
+                };
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                unop(a, XMMOrMemory::XMM(value), value);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, value);
+            }
+            Operator::I8x16AnyTrue
+            | Operator::I16x8AnyTrue
+            | Operator::I32x4AnyTrue
+            | Operator::I64x2AnyTrue => {
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let tmp = self.machine.acquire_temp_gpr().unwrap();
+
+                a.emit_mov(Size::S32, Location::Imm32(0), Location::GPR(tmp));
+                a.emit_ptest(XMMOrMemory::XMM(value), value);
+                a.emit_set(Condition::NotEqual, tmp);
+
+                self.machine.release_temp_xmm(value);
+                Self::emit_push_gpr(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    WpType::I32,
+                    tmp,
+                );
+                self.machine.release_temp_gpr(tmp);
+            }
+            Operator::I8x16AllTrue
+            | Operator::I16x8AllTrue
+            | Operator::I32x4AllTrue
+            | Operator::I64x2AllTrue => {
+                let cmpeq: fn(&mut Assembler, XMM, XMMOrMemory, XMM) = match *op {
+                    Operator::I8x16AllTrue => Assembler::emit_vpcmpeqb,
+                    Operator::I16x8AllTrue => Assembler::emit_vpcmpeqw,
+                    Operator::I32x4AllTrue => Assembler::emit_vpcmpeqd,
+                    _ => Assembler::emit_vpcmpeqq,
+                };
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let zero = self.machine.acquire_temp_xmm().unwrap();
+                let tmp = self.machine.acquire_temp_gpr().unwrap();
+
+                // Sets the lanes that are zero, then checks that none was.
+                a.emit_vpxor(zero, XMMOrMemory::XMM(zero), zero);
+                cmpeq(a, value, XMMOrMemory::XMM(zero), value);
+                a.emit_mov(Size::S32, Location::Imm32(0), Location::GPR(tmp));
+                a.emit_ptest(XMMOrMemory::XMM(value), value);
+                a.emit_set(Condition::Equal, tmp);
+
+                self.machine.release_temp_xmm(zero);
+                self.machine.release_temp_xmm(value);
+                Self::emit_push_gpr(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    WpType::I32,
+                    tmp,
+                );
+                self.machine.release_temp_gpr(tmp);
+            }
+            Operator::I16x8Shl
+            | Operator::I32x4Shl
+            | Operator::I64x2Shl
+            | Operator::I16x8ShrS
+            | Operator::I32x4ShrS
+            | Operator::I16x8ShrU
+            | Operator::I32x4ShrU
+            | Operator::I64x2ShrU => {
+                let (lane_bits, shift): (u32, fn(&mut Assembler, XMM, XMMOrMemory, XMM)) = match *op
+                {
+                    Operator::I16x8Shl => (16, Assembler::emit_vpsllw),
+                    Operator::I32x4Shl => (32, Assembler::emit_vpslld),
+                    Operator::I64x2Shl => (64, Assembler::emit_vpsllq),
+                    Operator::I16x8ShrS => (16, Assembler::emit_vpsraw),
+                    Operator::I32x4ShrS => (32, Assembler::emit_vpsrad),
+                    Operator::I16x8ShrU => (16, Assembler::emit_vpsrlw),
+                    Operator::I32x4ShrU => (32, Assembler::emit_vpsrld),
+                    _ => (64, Assembler::emit_vpsrlq),
+                };
+                Self::emit_v128_shift(
+                    a,
+                    &mut self.machine,
+                    &mut self.value_stack,
+                    lane_bits,
+                    shift,
+                );
+            }
+            Operator::I8x16Shl | Operator::I8x16ShrU => {
+                let count =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let tmp = self.machine.acquire_temp_xmm().unwrap();
+
+                // There are no 8-bit shifts, so 16-bit lanes are shifted and the bits
+                // that crossed into the neighbouring byte are masked off.
+                a.emit_mov(Size::S32, count, Location::GPR(GPR::RCX));
+                a.emit_and(Size::S32, Location::Imm32(7), Location::GPR(GPR::RCX));
+                a.emit_mov(Size::S32, Location::GPR(GPR::RCX), Location::XMM(tmp));
+                a.emit_mov(Size::S32, Location::Imm32(0xff), Location::GPR(GPR::RAX));
+                match *op {
+                    Operator::I8x16Shl => {
+                        a.emit_vpsllw(value, XMMOrMemory::XMM(tmp), value);
+                        a.emit_shl(Size::S32, Location::GPR(GPR::RCX), Location::GPR(GPR::RAX));
+                    }
+                    _ => {
+                        a.emit_vpsrlw(value, XMMOrMemory::XMM(tmp), value);
+                        a.emit_shr(Size::S32, Location::GPR(GPR::RCX), Location::GPR(GPR::RAX));
                     }
                 }
+                a.emit_and(Size::S32, Location::Imm32(0xff), Location::GPR(GPR::RAX));
+                a.emit_imul_imm32_gpr64(0x0101_0101, GPR::RAX);
+                a.emit_mov(Size::S32, Location::GPR(GPR::RAX), Location::XMM(tmp));
+                a.emit_pshufd(0, XMMOrMemory::XMM(tmp), tmp);
+                a.emit_vpand(value, XMMOrMemory::XMM(tmp), value);
+
+                self.machine.release_temp_xmm(tmp);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, value);
+            }
+            Operator::I8x16ShrS => {
+                let count =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let high = self.machine.acquire_temp_xmm().unwrap();
+                let tmp = self.machine.acquire_temp_xmm().unwrap();
+
+                // Each byte is moved into the high byte of a 16-bit lane, shifted along
+                // with it and packed back, which can't saturate.
+                a.emit_mov(Size::S32, count, Location::GPR(GPR::RCX));
+                a.emit_and(Size::S32, Location::Imm32(7), Location::GPR(GPR::RCX));
+                a.emit_add(Size::S32, Location::Imm32(8), Location::GPR(GPR::RCX));
+                a.emit_mov(Size::S32, Location::GPR(GPR::RCX), Location::XMM(tmp));
+                a.emit_vpunpckhbw(value, XMMOrMemory::XMM(value), high);
+                a.emit_vpunpcklbw(value, XMMOrMemory::XMM(value), value);
+                a.emit_vpsraw(value, XMMOrMemory::XMM(tmp), value);
+                a.emit_vpsraw(high, XMMOrMemory::XMM(tmp), high);
+                a.emit_vpacksswb(value, XMMOrMemory::XMM(high), value);
+
+                self.machine.release_temp_xmm(tmp);
+                self.machine.release_temp_xmm(high);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, value);
+            }
+            Operator::I64x2ShrS => {
+                let count =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let sign = self.machine.acquire_temp_xmm().unwrap();
+                let tmp = self.machine.acquire_temp_xmm().unwrap();
+
+                // There is no 64-bit arithmetic shift, so the lanes are shifted logically
+                // and sign-extended from the shifted sign bit `m` as `(x ^ m) - m`.
+                a.emit_mov(Size::S32, count, Location::GPR(GPR::RCX));
+                a.emit_and(Size::S32, Location::Imm32(63), Location::GPR(GPR::RCX));
+                a.emit_mov(Size::S32, Location::GPR(GPR::RCX), Location::XMM(tmp));
+                a.emit_mov(
+                    Size::S64,
+                    Location::Imm64(0x8000_0000_0000_0000),
+                    Location::GPR(GPR::RAX),
+                );
+                a.emit_shr(Size::S64, Location::GPR(GPR::RCX), Location::GPR(GPR::RAX));
+                a.emit_mov(Size::S64, Location::GPR(GPR::RAX), Location::XMM(sign));
+                a.emit_vpunpcklqdq(sign, XMMOrMemory::XMM(sign), sign);
+                a.emit_vpsrlq(value, XMMOrMemory::XMM(tmp), value);
+                a.emit_vpxor(value, XMMOrMemory::XMM(sign), value);
+                a.emit_vpsubq(value, XMMOrMemory::XMM(sign), value);
+
+                self.machine.release_temp_xmm(tmp);
+                self.machine.release_temp_xmm(sign);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, value);
+            }
+            Operator::F32x4ConvertUI32x4 => {
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let low = self.machine.acquire_temp_xmm().unwrap();
+
+                // The low 16 bits convert exactly. The rest is halved to fit a signed
+                // integer and doubled again, which is also exact, so only the final
+                // addition rounds.
+                a.emit_movdqu(XMMOrMemory::XMM(value), XMMOrMemory::XMM(low));
+                a.emit_pslld_imm(16, low);
+                a.emit_psrld_imm(16, low);
+                a.emit_vpsubd(value, XMMOrMemory::XMM(low), value);
+                a.emit_cvtdq2ps(XMMOrMemory::XMM(low), low);
+                a.emit_psrld_imm(1, value);
+                a.emit_cvtdq2ps(XMMOrMemory::XMM(value), value);
+                a.emit_vaddps(value, XMMOrMemory::XMM(value), value);
+                a.emit_vaddps(value, XMMOrMemory::XMM(low), value);
+
+                self.machine.release_temp_xmm(low);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, value);
+            }
+            Operator::I32x4TruncSF32x4Sat => {
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let tmp = self.machine.acquire_temp_xmm().unwrap();
+
+                // NaNs become 0. Out of range lanes convert to 0x80000000, which is then
+                // flipped to 0x7fffffff where the input was positive.
+                a.emit_vcmpeqps(value, XMMOrMemory::XMM(value), tmp);
+                a.emit_vpand(value, XMMOrMemory::XMM(tmp), value);
+                a.emit_vpxor(tmp, XMMOrMemory::XMM(value), tmp);
+                a.emit_cvttps2dq(XMMOrMemory::XMM(value), value);
+                a.emit_vpand(tmp, XMMOrMemory::XMM(value), tmp);
+                a.emit_psrad_imm(31, tmp);
+                a.emit_vpxor(value, XMMOrMemory::XMM(tmp), value);
+
+                self.machine.release_temp_xmm(tmp);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, value);
+            }
+            Operator::I32x4TruncUF32x4Sat => {
+                let value = Self::emit_v128_pop(a, &mut self.machine, &mut self.value_stack);
+                let tmp = self.machine.acquire_temp_xmm().unwrap();
+                let high = self.machine.acquire_temp_xmm().unwrap();
+
+                // NaNs and negative lanes become 0. The lanes at or above 2^31 are
+                // converted separately, less 2^31, and added to the 0x80000000 that
+                // they convert to.
+                a.emit_vpxor(tmp, XMMOrMemory::XMM(tmp), tmp);
+                a.emit_vmaxps(value, XMMOrMemory::XMM(tmp), value);
+                a.emit_vpcmpeqd(tmp, XMMOrMemory::XMM(tmp), tmp);
+                a.emit_psrld_imm(1, tmp);
+                a.emit_cvtdq2ps(XMMOrMemory::XMM(tmp), tmp);
+                a.emit_vsubps(value, XMMOrMemory::XMM(tmp), high);
+                a.emit_vcmpleps(tmp, XMMOrMemory::XMM(high), tmp);
+                a.emit_cvttps2dq(XMMOrMemory::XMM(high), high);
+                a.emit_vpxor(high, XMMOrMemory::XMM(tmp), high);
+                a.emit_vpxor(tmp, XMMOrMemory::XMM(tmp), tmp);
+                a.emit_vpmaxsd(high, XMMOrMemory::XMM(tmp), high);
+                a.emit_cvttps2dq(XMMOrMemory::XMM(value), value);
+                a.emit_vpaddd(value, XMMOrMemory::XMM(high), value);
+
+                self.machine.release_temp_xmm(high);
+                self.machine.release_temp_xmm(tmp);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, value);
+            }
+            Operator::F64x2ConvertSI64x2 | Operator::F64x2ConvertUI64x2 => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let low = self.machine.acquire_temp_xmm().unwrap();
+                let high = self.machine.acquire_temp_xmm().unwrap();
+                let tmp = self.machine.acquire_temp_gpr().unwrap();
+
+                // There are no packed 64-bit conversions, so each lane is converted on its own.
+                for &(half, dst) in &[(loc, low), (Machine::get_high_half(loc), high)] {
+                    a.emit_mov(Size::S64, half, Location::GPR(tmp));
+                    match *op {
+                        Operator::F64x2ConvertSI64x2 => {
+                            a.emit_vcvtsi2sd_64(dst, GPROrMemory::GPR(tmp), dst)
+                        }
+                        _ => Self::emit_convert_u64_to_f64(a, &mut self.machine, tmp, dst),
+                    }
+                }
+                a.emit_vpunpcklqdq(low, XMMOrMemory::XMM(high), low);
+
+                self.machine.release_temp_gpr(tmp);
+                self.machine.release_temp_xmm(high);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, low);
+            }
+            Operator::I64x2TruncSF64x2Sat | Operator::I64x2TruncUF64x2Sat => {
+                let loc =
+                    get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
+                let ret = self.machine.acquire_temp_xmm().unwrap();
+                let value = self.machine.acquire_temp_xmm().unwrap();
+                let tmp = self.machine.acquire_temp_xmm().unwrap();
+                let tmp_out = self.machine.acquire_temp_gpr().unwrap();
+                let tmp_bits = self.machine.acquire_temp_gpr().unwrap();
+
+                // There are no packed 64-bit conversions, so each lane is converted on its own.
+                for (lane, &half) in [loc, Machine::get_high_half(loc)].iter().enumerate() {
+                    let end_convert = a.get_label();
+                    a.emit_mov(Size::S64, half, Location::XMM(value));
+
+                    match *op {
+                        Operator::I64x2TruncSF64x2Sat => {
+                            // `cvttsd2si` gives `i64::MIN` for NaNs and out of range values.
+                            a.emit_cvttsd2si_64(XMMOrMemory::XMM(value), tmp_out);
+                            a.emit_mov(
+                                Size::S64,
+                                Location::Imm64(0x8000_0000_0000_0000),
+                                Location::GPR(tmp_bits),
+                            );
+                            a.emit_cmp(Size::S64, Location::GPR(tmp_bits), Location::GPR(tmp_out));
+                            a.emit_jmp(Condition::NotEqual, end_convert);
+
+                            // NaNs become 0.
+                            a.emit_vcmpeqsd(value, XMMOrMemory::XMM(value), tmp);
+                            a.emit_mov(Size::S64, Location::XMM(tmp), Location::GPR(tmp_bits));
+                            a.emit_and(Size::S64, Location::GPR(tmp_bits), Location::GPR(tmp_out));
+
+                            // Positive values saturate to `i64::MAX`.
+                            a.emit_vpxor(tmp, XMMOrMemory::XMM(tmp), tmp);
+                            a.emit_ucomisd(XMMOrMemory::XMM(tmp), value);
+                            a.emit_jmp(Condition::BelowEqual, end_convert);
+                            a.emit_mov(
+                                Size::S64,
+                                Location::Imm64(0x7fff_ffff_ffff_ffff),
+                                Location::GPR(tmp_out),
+                            );
+                        }
+                        _ => {
+                            let large = a.get_label();
+                            let saturate = a.get_label();
+
+                            // NaNs and values up to 0 become 0.
+                            a.emit_mov(Size::S64, Location::Imm32(0), Location::GPR(tmp_out));
+                            a.emit_vpxor(tmp, XMMOrMemory::XMM(tmp), tmp);
+                            a.emit_ucomisd(XMMOrMemory::XMM(tmp), value);
+                            a.emit_jmp(Condition::BelowEqual, end_convert);
+
+                            // Values from 2^63 are converted less 2^63, like `I64TruncUF64`.
+                            a.emit_mov(
+                                Size::S64,
+                                Location::Imm64(0x43e0_0000_0000_0000),
+                                Location::GPR(tmp_bits),
+                            );
+                            a.emit_mov(Size::S64, Location::GPR(tmp_bits), Location::XMM(tmp));
+                            a.emit_ucomisd(XMMOrMemory::XMM(tmp), value);
+                            a.emit_jmp(Condition::AboveEqual, large);
+                            a.emit_cvttsd2si_64(XMMOrMemory::XMM(value), tmp_out);
+                            a.emit_jmp(Condition::None, end_convert);
+
+                            a.emit_label(large);
+                            a.emit_vsubsd(value, XMMOrMemory::XMM(tmp), value);
+                            a.emit_cvttsd2si_64(XMMOrMemory::XMM(value), tmp_out);
+                            a.emit_test_gpr_64(tmp_out);
+                            a.emit_jmp(Condition::Signed, saturate);
+                            a.emit_btc_gpr_imm8_64(63, tmp_out);
+                            a.emit_jmp(Condition::None, end_convert);
+
+                            // Values from 2^64 saturate to `u64::MAX`.
+                            a.emit_label(saturate);
+                            a.emit_mov(
+                                Size::S64,
+                                Location::Imm64(0xffff_ffff_ffff_ffff),
+                                Location::GPR(tmp_out),
+                            );
+                        }
+                    }
+
+                    a.emit_label(end_convert);
+                    a.emit_pinsr(Size::S64, tmp_out, lane as u8, ret);
+                }
+
+                self.machine.release_temp_gpr(tmp_bits);
+                self.machine.release_temp_gpr(tmp_out);
+                self.machine.release_temp_xmm(tmp);
+                self.machine.release_temp_xmm(value);
+                Self::emit_v128_push(a, &mut self.machine, &mut self.value_stack, ret);
             }
             _ => {
-                panic!("not yet implemented: {:?}", op);
+                return Err(CodegenError {
+                    message: format!("unsupported operator: {:?}", op),
+                });
             }
         }
 
@@ -4913,6 +7378,103 @@ fn type_to_wp_type(ty: Type) -> WpType {
         Type::F32 => WpType::F32,
         Type::F64 => WpType::F64,
         Type::V128 => WpType::V128,
+        Type::AnyRef => WpType::AnyRef,
+        Type::FuncRef => WpType::AnyFunc,
+    }
+}
+
+/// The size of the memory accessed by an atomic operator and the type of the
+/// value it operates on.
+fn atomic_access(op: &Operator) -> (Size, WpType) {
+    match *op {
+        Operator::I32AtomicLoad8U { .. }
+        | Operator::I32AtomicStore8 { .. }
+        | Operator::I32AtomicRmw8UAdd { .. }
+        | Operator::I32AtomicRmw8USub { .. }
+        | Operator::I32AtomicRmw8UAnd { .. }
+        | Operator::I32AtomicRmw8UOr { .. }
+        | Operator::I32AtomicRmw8UXor { .. }
+        | Operator::I32AtomicRmw8UXchg { .. }
+        | Operator::I32AtomicRmw8UCmpxchg { .. } => (Size::S8, WpType::I32),
+        Operator::I32AtomicLoad16U { .. }
+        | Operator::I32AtomicStore16 { .. }
+        | Operator::I32AtomicRmw16UAdd { .. }
+        | Operator::I32AtomicRmw16USub { .. }
+        | Operator::I32AtomicRmw16UAnd { .. }
+        | Operator::I32AtomicRmw16UOr { .. }
+        | Operator::I32AtomicRmw16UXor { .. }
+        | Operator::I32AtomicRmw16UXchg { .. }
+        | Operator::I32AtomicRmw16UCmpxchg { .. } => (Size::S16, WpType::I32),
+        Operator::I64AtomicLoad8U { .. }
+        | Operator::I64AtomicStore8 { .. }
+        | Operator::I64AtomicRmw8UAdd { .. }
+        | Operator::I64AtomicRmw8USub { .. }
+        | Operator::I64AtomicRmw8UAnd { .. }
+        | Operator::I64AtomicRmw8UOr { .. }
+        | Operator::I64AtomicRmw8UXor { .. }
+        | Operator::I64AtomicRmw8UXchg { .. }
+        | Operator::I64AtomicRmw8UCmpxchg { .. } => (Size::S8, WpType::I64),
+        Operator::I64AtomicLoad16U { .. }
+        | Operator::I64AtomicStore16 { .. }
+        | Operator::I64AtomicRmw16UAdd { .. }
+        | Operator::I64AtomicRmw16USub { .. }
+        | Operator::I64AtomicRmw16UAnd { .. }
+        | Operator::I64AtomicRmw16UOr { .. }
+        | Operator::I64AtomicRmw16UXor { .. }
+        | Operator::I64AtomicRmw16UXchg { .. }
+        | Operator::I64AtomicRmw16UCmpxchg { .. } => (Size::S16, WpType::I64),
+        Operator::I64AtomicLoad32U { .. }
+        | Operator::I64AtomicStore32 { .. }
+        | Operator::I64AtomicRmw32UAdd { .. }
+        | Operator::I64AtomicRmw32USub { .. }
+        | Operator::I64AtomicRmw32UAnd { .. }
+        | Operator::I64AtomicRmw32UOr { .. }
+        | Operator::I64AtomicRmw32UXor { .. }
+        | Operator::I64AtomicRmw32UXchg { .. }
+        | Operator::I64AtomicRmw32UCmpxchg { .. } => (Size::S32, WpType::I64),
+        Operator::I64AtomicLoad { .. }
+        | Operator::I64AtomicStore { .. }
+        | Operator::I64AtomicRmwAdd { .. }
+        | Operator::I64AtomicRmwSub { .. }
+        | Operator::I64AtomicRmwAnd { .. }
+        | Operator::I64AtomicRmwOr { .. }
+        | Operator::I64AtomicRmwXor { .. }
+        | Operator::I64AtomicRmwXchg { .. }
+        | Operator::I64AtomicRmwCmpxchg { .. } => (Size::S64, WpType::I64),
+        _ => (Size::S32, WpType::I32),
+    }
+}
+
+fn size_bytes(sz: Size) -> usize {
+    match sz {
+        Size::S8 => 1,
+        Size::S16 => 2,
+        Size::S32 => 4,
+        Size::S64 => 8,
+    }
+}
+
+fn wp_type_size(ty: WpType) -> Size {
+    match ty {
+        WpType::I64 => Size::S64,
+        _ => Size::S32,
+    }
+}
+
+/// Returns the 64-bit halves that a value of type `ty` at `loc` is moved as.
+fn value_halves(loc: Location, ty: WpType) -> SmallVec<[Location; 2]> {
+    if ty == WpType::V128 {
+        smallvec![loc, Machine::get_high_half(loc)]
+    } else {
+        smallvec![loc]
+    }
+}
+
+/// The operand referring to the 16-byte slot of a `V128` value.
+fn v128_operand(loc: Location) -> XMMOrMemory {
+    match loc {
+        Location::Memory(base, disp) => XMMOrMemory::Memory(base, disp),
+        _ => unreachable!(),
     }
 }
 
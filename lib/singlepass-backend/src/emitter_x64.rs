@@ -1,5 +1,6 @@
 use dynasmrt::{x64::Assembler, AssemblyOffset, DynamicLabel, DynasmApi, DynasmLabelApi};
 pub use wasmer_runtime_core::state::x64::{GPR, XMM};
+use wasmer_runtime_core::typed_func::WasmTrapInfo;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Location {
@@ -69,7 +70,7 @@ pub trait Emitter {
     fn emit_xor(&mut self, sz: Size, src: Location, dst: Location);
     fn emit_jmp(&mut self, condition: Condition, label: Self::Label);
     fn emit_jmp_location(&mut self, loc: Location);
-    fn emit_conditional_trap(&mut self, condition: Condition);
+    fn emit_conditional_trap(&mut self, condition: Condition, kind: WasmTrapInfo);
     fn emit_set(&mut self, condition: Condition, dst: GPR);
     fn emit_push(&mut self, sz: Size, src: Location);
     fn emit_pop(&mut self, sz: Size, dst: Location);
@@ -80,6 +81,7 @@ pub trait Emitter {
     fn emit_imul_imm32_gpr64(&mut self, src: u32, dst: GPR);
     fn emit_div(&mut self, sz: Size, divisor: Location);
     fn emit_idiv(&mut self, sz: Size, divisor: Location);
+    fn emit_neg(&mut self, sz: Size, value: Location);
     fn emit_shl(&mut self, sz: Size, src: Location, dst: Location);
     fn emit_shr(&mut self, sz: Size, src: Location, dst: Location);
     fn emit_sar(&mut self, sz: Size, src: Location, dst: Location);
@@ -99,6 +101,10 @@ pub trait Emitter {
     fn emit_cmovae_gpr_32(&mut self, src: GPR, dst: GPR);
     fn emit_cmovae_gpr_64(&mut self, src: GPR, dst: GPR);
 
+    fn emit_xchg(&mut self, sz: Size, src: Location, dst: Location);
+    fn emit_lock_xadd(&mut self, sz: Size, src: Location, dst: Location);
+    fn emit_lock_cmpxchg(&mut self, sz: Size, src: Location, dst: Location);
+
     fn emit_vaddss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
     fn emit_vaddsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
     fn emit_vsubss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
@@ -158,9 +164,117 @@ pub trait Emitter {
     fn emit_vcvtsi2sd_32(&mut self, src1: XMM, src2: GPROrMemory, dst: XMM);
     fn emit_vcvtsi2sd_64(&mut self, src1: XMM, src2: GPROrMemory, dst: XMM);
 
+    fn emit_movdqu(&mut self, src: XMMOrMemory, dst: XMMOrMemory);
+
+    fn emit_vpaddb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmullw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmulld(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpaddsb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddsw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddusb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddusw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubsb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubsw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubusb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubusw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpand(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpandn(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpor(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpxor(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpcmpeqb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpeqw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpeqd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpeqq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpgtb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpgtw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpgtd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpminsb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpminsw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpminsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpminub(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpminuw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpminud(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxsb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxsw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxub(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxuw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxud(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpshufb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpunpcklbw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpunpckhbw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpunpcklqdq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpacksswb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpsllw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpslld(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsllq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsrlw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsrld(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsrlq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsraw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsrad(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vaddps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vaddpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vsubps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vsubpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vmulps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vmulpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vdivps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vdivpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vminps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vminpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vmaxps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vmaxpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vcmpeqps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpeqpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpneqps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpneqpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpltps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpltpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpleps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmplepd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpunordps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpunordpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_sqrtps(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_sqrtpd(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_cvtdq2ps(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_cvttps2dq(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_ptest(&mut self, src: XMMOrMemory, dst: XMM);
+
+    fn emit_psllw_imm(&mut self, imm: u8, dst: XMM);
+    fn emit_pslld_imm(&mut self, imm: u8, dst: XMM);
+    fn emit_psllq_imm(&mut self, imm: u8, dst: XMM);
+    fn emit_psrlw_imm(&mut self, imm: u8, dst: XMM);
+    fn emit_psrld_imm(&mut self, imm: u8, dst: XMM);
+    fn emit_psrlq_imm(&mut self, imm: u8, dst: XMM);
+    fn emit_psrad_imm(&mut self, imm: u8, dst: XMM);
+
+    fn emit_pshufd(&mut self, order: u8, src: XMMOrMemory, dst: XMM);
+    fn emit_pshuflw(&mut self, order: u8, src: XMMOrMemory, dst: XMM);
+    fn emit_pextr(&mut self, sz: Size, src: XMM, lane: u8, dst: GPR);
+    fn emit_pinsr(&mut self, sz: Size, src: GPR, lane: u8, dst: XMM);
+
     fn emit_test_gpr_64(&mut self, reg: GPR);
 
     fn emit_ud2(&mut self);
+    /// Emits a `ud2` followed by a byte recording why the trap happened.
+    fn emit_trap(&mut self, kind: WasmTrapInfo);
     fn emit_ret(&mut self);
     fn emit_call_label(&mut self, label: Self::Label);
     fn emit_call_location(&mut self, loc: Location);
@@ -338,15 +452,17 @@ macro_rules! jmp_op {
 }
 
 macro_rules! trap_op {
-    ($ins:ident, $assembler:tt) => {
+    ($ins:ident, $assembler:tt, $kind:expr) => {{
         dynasm!($assembler
             ; $ins >trap
             ; jmp >after
             ; trap:
-            ; ud2
+        );
+        $assembler.emit_trap($kind);
+        dynasm!($assembler
             ; after:
         );
-    }
+    }}
 }
 
 macro_rules! avx_fn {
@@ -448,6 +564,36 @@ macro_rules! avx_round_fn {
     }
 }
 
+macro_rules! sse_fn {
+    ($ins:ident, $name:ident) => {
+        fn $name(&mut self, src: XMMOrMemory, dst: XMM) {
+            match src {
+                XMMOrMemory::XMM(x) => dynasm!(self ; $ins Rx((dst as u8)), Rx((x as u8))),
+                XMMOrMemory::Memory(base, disp) => dynasm!(self ; $ins Rx((dst as u8)), [Rq((base as u8)) + disp]),
+            }
+        }
+    }
+}
+
+macro_rules! sse_shift_imm_fn {
+    ($ins:ident, $name:ident) => {
+        fn $name(&mut self, imm: u8, dst: XMM) {
+            dynasm!(self ; $ins Rx((dst as u8)), imm as i8);
+        }
+    }
+}
+
+macro_rules! sse_shuffle_fn {
+    ($ins:ident, $name:ident) => {
+        fn $name(&mut self, order: u8, src: XMMOrMemory, dst: XMM) {
+            match src {
+                XMMOrMemory::XMM(x) => dynasm!(self ; $ins Rx((dst as u8)), Rx((x as u8)), order as i8),
+                XMMOrMemory::Memory(base, disp) => dynasm!(self ; $ins Rx((dst as u8)), [Rq((base as u8)) + disp], order as i8),
+            }
+        }
+    }
+}
+
 impl Emitter for Assembler {
     type Label = DynamicLabel;
     type Offset = AssemblyOffset;
@@ -599,20 +745,20 @@ impl Emitter for Assembler {
             _ => unreachable!(),
         }
     }
-    fn emit_conditional_trap(&mut self, condition: Condition) {
+    fn emit_conditional_trap(&mut self, condition: Condition, kind: WasmTrapInfo) {
         match condition {
-            Condition::None => trap_op!(jmp, self),
-            Condition::Above => trap_op!(ja, self),
-            Condition::AboveEqual => trap_op!(jae, self),
-            Condition::Below => trap_op!(jb, self),
-            Condition::BelowEqual => trap_op!(jbe, self),
-            Condition::Greater => trap_op!(jg, self),
-            Condition::GreaterEqual => trap_op!(jge, self),
-            Condition::Less => trap_op!(jl, self),
-            Condition::LessEqual => trap_op!(jle, self),
-            Condition::Equal => trap_op!(je, self),
-            Condition::NotEqual => trap_op!(jne, self),
-            Condition::Signed => trap_op!(js, self),
+            Condition::None => trap_op!(jmp, self, kind),
+            Condition::Above => trap_op!(ja, self, kind),
+            Condition::AboveEqual => trap_op!(jae, self, kind),
+            Condition::Below => trap_op!(jb, self, kind),
+            Condition::BelowEqual => trap_op!(jbe, self, kind),
+            Condition::Greater => trap_op!(jg, self, kind),
+            Condition::GreaterEqual => trap_op!(jge, self, kind),
+            Condition::Less => trap_op!(jl, self, kind),
+            Condition::LessEqual => trap_op!(jle, self, kind),
+            Condition::Equal => trap_op!(je, self, kind),
+            Condition::NotEqual => trap_op!(jne, self, kind),
+            Condition::Signed => trap_op!(js, self, kind),
         }
     }
     fn emit_set(&mut self, condition: Condition, dst: GPR) {
@@ -675,6 +821,9 @@ impl Emitter for Assembler {
     fn emit_idiv(&mut self, sz: Size, divisor: Location) {
         unop_gpr_or_mem!(idiv, self, sz, divisor, { unreachable!() });
     }
+    fn emit_neg(&mut self, sz: Size, value: Location) {
+        unop_gpr_or_mem!(neg, self, sz, value, { unreachable!() });
+    }
     fn emit_shl(&mut self, sz: Size, src: Location, dst: Location) {
         binop_shift!(shl, self, sz, src, dst, { unreachable!() });
     }
@@ -792,6 +941,60 @@ impl Emitter for Assembler {
         dynasm!(self ; cmovae Rq(dst as u8), Rq(src as u8));
     }
 
+    fn emit_xchg(&mut self, sz: Size, src: Location, dst: Location) {
+        match (sz, src, dst) {
+            (Size::S8, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; xchg [Rq(dst as u8) + disp], Rb(src as u8));
+            }
+            (Size::S16, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; xchg [Rq(dst as u8) + disp], Rw(src as u8));
+            }
+            (Size::S32, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; xchg [Rq(dst as u8) + disp], Rd(src as u8));
+            }
+            (Size::S64, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; xchg [Rq(dst as u8) + disp], Rq(src as u8));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn emit_lock_xadd(&mut self, sz: Size, src: Location, dst: Location) {
+        match (sz, src, dst) {
+            (Size::S8, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; lock xadd [Rq(dst as u8) + disp], Rb(src as u8));
+            }
+            (Size::S16, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; lock xadd [Rq(dst as u8) + disp], Rw(src as u8));
+            }
+            (Size::S32, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; lock xadd [Rq(dst as u8) + disp], Rd(src as u8));
+            }
+            (Size::S64, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; lock xadd [Rq(dst as u8) + disp], Rq(src as u8));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn emit_lock_cmpxchg(&mut self, sz: Size, src: Location, dst: Location) {
+        match (sz, src, dst) {
+            (Size::S8, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; lock cmpxchg [Rq(dst as u8) + disp], Rb(src as u8));
+            }
+            (Size::S16, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; lock cmpxchg [Rq(dst as u8) + disp], Rw(src as u8));
+            }
+            (Size::S32, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; lock cmpxchg [Rq(dst as u8) + disp], Rd(src as u8));
+            }
+            (Size::S64, Location::GPR(src), Location::Memory(dst, disp)) => {
+                dynasm!(self ; lock cmpxchg [Rq(dst as u8) + disp], Rq(src as u8));
+            }
+            _ => unreachable!(),
+        }
+    }
+
     avx_fn!(vaddss, emit_vaddss);
     avx_fn!(vaddsd, emit_vaddsd);
 
@@ -848,6 +1051,141 @@ impl Emitter for Assembler {
     avx_i2f_64_fn!(vcvtsi2ss, emit_vcvtsi2ss_64);
     avx_i2f_64_fn!(vcvtsi2sd, emit_vcvtsi2sd_64);
 
+    avx_fn!(vpaddb, emit_vpaddb);
+    avx_fn!(vpaddw, emit_vpaddw);
+    avx_fn!(vpaddd, emit_vpaddd);
+    avx_fn!(vpaddq, emit_vpaddq);
+    avx_fn!(vpsubb, emit_vpsubb);
+    avx_fn!(vpsubw, emit_vpsubw);
+    avx_fn!(vpsubd, emit_vpsubd);
+    avx_fn!(vpsubq, emit_vpsubq);
+    avx_fn!(vpmullw, emit_vpmullw);
+    avx_fn!(vpmulld, emit_vpmulld);
+
+    avx_fn!(vpaddsb, emit_vpaddsb);
+    avx_fn!(vpaddsw, emit_vpaddsw);
+    avx_fn!(vpaddusb, emit_vpaddusb);
+    avx_fn!(vpaddusw, emit_vpaddusw);
+    avx_fn!(vpsubsb, emit_vpsubsb);
+    avx_fn!(vpsubsw, emit_vpsubsw);
+    avx_fn!(vpsubusb, emit_vpsubusb);
+    avx_fn!(vpsubusw, emit_vpsubusw);
+
+    avx_fn!(vpand, emit_vpand);
+    avx_fn!(vpandn, emit_vpandn);
+    avx_fn!(vpor, emit_vpor);
+    avx_fn!(vpxor, emit_vpxor);
+
+    avx_fn!(vpcmpeqb, emit_vpcmpeqb);
+    avx_fn!(vpcmpeqw, emit_vpcmpeqw);
+    avx_fn!(vpcmpeqd, emit_vpcmpeqd);
+    avx_fn!(vpcmpeqq, emit_vpcmpeqq);
+    avx_fn!(vpcmpgtb, emit_vpcmpgtb);
+    avx_fn!(vpcmpgtw, emit_vpcmpgtw);
+    avx_fn!(vpcmpgtd, emit_vpcmpgtd);
+
+    avx_fn!(vpminsb, emit_vpminsb);
+    avx_fn!(vpminsw, emit_vpminsw);
+    avx_fn!(vpminsd, emit_vpminsd);
+    avx_fn!(vpminub, emit_vpminub);
+    avx_fn!(vpminuw, emit_vpminuw);
+    avx_fn!(vpminud, emit_vpminud);
+    avx_fn!(vpmaxsb, emit_vpmaxsb);
+    avx_fn!(vpmaxsw, emit_vpmaxsw);
+    avx_fn!(vpmaxsd, emit_vpmaxsd);
+    avx_fn!(vpmaxub, emit_vpmaxub);
+    avx_fn!(vpmaxuw, emit_vpmaxuw);
+    avx_fn!(vpmaxud, emit_vpmaxud);
+
+    avx_fn!(vpshufb, emit_vpshufb);
+    avx_fn!(vpunpcklbw, emit_vpunpcklbw);
+    avx_fn!(vpunpckhbw, emit_vpunpckhbw);
+    avx_fn!(vpunpcklqdq, emit_vpunpcklqdq);
+    avx_fn!(vpacksswb, emit_vpacksswb);
+
+    avx_fn!(vpsllw, emit_vpsllw);
+    avx_fn!(vpslld, emit_vpslld);
+    avx_fn!(vpsllq, emit_vpsllq);
+    avx_fn!(vpsrlw, emit_vpsrlw);
+    avx_fn!(vpsrld, emit_vpsrld);
+    avx_fn!(vpsrlq, emit_vpsrlq);
+    avx_fn!(vpsraw, emit_vpsraw);
+    avx_fn!(vpsrad, emit_vpsrad);
+
+    avx_fn!(vaddps, emit_vaddps);
+    avx_fn!(vaddpd, emit_vaddpd);
+    avx_fn!(vsubps, emit_vsubps);
+    avx_fn!(vsubpd, emit_vsubpd);
+    avx_fn!(vmulps, emit_vmulps);
+    avx_fn!(vmulpd, emit_vmulpd);
+    avx_fn!(vdivps, emit_vdivps);
+    avx_fn!(vdivpd, emit_vdivpd);
+    avx_fn!(vminps, emit_vminps);
+    avx_fn!(vminpd, emit_vminpd);
+    avx_fn!(vmaxps, emit_vmaxps);
+    avx_fn!(vmaxpd, emit_vmaxpd);
+
+    avx_fn!(vcmpeqps, emit_vcmpeqps);
+    avx_fn!(vcmpeqpd, emit_vcmpeqpd);
+    avx_fn!(vcmpneqps, emit_vcmpneqps);
+    avx_fn!(vcmpneqpd, emit_vcmpneqpd);
+    avx_fn!(vcmpltps, emit_vcmpltps);
+    avx_fn!(vcmpltpd, emit_vcmpltpd);
+    avx_fn!(vcmpleps, emit_vcmpleps);
+    avx_fn!(vcmplepd, emit_vcmplepd);
+    avx_fn!(vcmpunordps, emit_vcmpunordps);
+    avx_fn!(vcmpunordpd, emit_vcmpunordpd);
+
+    sse_fn!(sqrtps, emit_sqrtps);
+    sse_fn!(sqrtpd, emit_sqrtpd);
+    sse_fn!(cvtdq2ps, emit_cvtdq2ps);
+    sse_fn!(cvttps2dq, emit_cvttps2dq);
+    sse_fn!(ptest, emit_ptest);
+
+    sse_shift_imm_fn!(psllw, emit_psllw_imm);
+    sse_shift_imm_fn!(pslld, emit_pslld_imm);
+    sse_shift_imm_fn!(psllq, emit_psllq_imm);
+    sse_shift_imm_fn!(psrlw, emit_psrlw_imm);
+    sse_shift_imm_fn!(psrld, emit_psrld_imm);
+    sse_shift_imm_fn!(psrlq, emit_psrlq_imm);
+    sse_shift_imm_fn!(psrad, emit_psrad_imm);
+
+    sse_shuffle_fn!(pshufd, emit_pshufd);
+    sse_shuffle_fn!(pshuflw, emit_pshuflw);
+
+    fn emit_movdqu(&mut self, src: XMMOrMemory, dst: XMMOrMemory) {
+        match (src, dst) {
+            (XMMOrMemory::XMM(src), XMMOrMemory::XMM(dst)) => {
+                dynasm!(self ; movdqu Rx(dst as u8), Rx(src as u8))
+            }
+            (XMMOrMemory::Memory(base, disp), XMMOrMemory::XMM(dst)) => {
+                dynasm!(self ; movdqu Rx(dst as u8), [Rq(base as u8) + disp])
+            }
+            (XMMOrMemory::XMM(src), XMMOrMemory::Memory(base, disp)) => {
+                dynasm!(self ; movdqu [Rq(base as u8) + disp], Rx(src as u8))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn emit_pextr(&mut self, sz: Size, src: XMM, lane: u8, dst: GPR) {
+        match sz {
+            Size::S8 => dynasm!(self ; pextrb Rd(dst as u8), Rx(src as u8), lane as i8),
+            Size::S16 => dynasm!(self ; pextrw Rd(dst as u8), Rx(src as u8), lane as i8),
+            Size::S32 => dynasm!(self ; pextrd Rd(dst as u8), Rx(src as u8), lane as i8),
+            Size::S64 => dynasm!(self ; pextrq Rq(dst as u8), Rx(src as u8), lane as i8),
+        }
+    }
+
+    fn emit_pinsr(&mut self, sz: Size, src: GPR, lane: u8, dst: XMM) {
+        match sz {
+            Size::S8 => dynasm!(self ; pinsrb Rx(dst as u8), Rd(src as u8), lane as i8),
+            Size::S16 => dynasm!(self ; pinsrw Rx(dst as u8), Rd(src as u8), lane as i8),
+            Size::S32 => dynasm!(self ; pinsrd Rx(dst as u8), Rd(src as u8), lane as i8),
+            Size::S64 => dynasm!(self ; pinsrq Rx(dst as u8), Rq(src as u8), lane as i8),
+        }
+    }
+
     fn emit_ucomiss(&mut self, src: XMMOrMemory, dst: XMM) {
         match src {
             XMMOrMemory::XMM(x) => dynasm!(self ; ucomiss Rx(dst as u8), Rx(x as u8)),
@@ -909,6 +1247,10 @@ impl Emitter for Assembler {
     fn emit_ud2(&mut self) {
         dynasm!(self ; ud2);
     }
+    fn emit_trap(&mut self, kind: WasmTrapInfo) {
+        dynasm!(self ; ud2);
+        self.push(kind as u8);
+    }
     fn emit_ret(&mut self) {
         dynasm!(self ; ret);
     }
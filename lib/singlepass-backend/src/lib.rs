@@ -27,6 +27,11 @@ extern crate byteorder;
 #[macro_use]
 extern crate smallvec;
 
+#[macro_use]
+extern crate serde_derive;
+
+extern crate serde;
+
 mod codegen_x64;
 mod emitter_x64;
 mod machine;
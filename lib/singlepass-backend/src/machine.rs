@@ -12,6 +12,8 @@ pub struct Machine {
     used_xmms: HashSet<XMM>,
     stack_offset: MachineStackOffset,
     save_area_offset: Option<MachineStackOffset>,
+    /// Stack offsets of the 16-byte slots holding `V128` stack values.
+    wide_slots: HashSet<usize>,
     pub state: MachineState,
     pub(crate) track_state: bool,
 }
@@ -23,6 +25,7 @@ impl Machine {
             used_xmms: HashSet::new(),
             stack_offset: MachineStackOffset(0),
             save_area_offset: None,
+            wide_slots: HashSet::new(),
             state: x64::new_machine_state(),
             track_state: true,
         }
@@ -44,6 +47,24 @@ impl Machine {
         GPR::R15
     }
 
+    /// Returns the location of the high half of a `V128` value at `loc`.
+    ///
+    /// `V128` values always live in memory, with their low half first.
+    pub fn get_high_half(loc: Location) -> Location {
+        match loc {
+            Location::Memory(base, disp) => Location::Memory(base, disp + 8),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Whether `loc` is a `V128` stack value that hasn't been released yet.
+    pub fn is_v128(&self, loc: Location) -> bool {
+        match loc {
+            Location::Memory(GPR::RBP, x) if x < 0 => self.wide_slots.contains(&((-x) as usize)),
+            _ => false,
+        }
+    }
+
     /// Picks an unused general purpose register for local/stack/argument use.
     ///
     /// This method does not mark the register as used.
@@ -144,15 +165,23 @@ impl Machine {
         for (ty, mv) in tys {
             let loc = match *ty {
                 WpType::F32 | WpType::F64 => self.pick_xmm().map(Location::XMM),
-                WpType::I32 | WpType::I64 => self.pick_gpr().map(Location::GPR),
+                WpType::I32 | WpType::I64 | WpType::AnyRef | WpType::AnyFunc => {
+                    self.pick_gpr().map(Location::GPR)
+                }
+                // `V128` values don't fit in a register of either kind.
+                WpType::V128 => None,
                 _ => unreachable!(),
             };
 
             let loc = if let Some(x) = loc {
                 x
             } else {
-                self.stack_offset.0 += 8;
-                delta_stack_offset += 8;
+                let size = if *ty == WpType::V128 { 16 } else { 8 };
+                self.stack_offset.0 += size;
+                delta_stack_offset += size;
+                if *ty == WpType::V128 {
+                    self.wide_slots.insert(self.stack_offset.0);
+                }
                 Location::Memory(GPR::RBP, -(self.stack_offset.0 as i32))
             };
             if let Location::GPR(x) = loc {
@@ -162,6 +191,10 @@ impl Machine {
                 self.used_xmms.insert(x);
                 self.state.register_values[X64Register::XMM(x).to_index().0] = *mv;
             } else {
+                if *ty == WpType::V128 {
+                    // Machine states hold 64-bit values, so only the low half is tracked.
+                    self.state.stack_values.push(MachineValue::Undefined);
+                }
                 self.state.stack_values.push(*mv);
             }
             self.state.wasm_stack.push(WasmAbstractValue::Runtime);
@@ -178,6 +211,9 @@ impl Machine {
         if zeroed {
             for i in 0..tys.len() {
                 assembler.emit_mov(Size::S64, Location::Imm32(0), ret[i]);
+                if tys[i].0 == WpType::V128 {
+                    assembler.emit_mov(Size::S64, Location::Imm32(0), Self::get_high_half(ret[i]));
+                }
             }
         }
         ret
@@ -207,9 +243,16 @@ impl Machine {
                     if offset != self.stack_offset.0 {
                         unreachable!();
                     }
-                    self.stack_offset.0 -= 8;
-                    delta_stack_offset += 8;
-                    self.state.stack_values.pop().unwrap();
+                    let size = if self.wide_slots.remove(&offset) {
+                        16
+                    } else {
+                        8
+                    };
+                    self.stack_offset.0 -= size;
+                    delta_stack_offset += size;
+                    for _ in 0..size / 8 {
+                        self.state.stack_values.pop().unwrap();
+                    }
                 }
                 _ => {}
             }
@@ -261,9 +304,16 @@ impl Machine {
                     if offset != self.stack_offset.0 {
                         unreachable!();
                     }
-                    self.stack_offset.0 -= 8;
-                    delta_stack_offset += 8;
-                    self.state.stack_values.pop().unwrap();
+                    let size = if self.wide_slots.remove(&offset) {
+                        16
+                    } else {
+                        8
+                    };
+                    self.stack_offset.0 -= size;
+                    delta_stack_offset += size;
+                    for _ in 0..size / 8 {
+                        self.state.stack_values.pop().unwrap();
+                    }
                 }
                 _ => {}
             }
@@ -299,8 +349,13 @@ impl Machine {
                     if offset != stack_offset {
                         unreachable!();
                     }
-                    stack_offset -= 8;
-                    delta_stack_offset += 8;
+                    let size = if self.wide_slots.contains(&offset) {
+                        16
+                    } else {
+                        8
+                    };
+                    stack_offset -= size;
+                    delta_stack_offset += size;
                 }
                 _ => {}
             }
@@ -318,7 +373,7 @@ impl Machine {
     pub fn init_locals<E: Emitter>(
         &mut self,
         a: &mut E,
-        n: usize,
+        local_types: &[WpType],
         n_params: usize,
     ) -> Vec<Location> {
         // Use callee-saved registers for locals.
@@ -333,32 +388,24 @@ impl Machine {
         }
 
         let mut locations: Vec<Location> = vec![];
-        let mut allocated: usize = 0;
-
-        // Determine locations for parameters.
-        for i in 0..n_params {
-            let loc = Self::get_param_location(i + 1);
-            locations.push(match loc {
-                Location::GPR(_) => {
-                    let old_idx = allocated;
-                    allocated += 1;
-                    get_local_location(old_idx)
-                }
-                Location::Memory(_, _) => {
-                    let old_idx = allocated;
-                    allocated += 1;
-                    get_local_location(old_idx)
-                }
-                _ => unreachable!(),
+        let mut allocated_regs: usize = 0;
+        let mut allocated_slots: usize = 4;
+
+        // Determine locations for parameters and normal locals. A `V128` local
+        // takes two machine stack slots, with its low half at the lower address.
+        for ty in local_types {
+            locations.push(if *ty == WpType::V128 {
+                allocated_slots += 2;
+                get_local_location(allocated_slots - 1)
+            } else if allocated_regs < 4 {
+                allocated_regs += 1;
+                get_local_location(allocated_regs - 1)
+            } else {
+                allocated_slots += 1;
+                get_local_location(allocated_slots - 1)
             });
         }
 
-        // Determine locations for normal locals.
-        for _ in n_params..n {
-            locations.push(get_local_location(allocated));
-            allocated += 1;
-        }
-
         for (i, loc) in locations.iter().enumerate() {
             match *loc {
                 Location::GPR(x) => {
@@ -366,6 +413,9 @@ impl Machine {
                         MachineValue::WasmLocal(i);
                 }
                 Location::Memory(_, _) => {
+                    if local_types[i] == WpType::V128 {
+                        self.state.stack_values.push(MachineValue::Undefined);
+                    }
                     self.state.stack_values.push(MachineValue::WasmLocal(i));
                 }
                 _ => unreachable!(),
@@ -373,13 +423,7 @@ impl Machine {
         }
 
         // How many machine stack slots did all the locals use?
-        let num_mem_slots = locations
-            .iter()
-            .filter(|&&loc| match loc {
-                Location::Memory(_, _) => true,
-                _ => false,
-            })
-            .count();
+        let num_mem_slots = allocated_slots - 4;
 
         // Move RSP down to reserve space for machine stack slots.
         if num_mem_slots > 0 {
@@ -412,24 +456,33 @@ impl Machine {
         // Save the offset of static area.
         self.save_area_offset = Some(MachineStackOffset(self.stack_offset.0));
 
-        // Load in-register parameters into the allocated locations.
+        // Load in-register parameters into the allocated locations. A `V128`
+        // parameter is passed as two arguments, low half first.
+        let mut arg = 1;
         for i in 0..n_params {
-            let loc = Self::get_param_location(i + 1);
-            match loc {
-                Location::GPR(_) => {
-                    a.emit_mov(Size::S64, loc, locations[i]);
-                }
-                Location::Memory(_, _) => match locations[i] {
+            let mut halves = vec![locations[i]];
+            if local_types[i] == WpType::V128 {
+                halves.push(Self::get_high_half(locations[i]));
+            }
+            for dst in halves {
+                let loc = Self::get_param_location(arg);
+                arg += 1;
+                match loc {
                     Location::GPR(_) => {
-                        a.emit_mov(Size::S64, loc, locations[i]);
-                    }
-                    Location::Memory(_, _) => {
-                        a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                        a.emit_mov(Size::S64, Location::GPR(GPR::RAX), locations[i]);
+                        a.emit_mov(Size::S64, loc, dst);
                     }
+                    Location::Memory(_, _) => match dst {
+                        Location::GPR(_) => {
+                            a.emit_mov(Size::S64, loc, dst);
+                        }
+                        Location::Memory(_, _) => {
+                            a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
+                            a.emit_mov(Size::S64, Location::GPR(GPR::RAX), dst);
+                        }
+                        _ => unreachable!(),
+                    },
                     _ => unreachable!(),
-                },
-                _ => unreachable!(),
+                }
             }
         }
 
@@ -441,13 +494,40 @@ impl Machine {
         );
 
         // Initialize all normal locals to zero.
-        for i in n_params..n {
+        for i in n_params..local_types.len() {
             a.emit_mov(Size::S64, Location::Imm32(0), locations[i]);
+            if local_types[i] == WpType::V128 {
+                a.emit_mov(
+                    Size::S64,
+                    Location::Imm32(0),
+                    Self::get_high_half(locations[i]),
+                );
+            }
         }
 
         locations
     }
 
+    /// Reserves `n` machine stack slots that stay allocated until the function returns.
+    ///
+    /// This must be called right after `init_locals`, before any stack value is acquired.
+    pub fn reserve_fixed_slots<E: Emitter>(&mut self, a: &mut E, n: usize) -> Vec<Location> {
+        let mut slots = vec![];
+        for _ in 0..n {
+            self.stack_offset.0 += 8;
+            self.state.stack_values.push(MachineValue::Undefined);
+            slots.push(Location::Memory(GPR::RBP, -(self.stack_offset.0 as i32)));
+        }
+        if n > 0 {
+            a.emit_sub(
+                Size::S64,
+                Location::Imm32((n * 8) as u32),
+                Location::GPR(GPR::RSP),
+            );
+        }
+        slots
+    }
+
     pub fn finalize_locals<E: Emitter>(&mut self, a: &mut E, locations: &[Location]) {
         // Unwind stack to the "save area".
         a.emit_lea(
@@ -167,6 +167,9 @@ mod tests {
 
         let mut features = wabt::Features::new();
         features.enable_simd();
+        features.enable_bulk_memory();
+        features.enable_sign_extension();
+        features.enable_sat_float_to_int();
         let mut parser: ScriptParser =
             ScriptParser::from_source_and_name_with_features(&source, filename, features)
                 .expect(&format!("Failed to parse script {}", &filename));
@@ -203,7 +206,11 @@ mod tests {
                         let spectest_import_object =
                             get_spectest_import_object(&registered_modules);
                         let config = CompilerConfig {
-                            features: Features { simd: true },
+                            features: Features {
+                                simd: true,
+                                bulk_memory: true,
+                                ..Default::default()
+                            },
                             ..Default::default()
                         };
                         let module = wasmer_runtime_core::compile_with_config(
@@ -590,7 +597,9 @@ mod tests {
                                                     // TODO assert message?
                                                     test_report.count_passed()
                                                 }
-                                                RuntimeError::Error { .. } => {
+                                                RuntimeError::Error { .. }
+                                                | RuntimeError::Interrupted
+                                                | RuntimeError::Timeout => {
                                                     test_report.add_failure(
                                                         SpecFailure {
                                                             file: filename.to_string(),
@@ -630,7 +639,11 @@ mod tests {
                     //                    println!("AssertInvalid");
                     let result = panic::catch_unwind(|| {
                         let config = CompilerConfig {
-                            features: Features { simd: true },
+                            features: Features {
+                                simd: true,
+                                bulk_memory: true,
+                                ..Default::default()
+                            },
                             ..Default::default()
                         };
                         wasmer_runtime_core::compile_with_config(
@@ -681,7 +694,11 @@ mod tests {
 
                     let result = panic::catch_unwind(|| {
                         let config = CompilerConfig {
-                            features: Features { simd: true },
+                            features: Features {
+                                simd: true,
+                                bulk_memory: true,
+                                ..Default::default()
+                            },
                             ..Default::default()
                         };
                         wasmer_runtime_core::compile_with_config(
@@ -798,7 +815,11 @@ mod tests {
                         let spectest_import_object =
                             get_spectest_import_object(&registered_modules);
                         let config = CompilerConfig {
-                            features: Features { simd: true },
+                            features: Features {
+                                simd: true,
+                                bulk_memory: true,
+                                ..Default::default()
+                            },
                             ..Default::default()
                         };
                         let module = wasmer_runtime_core::compile_with_config(
@@ -983,6 +1004,10 @@ mod tests {
             wasmer_runtime_core::types::Value::F32(v) => SpectestValue::F32(v.to_bits()),
             wasmer_runtime_core::types::Value::F64(v) => SpectestValue::F64(v.to_bits()),
             wasmer_runtime_core::types::Value::V128(v) => SpectestValue::V128(v),
+            wasmer_runtime_core::types::Value::AnyRef(_)
+            | wasmer_runtime_core::types::Value::FuncRef(_) => {
+                panic!("reference values are not supported in spectests")
+            }
         }
     }
 
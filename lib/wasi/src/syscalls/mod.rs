@@ -67,6 +67,8 @@ fn read_bytes<T: Read>(
     for iov in iovs_arr_cell {
         let iov_inner = iov.get();
         let bytes = iov_inner.buf.deref(memory, 0, iov_inner.buf_len)?;
+        // The reader may be a file, which the kernel writes into directly.
+        memory.prepare_os_write(iov_inner.buf.offset() as usize, iov_inner.buf_len as usize);
         let mut raw_bytes: &mut [u8] =
             unsafe { &mut *(bytes as *const [_] as *mut [_] as *mut [u8]) };
         bytes_read += reader.read(raw_bytes).map_err(|_| __WASI_EIO)? as u32;
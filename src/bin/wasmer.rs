@@ -85,6 +85,22 @@ struct PrestandardFeatures {
     #[structopt(long = "enable-simd")]
     simd: bool,
 
+    /// Enable support for the multi-value proposal.
+    #[structopt(long = "enable-multi-value")]
+    multi_value: bool,
+
+    /// Enable support for the bulk memory operations proposal.
+    #[structopt(long = "enable-bulk-memory")]
+    bulk_memory: bool,
+
+    /// Enable support for the threads proposal.
+    #[structopt(long = "enable-threads")]
+    threads: bool,
+
+    /// Enable support for the reference types proposal.
+    #[structopt(long = "enable-reference-types")]
+    reference_types: bool,
+
     /// Enable support for all pre-standard proposals.
     #[structopt(long = "enable-all")]
     all: bool,
@@ -366,8 +382,8 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
     };
 
     // Don't error on --enable-all for other backends.
-    if options.features.simd && options.backend != Backend::LLVM {
-        return Err("SIMD is only supported in the LLVM backend for now".to_string());
+    if options.features.simd && options.backend == Backend::Cranelift {
+        return Err("SIMD is not supported in the Cranelift backend".to_string());
     }
 
     if !utils::is_wasm_binary(&wasm_binary) {
@@ -375,6 +391,18 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         if options.features.simd || options.features.all {
             features.enable_simd();
         }
+        if options.features.multi_value || options.features.all {
+            features.enable_multi_value();
+        }
+        if options.features.bulk_memory || options.features.all {
+            features.enable_bulk_memory();
+        }
+        if options.features.threads || options.features.all {
+            features.enable_threads();
+        }
+        if options.features.reference_types || options.features.all {
+            features.enable_reference_types();
+        }
         wasm_binary = wabt::wat2wasm_with_features(wasm_binary, features)
             .map_err(|e| format!("Can't convert from wast to wasm: {:?}", e))?;
     }
@@ -427,6 +455,11 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                 track_state,
                 features: Features {
                     simd: options.features.simd || options.features.all,
+                    multi_value: options.features.multi_value || options.features.all,
+                    bulk_memory: options.features.bulk_memory || options.features.all,
+                    threads: options.features.threads || options.features.all,
+                    reference_types: options.features.reference_types || options.features.all,
+                    ..Default::default()
                 },
             },
             &*compiler,
@@ -440,6 +473,11 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                 track_state,
                 features: Features {
                     simd: options.features.simd || options.features.all,
+                    multi_value: options.features.multi_value || options.features.all,
+                    bulk_memory: options.features.bulk_memory || options.features.all,
+                    threads: options.features.threads || options.features.all,
+                    reference_types: options.features.reference_types || options.features.all,
+                    ..Default::default()
                 },
                 ..Default::default()
             },
@@ -456,11 +494,17 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         let mut cache = unsafe {
             FileSystemCache::new(wasmer_cache_dir).map_err(|e| format!("Cache error: {:?}", e))?
         };
+        // Modules compiled with other middlewares than `compiler`'s aren't loaded.
+        let middleware_fingerprint = compiler.middleware_fingerprint();
         let load_cache_key = || -> Result<_, String> {
             if let Some(ref prehashed_cache_key) = options.cache_key {
                 if let Ok(module) =
                     WasmHash::decode(prehashed_cache_key).and_then(|prehashed_key| {
-                        cache.load_with_backend(prehashed_key, options.backend)
+                        cache.load_with_fingerprint(
+                            prehashed_key,
+                            options.backend,
+                            &middleware_fingerprint,
+                        )
                     })
                 {
                     debug!("using prehashed key: {}", prehashed_cache_key);
@@ -474,7 +518,7 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
             // cache.load will return the Module if it's able to deserialize it properly, and an error if:
             // * The file is not found
             // * The file exists, but it's corrupted or can't be converted to a module
-            match cache.load_with_backend(hash, options.backend) {
+            match cache.load_with_fingerprint(hash, options.backend, &middleware_fingerprint) {
                 Ok(module) => {
                     // We are able to load the module from cache
                     Ok(module)
@@ -487,6 +531,12 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                             track_state,
                             features: Features {
                                 simd: options.features.simd || options.features.all,
+                                multi_value: options.features.multi_value || options.features.all,
+                                bulk_memory: options.features.bulk_memory || options.features.all,
+                                threads: options.features.threads || options.features.all,
+                                reference_types: options.features.reference_types
+                                    || options.features.all,
+                                ..Default::default()
                             },
                             ..Default::default()
                         },
@@ -610,7 +660,12 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                         let mut f = File::open(path).unwrap();
                         let mut out: Vec<u8> = vec![];
                         f.read_to_end(&mut out).unwrap();
-                        Some(InstanceImage::from_bytes(&out).expect("failed to decode image"))
+                        let image = InstanceImage::from_bytes(&out)
+                            .map_err(|e| format!("Can't load image: {}", e))?;
+                        image
+                            .check_module(&instance.module.info)
+                            .map_err(|e| format!("Can't load image: {}", e))?;
+                        Some(image)
                     } else {
                         None
                     };
@@ -644,6 +699,7 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                             if let Some(new_image) = e.downcast_ref::<InstanceImage>() {
                                 let op = interactive_shell(InteractiveShellContext {
                                     image: Some(new_image.clone()),
+                                    module_info: &instance.module.info,
                                 });
                                 match op {
                                     ShellExitOperation::ContinueWith(new_image) => {
@@ -666,7 +722,7 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
 
                 if let Err(ref err) = result {
                     match err {
-                        RuntimeError::Trap { msg } => panic!("wasm trap occured: {}", msg),
+                        RuntimeError::Trap { msg, .. } => panic!("wasm trap occured: {}", msg),
                         #[cfg(feature = "wasi")]
                         RuntimeError::Error { data } => {
                             if let Some(error_code) = data.downcast_ref::<wasmer_wasi::ExitCode>() {
@@ -675,6 +731,7 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                         }
                         #[cfg(not(feature = "wasi"))]
                         RuntimeError::Error { .. } => (),
+                        RuntimeError::Interrupted | RuntimeError::Timeout => (),
                     }
                     panic!("error: {:?}", err)
                 }
@@ -703,8 +760,9 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
 }
 
 #[cfg(feature = "backend-singlepass")]
-struct InteractiveShellContext {
+struct InteractiveShellContext<'a> {
     image: Option<wasmer_runtime_core::state::InstanceImage>,
+    module_info: &'a wasmer_runtime_core::module::ModuleInfo,
 }
 
 #[cfg(feature = "backend-singlepass")]
@@ -714,7 +772,7 @@ enum ShellExitOperation {
 }
 
 #[cfg(feature = "backend-singlepass")]
-fn interactive_shell(mut ctx: InteractiveShellContext) -> ShellExitOperation {
+fn interactive_shell(mut ctx: InteractiveShellContext<'_>) -> ShellExitOperation {
     use std::io::Write;
 
     let mut stdout = ::std::io::stdout();
@@ -744,7 +802,15 @@ fn interactive_shell(mut ctx: InteractiveShellContext) -> ShellExitOperation {
                 let path = path.unwrap();
 
                 if let Some(ref image) = ctx.image {
-                    let buf = image.to_bytes();
+                    let buf = match image
+                        .to_bytes(wasmer_runtime_core::state::MemoryCompression::ZeroChunks)
+                    {
+                        Ok(x) => x,
+                        Err(e) => {
+                            println!("Cannot encode the snapshot: {}", e);
+                            continue;
+                        }
+                    };
                     let mut f = match File::create(path) {
                         Ok(x) => x,
                         Err(e) => {
@@ -770,7 +836,12 @@ fn interactive_shell(mut ctx: InteractiveShellContext) -> ShellExitOperation {
             }
             "backtrace" | "bt" => {
                 if let Some(ref image) = ctx.image {
-                    println!("{}", image.execution_state.colored_output());
+                    println!(
+                        "{}",
+                        image
+                            .execution_state
+                            .colored_output_with_info(ctx.module_info)
+                    );
                 } else {
                     println!("State not available");
                 }
@@ -819,6 +890,11 @@ fn validate_wasm(validate: Validate) -> Result<(), String> {
         &wasm_binary,
         Features {
             simd: validate.features.simd || validate.features.all,
+            multi_value: validate.features.multi_value || validate.features.all,
+            bulk_memory: validate.features.bulk_memory || validate.features.all,
+            threads: validate.features.threads || validate.features.all,
+            reference_types: validate.features.reference_types || validate.features.all,
+            ..Default::default()
         },
     )
     .map_err(|err| format!("Validation failed: {}", err))?;